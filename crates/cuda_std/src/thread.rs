@@ -315,14 +315,74 @@ pub fn sync_threads_or(predicate: u32) -> u32 {
 
 /// Acts as a memory fence at the grid level (all threads inside of a kernel execution).
 ///
-/// Note that this is NOT an execution synchronization like [`sync_threads`]. It is not possible
-/// to sync threads at a grid level. It is simply a memory fence.
+/// Note that this is NOT an execution synchronization like [`sync_threads`], it is simply a
+/// memory fence. Threads can only be synchronized at the grid level with [`grid_sync`] inside
+/// of cooperative launches.
 #[gpu_only]
 #[inline(always)]
 pub fn grid_fence() {
     unsafe { __nvvm_grid_fence() }
 }
 
+/// Waits until all threads in the grid have reached this point, and makes any global memory
+/// accesses made before this call visible to every thread in the grid after it.
+///
+/// Like [`sync_threads`], every thread in the grid must call this, otherwise execution will halt.
+///
+/// # Safety
+///
+/// The kernel must have been launched with a cooperative launch such as cust's `launch_cooperative!`,
+/// which guarantees that every block of the grid is resident on the device at the same time.
+/// Calling this in a kernel which was launched normally is undefined behavior.
+#[gpu_only]
+#[inline(always)]
+pub unsafe fn grid_sync() {
+    use crate::atomic::mid::{atomic_fetch_add_u32_device, atomic_load_32_device};
+    use core::sync::atomic::Ordering;
+
+    // cooperative launches get a workspace from the driver whose address is passed in the
+    // envreg1 (low bits) and envreg2 (high bits) registers. The workspace starts with its size
+    // followed by the counter used as the grid barrier, which is the same barrier CUDA C++
+    // cooperative groups use.
+    let lo: u32;
+    let hi: u32;
+    asm!("mov.u32 {}, %envreg1;", out(reg32) lo);
+    asm!("mov.u32 {}, %envreg2;", out(reg32) hi);
+    let workspace = (((hi as u64) << 32) | lo as u64) as *mut u32;
+    let arrived = workspace.add(1);
+
+    sync_threads();
+    if thread_idx() == Vec3::zero() {
+        let grid_dim = grid_dim();
+        let expected = grid_dim.x * grid_dim.y * grid_dim.z;
+        // every block adds 1 to the counter, except for the first block which adds enough to
+        // flip the high bit once every block has arrived.
+        let increment = grid_barrier_increment(block_idx() == Vec3::zero(), expected);
+        let old = atomic_fetch_add_u32_device(arrived, Ordering::AcqRel, increment);
+        while !grid_barrier_released(old, atomic_load_32_device(arrived, Ordering::Acquire)) {}
+    }
+    sync_threads();
+}
+
+/// The amount a block adds to the grid barrier counter when it arrives at [`grid_sync`].
+#[inline(always)]
+#[cfg_attr(not(target_os = "cuda"), allow(dead_code))]
+fn grid_barrier_increment(first_block: bool, blocks: u32) -> u32 {
+    if first_block {
+        0x8000_0000 - (blocks - 1)
+    } else {
+        1
+    }
+}
+
+/// Whether the barrier a block arrived at when the counter was `old` has been released, which
+/// happens when the last block arrives and the high bit of the counter flips.
+#[inline(always)]
+#[cfg_attr(not(target_os = "cuda"), allow(dead_code))]
+fn grid_barrier_released(old: u32, current: u32) -> bool {
+    (old ^ current) & 0x8000_0000 != 0
+}
+
 /// Acts as a memory fence at the device level.
 #[gpu_only]
 #[inline(always)]
@@ -350,3 +410,39 @@ pub fn nanosleep(nanos: u32) {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // runs `barriers` grid barriers of `blocks` blocks on the host, with the blocks arriving in
+    // the order of `order`.
+    fn run_barriers(blocks: u32, order: &[u32], barriers: usize) {
+        let mut counter = 0u32;
+        for _ in 0..barriers {
+            let mut arrived = Vec::new();
+            for (i, &block) in order.iter().enumerate() {
+                let old = counter;
+                counter = counter.wrapping_add(grid_barrier_increment(block == 0, blocks));
+                arrived.push(old);
+                let last = i + 1 == order.len();
+                for &old in &arrived {
+                    assert_eq!(grid_barrier_released(old, counter), last);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_grid_barrier() {
+        for &blocks in &[1u32, 2, 3, 7, 64, 1000] {
+            let forward = (0..blocks).collect::<Vec<_>>();
+            let backward = (0..blocks).rev().collect::<Vec<_>>();
+            let mut middle = forward.clone();
+            middle.rotate_left(blocks as usize / 2);
+            for order in &[forward, backward, middle] {
+                run_barriers(blocks, order, 5);
+            }
+        }
+    }
+}
@@ -1,3 +1,10 @@
 fn main() {
-    find_cuda_helper::include_cuda();
+    // the emulated driver is compiled into cust_raw, there is nothing to link to.
+    if std::env::var_os("CARGO_FEATURE_MOCK").is_none() {
+        find_cuda_helper::include_cuda();
+    }
+
+    if std::env::var_os("CARGO_FEATURE_NVRTC").is_some() {
+        println!("cargo:rustc-link-lib=dylib=nvrtc");
+    }
 }
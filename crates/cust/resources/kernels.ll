@@ -0,0 +1,412 @@
+; Kernels used by `DeviceSlice` for operations which have no driver API equivalent, such as filling
+; a slice with a value bigger than 4 bytes. The PTX embedded in cust is generated from this file with:
+;
+;   opt -O3 kernels.ll | llc -march=nvptx64 -mcpu=sm_30 -mattr=+ptx60 -O3 -o kernels.ptx
+;
+; All kernels are launched with a 1D grid and use grid-stride loops, so they work for any grid size.
+; Lengths and offsets are in units of the kernel's element width (bytes for `b8`, words for `b32`).
+
+target datalayout = "e-i64:64-i128:128-v16:16-v32:32-n16:32:64"
+target triple = "nvptx64-nvidia-cuda"
+
+declare i32 @llvm.nvvm.read.ptx.sreg.tid.x()
+declare i32 @llvm.nvvm.read.ptx.sreg.ntid.x()
+declare i32 @llvm.nvvm.read.ptx.sreg.ctaid.x()
+declare i32 @llvm.nvvm.read.ptx.sreg.nctaid.x()
+
+define internal i64 @global_index() alwaysinline {
+  %tid = call i32 @llvm.nvvm.read.ptx.sreg.tid.x()
+  %ntid = call i32 @llvm.nvvm.read.ptx.sreg.ntid.x()
+  %ctaid = call i32 @llvm.nvvm.read.ptx.sreg.ctaid.x()
+  %tid64 = zext i32 %tid to i64
+  %ntid64 = zext i32 %ntid to i64
+  %ctaid64 = zext i32 %ctaid to i64
+  %base = mul i64 %ctaid64, %ntid64
+  %idx = add i64 %base, %tid64
+  ret i64 %idx
+}
+
+define internal i64 @grid_stride() alwaysinline {
+  %ntid = call i32 @llvm.nvvm.read.ptx.sreg.ntid.x()
+  %nctaid = call i32 @llvm.nvvm.read.ptx.sreg.nctaid.x()
+  %ntid64 = zext i32 %ntid to i64
+  %nctaid64 = zext i32 %nctaid to i64
+  %stride = mul i64 %ntid64, %nctaid64
+  ret i64 %stride
+}
+
+; Copies `len` elements from `src` to `dst`.
+define internal void @copy_b8(i8 addrspace(1)* %dst, i8 addrspace(1)* %src, i64 %len) alwaysinline {
+entry:
+  %empty = icmp eq i64 %len, 0
+  br i1 %empty, label %exit, label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %next, %loop ]
+  %from = getelementptr i8, i8 addrspace(1)* %src, i64 %i
+  %to = getelementptr i8, i8 addrspace(1)* %dst, i64 %i
+  %v = load i8, i8 addrspace(1)* %from
+  store i8 %v, i8 addrspace(1)* %to
+  %next = add i64 %i, 1
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define internal void @copy_b32(i32 addrspace(1)* %dst, i32 addrspace(1)* %src, i64 %len) alwaysinline {
+entry:
+  %empty = icmp eq i64 %len, 0
+  br i1 %empty, label %exit, label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %next, %loop ]
+  %from = getelementptr i32, i32 addrspace(1)* %src, i64 %i
+  %to = getelementptr i32, i32 addrspace(1)* %dst, i64 %i
+  %v = load i32, i32 addrspace(1)* %from
+  store i32 %v, i32 addrspace(1)* %to
+  %next = add i64 %i, 1
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+; Writes the first `width` units of `value` to every `pitch` units of `dst`, `len` units in total. This
+; fills a slice when `width == pitch`, and one part of each element when filling with bigger values.
+define void @cust_fill_b8(i8 addrspace(1)* %dst, i64 %len, [256 x i8]* byval([256 x i8]) align 4 %value, i64 %width, i64 %pitch) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %row = udiv i64 %i, %width
+  %k = urem i64 %i, %width
+  %from = getelementptr [256 x i8], [256 x i8]* %value, i64 0, i64 %k
+  %v = load i8, i8* %from
+  %row_offset = mul i64 %row, %pitch
+  %offset = add i64 %row_offset, %k
+  %to = getelementptr i8, i8 addrspace(1)* %dst, i64 %offset
+  store i8 %v, i8 addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_fill_b32(i32 addrspace(1)* %dst, i64 %len, [64 x i32]* byval([64 x i32]) align 4 %value, i64 %width, i64 %pitch) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %row = udiv i64 %i, %width
+  %k = urem i64 %i, %width
+  %from = getelementptr [64 x i32], [64 x i32]* %value, i64 0, i64 %k
+  %v = load i32, i32* %from
+  %row_offset = mul i64 %row, %pitch
+  %offset = add i64 %row_offset, %k
+  %to = getelementptr i32, i32 addrspace(1)* %dst, i64 %offset
+  store i32 %v, i32 addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+; dst[i] = start + i * step for i < len, wrapping for integers.
+define void @cust_iota_u32(i32 addrspace(1)* %dst, i64 %len, i32 %first, i32 %step) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %i32 = trunc i64 %i to i32
+  %offset = mul i32 %i32, %step
+  %v = add i32 %first, %offset
+  %to = getelementptr i32, i32 addrspace(1)* %dst, i64 %i
+  store i32 %v, i32 addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_iota_u64(i64 addrspace(1)* %dst, i64 %len, i64 %first, i64 %step) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %offset = mul i64 %i, %step
+  %v = add i64 %first, %offset
+  %to = getelementptr i64, i64 addrspace(1)* %dst, i64 %i
+  store i64 %v, i64 addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_iota_f32(float addrspace(1)* %dst, i64 %len, float %first, float %step) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %fi = uitofp i64 %i to float
+  %offset = fmul float %fi, %step
+  %v = fadd float %first, %offset
+  %to = getelementptr float, float addrspace(1)* %dst, i64 %i
+  store float %v, float addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_iota_f64(double addrspace(1)* %dst, i64 %len, double %first, double %step) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %fi = uitofp i64 %i to double
+  %offset = fmul double %fi, %step
+  %v = fadd double %first, %offset
+  %to = getelementptr double, double addrspace(1)* %dst, i64 %i
+  store double %v, double addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+; Copies element i (of `size` units) from src + i * src_pitch to dst + i * dst_pitch for i < count.
+define void @cust_copy_strided_b8(i8 addrspace(1)* %dst, i64 %dst_pitch, i8 addrspace(1)* %src, i64 %src_pitch, i64 %count, i64 %size) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %count
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %dst_offset = mul i64 %i, %dst_pitch
+  %src_offset = mul i64 %i, %src_pitch
+  %to = getelementptr i8, i8 addrspace(1)* %dst, i64 %dst_offset
+  %from = getelementptr i8, i8 addrspace(1)* %src, i64 %src_offset
+  call void @copy_b8(i8 addrspace(1)* %to, i8 addrspace(1)* %from, i64 %size)
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %count
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_copy_strided_b32(i32 addrspace(1)* %dst, i64 %dst_pitch, i32 addrspace(1)* %src, i64 %src_pitch, i64 %count, i64 %size) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %count
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %dst_offset = mul i64 %i, %dst_pitch
+  %src_offset = mul i64 %i, %src_pitch
+  %to = getelementptr i32, i32 addrspace(1)* %dst, i64 %dst_offset
+  %from = getelementptr i32, i32 addrspace(1)* %src, i64 %src_offset
+  call void @copy_b32(i32 addrspace(1)* %to, i32 addrspace(1)* %from, i64 %size)
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %count
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+; dst[i] = src[indices[i]] for i < count, skipping indices which are not below src_len.
+define void @cust_gather_b8(i8 addrspace(1)* %dst, i8 addrspace(1)* %src, i64 %src_len, i32 addrspace(1)* %indices, i64 %count, i64 %size) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %count
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %latch ]
+  %index_ptr = getelementptr i32, i32 addrspace(1)* %indices, i64 %i
+  %index32 = load i32, i32 addrspace(1)* %index_ptr
+  %index = zext i32 %index32 to i64
+  %valid = icmp ult i64 %index, %src_len
+  br i1 %valid, label %body, label %latch
+body:
+  %dst_offset = mul i64 %i, %size
+  %src_offset = mul i64 %index, %size
+  %to = getelementptr i8, i8 addrspace(1)* %dst, i64 %dst_offset
+  %from = getelementptr i8, i8 addrspace(1)* %src, i64 %src_offset
+  call void @copy_b8(i8 addrspace(1)* %to, i8 addrspace(1)* %from, i64 %size)
+  br label %latch
+latch:
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %count
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_gather_b32(i32 addrspace(1)* %dst, i32 addrspace(1)* %src, i64 %src_len, i32 addrspace(1)* %indices, i64 %count, i64 %size) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %count
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %latch ]
+  %index_ptr = getelementptr i32, i32 addrspace(1)* %indices, i64 %i
+  %index32 = load i32, i32 addrspace(1)* %index_ptr
+  %index = zext i32 %index32 to i64
+  %valid = icmp ult i64 %index, %src_len
+  br i1 %valid, label %body, label %latch
+body:
+  %dst_offset = mul i64 %i, %size
+  %src_offset = mul i64 %index, %size
+  %to = getelementptr i32, i32 addrspace(1)* %dst, i64 %dst_offset
+  %from = getelementptr i32, i32 addrspace(1)* %src, i64 %src_offset
+  call void @copy_b32(i32 addrspace(1)* %to, i32 addrspace(1)* %from, i64 %size)
+  br label %latch
+latch:
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %count
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+; dst[indices[i]] = src[i] for i < count, skipping indices which are not below dst_len.
+define void @cust_scatter_b8(i8 addrspace(1)* %dst, i64 %dst_len, i8 addrspace(1)* %src, i32 addrspace(1)* %indices, i64 %count, i64 %size) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %count
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %latch ]
+  %index_ptr = getelementptr i32, i32 addrspace(1)* %indices, i64 %i
+  %index32 = load i32, i32 addrspace(1)* %index_ptr
+  %index = zext i32 %index32 to i64
+  %valid = icmp ult i64 %index, %dst_len
+  br i1 %valid, label %body, label %latch
+body:
+  %dst_offset = mul i64 %index, %size
+  %src_offset = mul i64 %i, %size
+  %to = getelementptr i8, i8 addrspace(1)* %dst, i64 %dst_offset
+  %from = getelementptr i8, i8 addrspace(1)* %src, i64 %src_offset
+  call void @copy_b8(i8 addrspace(1)* %to, i8 addrspace(1)* %from, i64 %size)
+  br label %latch
+latch:
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %count
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_scatter_b32(i32 addrspace(1)* %dst, i64 %dst_len, i32 addrspace(1)* %src, i32 addrspace(1)* %indices, i64 %count, i64 %size) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %count
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %latch ]
+  %index_ptr = getelementptr i32, i32 addrspace(1)* %indices, i64 %i
+  %index32 = load i32, i32 addrspace(1)* %index_ptr
+  %index = zext i32 %index32 to i64
+  %valid = icmp ult i64 %index, %dst_len
+  br i1 %valid, label %body, label %latch
+body:
+  %dst_offset = mul i64 %index, %size
+  %src_offset = mul i64 %i, %size
+  %to = getelementptr i32, i32 addrspace(1)* %dst, i64 %dst_offset
+  %from = getelementptr i32, i32 addrspace(1)* %src, i64 %src_offset
+  call void @copy_b32(i32 addrspace(1)* %to, i32 addrspace(1)* %from, i64 %size)
+  br label %latch
+latch:
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %count
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+; dst[i] = src[i] converted with round-to-nearest-even for i < len.
+define void @cust_convert_f32_f16(half addrspace(1)* %dst, float addrspace(1)* %src, i64 %len) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %from = getelementptr float, float addrspace(1)* %src, i64 %i
+  %v = load float, float addrspace(1)* %from
+  %h = fptrunc float %v to half
+  %to = getelementptr half, half addrspace(1)* %dst, i64 %i
+  store half %h, half addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @cust_convert_f16_f32(float addrspace(1)* %dst, half addrspace(1)* %src, i64 %len) {
+entry:
+  %start = call i64 @global_index()
+  %stride = call i64 @grid_stride()
+  %any = icmp ult i64 %start, %len
+  br i1 %any, label %loop, label %exit
+loop:
+  %i = phi i64 [ %start, %entry ], [ %next, %loop ]
+  %from = getelementptr half, half addrspace(1)* %src, i64 %i
+  %h = load half, half addrspace(1)* %from
+  %v = fpext half %h to float
+  %to = getelementptr float, float addrspace(1)* %dst, i64 %i
+  store float %v, float addrspace(1)* %to
+  %next = add i64 %i, %stride
+  %more = icmp ult i64 %next, %len
+  br i1 %more, label %loop, label %exit
+exit:
+  ret void
+}
+
+!nvvm.annotations = !{!0, !1, !2, !3, !4, !5, !6, !7, !8, !9, !10, !11, !12, !13}
+
+!0 = !{void (i8 addrspace(1)*, i64, [256 x i8]*, i64, i64)* @cust_fill_b8, !"kernel", i32 1}
+!1 = !{void (i32 addrspace(1)*, i64, [64 x i32]*, i64, i64)* @cust_fill_b32, !"kernel", i32 1}
+!2 = !{void (i32 addrspace(1)*, i64, i32, i32)* @cust_iota_u32, !"kernel", i32 1}
+!3 = !{void (i64 addrspace(1)*, i64, i64, i64)* @cust_iota_u64, !"kernel", i32 1}
+!4 = !{void (float addrspace(1)*, i64, float, float)* @cust_iota_f32, !"kernel", i32 1}
+!5 = !{void (double addrspace(1)*, i64, double, double)* @cust_iota_f64, !"kernel", i32 1}
+!6 = !{void (i8 addrspace(1)*, i64, i8 addrspace(1)*, i64, i64, i64)* @cust_copy_strided_b8, !"kernel", i32 1}
+!7 = !{void (i32 addrspace(1)*, i64, i32 addrspace(1)*, i64, i64, i64)* @cust_copy_strided_b32, !"kernel", i32 1}
+!8 = !{void (i8 addrspace(1)*, i8 addrspace(1)*, i64, i32 addrspace(1)*, i64, i64)* @cust_gather_b8, !"kernel", i32 1}
+!9 = !{void (i32 addrspace(1)*, i32 addrspace(1)*, i64, i32 addrspace(1)*, i64, i64)* @cust_gather_b32, !"kernel", i32 1}
+!10 = !{void (i8 addrspace(1)*, i64, i8 addrspace(1)*, i32 addrspace(1)*, i64, i64)* @cust_scatter_b8, !"kernel", i32 1}
+!11 = !{void (i32 addrspace(1)*, i64, i32 addrspace(1)*, i32 addrspace(1)*, i64, i64)* @cust_scatter_b32, !"kernel", i32 1}
+!12 = !{void (half addrspace(1)*, float addrspace(1)*, i64)* @cust_convert_f32_f16, !"kernel", i32 1}
+!13 = !{void (float addrspace(1)*, half addrspace(1)*, i64)* @cust_convert_f16_f32, !"kernel", i32 1}
@@ -1,2 +1,417 @@
-//! Utilities for compiling PTX strings to cubin.
+//! Runtime compilation of CUDA C++ to PTX and cubin with NVRTC.
+//!
+//! NVRTC compiles CUDA C++ source code while the program is running, so kernels written in CUDA
+//! C++ can be used next to Rust kernels without invoking `nvcc` at build time. The PTX or cubin
+//! it produces can be loaded with [`Module::from_ptx`](crate::module::Module::from_ptx) or
+//! [`Module::from_cubin`](crate::module::Module::from_cubin).
+//!
+//! This module is only available with the `nvrtc` feature, which links to the NVRTC library
+//! from the CUDA toolkit.
+//!
+//! # Example
+//!
+//! ```
+//! # use cust::*;
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! # let _ctx = quick_init()?;
+//! use cust::compile::{CompileOption, Program};
+//! use cust::module::Module;
+//!
+//! let source = r#"
+//!     template<typename T>
+//!     __global__ void scale(T* data, T factor, int n) {
+//!         int i = blockIdx.x * blockDim.x + threadIdx.x;
+//!         if (i < n) data[i] *= factor;
+//!     }
+//! "#;
+//!
+//! let mut program = Program::new(source, "scale.cu", &[])?;
+//! program.add_name_expression("scale<float>")?;
+//! if let Err(e) = program.compile(&[CompileOption::FastMath]) {
+//!     eprintln!("{}", program.log()?);
+//!     return Err(e.into());
+//! }
+//!
+//! let module = Module::from_ptx(program.ptx()?, &[])?;
+//! let scale = module.get_function(&program.lowered_name("scale<float>")?)?;
+//! # Ok(())
+//! # }
+//! ```
 
+use crate::module::JitTarget;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+#[allow(non_camel_case_types)]
+type nvrtcProgram = *mut std::os::raw::c_void;
+#[allow(non_camel_case_types)]
+type nvrtcResult = c_int;
+
+extern "C" {
+    fn nvrtcGetErrorString(result: nvrtcResult) -> *const c_char;
+    fn nvrtcVersion(major: *mut c_int, minor: *mut c_int) -> nvrtcResult;
+    fn nvrtcCreateProgram(
+        prog: *mut nvrtcProgram,
+        src: *const c_char,
+        name: *const c_char,
+        num_headers: c_int,
+        headers: *const *const c_char,
+        include_names: *const *const c_char,
+    ) -> nvrtcResult;
+    fn nvrtcDestroyProgram(prog: *mut nvrtcProgram) -> nvrtcResult;
+    fn nvrtcCompileProgram(
+        prog: nvrtcProgram,
+        num_options: c_int,
+        options: *const *const c_char,
+    ) -> nvrtcResult;
+    fn nvrtcGetPTXSize(prog: nvrtcProgram, size: *mut usize) -> nvrtcResult;
+    fn nvrtcGetPTX(prog: nvrtcProgram, ptx: *mut c_char) -> nvrtcResult;
+    fn nvrtcGetCUBINSize(prog: nvrtcProgram, size: *mut usize) -> nvrtcResult;
+    fn nvrtcGetCUBIN(prog: nvrtcProgram, cubin: *mut c_char) -> nvrtcResult;
+    fn nvrtcGetProgramLogSize(prog: nvrtcProgram, size: *mut usize) -> nvrtcResult;
+    fn nvrtcGetProgramLog(prog: nvrtcProgram, log: *mut c_char) -> nvrtcResult;
+    fn nvrtcAddNameExpression(prog: nvrtcProgram, name_expression: *const c_char) -> nvrtcResult;
+    fn nvrtcGetLoweredName(
+        prog: nvrtcProgram,
+        name_expression: *const c_char,
+        lowered_name: *mut *const c_char,
+    ) -> nvrtcResult;
+}
+
+/// Errors returned by NVRTC.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NvrtcError {
+    OutOfMemory,
+    ProgramCreationFailure,
+    InvalidInput,
+    InvalidProgram,
+    InvalidOption,
+    /// The program failed to compile, the reason is in [`Program::log`].
+    Compilation,
+    BuiltinOperationFailure,
+    NoNameExpressionsAfterCompilation,
+    NoLoweredNamesBeforeCompilation,
+    NameExpressionNotValid,
+    InternalError,
+    UnknownError,
+}
+
+impl NvrtcError {
+    fn from_raw(result: nvrtcResult) -> Self {
+        match result {
+            1 => NvrtcError::OutOfMemory,
+            2 => NvrtcError::ProgramCreationFailure,
+            3 => NvrtcError::InvalidInput,
+            4 => NvrtcError::InvalidProgram,
+            5 => NvrtcError::InvalidOption,
+            6 => NvrtcError::Compilation,
+            7 => NvrtcError::BuiltinOperationFailure,
+            8 => NvrtcError::NoNameExpressionsAfterCompilation,
+            9 => NvrtcError::NoLoweredNamesBeforeCompilation,
+            10 => NvrtcError::NameExpressionNotValid,
+            11 => NvrtcError::InternalError,
+            _ => NvrtcError::UnknownError,
+        }
+    }
+
+    fn to_raw(self) -> nvrtcResult {
+        match self {
+            NvrtcError::OutOfMemory => 1,
+            NvrtcError::ProgramCreationFailure => 2,
+            NvrtcError::InvalidInput => 3,
+            NvrtcError::InvalidProgram => 4,
+            NvrtcError::InvalidOption => 5,
+            NvrtcError::Compilation => 6,
+            NvrtcError::BuiltinOperationFailure => 7,
+            NvrtcError::NoNameExpressionsAfterCompilation => 8,
+            NvrtcError::NoLoweredNamesBeforeCompilation => 9,
+            NvrtcError::NameExpressionNotValid => 10,
+            NvrtcError::InternalError => 11,
+            NvrtcError::UnknownError => -1,
+        }
+    }
+}
+
+impl fmt::Display for NvrtcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == NvrtcError::UnknownError {
+            return write!(f, "Unknown NVRTC error");
+        }
+        unsafe {
+            let ptr = nvrtcGetErrorString(self.to_raw());
+            write!(f, "{}", CStr::from_ptr(ptr).to_string_lossy())
+        }
+    }
+}
+
+impl Error for NvrtcError {}
+
+/// Result type for NVRTC functions.
+pub type NvrtcResult<T> = Result<T, NvrtcError>;
+
+trait ToResult {
+    fn to_result(self) -> NvrtcResult<()>;
+}
+
+impl ToResult for nvrtcResult {
+    fn to_result(self) -> NvrtcResult<()> {
+        match self {
+            0 => Ok(()),
+            other => Err(NvrtcError::from_raw(other)),
+        }
+    }
+}
+
+/// Returns the major and minor version of the NVRTC library.
+pub fn version() -> NvrtcResult<(i32, i32)> {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        nvrtcVersion(&mut major as *mut _, &mut minor as *mut _).to_result()?;
+    }
+    Ok((major, minor))
+}
+
+/// Options for compiling a [`Program`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileOption {
+    /// Compiles for a virtual architecture such as `compute_80`, which produces PTX but no cubin.
+    Target(JitTarget),
+    /// Compiles for a real architecture such as `sm_80`, which produces a cubin which can be
+    /// retrieved with [`Program::cubin`] as well as PTX.
+    BinaryTarget(JitTarget),
+    /// Enables fast but less precise math, this is `-use_fast_math`.
+    FastMath,
+    /// Defines a macro, either as `NAME` or `NAME=VALUE`.
+    Define(String),
+    /// Adds a directory to search for headers which are not given to [`Program::new`].
+    IncludePath(String),
+    /// Sets the C++ dialect, such as `c++17`.
+    Std(String),
+    /// Specifies the maximum amount of registers kernels are allowed to use.
+    MaxRegisters(u32),
+    /// Generates relocatable code which can be linked with other relocatable code.
+    Relocatable,
+    /// Generates debug info.
+    DebugInfo,
+    /// Generates line info.
+    LineInfo,
+    /// Any other option, passed to NVRTC as is.
+    Raw(String),
+}
+
+impl CompileOption {
+    fn to_arg(&self) -> String {
+        match self {
+            Self::Target(target) => format!("--gpu-architecture=compute_{}", *target as u32),
+            Self::BinaryTarget(target) => format!("--gpu-architecture=sm_{}", *target as u32),
+            Self::FastMath => "--use_fast_math".to_string(),
+            Self::Define(define) => format!("--define-macro={}", define),
+            Self::IncludePath(path) => format!("--include-path={}", path),
+            Self::Std(std) => format!("--std={}", std),
+            Self::MaxRegisters(regs) => format!("--maxrregcount={}", regs),
+            Self::Relocatable => "--relocatable-device-code=true".to_string(),
+            Self::DebugInfo => "--device-debug".to_string(),
+            Self::LineInfo => "--generate-line-info".to_string(),
+            Self::Raw(raw) => raw.clone(),
+        }
+    }
+}
+
+// NVRTC takes nul-terminated strings, which a `&str` may contain, so reject them like `CString::new`
+fn to_cstring(s: &str) -> NvrtcResult<CString> {
+    CString::new(s).map_err(|_| NvrtcError::InvalidInput)
+}
+
+/// A CUDA C++ program which can be compiled to PTX and cubin.
+///
+/// See the module-level documentation for more information.
+#[derive(Debug)]
+pub struct Program {
+    raw: nvrtcProgram,
+}
+
+unsafe impl Send for Program {}
+
+impl Program {
+    /// Creates a program from CUDA C++ source code. `name` is the name of the source file used in
+    /// the log, and `headers` are `(include name, contents)` pairs of headers the source can
+    /// `#include` without them being on disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NvrtcError::InvalidInput`] if any of the strings contain a nul character, or the
+    /// error from NVRTC if the program could not be created.
+    pub fn new(source: &str, name: &str, headers: &[(&str, &str)]) -> NvrtcResult<Self> {
+        let source = to_cstring(source)?;
+        let name = to_cstring(name)?;
+        let include_names = headers
+            .iter()
+            .map(|(name, _)| to_cstring(name))
+            .collect::<NvrtcResult<Vec<_>>>()?;
+        let contents = headers
+            .iter()
+            .map(|(_, contents)| to_cstring(contents))
+            .collect::<NvrtcResult<Vec<_>>>()?;
+        let include_name_ptrs = include_names.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        let content_ptrs = contents.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+
+        let mut raw = ptr::null_mut();
+        unsafe {
+            nvrtcCreateProgram(
+                &mut raw as *mut _,
+                source.as_ptr(),
+                name.as_ptr(),
+                headers.len() as c_int,
+                content_ptrs.as_ptr(),
+                include_name_ptrs.as_ptr(),
+            )
+            .to_result()?;
+        }
+        Ok(Self { raw })
+    }
+
+    /// Adds a name expression such as `scale<float>` or `&my_namespace::kernel` to the program
+    /// before compiling it, this instantiates templates named by the expression, and makes the
+    /// mangled name of the kernel or variable available with [`Program::lowered_name`].
+    pub fn add_name_expression(&mut self, expression: &str) -> NvrtcResult<()> {
+        let expression = to_cstring(expression)?;
+        unsafe { nvrtcAddNameExpression(self.raw, expression.as_ptr()).to_result() }
+    }
+
+    /// Compiles the program with some options.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NvrtcError::Compilation`] if the program fails to compile, the errors can
+    /// be retrieved with [`Program::log`].
+    pub fn compile(&mut self, options: &[CompileOption]) -> NvrtcResult<()> {
+        let options = options
+            .iter()
+            .map(|option| to_cstring(&option.to_arg()))
+            .collect::<NvrtcResult<Vec<_>>>()?;
+        let option_ptrs = options.iter().map(|s| s.as_ptr()).collect::<Vec<_>>();
+        unsafe {
+            nvrtcCompileProgram(self.raw, option_ptrs.len() as c_int, option_ptrs.as_ptr())
+                .to_result()
+        }
+    }
+
+    /// Returns the log of the last compilation, which contains its errors and warnings.
+    pub fn log(&self) -> NvrtcResult<String> {
+        let bytes = self.get_bytes(nvrtcGetProgramLogSize, nvrtcGetProgramLog)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Returns the PTX of the compiled program, which can be loaded with
+    /// [`Module::from_ptx`](crate::module::Module::from_ptx).
+    pub fn ptx(&self) -> NvrtcResult<String> {
+        let bytes = self.get_bytes(nvrtcGetPTXSize, nvrtcGetPTX)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Returns the cubin of the compiled program, which can be loaded with
+    /// [`Module::from_cubin`](crate::module::Module::from_cubin).
+    ///
+    /// A cubin is only generated when compiling for a real architecture with
+    /// [`CompileOption::BinaryTarget`], otherwise this returns [`NvrtcError::InvalidInput`].
+    pub fn cubin(&self) -> NvrtcResult<Vec<u8>> {
+        let bytes = self.get_bytes(nvrtcGetCUBINSize, nvrtcGetCUBIN)?;
+        if bytes.is_empty() {
+            return Err(NvrtcError::InvalidInput);
+        }
+        Ok(bytes)
+    }
+
+    /// Returns the mangled name of a name expression added with [`Program::add_name_expression`],
+    /// which can be used to get the kernel with [`Module::get_function`](crate::module::Module::get_function).
+    pub fn lowered_name(&self, expression: &str) -> NvrtcResult<String> {
+        let expression = to_cstring(expression)?;
+        let mut lowered = ptr::null();
+        unsafe {
+            nvrtcGetLoweredName(self.raw, expression.as_ptr(), &mut lowered as *mut _)
+                .to_result()?;
+            Ok(CStr::from_ptr(lowered).to_string_lossy().into_owned())
+        }
+    }
+
+    // the log and the ptx are nul-terminated, so the nul is removed here. cubins are not, but
+    // cubins never end in a nul byte anyway.
+    fn get_bytes(
+        &self,
+        get_size: unsafe extern "C" fn(nvrtcProgram, *mut usize) -> nvrtcResult,
+        get: unsafe extern "C" fn(nvrtcProgram, *mut c_char) -> nvrtcResult,
+    ) -> NvrtcResult<Vec<u8>> {
+        unsafe {
+            let mut size = 0;
+            get_size(self.raw, &mut size as *mut _).to_result()?;
+            let mut bytes = vec![0u8; size];
+            if size > 0 {
+                get(self.raw, bytes.as_mut_ptr() as *mut c_char).to_result()?;
+            }
+            if bytes.last() == Some(&0) {
+                bytes.pop();
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        if self.raw.is_null() {
+            return;
+        }
+
+        unsafe {
+            nvrtcDestroyProgram(&mut self.raw as *mut _);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r#"
+        #include "config.h"
+
+        template<typename T>
+        __global__ void fill(T* data, int n) {
+            int i = blockIdx.x * blockDim.x + threadIdx.x;
+            if (i < n) data[i] = VALUE;
+        }
+    "#;
+
+    #[test]
+    fn test_compile_with_headers_and_name_expressions() {
+        let mut program =
+            Program::new(SOURCE, "fill.cu", &[("config.h", "#define VALUE 1")]).unwrap();
+        program.add_name_expression("fill<float>").unwrap();
+        program
+            .compile(&[
+                CompileOption::BinaryTarget(JitTarget::Compute52),
+                CompileOption::Define("UNUSED=2".to_string()),
+                CompileOption::FastMath,
+            ])
+            .unwrap();
+
+        let name = program.lowered_name("fill<float>").unwrap();
+        assert!(name.starts_with("_Z"));
+        assert!(program.ptx().unwrap().contains(&name));
+        assert!(!program.cubin().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compile_error_log() {
+        let mut program = Program::new("__global__ void broken( {}", "broken.cu", &[]).unwrap();
+        assert_eq!(program.compile(&[]), Err(NvrtcError::Compilation));
+        assert!(program.log().unwrap().contains("broken.cu"));
+    }
+}
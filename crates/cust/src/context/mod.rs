@@ -38,7 +38,8 @@ pub mod legacy;
 
 use crate::{
     device::Device,
-    error::{CudaResult, DropResult, ToResult},
+    error::{CudaResult, DriverResult, DropResult, ToResult},
+    memory::{caching, kernels, CachingAllocator},
     private::Sealed,
     sys as cuda, CudaApiVersion,
 };
@@ -191,7 +192,8 @@ impl Context {
     pub fn new(device: Device) -> CudaResult<Self> {
         let mut inner = MaybeUninit::uninit();
         unsafe {
-            cuda::cuDevicePrimaryCtxRetain(inner.as_mut_ptr(), device.as_raw()).to_result()?;
+            cuda::cuDevicePrimaryCtxRetain(inner.as_mut_ptr(), device.as_raw())
+                .to_result_from("cuDevicePrimaryCtxRetain")?;
             let inner = inner.assume_init();
             cuda::cuCtxSetCurrent(inner);
             Ok(Self {
@@ -211,13 +213,30 @@ impl Context {
     /// Nothing else should be using the primary context for this device, otherwise,
     /// spurious errors or segfaults will occur.
     pub unsafe fn reset(device: &Device) -> CudaResult<()> {
-        cuda::cuDevicePrimaryCtxReset_v2(device.as_raw()).to_result()
+        // retaining and releasing an active primary context gives its handle without
+        // destroying it.
+        let mut inner = ptr::null_mut();
+        if primary_context_active(device.as_raw()) {
+            cuda::cuDevicePrimaryCtxRetain(&mut inner, device.as_raw())
+                .to_result_from("cuDevicePrimaryCtxRetain")?;
+            cuda::cuDevicePrimaryCtxRelease_v2(device.as_raw())
+                .to_result_from("cuDevicePrimaryCtxRelease_v2")?;
+        }
+        cuda::cuDevicePrimaryCtxReset_v2(device.as_raw())
+            .to_result_from("cuDevicePrimaryCtxReset_v2")?;
+        if !inner.is_null() {
+            kernels::context_destroyed(inner);
+        }
+        Ok(())
     }
 
     /// Sets the flags for the device context, these flags will apply to any user of the primary
     /// context associated with this device.
     pub fn set_flags(&self, flags: ContextFlags) -> CudaResult<()> {
-        unsafe { cuda::cuDevicePrimaryCtxSetFlags_v2(self.device, flags.bits()).to_result() }
+        unsafe {
+            cuda::cuDevicePrimaryCtxSetFlags_v2(self.device, flags.bits())
+                .to_result_from("cuDevicePrimaryCtxSetFlags_v2")
+        }
     }
 
     /// Returns the raw handle to this context.
@@ -247,13 +266,99 @@ impl Context {
     pub fn get_api_version(&self) -> CudaResult<CudaApiVersion> {
         unsafe {
             let mut api_version = 0u32;
-            cuda::cuCtxGetApiVersion(self.inner, &mut api_version as *mut u32).to_result()?;
+            cuda::cuCtxGetApiVersion(self.inner, &mut api_version as *mut u32)
+                .to_result_from("cuCtxGetApiVersion")?;
             Ok(CudaApiVersion {
                 version: api_version as i32,
             })
         }
     }
 
+    /// Allows this context to directly access memory allocated in `peer`, which allows kernels
+    /// launched in this context to read and write memory on the device of `peer`, and makes copies
+    /// between the two contexts not go through host memory.
+    ///
+    /// Access is only granted in one direction, for `peer` to access memory in this context,
+    /// peer access must be enabled the other way around as well. Whether the devices support this
+    /// can be checked with [`Device::can_access_peer`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CudaError::PeerAccessUnsupported`](crate::error::CudaError::PeerAccessUnsupported)
+    /// if the devices cannot access each other, and
+    /// [`CudaError::PeerAccessAlreadyEnabled`](crate::error::CudaError::PeerAccessAlreadyEnabled) if
+    /// access was already enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cust::device::Device;
+    /// # use cust::context::Context;
+    /// # use std::error::Error;
+    /// #
+    /// # fn main () -> Result<(), Box<dyn Error>> {
+    /// cust::init(cust::CudaFlags::empty())?;
+    /// if Device::num_devices()? < 2 {
+    ///     return Ok(());
+    /// }
+    /// let (first, second) = (Device::get_device(0)?, Device::get_device(1)?);
+    /// if first.can_access_peer(second)? {
+    ///     let first_ctx = Context::new(first)?;
+    ///     let second_ctx = Context::new(second)?;
+    ///     first_ctx.enable_peer_access(&second_ctx)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enable_peer_access(&self, peer: &Context) -> CudaResult<()> {
+        self.with_current(|| unsafe {
+            cuda::cuCtxEnablePeerAccess(peer.inner, 0).to_result_from("cuCtxEnablePeerAccess")
+        })
+    }
+
+    /// Disables access to memory in `peer` previously enabled with
+    /// [`Context::enable_peer_access`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CudaError::PeerAccessNotEnabled`](crate::error::CudaError::PeerAccessNotEnabled)
+    /// if access was not enabled.
+    pub fn disable_peer_access(&self, peer: &Context) -> CudaResult<()> {
+        self.with_current(|| unsafe {
+            cuda::cuCtxDisablePeerAccess(peer.inner).to_result_from("cuCtxDisablePeerAccess")
+        })
+    }
+
+    /// Makes [`DeviceBuffer`](crate::memory::DeviceBuffer)s allocated while this context is current
+    /// come from `allocator`, or from the driver again if `None`. The previous allocator's cached
+    /// memory in this context is freed.
+    ///
+    /// The primary context is retained while an allocator is installed, so it is not destroyed
+    /// when every `Context` handle to it is dropped. Uninstall the allocator to release it.
+    pub fn set_caching_allocator(&self, allocator: Option<&CachingAllocator>) -> CudaResult<()> {
+        caching::install(self.inner, Some(self.device), allocator)
+    }
+
+    /// Returns the allocator installed with [`Context::set_caching_allocator`].
+    pub fn caching_allocator(&self) -> Option<CachingAllocator> {
+        caching::installed(self.inner)
+    }
+
+    // peer access functions act on the current context, so temporarily make this context
+    // current and restore the old one afterwards.
+    fn with_current<T>(&self, f: impl FnOnce() -> CudaResult<T>) -> CudaResult<T> {
+        unsafe {
+            let mut old = ptr::null_mut();
+            cuda::cuCtxGetCurrent(&mut old as *mut _).to_result_from("cuCtxGetCurrent")?;
+            cuda::cuCtxSetCurrent(self.inner).to_result_from("cuCtxSetCurrent")?;
+            let res = f();
+            let restored = cuda::cuCtxSetCurrent(old).to_result_from("cuCtxSetCurrent");
+            let val = res?;
+            restored?;
+            Ok(val)
+        }
+    }
+
     /// Destroy a `Context`, returning an error.
     ///
     /// Destroying a context can return errors from previous asynchronous work. This function
@@ -287,7 +392,7 @@ impl Context {
 
         unsafe {
             let inner = mem::replace(&mut ctx.inner, ptr::null_mut());
-            match cuda::cuDevicePrimaryCtxRelease_v2(ctx.device).to_result() {
+            match release_primary_context(inner, ctx.device) {
                 Ok(()) => {
                     mem::forget(ctx);
                     Ok(())
@@ -311,12 +416,34 @@ impl Drop for Context {
         }
 
         unsafe {
-            self.inner = ptr::null_mut();
-            cuda::cuDevicePrimaryCtxRelease_v2(self.device);
+            let inner = mem::replace(&mut self.inner, ptr::null_mut());
+            let _ = release_primary_context(inner, self.device);
         }
     }
 }
 
+fn primary_context_active(device: cuda::CUdevice) -> bool {
+    let mut flags = 0;
+    let mut active = 0;
+    unsafe {
+        cuda::cuDevicePrimaryCtxGetState(device, &mut flags, &mut active);
+    }
+    active != 0
+}
+
+/// Releases the primary context `context` of `device`, and forgets the state cust keeps for the
+/// context if that destroyed it, since its handle may be reused by a later context.
+pub(crate) unsafe fn release_primary_context(
+    context: cuda::CUcontext,
+    device: cuda::CUdevice,
+) -> CudaResult<()> {
+    cuda::cuDevicePrimaryCtxRelease_v2(device).to_result_from("cuDevicePrimaryCtxRelease_v2")?;
+    if !primary_context_active(device) {
+        kernels::context_destroyed(context);
+    }
+    Ok(())
+}
+
 /// Type representing the context being currently used.
 #[derive(Debug)]
 pub struct CurrentContext;
@@ -347,7 +474,7 @@ impl CurrentContext {
         unsafe {
             let mut config = CacheConfig::PreferNone;
             cuda::cuCtxGetCacheConfig(&mut config as *mut CacheConfig as *mut cuda::CUfunc_cache)
-                .to_result()?;
+                .to_result_from("cuCtxGetCacheConfig")?;
             Ok(config)
         }
     }
@@ -372,7 +499,8 @@ impl CurrentContext {
     pub fn get_device() -> CudaResult<Device> {
         unsafe {
             let mut device = Device { device: 0 };
-            cuda::cuCtxGetDevice(&mut device.device as *mut cuda::CUdevice).to_result()?;
+            cuda::cuCtxGetDevice(&mut device.device as *mut cuda::CUdevice)
+                .to_result_from("cuCtxGetDevice")?;
             Ok(device)
         }
     }
@@ -397,7 +525,7 @@ impl CurrentContext {
     pub fn get_flags() -> CudaResult<ContextFlags> {
         unsafe {
             let mut flags = 0u32;
-            cuda::cuCtxGetFlags(&mut flags as *mut u32).to_result()?;
+            cuda::cuCtxGetFlags(&mut flags as *mut u32).to_result_from("cuCtxGetFlags")?;
             Ok(ContextFlags::from_bits_truncate(flags))
         }
     }
@@ -422,7 +550,8 @@ impl CurrentContext {
     pub fn get_resource_limit(resource: ResourceLimit) -> CudaResult<usize> {
         unsafe {
             let mut limit: usize = 0;
-            cuda::cuCtxGetLimit(&mut limit as *mut usize, transmute(resource)).to_result()?;
+            cuda::cuCtxGetLimit(&mut limit as *mut usize, transmute(resource))
+                .to_result_from("cuCtxGetLimit")?;
             Ok(limit)
         }
     }
@@ -450,7 +579,7 @@ impl CurrentContext {
             cuda::cuCtxGetSharedMemConfig(
                 &mut cfg as *mut SharedMemoryConfig as *mut cuda::CUsharedconfig,
             )
-            .to_result()?;
+            .to_result_from("cuCtxGetSharedMemConfig")?;
             Ok(cfg)
         }
     }
@@ -486,7 +615,7 @@ impl CurrentContext {
                 &mut range.least as *mut i32,
                 &mut range.greatest as *mut i32,
             )
-            .to_result()?;
+            .to_result_from("cuCtxGetStreamPriorityRange")?;
             Ok(range)
         }
     }
@@ -517,7 +646,7 @@ impl CurrentContext {
     /// # }
     /// ```
     pub fn set_cache_config(cfg: CacheConfig) -> CudaResult<()> {
-        unsafe { cuda::cuCtxSetCacheConfig(transmute(cfg)).to_result() }
+        unsafe { cuda::cuCtxSetCacheConfig(transmute(cfg)).to_result_from("cuCtxSetCacheConfig") }
     }
 
     /// Sets a requested resource limit for the current context.
@@ -562,7 +691,7 @@ impl CurrentContext {
     /// ```
     pub fn set_resource_limit(resource: ResourceLimit, limit: usize) -> CudaResult<()> {
         unsafe {
-            cuda::cuCtxSetLimit(transmute(resource), limit).to_result()?;
+            cuda::cuCtxSetLimit(transmute(resource), limit).to_result_from("cuCtxSetLimit")?;
             Ok(())
         }
     }
@@ -588,7 +717,9 @@ impl CurrentContext {
     /// # }
     /// ```
     pub fn set_shared_memory_config(cfg: SharedMemoryConfig) -> CudaResult<()> {
-        unsafe { cuda::cuCtxSetSharedMemConfig(transmute(cfg)).to_result() }
+        unsafe {
+            cuda::cuCtxSetSharedMemConfig(transmute(cfg)).to_result_from("cuCtxSetSharedMemConfig")
+        }
     }
 
     /// Set the given context as the current context for this thread.
@@ -610,16 +741,13 @@ impl CurrentContext {
     /// ```
     pub fn set_current<C: ContextHandle>(c: &C) -> CudaResult<()> {
         unsafe {
-            cuda::cuCtxSetCurrent(c.get_inner()).to_result()?;
+            cuda::cuCtxSetCurrent(c.get_inner()).to_result_from("cuCtxSetCurrent")?;
             Ok(())
         }
     }
 
     /// Block to wait for a context's tasks to complete.
-    pub fn synchronize() -> CudaResult<()> {
-        unsafe {
-            cuda::cuCtxSynchronize().to_result()?;
-            Ok(())
-        }
+    pub fn synchronize() -> DriverResult<()> {
+        unsafe { cuda::cuCtxSynchronize().to_driver_result("cuCtxSynchronize") }
     }
 }
@@ -193,6 +193,10 @@ pub enum DeviceAttribute {
     ComputePreemptionSupported = 90,
     /// Device can access host registered memory at the same virtual address as the CPU
     CanUseHostPointerForRegisteredMem = 91,
+    /// Device supports launching cooperative kernels
+    CooperativeLaunch = 95,
+    /// Device supports launching cooperative kernels on multiple devices
+    CooperativeMultiDeviceLaunch = 96,
 }
 
 /// Opaque handle to a CUDA device.
@@ -221,7 +225,7 @@ impl Device {
     pub fn num_devices() -> CudaResult<u32> {
         unsafe {
             let mut num_devices = 0i32;
-            cuDeviceGetCount(&mut num_devices as *mut i32).to_result()?;
+            cuDeviceGetCount(&mut num_devices as *mut i32).to_result_from("cuDeviceGetCount")?;
             Ok(num_devices as u32)
         }
     }
@@ -245,7 +249,8 @@ impl Device {
     pub fn get_device(ordinal: u32) -> CudaResult<Device> {
         unsafe {
             let mut device = Device { device: 0 };
-            cuDeviceGet(&mut device.device as *mut CUdevice, ordinal as i32).to_result()?;
+            cuDeviceGet(&mut device.device as *mut CUdevice, ordinal as i32)
+                .to_result_from("cuDeviceGet")?;
             Ok(device)
         }
     }
@@ -289,7 +294,8 @@ impl Device {
     pub fn total_memory(self) -> CudaResult<usize> {
         unsafe {
             let mut memory = 0;
-            cuDeviceTotalMem_v2(&mut memory as *mut usize, self.device).to_result()?;
+            cuDeviceTotalMem_v2(&mut memory as *mut usize, self.device)
+                .to_result_from("cuDeviceTotalMem_v2")?;
             Ok(memory)
         }
     }
@@ -316,7 +322,7 @@ impl Device {
                 128,
                 self.device,
             )
-            .to_result()?;
+            .to_result_from("cuDeviceGetName")?;
             let nul_index = name
                 .iter()
                 .cloned()
@@ -344,7 +350,7 @@ impl Device {
     pub fn uuid(self) -> CudaResult<[u8; 16]> {
         let mut cu_uuid = CUuuid { bytes: [0i8; 16] };
         unsafe {
-            cuDeviceGetUuid(&mut cu_uuid, self.device).to_result()?;
+            cuDeviceGetUuid(&mut cu_uuid, self.device).to_result_from("cuDeviceGetUuid")?;
         }
         let uuid: [u8; 16] = cu_uuid.bytes.map(|byte| byte as u8);
         Ok(uuid)
@@ -374,11 +380,39 @@ impl Device {
                 ::std::mem::transmute(attr),
                 self.device,
             )
-            .to_result()?;
+            .to_result_from("cuDeviceGetAttribute")?;
             Ok(val)
         }
     }
 
+    /// Returns whether contexts on this device can directly access memory allocated on `peer`
+    /// once peer access is enabled with [`Context::enable_peer_access`](crate::context::Context::enable_peer_access).
+    ///
+    /// # Example
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # init(CudaFlags::empty())?;
+    /// use cust::device::Device;
+    /// let devices = Device::devices()?.collect::<Result<Vec<_>, _>>()?;
+    /// for device in &devices {
+    ///     for peer in devices.iter().filter(|peer| *peer != device) {
+    ///         println!("{} -> {}: {}", device.name()?, peer.name()?, device.can_access_peer(*peer)?);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn can_access_peer(self, peer: Device) -> CudaResult<bool> {
+        unsafe {
+            let mut can_access = 0;
+            cuDeviceCanAccessPeer(&mut can_access as *mut _, self.device, peer.device)
+                .to_result_from("cuDeviceCanAccessPeer")?;
+            Ok(can_access != 0)
+        }
+    }
+
     /// Returns a raw handle to this device, not handing over ownership, meaning that dropping
     /// this device will try to drop the underlying device.
     pub fn as_raw(&self) -> CUdevice {
@@ -403,6 +437,8 @@ impl Iterator for Devices {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::context::Context;
+    use crate::error::CudaError;
     use std::error::Error;
 
     fn test_init() -> Result<(), Box<dyn Error>> {
@@ -437,6 +473,32 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_can_access_peer() -> Result<(), Box<dyn Error>> {
+        test_init()?;
+        let device = Device::get_device(0)?;
+        let context = Context::new(device)?;
+        for peer in Device::devices()? {
+            let peer = peer?;
+            if peer == device {
+                continue;
+            }
+            // whether peers are supported depends on the system, but enabling access has to
+            // agree with the query either way.
+            let peer_context = Context::new(peer)?;
+            if device.can_access_peer(peer)? {
+                context.enable_peer_access(&peer_context)?;
+                context.disable_peer_access(&peer_context)?;
+            } else {
+                assert_eq!(
+                    context.enable_peer_access(&peer_context),
+                    Err(CudaError::PeerAccessUnsupported)
+                );
+            }
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_get_memory() -> Result<(), Box<dyn Error>> {
         test_init()?;
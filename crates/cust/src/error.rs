@@ -6,8 +6,22 @@
 //! the CUDA API. It is important to note that nearly every function in CUDA (and therefore
 //! cust) can fail. Even those functions which have no normal failure conditions can return
 //! errors related to previous asynchronous launches.
+//!
+//! Some errors, such as [`CudaError::LaunchFailed`], are sticky: they leave the context in an
+//! unusable state and every later call in it returns the same error, so the error usually shows
+//! up in a call which has nothing to do with its cause. [`CudaError::is_sticky`] tells them
+//! apart from errors which can be recovered from.
+//!
+//! Launches and synchronization, where the errors of earlier asynchronous work show up, return a
+//! [`DriverError`] with the name of the driver function which failed and the driver's description
+//! of the error. It converts into a [`CudaError`], so `?` works in functions returning a
+//! [`CudaResult`]. For the other functions, [`last_error`] returns the `DriverError` for the last
+//! driver call that failed on the current thread.
 
 use crate::sys::{self as cuda, cudaError_enum};
+#[cfg(feature = "backtrace")]
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
 use std::error::Error;
 use std::ffi::CStr;
 use std::fmt;
@@ -78,32 +92,94 @@ pub enum CudaError {
     InvalidAddressSpace = 717,
     InvalidProgramCounter = 718,
     LaunchFailed = 719,
+    CooperativeLaunchTooLarge = 720,
     NotPermitted = 800,
     NotSupported = 801,
+    StreamCaptureUnsupported = 900,
+    StreamCaptureInvalidated = 901,
+    StreamCaptureMerge = 902,
+    StreamCaptureUnmatched = 903,
+    StreamCaptureUnjoined = 904,
+    StreamCaptureIsolation = 905,
+    StreamCaptureImplicit = 906,
+    CapturedEvent = 907,
+    StreamCaptureWrongThread = 908,
+    Timeout = 909,
+    GraphExecUpdateFailure = 910,
     UnknownError = 999,
 
     // cust errors
     InvalidMemoryAllocation = 100_100,
     OptixError = 100_101,
 }
+impl CudaError {
+    /// Returns the name of the error given by `cuGetErrorName`, such as
+    /// `"CUDA_ERROR_LAUNCH_FAILED"`, or `None` for errors which do not come from the driver.
+    pub fn name(self) -> Option<&'static str> {
+        self.driver_string(cuda::cuGetErrorName)
+    }
+
+    /// Returns the description of the error given by `cuGetErrorString`, such as
+    /// `"unspecified launch failure"`, or `None` for errors which do not come from the driver.
+    pub fn description(self) -> Option<&'static str> {
+        self.driver_string(cuda::cuGetErrorString)
+    }
+
+    /// Returns `true` if the error is sticky.
+    ///
+    /// Sticky errors happen when a kernel does something the device cannot recover from, such as
+    /// accessing an illegal address. They corrupt the context, and every later call using it will
+    /// return the same error, so the only way to recover is to destroy the context and create a
+    /// new one. Other errors only affect the call which returned them.
+    pub fn is_sticky(self) -> bool {
+        matches!(
+            self,
+            CudaError::EccUncorrectable
+                | CudaError::NvlinkUncorrectable
+                | CudaError::IllegalAddress
+                | CudaError::LaunchTimeout
+                | CudaError::AssertError
+                | CudaError::HardwareStackError
+                | CudaError::IllegalInstruction
+                | CudaError::MisalignedAddress
+                | CudaError::InvalidAddressSpace
+                | CudaError::InvalidProgramCounter
+                | CudaError::LaunchFailed
+        )
+    }
+
+    fn driver_string(
+        self,
+        get: unsafe extern "C" fn(cudaError_enum, *mut *const c_char) -> cudaError_enum,
+    ) -> Option<&'static str> {
+        let value = self as u32;
+        if value > 999 {
+            return None;
+        }
+        let mut ptr: *const c_char = ptr::null();
+        unsafe {
+            if get(mem::transmute(value), &mut ptr as *mut *const c_char)
+                != cudaError_enum::CUDA_SUCCESS
+                || ptr.is_null()
+            {
+                return None;
+            }
+            // the driver's strings live for as long as the process.
+            CStr::from_ptr(ptr).to_str().ok()
+        }
+    }
+}
+
 impl fmt::Display for CudaError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             CudaError::InvalidMemoryAllocation => write!(f, "Invalid memory allocation"),
             CudaError::OptixError => write!(f, "OptiX error"),
-            other if (other as u32) <= 999 => {
-                let value = other as u32;
-                let mut ptr: *const c_char = ptr::null();
-                unsafe {
-                    cuda::cuGetErrorString(mem::transmute(value), &mut ptr as *mut *const c_char)
-                        .to_result()
-                        .map_err(|_| fmt::Error)?;
-                    let cstr = CStr::from_ptr(ptr);
-                    write!(f, "{:?}", cstr)
-                }
-            }
-            // This shouldn't happen
-            _ => write!(f, "Unknown error"),
+            other => match other.description() {
+                Some(description) => write!(f, "{:?}", description),
+                // This shouldn't happen
+                None => write!(f, "Unknown error"),
+            },
         }
     }
 }
@@ -115,103 +191,395 @@ pub type CudaResult<T> = Result<T, CudaError>;
 /// Special result type for `drop` functions which includes the un-dropped value with the error.
 pub type DropResult<T> = Result<(), (CudaError, T)>;
 
+/// Result type for the functions which return a [`DriverError`].
+pub type DriverResult<T> = Result<T, DriverError>;
+
+/// A failed call to the CUDA driver, along with the name of the driver function which failed.
+///
+/// Launches and synchronization return a `DriverError`, most other cust functions return plain
+/// [`CudaError`]s and the `DriverError` for the last failed driver call on a thread can be
+/// retrieved with [`last_error`].
+#[derive(Debug)]
+pub struct DriverError {
+    error: CudaError,
+    function: Option<&'static str>,
+    message: Option<String>,
+    #[cfg(feature = "backtrace")]
+    backtrace: Option<Backtrace>,
+}
+
+impl DriverError {
+    pub(crate) fn new(error: CudaError, function: Option<&'static str>) -> Self {
+        Self {
+            error,
+            function,
+            message: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: {
+                let backtrace = Backtrace::capture();
+                if backtrace.status() == BacktraceStatus::Captured {
+                    Some(backtrace)
+                } else {
+                    None
+                }
+            },
+        }
+    }
+
+    /// Attaches a description of what was wrong with the call, for errors which cust detects
+    /// itself before calling `function`.
+    pub(crate) fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    /// The error returned by the driver.
+    pub fn error(&self) -> CudaError {
+        self.error
+    }
+
+    /// The name of the driver function which failed, such as `"cuMemAlloc_v2"`, if it is known.
+    pub fn function(&self) -> Option<&'static str> {
+        self.function
+    }
+
+    /// What was wrong with the call, such as the limit which it exceeded, if cust knows more than
+    /// the error code tells.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// The name of the error given by `cuGetErrorName`, see [`CudaError::name`].
+    pub fn name(&self) -> Option<&'static str> {
+        self.error.name()
+    }
+
+    /// The description of the error given by `cuGetErrorString`, see
+    /// [`CudaError::description`].
+    pub fn description(&self) -> Option<&'static str> {
+        self.error.description()
+    }
+
+    /// Whether the error corrupted the context, see [`CudaError::is_sticky`].
+    pub fn is_sticky(&self) -> bool {
+        self.error.is_sticky()
+    }
+
+    /// The backtrace of the call which failed. It is only captured if `RUST_BACKTRACE` or
+    /// `RUST_LIB_BACKTRACE` is set, like the backtraces of panics.
+    #[cfg(feature = "backtrace")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "backtrace")))]
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_ref()
+    }
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.function {
+            Some(function) => write!(f, "{} failed with ", function)?,
+            None => write!(f, "CUDA driver call failed with ")?,
+        }
+        match (self.name(), self.description()) {
+            (Some(name), Some(description)) => write!(f, "{} ({})", name, description)?,
+            _ => write!(f, "{:?}", self.error)?,
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if self.is_sticky() {
+            write!(f, ", the context is no longer usable")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DriverError {}
+
+impl From<CudaError> for DriverError {
+    fn from(error: CudaError) -> Self {
+        Self::new(error, None)
+    }
+}
+
+impl From<DriverError> for CudaError {
+    fn from(error: DriverError) -> Self {
+        error.error
+    }
+}
+
+impl PartialEq<CudaError> for DriverError {
+    fn eq(&self, other: &CudaError) -> bool {
+        self.error == *other
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<DriverError>> = RefCell::new(None);
+    static SUPPRESSED: Cell<usize> = Cell::new(0);
+}
+
+/// Takes the error of the last driver call which failed on the current thread, leaving `None`
+/// in its place.
+///
+/// Every failed driver call made by cust replaces the last error, so this should be called right
+/// after the cust function which failed. There are a few exceptions so that the error which
+/// caused a failure is not lost:
+///
+/// - A sticky error is kept until it is taken, instead of being replaced by the later calls which
+///   fail because of it.
+/// - [`CudaError::NotReady`] is a status rather than a failure and is never recorded.
+/// - Driver calls made while dropping cust types are never recorded, so the calls which free
+///   resources while a `?` unwinds don't replace the error which caused it.
+///
+/// # Example
+///
+/// ```
+/// # use cust::memory::DeviceBuffer;
+/// # let _context = cust::quick_init().unwrap();
+/// if let Err(e) = DeviceBuffer::<u8>::zeroed(usize::MAX / 2) {
+///     let err = cust::error::last_error().unwrap();
+///     assert_eq!(err.error(), e);
+///     println!("{}", err);
+/// }
+/// ```
+pub fn last_error() -> Option<DriverError> {
+    LAST_ERROR.with(|last| last.borrow_mut().take())
+}
+
+fn set_last_error(error: CudaError, function: Option<&'static str>) {
+    if error == CudaError::NotReady || SUPPRESSED.with(Cell::get) > 0 {
+        return;
+    }
+    LAST_ERROR.with(|last| {
+        let mut last = last.borrow_mut();
+        // every call after a sticky error fails with it, the first one is the one to look at.
+        if matches!(&*last, Some(last) if last.error == error && error.is_sticky()) {
+            return;
+        }
+        *last = Some(DriverError::new(error, function));
+    });
+}
+
+/// Stops recording failed driver calls for [`last_error`] on the current thread until the
+/// returned guard is dropped. Used by the `Drop` impls of cust types.
+pub(crate) fn suppress_last_error() -> SuppressLastError {
+    SUPPRESSED.with(|suppressed| suppressed.set(suppressed.get() + 1));
+    SuppressLastError(())
+}
+
+pub(crate) struct SuppressLastError(());
+
+impl Drop for SuppressLastError {
+    fn drop(&mut self) {
+        SUPPRESSED.with(|suppressed| suppressed.set(suppressed.get() - 1));
+    }
+}
+
 pub(crate) trait ToResult {
     fn to_result(self) -> CudaResult<()>;
+
+    /// Converts the status returned by the driver function `function` to a result, recording
+    /// the function for [`last_error`] if it failed.
+    fn to_result_from(self, _function: &'static str) -> CudaResult<()>
+    where
+        Self: Sized,
+    {
+        self.to_result()
+    }
+
+    /// Like [`ToResult::to_result_from`], but the error is the [`DriverError`] it records.
+    fn to_driver_result(self, function: &'static str) -> DriverResult<()>
+    where
+        Self: Sized,
+    {
+        self.to_result_from(function)
+            .map_err(|error| DriverError::new(error, Some(function)))
+    }
 }
 impl ToResult for cudaError_enum {
     fn to_result(self) -> CudaResult<()> {
-        match self {
-            cudaError_enum::CUDA_SUCCESS => Ok(()),
-            cudaError_enum::CUDA_ERROR_INVALID_VALUE => Err(CudaError::InvalidValue),
-            cudaError_enum::CUDA_ERROR_OUT_OF_MEMORY => Err(CudaError::OutOfMemory),
-            cudaError_enum::CUDA_ERROR_NOT_INITIALIZED => Err(CudaError::NotInitialized),
-            cudaError_enum::CUDA_ERROR_DEINITIALIZED => Err(CudaError::Deinitialized),
-            cudaError_enum::CUDA_ERROR_PROFILER_DISABLED => Err(CudaError::ProfilerDisabled),
-            cudaError_enum::CUDA_ERROR_PROFILER_NOT_INITIALIZED => {
-                Err(CudaError::ProfilerNotInitialized)
-            }
-            cudaError_enum::CUDA_ERROR_PROFILER_ALREADY_STARTED => {
-                Err(CudaError::ProfilerAlreadyStarted)
-            }
-            cudaError_enum::CUDA_ERROR_PROFILER_ALREADY_STOPPED => {
-                Err(CudaError::ProfilerAlreadyStopped)
-            }
-            cudaError_enum::CUDA_ERROR_NO_DEVICE => Err(CudaError::NoDevice),
-            cudaError_enum::CUDA_ERROR_INVALID_DEVICE => Err(CudaError::InvalidDevice),
-            cudaError_enum::CUDA_ERROR_INVALID_IMAGE => Err(CudaError::InvalidImage),
-            cudaError_enum::CUDA_ERROR_INVALID_CONTEXT => Err(CudaError::InvalidContext),
-            cudaError_enum::CUDA_ERROR_CONTEXT_ALREADY_CURRENT => {
-                Err(CudaError::ContextAlreadyCurrent)
-            }
-            cudaError_enum::CUDA_ERROR_MAP_FAILED => Err(CudaError::MapFailed),
-            cudaError_enum::CUDA_ERROR_UNMAP_FAILED => Err(CudaError::UnmapFailed),
-            cudaError_enum::CUDA_ERROR_ARRAY_IS_MAPPED => Err(CudaError::ArrayIsMapped),
-            cudaError_enum::CUDA_ERROR_ALREADY_MAPPED => Err(CudaError::AlreadyMapped),
-            cudaError_enum::CUDA_ERROR_NO_BINARY_FOR_GPU => Err(CudaError::NoBinaryForGpu),
-            cudaError_enum::CUDA_ERROR_ALREADY_ACQUIRED => Err(CudaError::AlreadyAcquired),
-            cudaError_enum::CUDA_ERROR_NOT_MAPPED => Err(CudaError::NotMapped),
-            cudaError_enum::CUDA_ERROR_NOT_MAPPED_AS_ARRAY => Err(CudaError::NotMappedAsArray),
-            cudaError_enum::CUDA_ERROR_NOT_MAPPED_AS_POINTER => Err(CudaError::NotMappedAsPointer),
-            cudaError_enum::CUDA_ERROR_ECC_UNCORRECTABLE => Err(CudaError::EccUncorrectable),
-            cudaError_enum::CUDA_ERROR_UNSUPPORTED_LIMIT => Err(CudaError::UnsupportedLimit),
-            cudaError_enum::CUDA_ERROR_CONTEXT_ALREADY_IN_USE => {
-                Err(CudaError::ContextAlreadyInUse)
-            }
-            cudaError_enum::CUDA_ERROR_PEER_ACCESS_UNSUPPORTED => {
-                Err(CudaError::PeerAccessUnsupported)
-            }
-            cudaError_enum::CUDA_ERROR_INVALID_PTX => Err(CudaError::InvalidPtx),
-            cudaError_enum::CUDA_ERROR_INVALID_GRAPHICS_CONTEXT => {
-                Err(CudaError::InvalidGraphicsContext)
-            }
-            cudaError_enum::CUDA_ERROR_NVLINK_UNCORRECTABLE => Err(CudaError::NvlinkUncorrectable),
-            cudaError_enum::CUDA_ERROR_INVALID_SOURCE => Err(CudaError::InvalidSource),
-            cudaError_enum::CUDA_ERROR_FILE_NOT_FOUND => Err(CudaError::FileNotFound),
-            cudaError_enum::CUDA_ERROR_SHARED_OBJECT_SYMBOL_NOT_FOUND => {
-                Err(CudaError::SharedObjectSymbolNotFound)
-            }
-            cudaError_enum::CUDA_ERROR_SHARED_OBJECT_INIT_FAILED => {
-                Err(CudaError::SharedObjectInitFailed)
-            }
-            cudaError_enum::CUDA_ERROR_OPERATING_SYSTEM => Err(CudaError::OperatingSystemError),
-            cudaError_enum::CUDA_ERROR_INVALID_HANDLE => Err(CudaError::InvalidHandle),
-            cudaError_enum::CUDA_ERROR_NOT_FOUND => Err(CudaError::NotFound),
-            cudaError_enum::CUDA_ERROR_NOT_READY => Err(CudaError::NotReady),
-            cudaError_enum::CUDA_ERROR_ILLEGAL_ADDRESS => Err(CudaError::IllegalAddress),
-            cudaError_enum::CUDA_ERROR_LAUNCH_OUT_OF_RESOURCES => {
-                Err(CudaError::LaunchOutOfResources)
-            }
-            cudaError_enum::CUDA_ERROR_LAUNCH_TIMEOUT => Err(CudaError::LaunchTimeout),
-            cudaError_enum::CUDA_ERROR_LAUNCH_INCOMPATIBLE_TEXTURING => {
-                Err(CudaError::LaunchIncompatibleTexturing)
-            }
-            cudaError_enum::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED => {
-                Err(CudaError::PeerAccessAlreadyEnabled)
-            }
-            cudaError_enum::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED => {
-                Err(CudaError::PeerAccessNotEnabled)
-            }
-            cudaError_enum::CUDA_ERROR_PRIMARY_CONTEXT_ACTIVE => {
-                Err(CudaError::PrimaryContextActive)
-            }
-            cudaError_enum::CUDA_ERROR_CONTEXT_IS_DESTROYED => Err(CudaError::ContextIsDestroyed),
-            cudaError_enum::CUDA_ERROR_ASSERT => Err(CudaError::AssertError),
-            cudaError_enum::CUDA_ERROR_TOO_MANY_PEERS => Err(CudaError::TooManyPeers),
-            cudaError_enum::CUDA_ERROR_HOST_MEMORY_ALREADY_REGISTERED => {
-                Err(CudaError::HostMemoryAlreadyRegistered)
-            }
-            cudaError_enum::CUDA_ERROR_HOST_MEMORY_NOT_REGISTERED => {
-                Err(CudaError::HostMemoryNotRegistered)
-            }
-            cudaError_enum::CUDA_ERROR_HARDWARE_STACK_ERROR => Err(CudaError::HardwareStackError),
-            cudaError_enum::CUDA_ERROR_ILLEGAL_INSTRUCTION => Err(CudaError::IllegalInstruction),
-            cudaError_enum::CUDA_ERROR_MISALIGNED_ADDRESS => Err(CudaError::MisalignedAddress),
-            cudaError_enum::CUDA_ERROR_INVALID_ADDRESS_SPACE => Err(CudaError::InvalidAddressSpace),
-            cudaError_enum::CUDA_ERROR_INVALID_PC => Err(CudaError::InvalidProgramCounter),
-            cudaError_enum::CUDA_ERROR_LAUNCH_FAILED => Err(CudaError::LaunchFailed),
-            cudaError_enum::CUDA_ERROR_NOT_PERMITTED => Err(CudaError::NotPermitted),
-            cudaError_enum::CUDA_ERROR_NOT_SUPPORTED => Err(CudaError::NotSupported),
-            _ => Err(CudaError::UnknownError),
+        let res = into_result(self);
+        if let Err(error) = res {
+            set_last_error(error, None);
+        }
+        res
+    }
+
+    fn to_result_from(self, function: &'static str) -> CudaResult<()> {
+        let res = into_result(self);
+        if let Err(error) = res {
+            set_last_error(error, Some(function));
+        }
+        res
+    }
+}
+
+fn into_result(status: cudaError_enum) -> CudaResult<()> {
+    match status {
+        cudaError_enum::CUDA_SUCCESS => Ok(()),
+        cudaError_enum::CUDA_ERROR_INVALID_VALUE => Err(CudaError::InvalidValue),
+        cudaError_enum::CUDA_ERROR_OUT_OF_MEMORY => Err(CudaError::OutOfMemory),
+        cudaError_enum::CUDA_ERROR_NOT_INITIALIZED => Err(CudaError::NotInitialized),
+        cudaError_enum::CUDA_ERROR_DEINITIALIZED => Err(CudaError::Deinitialized),
+        cudaError_enum::CUDA_ERROR_PROFILER_DISABLED => Err(CudaError::ProfilerDisabled),
+        cudaError_enum::CUDA_ERROR_PROFILER_NOT_INITIALIZED => {
+            Err(CudaError::ProfilerNotInitialized)
+        }
+        cudaError_enum::CUDA_ERROR_PROFILER_ALREADY_STARTED => {
+            Err(CudaError::ProfilerAlreadyStarted)
+        }
+        cudaError_enum::CUDA_ERROR_PROFILER_ALREADY_STOPPED => {
+            Err(CudaError::ProfilerAlreadyStopped)
+        }
+        cudaError_enum::CUDA_ERROR_NO_DEVICE => Err(CudaError::NoDevice),
+        cudaError_enum::CUDA_ERROR_INVALID_DEVICE => Err(CudaError::InvalidDevice),
+        cudaError_enum::CUDA_ERROR_INVALID_IMAGE => Err(CudaError::InvalidImage),
+        cudaError_enum::CUDA_ERROR_INVALID_CONTEXT => Err(CudaError::InvalidContext),
+        cudaError_enum::CUDA_ERROR_CONTEXT_ALREADY_CURRENT => Err(CudaError::ContextAlreadyCurrent),
+        cudaError_enum::CUDA_ERROR_MAP_FAILED => Err(CudaError::MapFailed),
+        cudaError_enum::CUDA_ERROR_UNMAP_FAILED => Err(CudaError::UnmapFailed),
+        cudaError_enum::CUDA_ERROR_ARRAY_IS_MAPPED => Err(CudaError::ArrayIsMapped),
+        cudaError_enum::CUDA_ERROR_ALREADY_MAPPED => Err(CudaError::AlreadyMapped),
+        cudaError_enum::CUDA_ERROR_NO_BINARY_FOR_GPU => Err(CudaError::NoBinaryForGpu),
+        cudaError_enum::CUDA_ERROR_ALREADY_ACQUIRED => Err(CudaError::AlreadyAcquired),
+        cudaError_enum::CUDA_ERROR_NOT_MAPPED => Err(CudaError::NotMapped),
+        cudaError_enum::CUDA_ERROR_NOT_MAPPED_AS_ARRAY => Err(CudaError::NotMappedAsArray),
+        cudaError_enum::CUDA_ERROR_NOT_MAPPED_AS_POINTER => Err(CudaError::NotMappedAsPointer),
+        cudaError_enum::CUDA_ERROR_ECC_UNCORRECTABLE => Err(CudaError::EccUncorrectable),
+        cudaError_enum::CUDA_ERROR_UNSUPPORTED_LIMIT => Err(CudaError::UnsupportedLimit),
+        cudaError_enum::CUDA_ERROR_CONTEXT_ALREADY_IN_USE => Err(CudaError::ContextAlreadyInUse),
+        cudaError_enum::CUDA_ERROR_PEER_ACCESS_UNSUPPORTED => Err(CudaError::PeerAccessUnsupported),
+        cudaError_enum::CUDA_ERROR_INVALID_PTX => Err(CudaError::InvalidPtx),
+        cudaError_enum::CUDA_ERROR_INVALID_GRAPHICS_CONTEXT => {
+            Err(CudaError::InvalidGraphicsContext)
+        }
+        cudaError_enum::CUDA_ERROR_NVLINK_UNCORRECTABLE => Err(CudaError::NvlinkUncorrectable),
+        cudaError_enum::CUDA_ERROR_INVALID_SOURCE => Err(CudaError::InvalidSource),
+        cudaError_enum::CUDA_ERROR_FILE_NOT_FOUND => Err(CudaError::FileNotFound),
+        cudaError_enum::CUDA_ERROR_SHARED_OBJECT_SYMBOL_NOT_FOUND => {
+            Err(CudaError::SharedObjectSymbolNotFound)
+        }
+        cudaError_enum::CUDA_ERROR_SHARED_OBJECT_INIT_FAILED => {
+            Err(CudaError::SharedObjectInitFailed)
+        }
+        cudaError_enum::CUDA_ERROR_OPERATING_SYSTEM => Err(CudaError::OperatingSystemError),
+        cudaError_enum::CUDA_ERROR_INVALID_HANDLE => Err(CudaError::InvalidHandle),
+        cudaError_enum::CUDA_ERROR_NOT_FOUND => Err(CudaError::NotFound),
+        cudaError_enum::CUDA_ERROR_NOT_READY => Err(CudaError::NotReady),
+        cudaError_enum::CUDA_ERROR_ILLEGAL_ADDRESS => Err(CudaError::IllegalAddress),
+        cudaError_enum::CUDA_ERROR_LAUNCH_OUT_OF_RESOURCES => Err(CudaError::LaunchOutOfResources),
+        cudaError_enum::CUDA_ERROR_LAUNCH_TIMEOUT => Err(CudaError::LaunchTimeout),
+        cudaError_enum::CUDA_ERROR_LAUNCH_INCOMPATIBLE_TEXTURING => {
+            Err(CudaError::LaunchIncompatibleTexturing)
+        }
+        cudaError_enum::CUDA_ERROR_PEER_ACCESS_ALREADY_ENABLED => {
+            Err(CudaError::PeerAccessAlreadyEnabled)
+        }
+        cudaError_enum::CUDA_ERROR_PEER_ACCESS_NOT_ENABLED => Err(CudaError::PeerAccessNotEnabled),
+        cudaError_enum::CUDA_ERROR_PRIMARY_CONTEXT_ACTIVE => Err(CudaError::PrimaryContextActive),
+        cudaError_enum::CUDA_ERROR_CONTEXT_IS_DESTROYED => Err(CudaError::ContextIsDestroyed),
+        cudaError_enum::CUDA_ERROR_ASSERT => Err(CudaError::AssertError),
+        cudaError_enum::CUDA_ERROR_TOO_MANY_PEERS => Err(CudaError::TooManyPeers),
+        cudaError_enum::CUDA_ERROR_HOST_MEMORY_ALREADY_REGISTERED => {
+            Err(CudaError::HostMemoryAlreadyRegistered)
+        }
+        cudaError_enum::CUDA_ERROR_HOST_MEMORY_NOT_REGISTERED => {
+            Err(CudaError::HostMemoryNotRegistered)
+        }
+        cudaError_enum::CUDA_ERROR_HARDWARE_STACK_ERROR => Err(CudaError::HardwareStackError),
+        cudaError_enum::CUDA_ERROR_ILLEGAL_INSTRUCTION => Err(CudaError::IllegalInstruction),
+        cudaError_enum::CUDA_ERROR_MISALIGNED_ADDRESS => Err(CudaError::MisalignedAddress),
+        cudaError_enum::CUDA_ERROR_INVALID_ADDRESS_SPACE => Err(CudaError::InvalidAddressSpace),
+        cudaError_enum::CUDA_ERROR_INVALID_PC => Err(CudaError::InvalidProgramCounter),
+        cudaError_enum::CUDA_ERROR_LAUNCH_FAILED => Err(CudaError::LaunchFailed),
+        cudaError_enum::CUDA_ERROR_COOPERATIVE_LAUNCH_TOO_LARGE => {
+            Err(CudaError::CooperativeLaunchTooLarge)
+        }
+        cudaError_enum::CUDA_ERROR_NOT_PERMITTED => Err(CudaError::NotPermitted),
+        cudaError_enum::CUDA_ERROR_NOT_SUPPORTED => Err(CudaError::NotSupported),
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_UNSUPPORTED => {
+            Err(CudaError::StreamCaptureUnsupported)
         }
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_INVALIDATED => {
+            Err(CudaError::StreamCaptureInvalidated)
+        }
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_MERGE => Err(CudaError::StreamCaptureMerge),
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_UNMATCHED => {
+            Err(CudaError::StreamCaptureUnmatched)
+        }
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_UNJOINED => Err(CudaError::StreamCaptureUnjoined),
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_ISOLATION => {
+            Err(CudaError::StreamCaptureIsolation)
+        }
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_IMPLICIT => Err(CudaError::StreamCaptureImplicit),
+        cudaError_enum::CUDA_ERROR_CAPTURED_EVENT => Err(CudaError::CapturedEvent),
+        cudaError_enum::CUDA_ERROR_STREAM_CAPTURE_WRONG_THREAD => {
+            Err(CudaError::StreamCaptureWrongThread)
+        }
+        cudaError_enum::CUDA_ERROR_TIMEOUT => Err(CudaError::Timeout),
+        cudaError_enum::CUDA_ERROR_GRAPH_EXEC_UPDATE_FAILURE => {
+            Err(CudaError::GraphExecUpdateFailure)
+        }
+        _ => Err(CudaError::UnknownError),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sticky_errors() {
+        assert!(CudaError::LaunchFailed.is_sticky());
+        assert!(CudaError::IllegalAddress.is_sticky());
+        assert!(!CudaError::OutOfMemory.is_sticky());
+        assert!(!CudaError::InvalidMemoryAllocation.is_sticky());
+    }
+
+    #[test]
+    fn test_last_error() {
+        let _context = crate::quick_init().unwrap();
+        let _ = last_error();
+        let err = unsafe { crate::memory::cuda_malloc::<u8>(usize::MAX / 2) }.unwrap_err();
+        let last = last_error().unwrap();
+        assert_eq!(last.error(), err);
+        assert_eq!(last.function(), Some("cuMemAlloc_v2"));
+        assert_eq!(last.name(), Some("CUDA_ERROR_OUT_OF_MEMORY"));
+        assert!(last_error().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_last_error_is_kept() {
+        use crate::memory::DeviceBuffer;
+        use crate::sys::mock;
+
+        let _context = crate::quick_init().unwrap();
+        let _ = last_error();
+        let buf = DeviceBuffer::<u8>::zeroed(16).unwrap();
+        mock::fail_next("cuCtxSynchronize", cudaError_enum::CUDA_ERROR_LAUNCH_FAILED);
+        mock::fail_next("cuMemFree_v2", cudaError_enum::CUDA_ERROR_LAUNCH_FAILED);
+        mock::fail_next(
+            "cuStreamSynchronize",
+            cudaError_enum::CUDA_ERROR_LAUNCH_FAILED,
+        );
+        // synchronizing returns the error with the function that failed.
+        let err = crate::context::CurrentContext::synchronize().unwrap_err();
+        assert_eq!(err, CudaError::LaunchFailed);
+        assert_eq!(err.function(), Some("cuCtxSynchronize"));
+        // neither the free in the drop nor the later call which fails with the same sticky error
+        // replace the first failure.
+        drop(buf);
+        let stream = crate::stream::Stream::new(crate::stream::StreamFlags::DEFAULT, None).unwrap();
+        assert!(stream.synchronize().is_err());
+        assert_eq!(last_error().unwrap().function(), Some("cuCtxSynchronize"));
+        mock::clear_faults();
+
+        // polling an event which is not done yet is not a failure.
+        mock::fail_next("cuEventQuery", cudaError_enum::CUDA_ERROR_NOT_READY);
+        let event = crate::event::Event::new(crate::event::EventFlags::DEFAULT).unwrap();
+        assert_eq!(event.query(), Ok(crate::event::EventStatus::NotReady));
+        assert!(last_error().is_none());
     }
 }
@@ -13,14 +13,16 @@
 // TODO: I'm not sure that these events are/can be safe by Rust's model of safety; they inherently
 // create state which can be mutated even while an immutable borrow is held.
 
-use crate::error::{CudaError, CudaResult, DropResult, ToResult};
-use crate::stream::Stream;
+use crate::error::{CudaResult, DriverResult, DropResult, ToResult};
+use crate::stream::{Stream, StreamWaitEventFlags, SynchronizeFuture};
 use crate::sys::{
     cuEventCreate, cuEventDestroy_v2, cuEventElapsedTime, cuEventQuery, cuEventRecord,
-    cuEventSynchronize, CUevent,
+    cuEventSynchronize, cuIpcGetEventHandle, cuIpcOpenEventHandle, cuStreamWaitEvent,
+    cudaError_enum, CUevent, CUipcEventHandle,
 };
 
 use std::mem;
+use std::os::raw::c_char;
 use std::ptr;
 use std::time::Duration;
 
@@ -40,9 +42,8 @@ bitflags::bitflags! {
         /// Specify that the created event does not need to record timing data.
         const DISABLE_TIMING = 0x2;
 
-        /// Specify that the created event may be used as an interprocess event.
-        /// (not supported yet by cust). This flag requires
-        /// `DISABLE_TIMING` to be set as well.
+        /// Specify that the created event may be used as an interprocess event
+        /// with [`Event::ipc_handle`]. This flag requires `DISABLE_TIMING` to be set as well.
         const INTERPROCESS = 0x4;
     }
 }
@@ -61,6 +62,29 @@ pub enum EventStatus {
     NotReady,
 }
 
+/// A handle to an [`Event`] which can be sent to another process and opened with
+/// [`Event::open_ipc`] to wait on the same event from that process.
+///
+/// The handle can be converted to and from bytes to send it over something like a Unix socket or
+/// a pipe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpcEventHandle([u8; 64]);
+
+impl IpcEventHandle {
+    /// The size in bytes of a handle converted to bytes with [`IpcEventHandle::to_bytes`].
+    pub const SIZE: usize = 64;
+
+    /// Converts this handle to bytes which can be sent to another process.
+    pub fn to_bytes(&self) -> [u8; IpcEventHandle::SIZE] {
+        self.0
+    }
+
+    /// Converts bytes made by [`IpcEventHandle::to_bytes`] back into a handle.
+    pub fn from_bytes(bytes: [u8; IpcEventHandle::SIZE]) -> Self {
+        Self(bytes)
+    }
+}
+
 /// An event to track work submitted to a stream.
 ///
 /// See the module-level documentation for more information.
@@ -90,7 +114,7 @@ impl Event {
     pub fn new(flags: EventFlags) -> CudaResult<Self> {
         unsafe {
             let mut event: CUevent = mem::zeroed();
-            cuEventCreate(&mut event, flags.bits()).to_result()?;
+            cuEventCreate(&mut event, flags.bits()).to_result_from("cuEventCreate")?;
             Ok(Event(event))
         }
     }
@@ -130,7 +154,7 @@ impl Event {
     /// ```
     pub fn record(&self, stream: &Stream) -> CudaResult<()> {
         unsafe {
-            cuEventRecord(self.0, stream.as_inner()).to_result()?;
+            cuEventRecord(self.0, stream.as_inner()).to_result_from("cuEventRecord")?;
             Ok(())
         }
     }
@@ -167,12 +191,11 @@ impl Event {
     /// }
     /// ```
     pub fn query(&self) -> CudaResult<EventStatus> {
-        let result = unsafe { cuEventQuery(self.0).to_result() };
-
-        match result {
-            Ok(()) => Ok(EventStatus::Ready),
-            Err(CudaError::NotReady) => Ok(EventStatus::NotReady),
-            Err(other) => Err(other),
+        match unsafe { cuEventQuery(self.0) } {
+            cudaError_enum::CUDA_ERROR_NOT_READY => Ok(EventStatus::NotReady),
+            status => status
+                .to_result_from("cuEventQuery")
+                .map(|()| EventStatus::Ready),
         }
     }
 
@@ -206,11 +229,61 @@ impl Event {
     /// # Ok(())
     /// }
     /// ```
-    pub fn synchronize(&self) -> CudaResult<()> {
+    pub fn synchronize(&self) -> DriverResult<()> {
+        unsafe { cuEventSynchronize(self.0).to_driver_result("cuEventSynchronize") }
+    }
+
+    /// Wait for an event to complete without blocking the current thread.
+    ///
+    /// Returns a future which completes once all work submitted before the event was last
+    /// recorded has completed. The future is woken by a callback from the driver instead of
+    /// polling the event, so it is suitable for use inside of async runtimes. If the event has
+    /// not been recorded, the future completes immediately.
+    ///
+    /// The callback is enqueued on `stream` after a wait on the event, so any work submitted to
+    /// `stream` afterwards also waits for the event. Pass a stream which is not used for other
+    /// work, such as one kept around for waiting on events, to avoid holding up that work. This
+    /// should not be the stream the event was recorded on, the future would wait for all work
+    /// submitted to that stream instead of just the work before the event.
+    ///
+    /// # Errors
+    ///
+    /// If waiting on the event cannot be scheduled, returns the error from CUDA. If the work
+    /// the event waits on fails, the future resolves to the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cust::quick_init;
+    /// # use cust::stream::{Stream, StreamFlags};
+    /// # use std::error::Error;
+    /// # async fn run() -> Result<(), Box<dyn Error>> {
+    /// # let _context = quick_init()?;
+    /// use cust::event::{Event, EventFlags};
+    ///
+    /// let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+    /// let waiter = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+    /// let event = Event::new(EventFlags::DISABLE_TIMING)?;
+    ///
+    /// // do some work ...
+    ///
+    /// event.record(&stream)?;
+    ///
+    /// // wait until the work is finished while letting other tasks run
+    /// event.synchronize_async(&waiter)?.await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn synchronize_async(&self, stream: &Stream) -> CudaResult<SynchronizeFuture> {
         unsafe {
-            cuEventSynchronize(self.0).to_result()?;
-            Ok(())
+            cuStreamWaitEvent(
+                stream.as_inner(),
+                self.0,
+                StreamWaitEventFlags::DEFAULT.bits(),
+            )
+            .to_result_from("cuStreamWaitEvent")?;
         }
+        SynchronizeFuture::new(stream)
     }
 
     /// Return the duration between two events.
@@ -280,7 +353,8 @@ impl Event {
     pub fn elapsed_time_f32(&self, start: &Self) -> CudaResult<f32> {
         unsafe {
             let mut millis: f32 = 0.0;
-            cuEventElapsedTime(&mut millis, start.0, self.0).to_result()?;
+            cuEventElapsedTime(&mut millis, start.0, self.0)
+                .to_result_from("cuEventElapsedTime")?;
             Ok(millis)
         }
     }
@@ -292,6 +366,55 @@ impl Event {
         Ok(Duration::from_nanos((time_f32 * 1e6) as u64))
     }
 
+    /// Creates a handle to this event which can be used by another process to wait on the
+    /// event with [`Event::open_ipc`].
+    ///
+    /// # Errors
+    ///
+    /// If the event was not created with `EventFlags::INTERPROCESS` and `EventFlags::DISABLE_TIMING`,
+    /// returns the error from CUDA.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let _context = quick_init()?;
+    /// # if cfg!(feature = "mock") { return Ok(()); }
+    /// use cust::event::{Event, EventFlags};
+    ///
+    /// let event = Event::new(EventFlags::INTERPROCESS | EventFlags::DISABLE_TIMING)?;
+    /// let bytes = event.ipc_handle()?.to_bytes();
+    /// // send `bytes` to the other process ...
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ipc_handle(&self) -> CudaResult<IpcEventHandle> {
+        unsafe {
+            let mut raw = CUipcEventHandle { reserved: [0; 64] };
+            cuIpcGetEventHandle(&mut raw as *mut _, self.0)
+                .to_result_from("cuIpcGetEventHandle")?;
+            Ok(IpcEventHandle(raw.reserved.map(|b| b as u8)))
+        }
+    }
+
+    /// Opens a handle made by another process with [`Event::ipc_handle`]. The returned event
+    /// can be waited on and queried, but not used for timing.
+    ///
+    /// A process cannot open handles to its own events.
+    pub fn open_ipc(handle: IpcEventHandle) -> CudaResult<Self> {
+        unsafe {
+            let mut event = ptr::null_mut();
+            let raw = CUipcEventHandle {
+                reserved: handle.0.map(|b| b as c_char),
+            };
+            cuIpcOpenEventHandle(&mut event as *mut _, raw)
+                .to_result_from("cuIpcOpenEventHandle")?;
+            Ok(Event(event))
+        }
+    }
+
     // Get the inner `CUevent` from the `Event`.
     //
     // Necessary for certain CUDA functions outside of this
@@ -333,7 +456,7 @@ impl Event {
 
         unsafe {
             let inner = mem::replace(&mut event.0, ptr::null_mut());
-            match cuEventDestroy_v2(inner).to_result() {
+            match cuEventDestroy_v2(inner).to_result_from("cuEventDestroy_v2") {
                 Ok(()) => {
                     mem::forget(event);
                     Ok(())
@@ -353,6 +476,7 @@ impl Drop for Event {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::CudaError;
     use crate::quick_init;
     use crate::stream::StreamFlags;
     use std::error::Error;
@@ -364,6 +488,19 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_ipc_handle() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let event = Event::new(EventFlags::INTERPROCESS | EventFlags::DISABLE_TIMING)?;
+        let handle = event.ipc_handle()?;
+        assert_eq!(IpcEventHandle::from_bytes(handle.to_bytes()), handle);
+
+        let event = Event::new(EventFlags::DISABLE_TIMING)?;
+        assert_eq!(event.ipc_handle(), Err(CudaError::InvalidValue));
+        Ok(())
+    }
+
     #[test]
     fn test_elapsed_time_f32_with_different_streams() -> Result<(), Box<dyn Error>> {
         let _context = quick_init()?;
@@ -0,0 +1,630 @@
+//! Running tasks on several streams with dependencies inferred from the buffers they use.
+//!
+//! Work on different streams can run concurrently, but ordering it correctly means recording an
+//! [`Event`] after every piece of work something else depends on and making the dependent streams
+//! wait on it. Wiring this by hand is tedious and easy to get wrong, especially as a pipeline
+//! changes.
+//!
+//! An [`Executor`] does the wiring itself. Every [`Task`] declares which device memory it reads
+//! and writes, and the executor orders it after the earlier tasks it has a hazard with:
+//!
+//! - Reading memory an earlier task writes (read after write).
+//! - Writing memory an earlier task reads (write after read).
+//! - Writing memory an earlier task writes (write after write).
+//!
+//! Tasks without hazards between them are spread over the streams of the executor so they can run
+//! concurrently. A task with dependencies is put on the stream which needs the fewest waits, and
+//! [`Stream::wait_event`] is only inserted for dependencies which are not already ordered before
+//! it, either by being on the same stream or through an earlier wait. The resulting
+//! [`Schedule`] can be inspected to see which stream every task ran on and why it waited.
+//!
+//! # Example
+//!
+//! ```
+//! # use cust::*;
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! # let _ctx = quick_init()?;
+//! use cust::executor::{Executor, Task};
+//! use cust::memory::*;
+//!
+//! let mut executor = Executor::new(2)?;
+//! let mut a = DeviceBuffer::<f32>::zeroed(1024)?;
+//! let mut b = DeviceBuffer::<f32>::zeroed(1024)?;
+//! let mut c = DeviceBuffer::<f32>::zeroed(1024)?;
+//!
+//! // independent, these run on different streams.
+//! executor.submit(Task::new("fill a").writes(&a), |stream| unsafe {
+//!     a.fill_async(1.0, stream)
+//! })?;
+//! executor.submit(Task::new("fill b").writes(&b), |stream| unsafe {
+//!     b.fill_async(2.0, stream)
+//! })?;
+//! // waits for "fill b" since it is on the stream of "fill a".
+//! executor.submit(Task::new("copy").reads(&a).reads(&b).writes(&c), |stream| unsafe {
+//!     c.async_copy_from(&b, stream)
+//! })?;
+//! executor.synchronize()?;
+//! println!("{}", executor.schedule());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::CudaResult;
+use crate::event::{Event, EventFlags, EventStatus};
+use crate::memory::{DeviceCopy, DeviceSlice};
+use crate::stream::{Stream, StreamFlags, StreamWaitEventFlags};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+
+// how many unfinished tasks are tracked before checking which of them are done.
+const PRUNE_THRESHOLD: usize = 256;
+
+/// Identifies a task submitted to an [`Executor`], in the order the tasks were submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(usize);
+
+impl TaskId {
+    /// The index of the task in the [`Schedule`] of the executor.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Why a task depends on an earlier task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hazard {
+    /// The task reads memory the earlier task writes.
+    ReadAfterWrite,
+    /// The task writes memory the earlier task reads.
+    WriteAfterRead,
+    /// The task writes memory the earlier task writes.
+    WriteAfterWrite,
+    /// The task was declared to run after the earlier task with [`Task::after`].
+    Explicit,
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Hazard::ReadAfterWrite => "read after write",
+            Hazard::WriteAfterRead => "write after read",
+            Hazard::WriteAfterWrite => "write after write",
+            Hazard::Explicit => "explicit",
+        })
+    }
+}
+
+/// A dependency of a task on an earlier task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dependency {
+    /// The earlier task.
+    pub task: TaskId,
+    /// Why the task depends on it.
+    pub hazard: Hazard,
+    /// Whether the stream of the task had to wait on an event of the earlier task. If not, the
+    /// dependency was already satisfied by stream order or by another wait.
+    pub waited: bool,
+}
+
+/// A task as it was scheduled by an [`Executor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledTask {
+    /// The id of the task.
+    pub id: TaskId,
+    /// The name of the task.
+    pub name: String,
+    /// The index of the stream the task ran on, see [`Executor::streams`].
+    pub stream: usize,
+    /// The earlier tasks this task depends on, sorted by id. Dependencies on tasks which had
+    /// already finished when the task was submitted are left out.
+    pub dependencies: Vec<Dependency>,
+}
+
+/// Every task submitted to an [`Executor`] and how it was scheduled.
+///
+/// It is displayed as one line per task, such as `2: blur on stream 1, waits for 0 (read after
+/// write)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    /// The tasks in the order they were submitted.
+    pub tasks: Vec<ScheduledTask>,
+}
+
+impl Schedule {
+    /// Returns the scheduled task with the given id, unless the schedule was cleared since.
+    pub fn task(&self, id: TaskId) -> Option<&ScheduledTask> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+
+    /// The total number of event waits inserted.
+    pub fn waits(&self) -> usize {
+        self.tasks
+            .iter()
+            .flat_map(|task| &task.dependencies)
+            .filter(|dep| dep.waited)
+            .count()
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for task in &self.tasks {
+            write!(f, "{}: {} on stream {}", task.id.0, task.name, task.stream)?;
+            for dep in &task.dependencies {
+                let verb = if dep.waited { "waits for" } else { "after" };
+                write!(f, ", {} {} ({})", verb, dep.task.0, dep.hazard)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Access {
+    start: u64,
+    end: u64,
+    write: bool,
+}
+
+impl Access {
+    fn of<T: DeviceCopy>(slice: &DeviceSlice<T>, write: bool) -> Self {
+        let start = slice.as_device_ptr().as_raw();
+        Access {
+            start,
+            end: start + (slice.len() * size_of::<T>()) as u64,
+            write,
+        }
+    }
+
+    fn overlaps(&self, other: &Access) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    fn contains(&self, other: &Access) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+}
+
+/// A unit of work for an [`Executor`], along with the device memory it uses.
+///
+/// Memory which is both read and written only needs to be declared with [`Task::writes`].
+/// Declaring more than the task uses is safe but may order it after tasks it does not depend on,
+/// declaring less is a data race.
+#[derive(Debug, Clone)]
+pub struct Task {
+    name: String,
+    accesses: Vec<Access>,
+    after: Vec<TaskId>,
+}
+
+impl Task {
+    /// Creates a task which does not use any memory yet. The name is only used in the
+    /// [`Schedule`].
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            accesses: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+
+    /// Declares that the task reads `slice`, which can also be a [`DeviceBuffer`] or part of one.
+    ///
+    /// [`DeviceBuffer`]: crate::memory::DeviceBuffer
+    pub fn reads<T: DeviceCopy>(mut self, slice: &DeviceSlice<T>) -> Self {
+        self.accesses.push(Access::of(slice, false));
+        self
+    }
+
+    /// Declares that the task writes `slice`, and may also read it.
+    pub fn writes<T: DeviceCopy>(mut self, slice: &DeviceSlice<T>) -> Self {
+        self.accesses.push(Access::of(slice, true));
+        self
+    }
+
+    /// Orders the task after an earlier task, for dependencies the executor cannot see such as
+    /// memory which is not in a [`DeviceSlice`].
+    pub fn after(mut self, task: TaskId) -> Self {
+        self.after.push(task);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Submitted {
+    stream: usize,
+    // the task is the `pos`th on its stream, counting from 1.
+    pos: u64,
+    event: Event,
+    // the `waited` row of its stream after its waits.
+    waited: Vec<u64>,
+}
+
+#[derive(Debug)]
+struct Recorded {
+    access: Access,
+    task: TaskId,
+}
+
+/// Runs [`Task`]s on a pool of streams, inserting event waits where they have hazards, see the
+/// [module-level documentation](self).
+#[derive(Debug)]
+pub struct Executor {
+    streams: Vec<Stream>,
+    // the number of tasks submitted to every stream.
+    submitted: Vec<u64>,
+    // the id of the last task submitted to every stream plus one, for picking the least recently
+    // used stream.
+    last_used: Vec<usize>,
+    // waited[s][t] is the position of the last task on stream t which stream s is known to be
+    // ordered after.
+    waited: Vec<Vec<u64>>,
+    // tasks which may not have finished yet.
+    tasks: HashMap<TaskId, Submitted>,
+    accesses: Vec<Recorded>,
+    free_events: Vec<Event>,
+    schedule: Schedule,
+    next_id: usize,
+}
+
+impl Executor {
+    /// Creates an executor with `streams` new non-blocking streams.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `streams` is zero.
+    pub fn new(streams: usize) -> CudaResult<Self> {
+        let streams = (0..streams)
+            .map(|_| Stream::new(StreamFlags::NON_BLOCKING, None))
+            .collect::<CudaResult<Vec<_>>>()?;
+        Ok(Self::from_streams(streams))
+    }
+
+    /// Creates an executor which runs tasks on the given streams.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `streams` is empty.
+    pub fn from_streams(streams: Vec<Stream>) -> Self {
+        assert!(!streams.is_empty(), "an executor needs at least one stream");
+        let n = streams.len();
+        Self {
+            streams,
+            submitted: vec![0; n],
+            last_used: vec![0; n],
+            waited: vec![vec![0; n]; n],
+            tasks: HashMap::new(),
+            accesses: Vec::new(),
+            free_events: Vec::new(),
+            schedule: Schedule::default(),
+            next_id: 0,
+        }
+    }
+
+    /// The streams tasks are run on.
+    pub fn streams(&self) -> &[Stream] {
+        &self.streams
+    }
+
+    /// Every task submitted since the executor was created or the schedule was cleared.
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Clears the [`Schedule`], which otherwise grows with every task. This does not affect the
+    /// dependencies of tasks submitted afterwards.
+    pub fn clear_schedule(&mut self) {
+        self.schedule.tasks.clear();
+    }
+
+    /// Submits a task, ordering it after the earlier tasks it has hazards with.
+    ///
+    /// `f` is called with the stream the task was assigned to and should submit all of the work
+    /// of the task to it, without waiting for the work to finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `f` or of the driver. A task which fails is not tracked, so later
+    /// tasks are not ordered after whatever part of it was submitted.
+    pub fn submit<F>(&mut self, task: Task, f: F) -> CudaResult<TaskId>
+    where
+        F: FnOnce(&Stream) -> CudaResult<()>,
+    {
+        self.submit_on(None, task, f)
+    }
+
+    fn submit_on<F>(&mut self, stream: Option<usize>, task: Task, f: F) -> CudaResult<TaskId>
+    where
+        F: FnOnce(&Stream) -> CudaResult<()>,
+    {
+        if self.tasks.len() >= PRUNE_THRESHOLD {
+            self.prune()?;
+        }
+        let id = TaskId(self.next_id);
+
+        let mut deps: Vec<(TaskId, Hazard)> = Vec::new();
+        let mut add = |task: TaskId, hazard| {
+            if !deps.iter().any(|(t, _)| *t == task) {
+                deps.push((task, hazard));
+            }
+        };
+        for access in &task.accesses {
+            for recorded in &self.accesses {
+                let hazard = match (recorded.access.write, access.write) {
+                    (true, true) => Hazard::WriteAfterWrite,
+                    (true, false) => Hazard::ReadAfterWrite,
+                    (false, true) => Hazard::WriteAfterRead,
+                    (false, false) => continue,
+                };
+                if recorded.access.overlaps(access) {
+                    add(recorded.task, hazard);
+                }
+            }
+        }
+        for &after in &task.after {
+            // tasks which are not tracked anymore have finished.
+            if self.tasks.contains_key(&after) {
+                add(after, Hazard::Explicit);
+            }
+        }
+        // the latest dependencies first, waiting on them may satisfy the earlier ones.
+        deps.sort_by_key(|&(id, _)| Reverse(id));
+
+        let stream = stream.unwrap_or_else(|| {
+            let waits = |s: usize| {
+                deps.iter()
+                    .filter(|(dep, _)| {
+                        let dep = &self.tasks[dep];
+                        dep.stream != s && self.waited[s][dep.stream] < dep.pos
+                    })
+                    .count()
+            };
+            (0..self.streams.len())
+                .min_by_key(|&s| (waits(s), self.last_used[s]))
+                .unwrap()
+        });
+
+        let mut dependencies = Vec::with_capacity(deps.len());
+        for (dep, hazard) in deps {
+            let submitted = &self.tasks[&dep];
+            let row = &mut self.waited[stream];
+            let waited = submitted.stream != stream && row[submitted.stream] < submitted.pos;
+            if waited {
+                self.streams[stream].wait_event(&submitted.event, StreamWaitEventFlags::DEFAULT)?;
+                // everything the other stream was ordered after is now ordered before this one.
+                for (known, &theirs) in row.iter_mut().zip(&submitted.waited) {
+                    *known = (*known).max(theirs);
+                }
+                row[submitted.stream] = row[submitted.stream].max(submitted.pos);
+            }
+            dependencies.push(Dependency {
+                task: dep,
+                hazard,
+                waited,
+            });
+        }
+        dependencies.sort_by_key(|dep| dep.task);
+
+        f(&self.streams[stream])?;
+        let event = match self.free_events.pop() {
+            Some(event) => event,
+            None => Event::new(EventFlags::DISABLE_TIMING)?,
+        };
+        if let Err(e) = event.record(&self.streams[stream]) {
+            self.free_events.push(event);
+            return Err(e);
+        }
+
+        self.next_id += 1;
+        self.submitted[stream] += 1;
+        self.last_used[stream] = self.next_id;
+        self.tasks.insert(
+            id,
+            Submitted {
+                stream,
+                pos: self.submitted[stream],
+                event,
+                waited: self.waited[stream].clone(),
+            },
+        );
+        // accesses overwritten by this task are ordered before it, so later tasks which conflict
+        // with them conflict with this task as well.
+        for access in task.accesses.iter().filter(|access| access.write) {
+            self.accesses
+                .retain(|recorded| !access.contains(&recorded.access));
+        }
+        self.accesses.extend(
+            task.accesses
+                .iter()
+                .map(|&access| Recorded { access, task: id }),
+        );
+        self.schedule.tasks.push(ScheduledTask {
+            id,
+            name: task.name,
+            stream,
+            dependencies,
+        });
+        Ok(id)
+    }
+
+    // Stops tracking the tasks which have finished.
+    fn prune(&mut self) -> CudaResult<()> {
+        let mut finished = Vec::new();
+        for (&id, task) in &self.tasks {
+            if task.event.query()? == EventStatus::Ready {
+                finished.push(id);
+            }
+        }
+        for id in finished {
+            let task = self.tasks.remove(&id).unwrap();
+            self.free_events.push(task.event);
+        }
+        let tasks = &self.tasks;
+        self.accesses
+            .retain(|recorded| tasks.contains_key(&recorded.task));
+        Ok(())
+    }
+
+    /// Waits for every task submitted so far to finish.
+    pub fn synchronize(&mut self) -> CudaResult<()> {
+        for stream in &self.streams {
+            stream.synchronize()?;
+        }
+        self.free_events
+            .extend(self.tasks.drain().map(|(_, task)| task.event));
+        self.accesses.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::DeviceBuffer;
+    use crate::quick_init;
+    use std::error::Error;
+
+    fn nothing(_: &Stream) -> CudaResult<()> {
+        Ok(())
+    }
+
+    fn dep(task: TaskId, hazard: Hazard, waited: bool) -> Dependency {
+        Dependency {
+            task,
+            hazard,
+            waited,
+        }
+    }
+
+    #[test]
+    fn test_hazards() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let a = DeviceBuffer::<u32>::zeroed(64)?;
+        let b = DeviceBuffer::<u32>::zeroed(64)?;
+        let c = DeviceBuffer::<u32>::zeroed(64)?;
+        let d = DeviceBuffer::<u32>::zeroed(64)?;
+        let mut executor = Executor::new(2)?;
+        #[cfg(feature = "mock")]
+        crate::sys::mock::clear_calls();
+
+        let t0 = executor.submit(Task::new("write a").writes(&a), nothing)?;
+        let t1 = executor.submit(Task::new("write b").writes(&b), nothing)?;
+        let t2 = executor.submit(Task::new("a to c").reads(&a).writes(&c), nothing)?;
+        let t3 = executor.submit(
+            Task::new("a and b to d").reads(&a).reads(&b).writes(&d),
+            nothing,
+        )?;
+        let t4 = executor.submit(Task::new("write a again").writes(&a), nothing)?;
+
+        let schedule = executor.schedule();
+        let streams = schedule.tasks.iter().map(|t| t.stream).collect::<Vec<_>>();
+        assert_eq!(streams, [0, 1, 0, 1, 0]);
+        assert!(schedule.task(t0).unwrap().dependencies.is_empty());
+        assert!(schedule.task(t1).unwrap().dependencies.is_empty());
+        assert_eq!(
+            schedule.task(t2).unwrap().dependencies,
+            [dep(t0, Hazard::ReadAfterWrite, false)]
+        );
+        assert_eq!(
+            schedule.task(t3).unwrap().dependencies,
+            [
+                dep(t0, Hazard::ReadAfterWrite, true),
+                dep(t1, Hazard::ReadAfterWrite, false)
+            ]
+        );
+        assert_eq!(
+            schedule.task(t4).unwrap().dependencies,
+            [
+                dep(t0, Hazard::WriteAfterWrite, false),
+                dep(t2, Hazard::WriteAfterRead, false),
+                dep(t3, Hazard::WriteAfterRead, true)
+            ]
+        );
+        assert_eq!(schedule.waits(), 2);
+        #[cfg(feature = "mock")]
+        assert_eq!(
+            crate::sys::mock::calls()
+                .iter()
+                .filter(|call| **call == "cuStreamWaitEvent")
+                .count(),
+            2
+        );
+        assert_eq!(
+            schedule.to_string().lines().nth(3),
+            Some("3: a and b to d on stream 1, waits for 0 (read after write), after 1 (read after write)")
+        );
+
+        // everything is done, so nothing needs to be waited for anymore.
+        executor.synchronize()?;
+        let t5 = executor.submit(Task::new("read a").reads(&a), nothing)?;
+        assert!(executor
+            .schedule()
+            .task(t5)
+            .unwrap()
+            .dependencies
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlapping_slices() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let buf = DeviceBuffer::<u32>::zeroed(1024)?;
+        let mut executor = Executor::new(2)?;
+
+        let t0 = executor.submit(Task::new("first half").writes(&buf.index(..512)), nothing)?;
+        let t1 = executor.submit(Task::new("last quarter").reads(&buf.index(768..)), nothing)?;
+        let t2 = executor.submit(Task::new("middle").reads(&buf.index(256..768)), nothing)?;
+        let schedule = executor.schedule();
+        assert!(schedule.task(t1).unwrap().dependencies.is_empty());
+        assert_eq!(
+            schedule.task(t2).unwrap().dependencies,
+            [dep(t0, Hazard::ReadAfterWrite, false)]
+        );
+        assert_eq!(schedule.task(t2).unwrap().stream, 0);
+
+        // a write covering everything replaces the earlier accesses.
+        let t3 = executor.submit(Task::new("all").writes(&buf), nothing)?;
+        assert_eq!(executor.accesses.len(), 1);
+        let t4 = executor.submit(Task::new("read").reads(&buf.index(..1)), nothing)?;
+        assert_eq!(
+            executor.schedule().task(t4).unwrap().dependencies,
+            [dep(t3, Hazard::ReadAfterWrite, false)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_transitive_waits() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let a = DeviceBuffer::<u32>::zeroed(64)?;
+        let b = DeviceBuffer::<u32>::zeroed(64)?;
+        let mut executor = Executor::new(3)?;
+
+        let t0 = executor.submit_on(Some(0), Task::new("a").writes(&a), nothing)?;
+        let t1 = executor.submit_on(Some(1), Task::new("b").writes(&b).after(t0), nothing)?;
+        // waiting for t1 orders stream 2 after t0 as well.
+        let t2 = executor.submit_on(Some(2), Task::new("ab").reads(&a).reads(&b), nothing)?;
+        assert_eq!(
+            executor.schedule().task(t2).unwrap().dependencies,
+            [
+                dep(t0, Hazard::ReadAfterWrite, false),
+                dep(t1, Hazard::ReadAfterWrite, true)
+            ]
+        );
+        assert_eq!(
+            executor.schedule().task(t1).unwrap().dependencies,
+            [dep(t0, Hazard::Explicit, true)]
+        );
+        assert_eq!(executor.schedule().waits(), 2);
+
+        executor.clear_schedule();
+        assert!(executor.schedule().task(t2).is_none());
+        executor.synchronize()?;
+        Ok(())
+    }
+}
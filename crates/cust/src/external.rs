@@ -1,10 +1,32 @@
 //! External memory and synchronization resources
+//!
+//! Memory and semaphores can only be imported from opaque file descriptors, such as the ones
+//! exported by Vulkan. Sync file descriptors and dma-buf handles are not supported, since the
+//! driver bindings in `cust_raw` have no handle types for them.
 
-use crate::error::{CudaResult, ToResult};
+use crate::error::{CudaResult, DropResult, ToResult};
+use crate::memory::array::{ArrayDescriptor, ArrayObject};
 use crate::memory::{DeviceCopy, DevicePointer};
+use crate::stream::Stream;
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::Deref;
 
 use cust_raw as sys;
 
+bitflags::bitflags! {
+    /// Bit flags for importing external memory.
+    pub struct ExternalMemoryFlags: u32 {
+        /// No flags set.
+        const DEFAULT = 0x0;
+
+        /// The memory is a dedicated allocation, such as Vulkan memory allocated with
+        /// `VkMemoryDedicatedAllocateInfo`. This must be set if and only if the memory was allocated
+        /// as a dedicated allocation.
+        const DEDICATED = sys::CUDA_EXTERNAL_MEMORY_DEDICATED;
+    }
+}
+
 #[repr(transparent)]
 pub struct ExternalMemory(sys::CUexternalMemory);
 
@@ -12,18 +34,36 @@ impl ExternalMemory {
     // Import an external memory referenced by `fd` with `size`
     #[allow(clippy::missing_safety_doc)]
     pub unsafe fn import(fd: i32, size: usize) -> CudaResult<ExternalMemory> {
+        ExternalMemory::import_with_flags(fd, size, ExternalMemoryFlags::DEFAULT)
+    }
+
+    /// Import an external memory referenced by the opaque file descriptor `fd` with `size`,
+    /// such as memory exported from Vulkan with `VK_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD_BIT`.
+    ///
+    /// The driver takes ownership of `fd` if the import succeeds, it must not be used or closed
+    /// afterwards.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must refer to exportable device memory of at least `size` bytes, and `flags` must
+    /// match how the memory was allocated.
+    pub unsafe fn import_with_flags(
+        fd: i32,
+        size: usize,
+        flags: ExternalMemoryFlags,
+    ) -> CudaResult<ExternalMemory> {
         let desc = sys::CUDA_EXTERNAL_MEMORY_HANDLE_DESC {
             type_: sys::CUexternalMemoryHandleType_enum::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD,
             handle: sys::CUDA_EXTERNAL_MEMORY_HANDLE_DESC_st__bindgen_ty_1 { fd },
             size: size as u64,
-            flags: 0,
+            flags: flags.bits(),
             reserved: Default::default(),
         };
 
         let mut memory: sys::CUexternalMemory = std::ptr::null_mut();
 
         sys::cuImportExternalMemory(&mut memory, &desc)
-            .to_result()
+            .to_result_from("cuImportExternalMemory")
             .map(|_| ExternalMemory(memory))
     }
 
@@ -51,16 +91,345 @@ impl ExternalMemory {
         let mut dptr = 0;
         unsafe {
             sys::cuExternalMemoryGetMappedBuffer(&mut dptr, self.0, &buffer_desc)
-                .to_result()
+                .to_result_from("cuExternalMemoryGetMappedBuffer")
                 .map(|_| DevicePointer::from_raw(dptr))
         }
     }
+
+    /// Map a mipmapped array with `num_levels` levels from this memory at `offset_in_bytes`, such
+    /// as the memory of a Vulkan image. `descriptor` and `num_levels` must match the format,
+    /// extent and mip levels the image was created with.
+    pub fn mapped_mipmapped_array(
+        &self,
+        offset_in_bytes: usize,
+        descriptor: &ArrayDescriptor,
+        num_levels: u32,
+    ) -> CudaResult<ExternalMipmappedArray<'_>> {
+        let mipmap_desc = sys::CUDA_EXTERNAL_MEMORY_MIPMAPPED_ARRAY_DESC {
+            offset: offset_in_bytes as u64,
+            arrayDesc: descriptor.desc,
+            numLevels: num_levels,
+            reserved: Default::default(),
+        };
+
+        let mut handle = std::ptr::null_mut();
+        unsafe {
+            sys::cuExternalMemoryGetMappedMipmappedArray(&mut handle, self.0, &mipmap_desc)
+                .to_result_from("cuExternalMemoryGetMappedMipmappedArray")?;
+        }
+        Ok(ExternalMipmappedArray {
+            handle,
+            num_levels,
+            memory: PhantomData,
+        })
+    }
 }
 
 impl Drop for ExternalMemory {
     fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
+        unsafe {
+            sys::cuDestroyExternalMemory(self.0)
+                .to_result_from("cuDestroyExternalMemory")
+                .unwrap();
+        }
+    }
+}
+
+/// A mipmapped array mapped from an [`ExternalMemory`] with
+/// [`ExternalMemory::mapped_mipmapped_array`].
+///
+/// The array is destroyed when this is dropped, but the memory stays owned by the
+/// `ExternalMemory`.
+#[derive(Debug)]
+pub struct ExternalMipmappedArray<'a> {
+    handle: sys::CUmipmappedArray,
+    num_levels: u32,
+    memory: PhantomData<&'a ExternalMemory>,
+}
+
+unsafe impl Send for ExternalMipmappedArray<'_> {}
+unsafe impl Sync for ExternalMipmappedArray<'_> {}
+
+impl<'a> ExternalMipmappedArray<'a> {
+    /// The number of mip levels of the array.
+    pub fn num_levels(&self) -> u32 {
+        self.num_levels
+    }
+
+    /// Returns the array of the mip level `level`, where level 0 is the full size image.
+    pub fn level(&self, level: u32) -> CudaResult<MipmapLevel<'_>> {
+        let mut handle = std::ptr::null_mut();
+        unsafe {
+            sys::cuMipmappedArrayGetLevel(&mut handle, self.handle, level)
+                .to_result_from("cuMipmappedArrayGetLevel")?;
+        }
+        Ok(MipmapLevel {
+            array: ManuallyDrop::new(ArrayObject { handle }),
+            mipmap: PhantomData,
+        })
+    }
+
+    /// Returns the raw handle of the array.
+    pub fn as_raw(&self) -> sys::CUmipmappedArray {
+        self.handle
+    }
+
+    /// Destroy an `ExternalMipmappedArray`, returning an error.
+    pub fn drop(array: ExternalMipmappedArray<'a>) -> DropResult<ExternalMipmappedArray<'a>> {
+        unsafe {
+            match sys::cuMipmappedArrayDestroy(array.handle)
+                .to_result_from("cuMipmappedArrayDestroy")
+            {
+                Ok(()) => {
+                    mem::forget(array);
+                    Ok(())
+                }
+                Err(e) => Err((e, array)),
+            }
+        }
+    }
+}
+
+impl Drop for ExternalMipmappedArray<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::cuMipmappedArrayDestroy(self.handle);
+        }
+    }
+}
+
+/// One mip level of an [`ExternalMipmappedArray`], which derefs to an [`ArrayObject`] to copy to
+/// and from it. The level is owned by the mipmapped array, so it is not destroyed when this is
+/// dropped.
+#[derive(Debug)]
+pub struct MipmapLevel<'a> {
+    array: ManuallyDrop<ArrayObject>,
+    mipmap: PhantomData<&'a ExternalMipmappedArray<'a>>,
+}
+
+impl Deref for MipmapLevel<'_> {
+    type Target = ArrayObject;
+
+    fn deref(&self) -> &ArrayObject {
+        &self.array
+    }
+}
+
+/// The kind of handle an [`ExternalSemaphore`] is imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExternalSemaphoreHandleType {
+    /// An opaque file descriptor of a binary semaphore, such as a Vulkan semaphore exported with
+    /// `VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT`.
+    OpaqueFd,
+    /// An opaque file descriptor of a timeline semaphore, such as a Vulkan semaphore created with
+    /// `VK_SEMAPHORE_TYPE_TIMELINE` and exported with `VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT`.
+    TimelineSemaphoreFd,
+}
+
+impl ExternalSemaphoreHandleType {
+    fn to_raw(self) -> sys::CUexternalSemaphoreHandleType {
+        use sys::CUexternalSemaphoreHandleType_enum::*;
+        match self {
+            ExternalSemaphoreHandleType::OpaqueFd => CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD,
+            ExternalSemaphoreHandleType::TimelineSemaphoreFd => {
+                CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_TIMELINE_SEMAPHORE_FD
+            }
+        }
+    }
+}
+
+/// A semaphore imported from another API such as Vulkan, used to order work on a [`Stream`] with
+/// work submitted to the other API without synchronizing the whole device.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// # let (fd, frame) = (0, 1);
+/// use cust::external::{ExternalSemaphore, ExternalSemaphoreHandleType};
+/// use cust::stream::{Stream, StreamFlags};
+///
+/// let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+/// // `fd` is a timeline semaphore exported from the renderer.
+/// let timeline =
+///     unsafe { ExternalSemaphore::import(fd, ExternalSemaphoreHandleType::TimelineSemaphoreFd)? };
+///
+/// // wait for the renderer to finish the frame, post-process it, then let the renderer continue.
+/// timeline.wait(&stream, 2 * frame)?;
+/// // launch kernels on `stream` ...
+/// timeline.signal(&stream, 2 * frame + 1)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct ExternalSemaphore(sys::CUexternalSemaphore);
+
+unsafe impl Send for ExternalSemaphore {}
+unsafe impl Sync for ExternalSemaphore {}
+
+impl ExternalSemaphore {
+    /// Import an external semaphore referenced by the file descriptor `fd`.
+    ///
+    /// The driver takes ownership of `fd` if the import succeeds, it must not be used or closed
+    /// afterwards.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be a semaphore of the kind `handle_type`.
+    pub unsafe fn import(
+        fd: i32,
+        handle_type: ExternalSemaphoreHandleType,
+    ) -> CudaResult<ExternalSemaphore> {
+        let desc = sys::CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC {
+            type_: handle_type.to_raw(),
+            handle: sys::CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC_st__bindgen_ty_1 { fd },
+            flags: 0,
+            reserved: Default::default(),
+        };
+
+        let mut semaphore: sys::CUexternalSemaphore = std::ptr::null_mut();
+
+        sys::cuImportExternalSemaphore(&mut semaphore, &desc)
+            .to_result_from("cuImportExternalSemaphore")
+            .map(|_| ExternalSemaphore(semaphore))
+    }
+
+    /// Signal the semaphore once all work previously submitted to `stream` is done. A timeline
+    /// semaphore is set to `value`, `value` is ignored for binary semaphores.
+    pub fn signal(&self, stream: &Stream, value: u64) -> CudaResult<()> {
+        let params = sys::CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS {
+            params: sys::CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS_st__bindgen_ty_1 {
+                fence: sys::CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS_st__bindgen_ty_1__bindgen_ty_1 {
+                    value,
+                },
+                nvSciSync:
+                    sys::CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS_st__bindgen_ty_1__bindgen_ty_2 {
+                        reserved: 0,
+                    },
+                keyedMutex: Default::default(),
+                reserved: Default::default(),
+            },
+            flags: 0,
+            reserved: Default::default(),
+        };
+
+        unsafe {
+            sys::cuSignalExternalSemaphoresAsync(&self.0, &params, 1, stream.as_inner())
+                .to_result_from("cuSignalExternalSemaphoresAsync")
+        }
+    }
+
+    /// Make all work submitted to `stream` after this call wait until the semaphore is signaled.
+    /// For a timeline semaphore this waits until its value is at least `value`, `value` is
+    /// ignored for binary semaphores.
+    pub fn wait(&self, stream: &Stream, value: u64) -> CudaResult<()> {
+        let params = sys::CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS {
+            params: sys::CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS_st__bindgen_ty_1 {
+                fence: sys::CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS_st__bindgen_ty_1__bindgen_ty_1 {
+                    value,
+                },
+                nvSciSync:
+                    sys::CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS_st__bindgen_ty_1__bindgen_ty_2 {
+                        reserved: 0,
+                    },
+                keyedMutex: Default::default(),
+                reserved: Default::default(),
+            },
+            flags: 0,
+            reserved: Default::default(),
+        };
+
+        unsafe {
+            sys::cuWaitExternalSemaphoresAsync(&self.0, &params, 1, stream.as_inner())
+                .to_result_from("cuWaitExternalSemaphoresAsync")
+        }
+    }
+
+    /// Returns the raw handle of the semaphore.
+    pub fn as_raw(&self) -> sys::CUexternalSemaphore {
+        self.0
+    }
+
+    /// Destroy an `ExternalSemaphore`, returning an error.
+    pub fn drop(semaphore: ExternalSemaphore) -> DropResult<ExternalSemaphore> {
+        unsafe {
+            match sys::cuDestroyExternalSemaphore(semaphore.0)
+                .to_result_from("cuDestroyExternalSemaphore")
+            {
+                Ok(()) => {
+                    mem::forget(semaphore);
+                    Ok(())
+                }
+                Err(e) => Err((e, semaphore)),
+            }
+        }
+    }
+}
+
+impl Drop for ExternalSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            sys::cuDestroyExternalSemaphore(self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::quick_init;
+    use crate::stream::StreamFlags;
+    use std::error::Error;
+
+    #[test]
+    fn test_import_invalid_fd() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
         unsafe {
-            sys::cuDestroyExternalMemory(self.0).to_result().unwrap();
+            assert!(ExternalMemory::import(-1, 1024).is_err());
+            assert!(
+                ExternalMemory::import_with_flags(-1, 1024, ExternalMemoryFlags::DEDICATED)
+                    .is_err()
+            );
+            assert!(ExternalSemaphore::import(-1, ExternalSemaphoreHandleType::OpaqueFd).is_err());
+            assert!(ExternalSemaphore::import(
+                -1,
+                ExternalSemaphoreHandleType::TimelineSemaphoreFd
+            )
+            .is_err());
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_semaphore() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let semaphore = ExternalSemaphore(std::ptr::null_mut());
+        assert!(semaphore.signal(&stream, 1).is_err());
+        assert!(semaphore.wait(&stream, 1).is_err());
+        assert!(ExternalSemaphore::drop(semaphore).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_timeline_semaphore() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        // the mock driver does not use the file descriptor.
+        let timeline = unsafe {
+            ExternalSemaphore::import(3, ExternalSemaphoreHandleType::TimelineSemaphoreFd)?
+        };
+        timeline.signal(&stream, 2)?;
+        timeline.wait(&stream, 2)?;
+        // work runs synchronously in the mock, so nothing could signal 3 anymore.
+        assert!(timeline.wait(&stream, 3).is_err());
+        ExternalSemaphore::drop(timeline).map_err(|(e, _)| e)?;
+        Ok(())
     }
 }
@@ -1,11 +1,21 @@
 //! Functions and types for working with CUDA kernels.
 
-use crate::context::{CacheConfig, SharedMemoryConfig};
-use crate::error::{CudaResult, ToResult};
+use crate::context::{CacheConfig, CurrentContext, SharedMemoryConfig};
+use crate::device::DeviceAttribute;
+use crate::error::{CudaError, CudaResult, DriverError, DriverResult, ToResult};
+use crate::memory::DeviceCopy;
 use crate::module::Module;
-use crate::sys::{self as cuda, CUfunction};
+use crate::private::Sealed;
+use crate::stream::Stream;
+use crate::sys::{self as cuda, CUfunction, CUmodule};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::c_void;
+use std::fmt;
 use std::marker::PhantomData;
-use std::mem::{transmute, MaybeUninit};
+use std::mem::{self, transmute, MaybeUninit};
+use std::sync::Mutex;
 
 /// Dimensions of a grid, or the number of thread blocks in a kernel launch.
 ///
@@ -203,20 +213,60 @@ pub enum FunctionAttribute {
 #[derive(Debug)]
 pub struct Function<'a> {
     inner: CUfunction,
+    name: String,
     module: PhantomData<&'a Module>,
 }
 
 unsafe impl Send for Function<'_> {}
 unsafe impl Sync for Function<'_> {}
 
+// The driver API has no way of querying the name of a function, so we keep track of the names
+// of the functions loaded from each module for debugging output which only has the raw handle,
+// such as graph dotfiles. The names of a module are removed when it is unloaded, since the driver
+// may reuse the handles of its functions.
+static FUNCTION_NAMES: Lazy<Mutex<HashMap<usize, HashMap<usize, String>>>> =
+    Lazy::new(Default::default);
+
+pub(crate) fn register_function_name(module: CUmodule, func: CUfunction, name: &str) {
+    if let Ok(mut names) = FUNCTION_NAMES.lock() {
+        names
+            .entry(module as usize)
+            .or_default()
+            .insert(func as usize, name.to_string());
+    }
+}
+
+pub(crate) fn forget_function_names(module: CUmodule) {
+    if let Ok(mut names) = FUNCTION_NAMES.lock() {
+        names.remove(&(module as usize));
+    }
+}
+
+/// Returns the name of a function if it was loaded through [`Module::get_function`] and its module
+/// is still loaded.
+pub(crate) fn function_name(func: CUfunction) -> Option<String> {
+    FUNCTION_NAMES
+        .lock()
+        .ok()?
+        .values()
+        .find_map(|functions| functions.get(&(func as usize)))
+        .cloned()
+}
+
 impl<'a> Function<'a> {
-    pub(crate) fn new(inner: CUfunction, _module: &Module) -> Function {
+    pub(crate) fn new(inner: CUfunction, name: &str, _module: &'a Module) -> Function<'a> {
         Function {
             inner,
+            name: name.to_string(),
             module: PhantomData,
         }
     }
 
+    /// The name of the kernel this function was loaded from.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// Returns information about a function.
     ///
     /// # Examples
@@ -246,7 +296,7 @@ impl<'a> Function<'a> {
                 ::std::mem::transmute(attr),
                 self.inner,
             )
-            .to_result()?;
+            .to_result_from("cuFuncGetAttribute")?;
             Ok(val)
         }
     }
@@ -280,7 +330,10 @@ impl<'a> Function<'a> {
     /// # }
     /// ```
     pub fn set_cache_config(&mut self, config: CacheConfig) -> CudaResult<()> {
-        unsafe { cuda::cuFuncSetCacheConfig(self.inner, transmute(config)).to_result() }
+        unsafe {
+            cuda::cuFuncSetCacheConfig(self.inner, transmute(config))
+                .to_result_from("cuFuncSetCacheConfig")
+        }
     }
 
     /// Sets the preferred shared memory configuration for this function.
@@ -307,7 +360,10 @@ impl<'a> Function<'a> {
     /// # }
     /// ```
     pub fn set_shared_memory_config(&mut self, cfg: SharedMemoryConfig) -> CudaResult<()> {
-        unsafe { cuda::cuFuncSetSharedMemConfig(self.inner, transmute(cfg)).to_result() }
+        unsafe {
+            cuda::cuFuncSetSharedMemConfig(self.inner, transmute(cfg))
+                .to_result_from("cuFuncSetSharedMemConfig")
+        }
     }
 
     /// Retrieves a raw handle to this function.
@@ -335,7 +391,7 @@ impl<'a> Function<'a> {
                 num_blocks as i32,
                 total_block_size as i32,
             )
-            .to_result()?;
+            .to_result_from("cuOccupancyAvailableDynamicSMemPerBlock")?;
             Ok(result.assume_init())
         }
     }
@@ -357,11 +413,93 @@ impl<'a> Function<'a> {
                 total_block_size as i32,
                 dynamic_smem_size,
             )
-            .to_result()?;
+            .to_result_from("cuOccupancyMaxActiveBlocksPerMultiprocessor")?;
             Ok(num_blocks.assume_init() as u32)
         }
     }
 
+    /// The maximum number of blocks a cooperative launch of this function can have when it is
+    /// launched with a specific `block_size` with some amount of dynamic shared memory. This is the
+    /// number of blocks which can be active at the same time on the device of the current context.
+    pub fn max_cooperative_grid_size(
+        &self,
+        block_size: BlockSize,
+        dynamic_smem_size: usize,
+    ) -> CudaResult<u32> {
+        let device = CurrentContext::get_device()?;
+        let multiprocessors = device.get_attribute(DeviceAttribute::MultiprocessorCount)? as u32;
+        let blocks = self.max_active_blocks_per_multiprocessor(block_size, dynamic_smem_size)?;
+        Ok(blocks.saturating_mul(multiprocessors))
+    }
+
+    /// Launch this function cooperatively, this is what [`launch_cooperative!`](crate::launch_cooperative)
+    /// uses and it should usually be used instead.
+    ///
+    /// Every block of a cooperative launch is guaranteed to be active on the device at the same
+    /// time, which allows the threads of the whole grid to synchronize with each other, such as
+    /// with `cuda_std::thread::grid_sync`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CudaError::NotSupported`] if the device does not support cooperative launches, and
+    /// [`CudaError::CooperativeLaunchTooLarge`] if the grid has more blocks than
+    /// [`Function::max_cooperative_grid_size`], with a [`DriverError::message`] giving both
+    /// numbers.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`launch!`](crate::launch), `args` must be pointers to the arguments of the
+    /// kernel and they must match its signature.
+    pub unsafe fn launch_cooperative<G, B>(
+        &self,
+        stream: &Stream,
+        grid_size: G,
+        block_size: B,
+        shared_mem_bytes: u32,
+        args: &[*mut c_void],
+    ) -> DriverResult<()>
+    where
+        G: Into<GridSize>,
+        B: Into<BlockSize>,
+    {
+        let grid_size: GridSize = grid_size.into();
+        let block_size: BlockSize = block_size.into();
+
+        let device = CurrentContext::get_device()?;
+        if device.get_attribute(DeviceAttribute::CooperativeLaunch)? == 0 {
+            return Err(CudaError::NotSupported.into());
+        }
+        // the driver checks this too, but checking it here gives the same error instead of
+        // a deadlock on drivers which do not.
+        let blocks = grid_size.x as u64 * grid_size.y as u64 * grid_size.z as u64;
+        let max_blocks = self.max_cooperative_grid_size(block_size, shared_mem_bytes as usize)?;
+        if blocks > max_blocks as u64 {
+            let message = format!(
+                "the grid has {} blocks, but only {} can be active on the device at once",
+                blocks, max_blocks
+            );
+            return Err(DriverError::new(
+                CudaError::CooperativeLaunchTooLarge,
+                Some("cuLaunchCooperativeKernel"),
+            )
+            .with_message(message));
+        }
+
+        cuda::cuLaunchCooperativeKernel(
+            self.inner,
+            grid_size.x,
+            grid_size.y,
+            grid_size.z,
+            block_size.x,
+            block_size.y,
+            block_size.z,
+            shared_mem_bytes,
+            stream.as_inner(),
+            args.as_ptr() as *mut _,
+        )
+        .to_driver_result("cuLaunchCooperativeKernel")
+    }
+
     // TODO(RDambrosio016): Figure out a way to safely wrap a rust closure to pass it to cuda for blockSizeToDynamicSMemSize.
     // It is an issue because we need to prevent unwinding but the no-unwinding wrapper cannot capture the function from its scope.
 
@@ -396,7 +534,7 @@ impl<'a> Function<'a> {
                 dynamic_smem_size,
                 total_block_size_limit as i32,
             )
-            .to_result()?;
+            .to_result_from("cuOccupancyMaxPotentialBlockSize")?;
             Ok((
                 min_grid_size.assume_init() as u32,
                 block_size.assume_init() as u32,
@@ -405,6 +543,291 @@ impl<'a> Function<'a> {
     }
 }
 
+/// The argument types of a kernel, used by [`TypedFunction`] to check the arguments of a launch.
+///
+/// This is implemented for tuples of up to 12 [`DeviceCopy`] types, with `()` for kernels
+/// without parameters. Each element of the tuple is one parameter of the kernel, in order.
+pub trait KernelArgs: Sealed {
+    /// The size in bytes of each parameter, in order.
+    fn param_sizes() -> Vec<usize>;
+
+    #[doc(hidden)]
+    fn with_param_ptrs<R>(&self, f: impl FnOnce(&[*mut c_void]) -> R) -> R;
+}
+
+macro_rules! impl_kernel_args {
+    ($($ty:ident $idx:tt),*) => {
+        impl<$($ty: DeviceCopy),*> Sealed for ($($ty,)*) {}
+
+        impl<$($ty: DeviceCopy),*> KernelArgs for ($($ty,)*) {
+            fn param_sizes() -> Vec<usize> {
+                vec![$(mem::size_of::<$ty>()),*]
+            }
+
+            fn with_param_ptrs<R>(&self, f: impl FnOnce(&[*mut c_void]) -> R) -> R {
+                f(&[$(&self.$idx as *const $ty as *mut c_void),*])
+            }
+        }
+    };
+}
+
+impl_kernel_args!();
+impl_kernel_args!(A 0);
+impl_kernel_args!(A 0, B 1);
+impl_kernel_args!(A 0, B 1, C 2);
+impl_kernel_args!(A 0, B 1, C 2, D 3);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_kernel_args!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+/// Error returned by [`TypedFunction::check_ptx`] if the parameters of a kernel in PTX do not
+/// match the argument types of the function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureMismatch {
+    /// The PTX does not contain a kernel with the name of the function.
+    MissingEntry(String),
+    /// The sizes in bytes of the parameters of the kernel are different from the sizes of the
+    /// argument types.
+    ParamSizes {
+        /// The name of the kernel.
+        name: String,
+        /// The sizes of the parameters declared in the `.entry` of the kernel.
+        ptx: Vec<usize>,
+        /// The sizes of the argument types, without zero-sized types.
+        args: Vec<usize>,
+    },
+}
+
+impl fmt::Display for SignatureMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureMismatch::MissingEntry(name) => {
+                write!(f, "no kernel named `{}` in the PTX", name)
+            }
+            SignatureMismatch::ParamSizes { name, ptx, args } => write!(
+                f,
+                "kernel `{}` takes parameters of {:?} bytes, but the arguments are {:?} bytes",
+                name, ptx, args
+            ),
+        }
+    }
+}
+
+impl Error for SignatureMismatch {}
+
+/// A kernel function which can only be launched with arguments of the types `Args`.
+///
+/// [`launch!`](crate::launch) passes each argument as an untyped pointer, so passing the wrong
+/// number of arguments, or arguments of the wrong type or in the wrong order, is undefined
+/// behavior which is easy to miss. A `TypedFunction` is obtained with
+/// [`Module::get_typed_function`] and its launches only accept a tuple of `Args`, and
+/// [`TypedFunction::check_ptx`] can check the arguments against the parameters of the kernel
+/// in the PTX it was loaded from.
+///
+/// # Examples
+///
+/// ```
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// use cust::memory::*;
+/// use cust::module::Module;
+/// use cust::stream::*;
+///
+/// let ptx = include_str!("../resources/add.ptx");
+/// let module = Module::from_ptx(ptx, &[])?;
+/// let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+///
+/// let sum = module.get_typed_function::<(
+///     DevicePointer<f32>,
+///     DevicePointer<f32>,
+///     DevicePointer<f32>,
+///     u32,
+/// )>("sum")?;
+/// // `sum` takes the length as a 32-bit integer, passing a `usize` would not compile.
+/// sum.check_ptx(ptx)?;
+///
+/// let x = DeviceBuffer::from_slice(&[1.0f32; 10])?;
+/// let y = DeviceBuffer::from_slice(&[2.0f32; 10])?;
+/// let out = DeviceBuffer::from_slice(&[0.0f32; 10])?;
+/// unsafe {
+///     sum.launch(
+///         &stream,
+///         1,
+///         10,
+///         0,
+///         (x.as_device_ptr(), y.as_device_ptr(), out.as_device_ptr(), 10),
+///     )?;
+/// }
+/// stream.synchronize()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TypedFunction<'a, Args> {
+    function: Function<'a>,
+    args: PhantomData<fn(Args)>,
+}
+
+impl<'a, Args: KernelArgs> TypedFunction<'a, Args> {
+    pub(crate) fn new(function: Function<'a>) -> Self {
+        TypedFunction {
+            function,
+            args: PhantomData,
+        }
+    }
+
+    /// The name of the kernel this function was loaded from.
+    pub fn name(&self) -> &str {
+        self.function.name()
+    }
+
+    /// Returns the untyped function, to query attributes or occupancy of the kernel.
+    pub fn function(&self) -> &Function<'a> {
+        &self.function
+    }
+
+    /// Returns the untyped function mutably, to set the cache or shared memory configuration
+    /// of the kernel.
+    pub fn function_mut(&mut self) -> &mut Function<'a> {
+        &mut self.function
+    }
+
+    /// Checks that the size of each argument type matches the size of the parameter declared
+    /// in the `.entry` of the kernel in `ptx`, which should be the PTX the module was loaded from.
+    ///
+    /// PTX only records the size of parameters, so this catches a wrong number of arguments and
+    /// most arguments in the wrong order, but not arguments of the wrong type with the same size,
+    /// such as an `f32` passed for an `u32`. Zero-sized arguments are not part of the comparison.
+    pub fn check_ptx(&self, ptx: &str) -> Result<(), SignatureMismatch> {
+        let name = self.name();
+        let ptx = ptx_param_sizes(ptx, name)
+            .ok_or_else(|| SignatureMismatch::MissingEntry(name.to_string()))?;
+        let args = Args::param_sizes()
+            .into_iter()
+            .filter(|&size| size != 0)
+            .collect::<Vec<_>>();
+        if ptx != args {
+            return Err(SignatureMismatch::ParamSizes {
+                name: name.to_string(),
+                ptx,
+                args,
+            });
+        }
+        Ok(())
+    }
+
+    /// Launch this function asynchronously on `stream` with the arguments `args`.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`launch!`](crate::launch) except the arguments, the kernel must take
+    /// parameters of the types `Args`, and the host must not access memory the kernel could write
+    /// to until it is done.
+    pub unsafe fn launch<G, B>(
+        &self,
+        stream: &Stream,
+        grid_size: G,
+        block_size: B,
+        shared_mem_bytes: u32,
+        args: Args,
+    ) -> DriverResult<()>
+    where
+        G: Into<GridSize>,
+        B: Into<BlockSize>,
+    {
+        args.with_param_ptrs(|ptrs| {
+            stream.launch(
+                &self.function,
+                grid_size,
+                block_size,
+                shared_mem_bytes,
+                ptrs,
+            )
+        })
+    }
+
+    /// Launch this function cooperatively on `stream` with the arguments `args`, see
+    /// [`Function::launch_cooperative`].
+    ///
+    /// # Safety
+    ///
+    /// The same as [`TypedFunction::launch`].
+    pub unsafe fn launch_cooperative<G, B>(
+        &self,
+        stream: &Stream,
+        grid_size: G,
+        block_size: B,
+        shared_mem_bytes: u32,
+        args: Args,
+    ) -> DriverResult<()>
+    where
+        G: Into<GridSize>,
+        B: Into<BlockSize>,
+    {
+        args.with_param_ptrs(|ptrs| {
+            self.function
+                .launch_cooperative(stream, grid_size, block_size, shared_mem_bytes, ptrs)
+        })
+    }
+}
+
+/// Finds the `.entry` of a kernel in PTX and returns the size in bytes of each of its parameters.
+fn ptx_param_sizes(ptx: &str, name: &str) -> Option<Vec<usize>> {
+    let mut rest = ptx;
+    while let Some(idx) = rest.find(".entry") {
+        rest = &rest[idx + ".entry".len()..];
+        let after_name = match rest.trim_start().strip_prefix(name) {
+            Some(after_name) => after_name.trim_start(),
+            None => continue,
+        };
+        if after_name.starts_with('{') {
+            return Some(Vec::new());
+        }
+        // another kernel whose name starts with the name.
+        if !after_name.starts_with('(') {
+            continue;
+        }
+        let params = &after_name[1..after_name.find(')')?];
+        return params
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .map(ptx_param_size)
+            .collect();
+    }
+    None
+}
+
+/// The size of a parameter declaration such as `.param .align 4 .b8 foo_param_0[12]`.
+fn ptx_param_size(param: &str) -> Option<usize> {
+    let mut tokens = param.split_whitespace();
+    if tokens.next()? != ".param" {
+        return None;
+    }
+    let tokens = tokens.collect::<Vec<_>>();
+    let elem_size = tokens.iter().find_map(|token| {
+        let token = token.strip_prefix('.')?;
+        if !token.starts_with(['b', 'u', 's', 'f']) {
+            return None;
+        }
+        let bits = token[1..].parse::<usize>().ok()?;
+        Some(bits / 8)
+    })?;
+    let name = tokens.last()?;
+    let len = match name.find('[') {
+        Some(idx) => name[idx + 1..].strip_suffix(']')?.parse::<usize>().ok()?,
+        None => 1,
+    };
+    Some(elem_size * len)
+}
+
 /// Launch a kernel function asynchronously.
 ///
 /// # Syntax:
@@ -507,6 +930,8 @@ impl<'a> Function<'a> {
 /// let mut out_host = [0.0f32; 20];
 /// out_1.copy_to(&mut out_host[0..10])?;
 /// out_2.copy_to(&mut out_host[10..20])?;
+/// # // the mock driver does not run kernels.
+/// # if cfg!(feature = "mock") { return Ok(()); }
 ///
 /// for x in out_host.iter() {
 ///     assert_eq!(3.0, *x);
@@ -522,7 +947,7 @@ macro_rules! launch {
             let function = $module.get_function(stringify!($function));
             match function {
                 Ok(f) => launch!(f<<<$grid, $block, $shared, $stream>>>( $($arg),* ) ),
-                Err(e) => Err(e),
+                Err(e) => Err(e.into()),
             }
         }
     };
@@ -545,3 +970,233 @@ macro_rules! launch {
         }
     };
 }
+
+/// Launch a kernel function cooperatively.
+///
+/// This has the same syntax as [`launch!`](crate::launch), but launches the kernel with
+/// [`Function::launch_cooperative`]. Every block of the grid is guaranteed to be active on the
+/// device at the same time, so the kernel can synchronize the whole grid with
+/// `cuda_std::thread::grid_sync`. The grid cannot have more blocks than
+/// [`Function::max_cooperative_grid_size`].
+///
+/// # Safety
+///
+/// Launching kernels must be done in an `unsafe` block, see [`launch!`](crate::launch).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use cust::*;
+/// # use std::error::Error;
+/// use cust::memory::*;
+/// use cust::module::Module;
+/// use cust::stream::*;
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let _ctx = cust::quick_init()?;
+/// let module = Module::from_ptx(include_str!("../resources/add.ptx"), &[])?;
+/// let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+/// let solve = module.get_function("solve")?;
+///
+/// let mut state = DeviceBuffer::from_slice(&[0.0f32; 4096])?;
+/// // use as many blocks as can be active at the same time.
+/// let blocks = solve.max_cooperative_grid_size(256.into(), 0)?;
+/// unsafe {
+///     launch_cooperative!(solve<<<blocks, 256, 0, stream>>>(
+///         state.as_device_ptr(),
+///         state.len()
+///     ))?;
+/// }
+/// stream.synchronize()?;
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! launch_cooperative {
+    ($module:ident . $function:ident <<<$grid:expr, $block:expr, $shared:expr, $stream:ident>>>( $( $arg:expr),* $(,)?)) => {
+        {
+            let function = $module.get_function(stringify!($function));
+            match function {
+                Ok(f) => launch_cooperative!(f<<<$grid, $block, $shared, $stream>>>( $($arg),* ) ),
+                Err(e) => Err(e.into()),
+            }
+        }
+    };
+    ($function:ident <<<$grid:expr, $block:expr, $shared:expr, $stream:ident>>>( $( $arg:expr),* $(,)?)) => {
+        {
+            fn assert_impl_devicecopy<T: $crate::memory::DeviceCopy>(_val: T) {}
+            if false {
+                $(
+                    assert_impl_devicecopy($arg);
+                )*
+            };
+
+            $function.launch_cooperative(&$stream, $grid, $block, $shared,
+                &[
+                    $(
+                        &$arg as *const _ as *mut ::std::ffi::c_void,
+                    )*
+                ]
+            )
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PTX: &str = include_str!("../resources/add.ptx");
+
+    #[test]
+    fn test_ptx_param_sizes() {
+        assert_eq!(ptx_param_sizes(PTX, "sum"), Some(vec![8, 8, 8, 4]));
+        assert_eq!(ptx_param_sizes(PTX, "su"), None);
+        assert_eq!(ptx_param_sizes(PTX, "missing"), None);
+
+        let ptx = ".visible .entry empty()\n{\n}\n.visible .entry by_value(\n.param .align 4 .b8 by_value_param_0[12],\n.param .u64 .ptr .global .align 4 by_value_param_1\n)\n{\n}";
+        assert_eq!(ptx_param_sizes(ptx, "empty"), Some(vec![]));
+        assert_eq!(ptx_param_sizes(ptx, "by_value"), Some(vec![12, 8]));
+    }
+
+    #[test]
+    fn test_check_ptx() {
+        let _context = crate::quick_init().unwrap();
+        let module = Module::from_ptx(PTX, &[]).unwrap();
+        type Ptr = crate::memory::DevicePointer<f32>;
+        let sum = module
+            .get_typed_function::<(Ptr, Ptr, Ptr, u32)>("sum")
+            .unwrap();
+        sum.check_ptx(PTX).unwrap();
+
+        let sum = module
+            .get_typed_function::<(Ptr, Ptr, Ptr, usize)>("sum")
+            .unwrap();
+        assert_eq!(
+            sum.check_ptx(PTX),
+            Err(SignatureMismatch::ParamSizes {
+                name: "sum".to_string(),
+                ptx: vec![8, 8, 8, 4],
+                args: vec![8, 8, 8, 8],
+            })
+        );
+    }
+
+    #[test]
+    fn test_function_names() -> CudaResult<()> {
+        let _context = crate::quick_init()?;
+        let module = Module::from_ptx(PTX, &[])?;
+        let sum = module.get_function("sum")?;
+        assert_eq!(sum.name(), "sum");
+        let raw = sum.to_raw();
+        assert_eq!(function_name(raw).as_deref(), Some("sum"));
+
+        // the handle may be reused after the module is unloaded.
+        drop(sum);
+        Module::drop(module).map_err(|(e, _)| e)?;
+        assert_eq!(function_name(raw), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_cooperative_grid_size() -> CudaResult<()> {
+        let _context = crate::quick_init()?;
+        let module = Module::from_ptx(PTX, &[])?;
+        let sum = module.get_function("sum")?;
+        let multiprocessors = CurrentContext::get_device()?
+            .get_attribute(DeviceAttribute::MultiprocessorCount)?
+            as u32;
+        for &(block_size, smem) in &[(32u32, 0), (256, 0), (1024, 0), (256, 32 * 1024)] {
+            let blocks = sum.max_active_blocks_per_multiprocessor(block_size.into(), smem)?;
+            assert!(blocks > 0);
+            assert_eq!(
+                sum.max_cooperative_grid_size(block_size.into(), smem)?,
+                blocks * multiprocessors
+            );
+        }
+        // 16 multiprocessors which fit 8 blocks of 256 threads, or 3 with 32K of shared memory.
+        #[cfg(feature = "mock")]
+        {
+            assert_eq!(sum.max_cooperative_grid_size(256.into(), 0)?, 128);
+            assert_eq!(sum.max_cooperative_grid_size(256.into(), 32 * 1024)?, 48);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_launch_cooperative_too_large() -> CudaResult<()> {
+        let _context = crate::quick_init()?;
+        let module = Module::from_ptx(PTX, &[])?;
+        let stream = Stream::new(crate::stream::StreamFlags::NON_BLOCKING, None)?;
+        type Ptr = crate::memory::DevicePointer<f32>;
+        let sum = module.get_typed_function::<(Ptr, Ptr, Ptr, u32)>("sum")?;
+        let max_blocks = sum.function().max_cooperative_grid_size(256.into(), 0)?;
+        let null = Ptr::from_raw(0);
+
+        // the grid size is checked before launching, so the kernel never sees the null pointers.
+        unsafe {
+            let err = sum
+                .launch_cooperative(&stream, max_blocks + 1, 256, 0, (null, null, null, 0))
+                .unwrap_err();
+            assert_eq!(err, CudaError::CooperativeLaunchTooLarge);
+            assert_eq!(err.function(), Some("cuLaunchCooperativeKernel"));
+            assert_eq!(
+                err.message(),
+                Some(&*format!(
+                    "the grid has {} blocks, but only {} can be active on the device at once",
+                    max_blocks + 1,
+                    max_blocks
+                ))
+            );
+            assert!(err.to_string().ends_with(err.message().unwrap()));
+            assert_eq!(
+                sum.launch_cooperative(&stream, (max_blocks, 2), 256, 0, (null, null, null, 0))
+                    .unwrap_err(),
+                CudaError::CooperativeLaunchTooLarge
+            );
+            // more shared memory per block leaves room for fewer blocks.
+            let smem_blocks = sum
+                .function()
+                .max_cooperative_grid_size(256.into(), 32 * 1024)?;
+            assert!(smem_blocks <= max_blocks);
+            assert_eq!(
+                sum.launch_cooperative(
+                    &stream,
+                    smem_blocks + 1,
+                    256,
+                    32 * 1024,
+                    (null, null, null, 0)
+                )
+                .unwrap_err(),
+                CudaError::CooperativeLaunchTooLarge
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_launch_cooperative() -> CudaResult<()> {
+        use crate::sys::{mock, CUresult};
+
+        let _context = crate::quick_init()?;
+        let module = Module::from_ptx(PTX, &[])?;
+        let stream = Stream::new(crate::stream::StreamFlags::NON_BLOCKING, None)?;
+        let sum = module.get_function("sum")?;
+        let max_blocks = sum.max_cooperative_grid_size(256.into(), 0)?;
+
+        mock::clear_calls();
+        unsafe { sum.launch_cooperative(&stream, max_blocks, 256, 0, &[])? };
+        assert_eq!(mock::calls().last(), Some(&"cuLaunchCooperativeKernel"));
+
+        // drivers which check the grid size themselves report the same error.
+        mock::fail_next(
+            "cuLaunchCooperativeKernel",
+            CUresult::CUDA_ERROR_COOPERATIVE_LAUNCH_TOO_LARGE,
+        );
+        let err = unsafe { sum.launch_cooperative(&stream, max_blocks, 256, 0, &[]) }.unwrap_err();
+        assert_eq!(err, CudaError::CooperativeLaunchTooLarge);
+        assert_eq!(err.function(), Some("cuLaunchCooperativeKernel"));
+        Ok(())
+    }
+}
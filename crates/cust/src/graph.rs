@@ -2,63 +2,101 @@
 
 use std::{
     ffi::c_void,
-    mem::{ManuallyDrop, MaybeUninit},
+    mem::{self, ManuallyDrop, MaybeUninit},
     os::raw::{c_char, c_uint},
+    panic::{self, AssertUnwindSafe},
     path::Path,
     ptr,
 };
 
 use crate::{
-    error::{CudaResult, ToResult},
+    error::{CudaError, CudaResult, DriverResult, ToResult},
+    event::Event,
     function::{BlockSize, GridSize},
+    memory::{
+        array::ArrayObject, DeviceBuffer, DeviceCopy, DevicePointer, DeviceSlice, LockedBuffer,
+    },
+    private::Sealed,
+    stream::Stream,
     sys as cuda,
 };
 
-/// Creates a kernel invocation using the same syntax as [`launch`] to be used to insert kernel launches inside graphs.
+/// Creates a kernel invocation using the same syntax as [`launch`](crate::launch) to be used to insert kernel launches inside graphs.
 /// This returns a Result of a kernel invocation object you can then pass to a graph.
+///
+/// The stream is ignored since graph nodes are not launched on a stream, it is only accepted so that
+/// existing [`launch`](crate::launch) calls can be turned into invocations easily. The arguments
+/// are copied into the invocation, so the invocation does not borrow anything.
 #[macro_export]
 macro_rules! kernel_invocation {
     ($module:ident . $function:ident <<<$grid:expr, $block:expr, $shared:expr, $stream:ident>>>( $( $arg:expr),* )) => {
         {
-            let name = std::ffi::CString::new(stringify!($function)).unwrap();
-            let function = $module.get_function(&name);
+            let function = $module.get_function(stringify!($function));
             match function {
-                Ok(f) => kernel_invocation!(f<<<$grid, $block, $shared, $stream>>>( $($arg),* ) ),
+                Ok(f) => $crate::kernel_invocation!(f<<<$grid, $block, $shared, $stream>>>( $($arg),* ) ),
                 Err(e) => Err(e),
             }
         }
     };
     ($function:ident <<<$grid:expr, $block:expr, $shared:expr, $stream:ident>>>( $( $arg:expr),* )) => {
         {
-            fn assert_impl_devicecopy<T: $crate::memory::DeviceCopy>(_val: T) {}
-            if false {
-                $(
-                    assert_impl_devicecopy($arg);
-                )*
-            };
+            // the stream is not used, but it should not be reported as unused either.
+            let _ = &$stream;
+            let result: $crate::error::CudaResult<$crate::graph::KernelInvocation> =
+                Ok($crate::graph::KernelInvocation::_new_internal(
+                    $crate::function::BlockSize::from($block),
+                    $crate::function::GridSize::from($grid),
+                    $shared,
+                    $function.to_raw(),
+                    vec![$($crate::graph::KernelParam::_new($arg)),*],
+                ));
+            result
+        }
+    };
+}
+
+/// A single kernel argument owned by a [`KernelInvocation`]. The value is boxed and type-erased
+/// so that a single invocation can hold arguments of different types.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct KernelParam {
+    ptr: *mut c_void,
+    drop_fn: unsafe fn(*mut c_void),
+}
 
-            let boxed = vec![$(&$arg as *const _ as *mut ::std::ffi::c_void),*].into_boxed_slice();
+impl KernelParam {
+    #[doc(hidden)]
+    pub fn _new<T: DeviceCopy>(val: T) -> Self {
+        unsafe fn drop_boxed<T>(ptr: *mut c_void) {
+            drop(Box::from_raw(ptr as *mut T));
+        }
 
-            Ok($crate::graph::KernelInvocation::_new_internal(
-                $crate::function::BlockSize::from($block),
-                $crate::function::GridSize::from($grid),
-                $shared,
-                $function.to_raw(),
-                vec![].into_boxed_slice(),
-            ))
+        Self {
+            ptr: Box::into_raw(Box::new(val)).cast(),
+            drop_fn: drop_boxed::<T>,
         }
-    };
+    }
+}
+
+impl Drop for KernelParam {
+    fn drop(&mut self) {
+        unsafe { (self.drop_fn)(self.ptr) }
+    }
 }
 
 /// A prepared kernel invocation to be added to a graph.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct KernelInvocation {
     pub block_dim: BlockSize,
     pub grid_dim: GridSize,
     pub shared_mem_bytes: u32,
     func: cuda::CUfunction,
-    params: Box<*mut c_void>,
-    params_len: Option<usize>,
+    // the params array given to us by the driver if this invocation was made from a raw
+    // invocation, otherwise the params are the pointers to the arguments we own.
+    raw_params: Option<*mut *mut c_void>,
+    param_ptrs: Vec<*mut c_void>,
+    // only held to keep the argument values pointed to by `param_ptrs` alive.
+    _args: Vec<KernelParam>,
 }
 
 impl KernelInvocation {
@@ -68,20 +106,22 @@ impl KernelInvocation {
         grid_dim: GridSize,
         shared_mem_bytes: u32,
         func: cuda::CUfunction,
-        params: Box<*mut c_void>,
-        params_len: usize,
+        args: Vec<KernelParam>,
     ) -> Self {
         Self {
             block_dim,
             grid_dim,
             shared_mem_bytes,
             func,
-            params,
-            params_len: Some(params_len),
+            raw_params: None,
+            param_ptrs: args.iter().map(|x| x.ptr).collect(),
+            _args: args,
         }
     }
 
-    pub fn to_raw(self) -> cuda::CUDA_KERNEL_NODE_PARAMS {
+    /// Converts this invocation into its raw counterpart. The returned params
+    /// point into this invocation and are only valid for as long as it is alive.
+    pub fn to_raw(&self) -> cuda::CUDA_KERNEL_NODE_PARAMS {
         cuda::CUDA_KERNEL_NODE_PARAMS {
             func: self.func,
             gridDimX: self.grid_dim.x,
@@ -90,7 +130,9 @@ impl KernelInvocation {
             blockDimX: self.block_dim.x,
             blockDimY: self.block_dim.y,
             blockDimZ: self.block_dim.z,
-            kernelParams: Box::into_raw(self.params),
+            kernelParams: self
+                .raw_params
+                .unwrap_or(self.param_ptrs.as_ptr() as *mut _),
             sharedMemBytes: self.shared_mem_bytes,
             extra: ptr::null_mut(),
         }
@@ -100,17 +142,427 @@ impl KernelInvocation {
     ///
     /// # Safety
     ///
-    /// The function pointer must be a valid CUfunction pointer and
-    /// params' "ownership" must be able to be transferred to the invocation
-    /// (it will be turned into a Box).
+    /// The function pointer must be a valid CUfunction pointer and the kernel
+    /// params must stay valid for as long as the invocation is used, the invocation
+    /// does not take ownership of them.
     pub unsafe fn from_raw(raw: cuda::CUDA_KERNEL_NODE_PARAMS) -> Self {
         Self {
             func: raw.func,
             grid_dim: GridSize::xyz(raw.gridDimX, raw.gridDimY, raw.gridDimZ),
-            block_dim: BlockSize::xyz(raw.blockDimX, raw.gridDimY, raw.gridDimZ),
-            params: Box::from_raw(raw.kernelParams),
+            block_dim: BlockSize::xyz(raw.blockDimX, raw.blockDimY, raw.blockDimZ),
             shared_mem_bytes: raw.sharedMemBytes,
-            params_len: None,
+            raw_params: Some(raw.kernelParams),
+            param_ptrs: Vec::new(),
+            _args: Vec::new(),
+        }
+    }
+}
+
+/// The location of one side of a memcpy node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemcpyLocation {
+    /// Linear device memory, laid out as `depth` slices of `height` rows, each row
+    /// starting `pitch` bytes after the previous one.
+    Device {
+        ptr: DevicePointer<u8>,
+        pitch: usize,
+        height: usize,
+    },
+    /// Linear host memory, laid out the same way as [`MemcpyLocation::Device`]. This should be
+    /// page-locked memory such as a [`LockedBuffer`].
+    Host {
+        ptr: *mut c_void,
+        pitch: usize,
+        height: usize,
+    },
+    /// A CUDA array, the offset is the `(x in bytes, y, z)` offset into the array.
+    Array {
+        handle: cuda::CUarray,
+        offset: [usize; 3],
+    },
+}
+
+/// Memory which can be the source or destination of a memcpy node.
+///
+/// This trait is sealed and cannot be implemented outside of cust.
+pub trait MemcpyEndpoint: Sealed {
+    /// The location of the memory and its extent as `[width in bytes, height, depth]`.
+    fn memcpy_location(&self) -> CudaResult<(MemcpyLocation, [usize; 3])>;
+}
+
+impl<T: DeviceCopy> MemcpyEndpoint for DeviceSlice<T> {
+    fn memcpy_location(&self) -> CudaResult<(MemcpyLocation, [usize; 3])> {
+        let width = self.len() * mem::size_of::<T>();
+        let location = MemcpyLocation::Device {
+            ptr: self.as_device_ptr().cast(),
+            pitch: width,
+            height: 1,
+        };
+        Ok((location, [width, 1, 1]))
+    }
+}
+
+impl<T: DeviceCopy> Sealed for DeviceBuffer<T> {}
+impl<T: DeviceCopy> MemcpyEndpoint for DeviceBuffer<T> {
+    fn memcpy_location(&self) -> CudaResult<(MemcpyLocation, [usize; 3])> {
+        self.as_slice().memcpy_location()
+    }
+}
+
+impl<T: DeviceCopy> Sealed for LockedBuffer<T> {}
+impl<T: DeviceCopy> MemcpyEndpoint for LockedBuffer<T> {
+    fn memcpy_location(&self) -> CudaResult<(MemcpyLocation, [usize; 3])> {
+        let width = mem::size_of_val(self.as_slice());
+        let location = MemcpyLocation::Host {
+            ptr: self.as_ptr() as *mut c_void,
+            pitch: width,
+            height: 1,
+        };
+        Ok((location, [width, 1, 1]))
+    }
+}
+
+impl Sealed for ArrayObject {}
+impl MemcpyEndpoint for ArrayObject {
+    fn memcpy_location(&self) -> CudaResult<(MemcpyLocation, [usize; 3])> {
+        let desc = self.descriptor()?;
+        let width = desc.width() * desc.num_channels() as usize * desc.format().mem_size();
+        let location = MemcpyLocation::Array {
+            handle: self.handle,
+            offset: [0; 3],
+        };
+        Ok((location, [width, desc.height().max(1), desc.depth().max(1)]))
+    }
+}
+
+/// The parameters of a memcpy node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemcpyNodeParams {
+    pub src: MemcpyLocation,
+    pub dst: MemcpyLocation,
+    /// The extent of the copy as `[width in bytes, height, depth]`.
+    pub extent: [usize; 3],
+}
+
+impl MemcpyNodeParams {
+    /// Makes the parameters for copying all of `src` into `dst`.
+    ///
+    /// If either side is an array or pitched memory, the extent of the copy is the extent of that
+    /// side and the other side is treated as tightly packed memory of the same extent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sizes of `src` and `dst` are different, or if both sides are arrays or pitched
+    /// memory with different extents.
+    #[track_caller]
+    pub fn new<S, D>(src: &S, dst: &mut D) -> CudaResult<Self>
+    where
+        S: MemcpyEndpoint + ?Sized,
+        D: MemcpyEndpoint + ?Sized,
+    {
+        Ok(Self::from_locations(
+            src.memcpy_location()?,
+            dst.memcpy_location()?,
+        ))
+    }
+
+    #[track_caller]
+    pub(crate) fn from_locations(
+        (mut src, src_extent): (MemcpyLocation, [usize; 3]),
+        (mut dst, dst_extent): (MemcpyLocation, [usize; 3]),
+    ) -> Self {
+        assert_eq!(
+            src_extent.iter().product::<usize>(),
+            dst_extent.iter().product::<usize>(),
+            "Memcpy source and destination sizes don't match"
+        );
+
+        // flat memory takes on the extent of the other side, so that it can be copied
+        // to and from arrays and pitched memory.
+        let is_flat = |location: MemcpyLocation, extent: [usize; 3]| {
+            !matches!(location, MemcpyLocation::Array { .. }) && extent[1] == 1 && extent[2] == 1
+        };
+        let extent = match (is_flat(src, src_extent), is_flat(dst, dst_extent)) {
+            (false, false) => {
+                assert_eq!(
+                    src_extent, dst_extent,
+                    "Memcpy source and destination extents don't match"
+                );
+                src_extent
+            }
+            (false, true) => src_extent,
+            _ => dst_extent,
+        };
+
+        for (location, own_extent) in [(&mut src, src_extent), (&mut dst, dst_extent)] {
+            if own_extent == extent {
+                continue;
+            }
+            if let MemcpyLocation::Device { pitch, height, .. }
+            | MemcpyLocation::Host { pitch, height, .. } = location
+            {
+                *pitch = extent[0];
+                *height = extent[1];
+            }
+        }
+
+        Self { src, dst, extent }
+    }
+
+    // 2D copies are the same as 3D copies with a depth of 1, minus the pitch between slices.
+    pub(crate) fn to_raw_2d(self) -> cuda::CUDA_MEMCPY2D {
+        assert_eq!(self.extent[2], 1, "2D memcpy with a depth other than 1");
+        let raw = self.to_raw();
+        cuda::CUDA_MEMCPY2D {
+            srcXInBytes: raw.srcXInBytes,
+            srcY: raw.srcY,
+            srcMemoryType: raw.srcMemoryType,
+            srcHost: raw.srcHost,
+            srcDevice: raw.srcDevice,
+            srcArray: raw.srcArray,
+            srcPitch: raw.srcPitch,
+            dstXInBytes: raw.dstXInBytes,
+            dstY: raw.dstY,
+            dstMemoryType: raw.dstMemoryType,
+            dstHost: raw.dstHost,
+            dstDevice: raw.dstDevice,
+            dstArray: raw.dstArray,
+            dstPitch: raw.dstPitch,
+            WidthInBytes: raw.WidthInBytes,
+            Height: raw.Height,
+        }
+    }
+
+    /// Converts these params into their raw counterpart.
+    pub fn to_raw(self) -> cuda::CUDA_MEMCPY3D {
+        let mut raw = cuda::CUDA_MEMCPY3D {
+            srcXInBytes: 0,
+            srcY: 0,
+            srcZ: 0,
+            srcLOD: 0,
+            srcMemoryType: cuda::CUmemorytype::CU_MEMORYTYPE_DEVICE,
+            srcHost: ptr::null(),
+            srcDevice: 0,
+            srcArray: ptr::null_mut(),
+            reserved0: ptr::null_mut(),
+            srcPitch: 0,
+            srcHeight: 0,
+            dstXInBytes: 0,
+            dstY: 0,
+            dstZ: 0,
+            dstLOD: 0,
+            dstMemoryType: cuda::CUmemorytype::CU_MEMORYTYPE_DEVICE,
+            dstHost: ptr::null_mut(),
+            dstDevice: 0,
+            dstArray: ptr::null_mut(),
+            reserved1: ptr::null_mut(),
+            dstPitch: 0,
+            dstHeight: 0,
+            WidthInBytes: self.extent[0],
+            Height: self.extent[1],
+            Depth: self.extent[2],
+        };
+
+        match self.src {
+            MemcpyLocation::Device { ptr, pitch, height } => {
+                raw.srcMemoryType = cuda::CUmemorytype::CU_MEMORYTYPE_DEVICE;
+                raw.srcDevice = ptr.as_raw();
+                raw.srcPitch = pitch;
+                raw.srcHeight = height;
+            }
+            MemcpyLocation::Host { ptr, pitch, height } => {
+                raw.srcMemoryType = cuda::CUmemorytype::CU_MEMORYTYPE_HOST;
+                raw.srcHost = ptr;
+                raw.srcPitch = pitch;
+                raw.srcHeight = height;
+            }
+            MemcpyLocation::Array { handle, offset } => {
+                raw.srcMemoryType = cuda::CUmemorytype::CU_MEMORYTYPE_ARRAY;
+                raw.srcArray = handle;
+                raw.srcXInBytes = offset[0];
+                raw.srcY = offset[1];
+                raw.srcZ = offset[2];
+            }
+        }
+
+        match self.dst {
+            MemcpyLocation::Device { ptr, pitch, height } => {
+                raw.dstMemoryType = cuda::CUmemorytype::CU_MEMORYTYPE_DEVICE;
+                raw.dstDevice = ptr.as_raw();
+                raw.dstPitch = pitch;
+                raw.dstHeight = height;
+            }
+            MemcpyLocation::Host { ptr, pitch, height } => {
+                raw.dstMemoryType = cuda::CUmemorytype::CU_MEMORYTYPE_HOST;
+                raw.dstHost = ptr;
+                raw.dstPitch = pitch;
+                raw.dstHeight = height;
+            }
+            MemcpyLocation::Array { handle, offset } => {
+                raw.dstMemoryType = cuda::CUmemorytype::CU_MEMORYTYPE_ARRAY;
+                raw.dstArray = handle;
+                raw.dstXInBytes = offset[0];
+                raw.dstY = offset[1];
+                raw.dstZ = offset[2];
+            }
+        }
+
+        raw
+    }
+
+    /// Makes new params from their raw counterpart. Offsets into linear memory are
+    /// folded into the pointers.
+    pub fn from_raw(raw: cuda::CUDA_MEMCPY3D) -> Self {
+        fn location(
+            ty: cuda::CUmemorytype,
+            host: *mut c_void,
+            device: cuda::CUdeviceptr,
+            array: cuda::CUarray,
+            offset: [usize; 3],
+            pitch: usize,
+            height: usize,
+        ) -> MemcpyLocation {
+            let linear_offset = offset[0] + offset[1] * pitch + offset[2] * pitch * height;
+            match ty {
+                cuda::CUmemorytype::CU_MEMORYTYPE_HOST => MemcpyLocation::Host {
+                    ptr: (host as *mut u8).wrapping_add(linear_offset) as *mut c_void,
+                    pitch,
+                    height,
+                },
+                cuda::CUmemorytype::CU_MEMORYTYPE_DEVICE
+                | cuda::CUmemorytype::CU_MEMORYTYPE_UNIFIED => MemcpyLocation::Device {
+                    ptr: DevicePointer::from_raw(device).wrapping_offset(linear_offset as isize),
+                    pitch,
+                    height,
+                },
+                cuda::CUmemorytype::CU_MEMORYTYPE_ARRAY => MemcpyLocation::Array {
+                    handle: array,
+                    offset,
+                },
+            }
+        }
+
+        Self {
+            src: location(
+                raw.srcMemoryType,
+                raw.srcHost as *mut c_void,
+                raw.srcDevice,
+                raw.srcArray,
+                [raw.srcXInBytes, raw.srcY, raw.srcZ],
+                raw.srcPitch,
+                raw.srcHeight,
+            ),
+            dst: location(
+                raw.dstMemoryType,
+                raw.dstHost,
+                raw.dstDevice,
+                raw.dstArray,
+                [raw.dstXInBytes, raw.dstY, raw.dstZ],
+                raw.dstPitch,
+                raw.dstHeight,
+            ),
+            extent: [raw.WidthInBytes, raw.Height, raw.Depth],
+        }
+    }
+}
+
+/// The parameters of a memset node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemsetNodeParams {
+    /// The memory to set.
+    pub dst: DevicePointer<u8>,
+    /// The pitch of the memory in bytes, only used if `height` is larger than `1`.
+    pub pitch: usize,
+    /// The value to set every element to, only the lowest `element_size` bytes are used.
+    pub value: u32,
+    /// The size of every element in bytes, must be `1`, `2`, or `4`.
+    pub element_size: u32,
+    /// The width of every row in elements.
+    pub width: usize,
+    /// The number of rows.
+    pub height: usize,
+}
+
+impl MemsetNodeParams {
+    #[track_caller]
+    fn new_for_slice<T: DeviceCopy>(
+        slice: &mut DeviceSlice<T>,
+        value: u32,
+        element_size: u32,
+    ) -> Self {
+        let data_len = mem::size_of::<T>() * slice.len();
+        let element_size_usize = element_size as usize;
+        assert_eq!(
+            data_len % element_size_usize,
+            0,
+            "Buffer length is not a multiple of {} bytes!",
+            element_size
+        );
+        assert_eq!(
+            slice.as_device_ptr().as_raw() % element_size as u64,
+            0,
+            "Buffer pointer is not aligned to at least {} bytes!",
+            element_size
+        );
+        Self {
+            dst: slice.as_device_ptr().cast(),
+            pitch: data_len,
+            value,
+            element_size,
+            width: data_len / element_size_usize,
+            height: 1,
+        }
+    }
+
+    /// Makes the parameters for setting a slice to contiguous `8-bit` values of `value`.
+    #[track_caller]
+    pub fn new_8<T: DeviceCopy>(slice: &mut DeviceSlice<T>, value: u8) -> Self {
+        Self::new_for_slice(slice, value as u32, 1)
+    }
+
+    /// Makes the parameters for setting a slice to contiguous `16-bit` values of `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slice is not aligned to at least 2 bytes or if its size is not a
+    /// multiple of 2 bytes.
+    #[track_caller]
+    pub fn new_16<T: DeviceCopy>(slice: &mut DeviceSlice<T>, value: u16) -> Self {
+        Self::new_for_slice(slice, value as u32, 2)
+    }
+
+    /// Makes the parameters for setting a slice to contiguous `32-bit` values of `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slice is not aligned to at least 4 bytes or if its size is not a
+    /// multiple of 4 bytes.
+    #[track_caller]
+    pub fn new_32<T: DeviceCopy>(slice: &mut DeviceSlice<T>, value: u32) -> Self {
+        Self::new_for_slice(slice, value, 4)
+    }
+
+    /// Converts these params into their raw counterpart.
+    pub fn to_raw(self) -> cuda::CUDA_MEMSET_NODE_PARAMS {
+        cuda::CUDA_MEMSET_NODE_PARAMS {
+            dst: self.dst.as_raw(),
+            pitch: self.pitch,
+            value: self.value,
+            elementSize: self.element_size,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Makes new params from their raw counterpart.
+    pub fn from_raw(raw: cuda::CUDA_MEMSET_NODE_PARAMS) -> Self {
+        Self {
+            dst: DevicePointer::from_raw(raw.dst),
+            pitch: raw.pitch,
+            value: raw.value,
+            element_size: raw.elementSize,
+            width: raw.width,
+            height: raw.height,
         }
     }
 }
@@ -291,7 +743,8 @@ impl Graph {
     pub fn num_nodes(&mut self) -> CudaResult<usize> {
         unsafe {
             let mut len = MaybeUninit::uninit();
-            cuda::cuGraphGetNodes(self.raw, ptr::null_mut(), len.as_mut_ptr()).to_result()?;
+            cuda::cuGraphGetNodes(self.raw, ptr::null_mut(), len.as_mut_ptr())
+                .to_result_from("cuGraphGetNodes")?;
             Ok(len.assume_init())
         }
     }
@@ -307,7 +760,7 @@ impl Graph {
                     vec.as_mut_ptr() as *mut cuda::CUgraphNode,
                     &mut len as *mut usize,
                 )
-                .to_result()?;
+                .to_result_from("cuGraphGetNodes")?;
                 vec.set_len(len);
                 self.node_cache = Some(vec);
             }
@@ -320,7 +773,7 @@ impl Graph {
         let mut raw = MaybeUninit::uninit();
 
         unsafe {
-            cuda::cuGraphCreate(raw.as_mut_ptr(), flags.bits).to_result()?;
+            cuda::cuGraphCreate(raw.as_mut_ptr(), flags.bits).to_result_from("cuGraphCreate")?;
 
             Ok(Self {
                 raw: raw.assume_init(),
@@ -329,8 +782,20 @@ impl Graph {
         }
     }
 
+    /// Renders this graph as a dotfile which can be turned into an image with graphviz.
+    ///
+    /// Unlike [`Graph::dump_debug_dotfile`] this only includes the most useful info for every
+    /// node, such as kernel names and launch dimensions, memcpy sizes, and the contents of child
+    /// graphs. Nodes are named by their index in the graph, so the output is stable across runs and
+    /// can be diffed. Kernel names are only known for functions loaded through
+    /// [`Module::get_function`](crate::module::Module::get_function).
+    pub fn to_dot(&mut self) -> CudaResult<String> {
+        crate::graph_dotfile::graph_to_dot(self)
+    }
+
     /// Dumps a dotfile to a path which contains a visual representation of the graph for debugging.
-    /// This dotfile can be turned into an image with graphviz.
+    /// This dotfile can be turned into an image with graphviz. This uses CUDA's own verbose format,
+    /// see [`Graph::to_dot`] for a more compact format.
     #[cfg(any(windows, unix))]
     pub fn dump_debug_dotfile<P: AsRef<Path>>(&mut self, path: P) -> CudaResult<()> {
         // not currently present in cuda-driver-sys for some reason
@@ -366,7 +831,54 @@ impl Graph {
             );
         }
 
-        unsafe { cuGraphDebugDotPrint(self.raw, "./out.dot\0".as_ptr().cast(), 1 << 0).to_result() }
+        unsafe {
+            cuGraphDebugDotPrint(self.raw, buf.as_ptr().cast(), 1 << 0)
+                .to_result_from("cuGraphDebugDotPrint")
+        }
+    }
+
+    // Adds a node using `add`, after checking the dependencies and invalidating the node cache.
+    fn add_node(
+        &mut self,
+        func_name: &str,
+        deps: &[GraphNode],
+        add: impl FnOnce(
+            *mut cuda::CUgraphNode,
+            cuda::CUgraph,
+            *const cuda::CUgraphNode,
+            usize,
+        ) -> cuda::CUresult,
+    ) -> CudaResult<GraphNode> {
+        self.check_deps_are_valid(func_name, deps)?;
+        // invalidate cache because it will change.
+        self.node_cache = None;
+        let mut node = MaybeUninit::<GraphNode>::uninit();
+        add(
+            node.as_mut_ptr().cast(),
+            self.raw,
+            deps.as_ptr().cast(),
+            deps.len(),
+        )
+        .to_result()?;
+        unsafe { Ok(node.assume_init()) }
+    }
+
+    // Checks that a node is valid and that it is of a certain type.
+    fn check_node_type(
+        &mut self,
+        func_name: &str,
+        node: GraphNode,
+        ty: GraphNodeType,
+    ) -> CudaResult<()> {
+        self.check_deps_are_valid(func_name, &[node])?;
+        assert_eq!(
+            self.node_type(node)?,
+            ty,
+            "Node given to `{}` was not a {:?} node",
+            func_name,
+            ty
+        );
+        Ok(())
     }
 
     /// Adds a kernel invocation node to this graph, [`KernelInvocation`] can be created using
@@ -377,24 +889,190 @@ impl Graph {
         invocation: KernelInvocation,
         dependencies: impl AsRef<[GraphNode]>,
     ) -> CudaResult<GraphNode> {
-        let deps = dependencies.as_ref();
-        self.check_deps_are_valid("add_kernel_node", deps)?;
-        // invalidate cache because it will change.
-        self.node_cache = None;
+        let params = invocation.to_raw();
+        self.add_node(
+            "add_kernel_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddKernelNode(node, graph, deps, num_deps, &params as *const _)
+            },
+        )
+    }
+
+    /// Adds a memcpy node to this graph which will copy memory as described by `params`, the
+    /// params for copying between two pieces of memory can be created using [`MemcpyNodeParams::new`].
+    /// This will place the node after its dependencies (which will execute before it).
+    ///
+    /// The memory must still be alive when the graph is executed.
+    pub fn add_memcpy_node(
+        &mut self,
+        params: MemcpyNodeParams,
+        dependencies: impl AsRef<[GraphNode]>,
+    ) -> CudaResult<GraphNode> {
+        let ctx = current_context()?;
+        let params = params.to_raw();
+        self.add_node(
+            "add_memcpy_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddMemcpyNode(node, graph, deps, num_deps, &params as *const _, ctx)
+            },
+        )
+    }
+
+    /// Adds a memset node to this graph which will set memory as described by `params`.
+    /// This will place the node after its dependencies (which will execute before it).
+    ///
+    /// The memory must still be alive when the graph is executed.
+    pub fn add_memset_node(
+        &mut self,
+        params: MemsetNodeParams,
+        dependencies: impl AsRef<[GraphNode]>,
+    ) -> CudaResult<GraphNode> {
+        let ctx = current_context()?;
+        let params = params.to_raw();
+        self.add_node(
+            "add_memset_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddMemsetNode(node, graph, deps, num_deps, &params as *const _, ctx)
+            },
+        )
+    }
+
+    /// Adds a node to this graph which will execute a function on the host (CPU).
+    /// This will place the node after its dependencies (which will execute before it).
+    ///
+    /// The function is kept alive for as long as this graph, or any graph or executable graph
+    /// made from it, is alive. Just like [`Stream::add_callback`], the function must not make
+    /// any CUDA API calls. Panics inside of the function are caught and ignored.
+    pub fn add_host_node<F>(
+        &mut self,
+        func: F,
+        dependencies: impl AsRef<[GraphNode]>,
+    ) -> CudaResult<GraphNode>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        unsafe extern "C" fn host_node_wrapper<F: Fn()>(data: *mut c_void) {
+            // Stop panics from unwinding across the FFI
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| (*(data as *const F))()));
+        }
+
+        unsafe extern "C" fn destroy_wrapper<F>(data: *mut c_void) {
+            drop(Box::from_raw(data as *mut F));
+        }
+
+        let data = Box::into_raw(Box::new(func)) as *mut c_void;
         unsafe {
-            let deps_ptr = deps.as_ptr().cast();
-            let mut node = MaybeUninit::<GraphNode>::uninit();
-            let params = invocation.to_raw();
-            cuda::cuGraphAddKernelNode(
-                node.as_mut_ptr().cast(),
+            // the function is owned by a user object which the graph holds a reference to, this way
+            // the driver keeps it alive for clones of the graph and executable graphs too.
+            let mut object = ptr::null_mut();
+            if let Err(e) = cuda::cuUserObjectCreate(
+                &mut object as *mut _,
+                data,
+                Some(destroy_wrapper::<F>),
+                1,
+                cuda::CUuserObject_flags::CU_USER_OBJECT_NO_DESTRUCTOR_SYNC as c_uint,
+            )
+            .to_result_from("cuUserObjectCreate")
+            {
+                destroy_wrapper::<F>(data);
+                return Err(e);
+            }
+            if let Err(e) = cuda::cuGraphRetainUserObject(
                 self.raw,
-                deps_ptr,
-                deps.len(),
-                &params as *const _,
+                object,
+                1,
+                cuda::CUuserObjectRetain_flags::CU_GRAPH_USER_OBJECT_MOVE as c_uint,
             )
-            .to_result()?;
-            Ok(node.assume_init())
+            .to_result_from("cuGraphRetainUserObject")
+            {
+                cuda::cuUserObjectRelease(object, 1);
+                return Err(e);
+            }
         }
+
+        let params = cuda::CUDA_HOST_NODE_PARAMS {
+            fn_: Some(host_node_wrapper::<F>),
+            userData: data,
+        };
+        self.add_node(
+            "add_host_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddHostNode(node, graph, deps, num_deps, &params as *const _)
+            },
+        )
+    }
+
+    /// Adds a node to this graph which will record an event, just like [`Event::record`].
+    /// This will place the node after its dependencies (which will execute before it).
+    ///
+    /// The event must still be alive when the graph is executed.
+    pub fn add_event_record_node(
+        &mut self,
+        event: &Event,
+        dependencies: impl AsRef<[GraphNode]>,
+    ) -> CudaResult<GraphNode> {
+        self.add_node(
+            "add_event_record_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddEventRecordNode(node, graph, deps, num_deps, event.as_inner())
+            },
+        )
+    }
+
+    /// Adds a node to this graph which will wait for an event, just like [`Stream::wait_event`].
+    /// This will place the node after its dependencies (which will execute before it).
+    ///
+    /// The event must still be alive when the graph is executed.
+    pub fn add_event_wait_node(
+        &mut self,
+        event: &Event,
+        dependencies: impl AsRef<[GraphNode]>,
+    ) -> CudaResult<GraphNode> {
+        self.add_node(
+            "add_event_wait_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddEventWaitNode(node, graph, deps, num_deps, event.as_inner())
+            },
+        )
+    }
+
+    /// Adds a node to this graph which does nothing. This is useful for joining many nodes
+    /// into a single dependency for other nodes.
+    pub fn add_empty_node(
+        &mut self,
+        dependencies: impl AsRef<[GraphNode]>,
+    ) -> CudaResult<GraphNode> {
+        self.add_node(
+            "add_empty_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddEmptyNode(node, graph, deps, num_deps)
+            },
+        )
+    }
+
+    /// Adds a node to this graph which executes a child graph. The child graph is cloned into
+    /// this graph, so changes to `child` afterwards do not affect this graph.
+    /// This will place the node after its dependencies (which will execute before it).
+    pub fn add_child_graph_node(
+        &mut self,
+        child: &mut Graph,
+        dependencies: impl AsRef<[GraphNode]>,
+    ) -> CudaResult<GraphNode> {
+        let child = child.raw;
+        self.add_node(
+            "add_child_graph_node",
+            dependencies.as_ref(),
+            |node, graph, deps, num_deps| unsafe {
+                cuda::cuGraphAddChildGraphNode(node, graph, deps, num_deps, child)
+            },
+        )
     }
 
     /// The number of edges (dependency edges) inside this graph.
@@ -407,7 +1085,7 @@ impl Graph {
                 ptr::null_mut(),
                 size.as_mut_ptr(),
             )
-            .to_result()?;
+            .to_result_from("cuGraphGetEdges")?;
             Ok(size.assume_init())
         }
     }
@@ -431,7 +1109,7 @@ impl Graph {
                 to.as_mut_ptr(),
                 &num_edges as *const _ as *mut usize,
             )
-            .to_result()?;
+            .to_result_from("cuGraphGetEdges")?;
 
             let mut out = Vec::with_capacity(num_edges);
             for (from, to) in from.iter().zip(to.iter()) {
@@ -446,31 +1124,115 @@ impl Graph {
         self.check_deps_are_valid("node_type", &[node])?;
         unsafe {
             let mut ty = MaybeUninit::uninit();
-            cuda::cuGraphNodeGetType(node.to_raw(), ty.as_mut_ptr()).to_result()?;
+            cuda::cuGraphNodeGetType(node.to_raw(), ty.as_mut_ptr())
+                .to_result_from("cuGraphNodeGetType")?;
             let raw = ty.assume_init();
             Ok(GraphNodeType::from_raw(raw))
         }
     }
 
-    /// Retrieves the invocation parameters for a kernel invocation node.
+    /// Retrieves the invocation parameters for a kernel invocation node. The kernel
+    /// params of the returned invocation are owned by the graph, they are only valid until the
+    /// graph is dropped or the params of the node are changed.
     ///
     /// # Panics
     ///
     /// Panics if the node is invalid or if the node is not a kernel invocation node.
     pub fn kernel_node_params(&mut self, node: GraphNode) -> CudaResult<KernelInvocation> {
-        self.check_deps_are_valid("kernel_node_params", &[node])?;
-        assert_eq!(
-            self.node_type(node)?,
-            GraphNodeType::KernelInvocation,
-            "Node given to `kernel_node_params` was not a kernel invocation node"
-        );
+        self.check_node_type("kernel_node_params", node, GraphNodeType::KernelInvocation)?;
         unsafe {
             let mut params = MaybeUninit::uninit();
-            cuda::cuGraphKernelNodeGetParams(node.to_raw(), params.as_mut_ptr());
+            cuda::cuGraphKernelNodeGetParams(node.to_raw(), params.as_mut_ptr())
+                .to_result_from("cuGraphKernelNodeGetParams")?;
             Ok(KernelInvocation::from_raw(params.assume_init()))
         }
     }
 
+    /// Retrieves the parameters of a memcpy node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is invalid or if the node is not a memcpy node.
+    pub fn memcpy_node_params(&mut self, node: GraphNode) -> CudaResult<MemcpyNodeParams> {
+        self.check_node_type("memcpy_node_params", node, GraphNodeType::Memcpy)?;
+        unsafe {
+            let mut params = MaybeUninit::uninit();
+            cuda::cuGraphMemcpyNodeGetParams(node.to_raw(), params.as_mut_ptr())
+                .to_result_from("cuGraphMemcpyNodeGetParams")?;
+            Ok(MemcpyNodeParams::from_raw(params.assume_init()))
+        }
+    }
+
+    /// Retrieves the parameters of a memset node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is invalid or if the node is not a memset node.
+    pub fn memset_node_params(&mut self, node: GraphNode) -> CudaResult<MemsetNodeParams> {
+        self.check_node_type("memset_node_params", node, GraphNodeType::Memset)?;
+        unsafe {
+            let mut params = MaybeUninit::uninit();
+            cuda::cuGraphMemsetNodeGetParams(node.to_raw(), params.as_mut_ptr())
+                .to_result_from("cuGraphMemsetNodeGetParams")?;
+            Ok(MemsetNodeParams::from_raw(params.assume_init()))
+        }
+    }
+
+    /// Retrieves the raw handle of the event recorded by an event record node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is invalid or if the node is not an event record node.
+    pub fn event_record_node_event(&mut self, node: GraphNode) -> CudaResult<cuda::CUevent> {
+        self.check_node_type("event_record_node_event", node, GraphNodeType::EventRecord)?;
+        unsafe {
+            let mut event = ptr::null_mut();
+            cuda::cuGraphEventRecordNodeGetEvent(node.to_raw(), &mut event as *mut _)
+                .to_result_from("cuGraphEventRecordNodeGetEvent")?;
+            Ok(event)
+        }
+    }
+
+    /// Retrieves the raw handle of the event waited on by an event wait node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is invalid or if the node is not an event wait node.
+    pub fn event_wait_node_event(&mut self, node: GraphNode) -> CudaResult<cuda::CUevent> {
+        self.check_node_type("event_wait_node_event", node, GraphNodeType::WaitEvent)?;
+        unsafe {
+            let mut event = ptr::null_mut();
+            cuda::cuGraphEventWaitNodeGetEvent(node.to_raw(), &mut event as *mut _)
+                .to_result_from("cuGraphEventWaitNodeGetEvent")?;
+            Ok(event)
+        }
+    }
+
+    /// Retrieves a clone of the child graph of a child graph node. Changes to the returned
+    /// graph do not affect this graph.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is invalid or if the node is not a child graph node.
+    pub fn child_graph_node_graph(&mut self, node: GraphNode) -> CudaResult<Graph> {
+        self.check_node_type("child_graph_node_graph", node, GraphNodeType::ChildGraph)?;
+        unsafe {
+            // the child graph is owned by this graph, so clone it to hand out an owned graph.
+            let mut child = ptr::null_mut();
+            cuda::cuGraphChildGraphNodeGetGraph(node.to_raw(), &mut child as *mut _)
+                .to_result_from("cuGraphChildGraphNodeGetGraph")?;
+            let mut clone = ptr::null_mut();
+            cuda::cuGraphClone(&mut clone as *mut _, child).to_result_from("cuGraphClone")?;
+            Ok(Graph::from_raw(clone))
+        }
+    }
+
+    /// Instantiates this graph into an executable graph which can then be launched.
+    /// This is a shortcut for [`GraphExec::new`].
+    pub fn instantiate(&mut self, flags: GraphInstantiateFlags) -> CudaResult<GraphExec> {
+        GraphExec::new(self, flags)
+    }
+
     /// Creates a new [`Graph`] from a raw handle.
     ///
     /// # Safety
@@ -501,3 +1263,402 @@ impl Drop for Graph {
         }
     }
 }
+
+// Memcpy and memset nodes need the context they operate in.
+fn current_context() -> CudaResult<cuda::CUcontext> {
+    unsafe {
+        let mut ctx = ptr::null_mut();
+        cuda::cuCtxGetCurrent(&mut ctx as *mut _).to_result_from("cuCtxGetCurrent")?;
+        Ok(ctx)
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags for instantiating a graph into a [`GraphExec`].
+    #[derive(Default)]
+    pub struct GraphInstantiateFlags: u64 {
+        /// Automatically free any memory allocated by memory allocation nodes in the graph
+        /// before relaunching the graph.
+        const AUTO_FREE_ON_LAUNCH = 0b00000001;
+    }
+}
+
+/// The outcome of trying to update an executable graph with [`GraphExec::update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GraphExecUpdateResult {
+    /// The update succeeded.
+    Success,
+    /// The update failed for an unexpected reason.
+    Error,
+    /// The update failed because the topology of the graph changed.
+    TopologyChanged,
+    /// The update failed because a node type changed.
+    NodeTypeChanged,
+    /// The update failed because the function of a kernel node changed.
+    FunctionChanged,
+    /// The update failed because the parameters of a node changed in a way that is not supported.
+    ParametersChanged,
+    /// The update failed because something about a node is not supported.
+    NotSupported,
+    /// The update failed because the function of a kernel node changed in an unsupported way.
+    UnsupportedFunctionChange,
+}
+
+impl GraphExecUpdateResult {
+    /// Converts a raw update result to a [`GraphExecUpdateResult`].
+    pub fn from_raw(raw: cuda::CUgraphExecUpdateResult) -> Self {
+        use cuda::CUgraphExecUpdateResult as R;
+
+        match raw {
+            R::CU_GRAPH_EXEC_UPDATE_SUCCESS => Self::Success,
+            R::CU_GRAPH_EXEC_UPDATE_ERROR => Self::Error,
+            R::CU_GRAPH_EXEC_UPDATE_ERROR_TOPOLOGY_CHANGED => Self::TopologyChanged,
+            R::CU_GRAPH_EXEC_UPDATE_ERROR_NODE_TYPE_CHANGED => Self::NodeTypeChanged,
+            R::CU_GRAPH_EXEC_UPDATE_ERROR_FUNCTION_CHANGED => Self::FunctionChanged,
+            R::CU_GRAPH_EXEC_UPDATE_ERROR_PARAMETERS_CHANGED => Self::ParametersChanged,
+            R::CU_GRAPH_EXEC_UPDATE_ERROR_NOT_SUPPORTED => Self::NotSupported,
+            R::CU_GRAPH_EXEC_UPDATE_ERROR_UNSUPPORTED_FUNCTION_CHANGE => {
+                Self::UnsupportedFunctionChange
+            }
+        }
+    }
+
+    /// Whether the update succeeded.
+    pub fn is_success(self) -> bool {
+        self == Self::Success
+    }
+}
+
+/// An executable graph, made by instantiating a [`Graph`].
+///
+/// Instantiating a graph does all of the expensive setup and validation work once, after which
+/// the graph can be launched as many times as needed with very little overhead, which is
+/// much cheaper than launching every kernel separately.
+///
+/// The executable graph is a snapshot of the graph at the time it was instantiated, changes to the
+/// graph afterwards do not affect it. To change the executable graph you can either set the params of
+/// individual kernel nodes using [`GraphExec::set_kernel_node_params`], or update the whole executable graph
+/// from a graph with the same topology using [`GraphExec::update`]. Both of these are much cheaper than
+/// instantiating the graph again.
+#[derive(Debug)]
+pub struct GraphExec {
+    raw: cuda::CUgraphExec,
+}
+
+// SAFETY: the executable graph is not tied to the thread which created it. It is not `Sync`
+// since launching the same executable graph from several threads at once is not allowed.
+unsafe impl Send for GraphExec {}
+
+impl GraphExec {
+    /// Instantiates a graph into an executable graph.
+    pub fn new(graph: &mut Graph, flags: GraphInstantiateFlags) -> CudaResult<Self> {
+        let mut raw = MaybeUninit::uninit();
+
+        unsafe {
+            cuda::cuGraphInstantiateWithFlags(raw.as_mut_ptr(), graph.raw, flags.bits)
+                .to_result_from("cuGraphInstantiateWithFlags")?;
+
+            Ok(Self {
+                raw: raw.assume_init(),
+            })
+        }
+    }
+
+    /// Launches this executable graph on a stream. Each launch is ordered behind any previous work
+    /// in the stream as well as any previous launches of this graph.
+    ///
+    /// # Safety
+    ///
+    /// Launching a graph has the same invariants as launching every kernel inside of it, additionally,
+    /// any memory used by the graph must not have been dropped.
+    pub unsafe fn launch(&self, stream: &Stream) -> DriverResult<()> {
+        cuda::cuGraphLaunch(self.raw, stream.as_inner()).to_driver_result("cuGraphLaunch")
+    }
+
+    /// Uploads this executable graph to the device without launching it. This is optional, but
+    /// it allows the setup cost of the first launch to be paid ahead of time.
+    pub fn upload(&self, stream: &Stream) -> CudaResult<()> {
+        unsafe { cuda::cuGraphUpload(self.raw, stream.as_inner()).to_result_from("cuGraphUpload") }
+    }
+
+    /// Sets the parameters of a kernel node in this executable graph. The node must be a node
+    /// of the graph this executable graph was instantiated from, which is passed for validation.
+    /// This does not modify the graph itself, only the executable graph.
+    ///
+    /// The kernel function of the node can only be changed to a function from the same context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node is invalid or if the node is not a kernel invocation node.
+    pub fn set_kernel_node_params(
+        &mut self,
+        graph: &mut Graph,
+        node: GraphNode,
+        invocation: &KernelInvocation,
+    ) -> CudaResult<()> {
+        graph.check_node_type(
+            "set_kernel_node_params",
+            node,
+            GraphNodeType::KernelInvocation,
+        )?;
+        unsafe {
+            let params = invocation.to_raw();
+            cuda::cuGraphExecKernelNodeSetParams(self.raw, node.to_raw(), &params as *const _)
+                .to_result_from("cuGraphExecKernelNodeSetParams")
+        }
+    }
+
+    /// Updates this executable graph with the node parameters of a graph which has the same
+    /// topology as the graph this executable graph was instantiated from.
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of the update, as well as the node which caused the update to fail,
+    /// if the driver reported one. If the update fails, the executable graph is left unchanged.
+    pub fn update(
+        &mut self,
+        graph: &mut Graph,
+    ) -> CudaResult<(GraphExecUpdateResult, Option<GraphNode>)> {
+        let mut error_node: cuda::CUgraphNode = ptr::null_mut();
+        let mut result = MaybeUninit::uninit();
+
+        unsafe {
+            match cuda::cuGraphExecUpdate(
+                self.raw,
+                graph.raw,
+                &mut error_node as *mut _,
+                result.as_mut_ptr(),
+            )
+            .to_result_from("cuGraphExecUpdate")
+            {
+                Ok(()) | Err(CudaError::GraphExecUpdateFailure) => {}
+                Err(e) => return Err(e),
+            }
+
+            let node = if error_node.is_null() {
+                None
+            } else {
+                Some(GraphNode::from_raw(error_node))
+            };
+            Ok((GraphExecUpdateResult::from_raw(result.assume_init()), node))
+        }
+    }
+
+    /// Creates a new [`GraphExec`] from a raw handle.
+    ///
+    /// # Safety
+    ///
+    /// The handle must be a valid executable graph handle and it must be exclusive, nothing
+    /// else can use it in any way, including trying to drop it.
+    pub unsafe fn from_raw(raw: cuda::CUgraphExec) -> Self {
+        Self { raw }
+    }
+
+    /// Consumes this [`GraphExec`], turning it into a raw handle. The handle will not be dropped,
+    /// it is up to the caller to ensure the executable graph is destroyed.
+    pub fn into_raw(self) -> cuda::CUgraphExec {
+        let me = ManuallyDrop::new(self);
+        me.raw
+    }
+}
+
+impl Drop for GraphExec {
+    fn drop(&mut self) {
+        unsafe {
+            cuda::cuGraphExecDestroy(self.raw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::launch;
+    use crate::memory::{CopyDestination, DeviceBuffer, LockedBuffer};
+    use crate::module::Module;
+    use crate::quick_init;
+    use crate::stream::{StreamCaptureMode, StreamCaptureStatus, StreamFlags};
+    use std::error::Error;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    static ADD_PTX: &str = include_str!("../resources/add.ptx");
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_launch_graph_and_set_kernel_node_params() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let module = Module::from_ptx(ADD_PTX, &[])?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+
+        let x = DeviceBuffer::from_slice(&[1.0f32; 16])?;
+        let y = DeviceBuffer::from_slice(&[2.0f32; 16])?;
+        let out = DeviceBuffer::from_slice(&[0.0f32; 16])?;
+        let out2 = DeviceBuffer::from_slice(&[0.0f32; 16])?;
+
+        let mut graph = Graph::new(GraphCreationFlags::NONE)?;
+        let invocation = kernel_invocation!(
+            module.sum<<<1, 16, 0, stream>>>(
+                x.as_device_ptr(),
+                y.as_device_ptr(),
+                out.as_device_ptr(),
+                out.len()
+            )
+        )?;
+        let node = graph.add_kernel_node(invocation, [])?;
+        let mut exec = graph.instantiate(GraphInstantiateFlags::empty())?;
+
+        unsafe { exec.launch(&stream)? };
+        stream.synchronize()?;
+        let mut host = [0.0f32; 16];
+        out.copy_to(&mut host[..])?;
+        assert_eq!(host, [3.0; 16]);
+
+        let invocation = kernel_invocation!(
+            module.sum<<<1, 16, 0, stream>>>(
+                x.as_device_ptr(),
+                x.as_device_ptr(),
+                out2.as_device_ptr(),
+                out2.len()
+            )
+        )?;
+        exec.set_kernel_node_params(&mut graph, node, &invocation)?;
+
+        unsafe { exec.launch(&stream)? };
+        stream.synchronize()?;
+        out2.copy_to(&mut host[..])?;
+        assert_eq!(host, [2.0; 16]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_capture_stream_into_graph() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let module = Module::from_ptx(ADD_PTX, &[])?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+
+        let x = DeviceBuffer::from_slice(&[1.0f32; 16])?;
+        let y = DeviceBuffer::from_slice(&[2.0f32; 16])?;
+        let out = DeviceBuffer::from_slice(&[0.0f32; 16])?;
+
+        assert_eq!(stream.capture_status()?, StreamCaptureStatus::None);
+        stream.begin_capture(StreamCaptureMode::ThreadLocal)?;
+        assert_eq!(stream.capture_status()?, StreamCaptureStatus::Active);
+        assert!(stream.capture_id()?.is_some());
+        unsafe {
+            launch!(module.sum<<<1, 16, 0, stream>>>(
+                x.as_device_ptr(),
+                y.as_device_ptr(),
+                out.as_device_ptr(),
+                out.len()
+            ))?;
+        }
+        let mut graph = stream.end_capture()?;
+        assert!(!stream.is_capturing()?);
+        assert_eq!(graph.num_nodes()?, 1);
+        let node = graph.nodes()?[0];
+        assert_eq!(graph.node_type(node)?, GraphNodeType::KernelInvocation);
+
+        let exec = graph.instantiate(GraphInstantiateFlags::empty())?;
+        unsafe { exec.launch(&stream)? };
+        stream.synchronize()?;
+        let mut host = [0.0f32; 16];
+        out.copy_to(&mut host[..])?;
+        assert_eq!(host, [3.0; 16]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_memset_memcpy_and_host_nodes() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+
+        let mut buf = DeviceBuffer::from_slice(&[0u32; 16])?;
+        let mut host = LockedBuffer::new(&0u32, 16)?;
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let mut graph = Graph::new(GraphCreationFlags::NONE)?;
+        let memset = graph.add_memset_node(MemsetNodeParams::new_32(&mut buf, 7), [])?;
+        let memcpy = graph.add_memcpy_node(MemcpyNodeParams::new(&*buf, &mut host)?, [memset])?;
+        let counter2 = counter.clone();
+        let host_node = graph.add_host_node(
+            move || {
+                counter2.fetch_add(1, Ordering::SeqCst);
+            },
+            [memcpy],
+        )?;
+        let empty = graph.add_empty_node([memset, host_node])?;
+
+        assert_eq!(graph.num_nodes()?, 4);
+        assert_eq!(graph.node_type(empty)?, GraphNodeType::Empty);
+        assert_eq!(graph.memset_node_params(memset)?.value, 7);
+        assert_eq!(graph.memcpy_node_params(memcpy)?.extent, [64, 1, 1]);
+
+        let exec = graph.instantiate(GraphInstantiateFlags::empty())?;
+        drop(graph);
+        unsafe {
+            exec.launch(&stream)?;
+            exec.launch(&stream)?;
+        }
+        stream.synchronize()?;
+        assert_eq!(host.as_slice(), &[7; 16]);
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_to_dot() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let module = Module::from_ptx(ADD_PTX, &[])?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+
+        let x = DeviceBuffer::from_slice(&[1.0f32; 16])?;
+        let mut out = DeviceBuffer::from_slice(&[0.0f32; 16])?;
+
+        let mut child = Graph::new(GraphCreationFlags::NONE)?;
+        child.add_memset_node(MemsetNodeParams::new_8(&mut out, 0), [])?;
+
+        let mut graph = Graph::new(GraphCreationFlags::NONE)?;
+        let child_node = graph.add_child_graph_node(&mut child, [])?;
+        let invocation = kernel_invocation!(
+            module.sum<<<1, 16, 0, stream>>>(
+                x.as_device_ptr(),
+                x.as_device_ptr(),
+                out.as_device_ptr(),
+                out.len()
+            )
+        )?;
+        graph.add_kernel_node(invocation, [child_node])?;
+
+        let dot = graph.to_dot()?;
+        assert_eq!(
+            dot,
+            "digraph cuda_graph {
+  node [shape = box];
+  subgraph cluster_n0 {
+    label = \"n0\";
+    style = dashed;
+    n0 [label = \"child graph\", shape = oval];
+    n0_0 [label = \"memset\\n64 x 1 bytes\\nvalue 0x0\"];
+    n0 -> n0_0 [style = dashed];
+  }
+  n1 [label = \"sum\\ngrid (1, 1, 1)\\nblock (16, 1, 1)\"];
+  n0 -> n1;
+}
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "Memcpy source and destination sizes don't match")]
+    fn test_memcpy_node_params_size_mismatch() {
+        let _context = quick_init().unwrap();
+        let buf = DeviceBuffer::from_slice(&[0u32; 16]).unwrap();
+        let mut host = LockedBuffer::new(&0u32, 8).unwrap();
+        let _ = MemcpyNodeParams::new(&*buf, &mut host);
+    }
+}
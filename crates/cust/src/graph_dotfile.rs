@@ -1,36 +1,155 @@
-/// Implementation of turning a Graph into a dotfile for debugging and visualization.
+//! Implementation of turning a Graph into a dotfile for debugging and visualization.
 use crate::{
     error::CudaResult,
-    graph::{Graph, GraphNode, GraphNodeType},
+    function::function_name,
+    graph::{Graph, GraphNode, GraphNodeType, MemcpyLocation},
 };
 use std::fmt::Write;
 
 // CUDA has a function exactly for this, but it has a couple issues:
 // - it includes useless info users dont really need
 // - it can only dump it to a file
-// - it takes a cstring for the
+// - it takes a cstring for the path
+// - the node names include handles, so the output changes every run
+//
+// So we render our own, nodes are named by their index in the graph so that the same graph
+// always renders to the same dotfile.
 
-// #[allow(unused_must_use)]
-// pub(crate) fn graph_to_dot(graph: &mut Graph) -> CudaResult<String> {
-//     let mut dot = String::new();
+#[allow(unused_must_use)]
+pub(crate) fn graph_to_dot(graph: &mut Graph) -> CudaResult<String> {
+    let mut dot = String::new();
 
-//     writeln!(dot, "digraph dot {{");
-//     writeln!(dot, "  subgraph cluster_1 {{");
-//     writeln!(dot, "    label = \"Graph 1\" graph[style = \"dashed\"];");
-//     writeln!(dot, "    ")
+    writeln!(dot, "digraph cuda_graph {{");
+    writeln!(dot, "  node [shape = box];");
+    write_graph(&mut dot, graph, "n", 1)?;
+    writeln!(dot, "}}");
 
-//     Ok(dot)
-// }
+    Ok(dot)
+}
 
-// fn node_to_dot(graph: &mut Graph, node: GraphNode, graph_num: u32, node_num: u32) -> CudaResult<String> {
-//     let kind = graph.node_type(node)?;
+#[allow(unused_must_use)]
+fn write_graph(dot: &mut String, graph: &mut Graph, prefix: &str, depth: usize) -> CudaResult<()> {
+    let indent = "  ".repeat(depth);
+    let nodes = graph.nodes()?.to_vec();
+    let index = |node: GraphNode| nodes.iter().position(|x| *x == node).unwrap();
 
-//     let name = format!("graph_{}_node_{}", graph_num, node_num);
-//     let style = "Mrecord";
-//     let contents = match graph.node_type(node)? {
-//         GraphNodeType::KernelInvocation => {
-//             let params = graph.kernel_node_params(node)?;
+    for (idx, node) in nodes.iter().enumerate() {
+        let node_name = format!("{}{}", prefix, idx);
+        if graph.node_type(*node)? == GraphNodeType::ChildGraph {
+            let mut child = graph.child_graph_node_graph(*node)?;
+            writeln!(dot, "{}subgraph cluster_{} {{", indent, node_name);
+            writeln!(dot, "{}  label = \"{}\";", indent, node_name);
+            writeln!(dot, "{}  style = dashed;", indent);
+            writeln!(
+                dot,
+                "{}  {} [label = \"child graph\", shape = oval];",
+                indent, node_name
+            );
+            let child_prefix = format!("{}_", node_name);
+            write_graph(dot, &mut child, &child_prefix, depth + 1)?;
 
-//         }
-//     }
-// }
+            // connect the child graph node to the root nodes of the child graph.
+            let child_nodes = child.nodes()?.to_vec();
+            let child_edges = child.edges()?;
+            for (child_idx, child_node) in child_nodes.iter().enumerate() {
+                if !child_edges.iter().any(|(_, to)| to == child_node) {
+                    writeln!(
+                        dot,
+                        "{}  {} -> {}{} [style = dashed];",
+                        indent, node_name, child_prefix, child_idx
+                    );
+                }
+            }
+            writeln!(dot, "{}}}", indent);
+        } else {
+            let label = node_label(graph, *node)?;
+            writeln!(dot, "{}{} [label = \"{}\"];", indent, node_name, label);
+        }
+    }
+
+    let mut edges = graph
+        .edges()?
+        .into_iter()
+        .map(|(from, to)| (index(from), index(to)))
+        .collect::<Vec<_>>();
+    edges.sort_unstable();
+    for (from, to) in edges {
+        writeln!(dot, "{}{}{} -> {}{};", indent, prefix, from, prefix, to);
+    }
+
+    Ok(())
+}
+
+fn node_label(graph: &mut Graph, node: GraphNode) -> CudaResult<String> {
+    Ok(match graph.node_type(node)? {
+        GraphNodeType::KernelInvocation => {
+            let invocation = graph.kernel_node_params(node)?;
+            let func = invocation.to_raw().func;
+            let name = function_name(func).unwrap_or_else(|| "kernel".to_string());
+            let grid = invocation.grid_dim;
+            let block = invocation.block_dim;
+            let mut label = format!(
+                "{}\\ngrid ({}, {}, {})\\nblock ({}, {}, {})",
+                escape(&name),
+                grid.x,
+                grid.y,
+                grid.z,
+                block.x,
+                block.y,
+                block.z
+            );
+            if invocation.shared_mem_bytes != 0 {
+                label.push_str(&format!("\\nshared {} bytes", invocation.shared_mem_bytes));
+            }
+            label
+        }
+        GraphNodeType::Memcpy => {
+            let params = graph.memcpy_node_params(node)?;
+            let [width, height, depth] = params.extent;
+            let size = if height == 1 && depth == 1 {
+                format!("{} bytes", width)
+            } else {
+                format!("{} x {} x {} bytes", width, height, depth)
+            };
+            format!(
+                "memcpy\\n{} -> {}\\n{}",
+                location_name(params.src),
+                location_name(params.dst),
+                size
+            )
+        }
+        GraphNodeType::Memset => {
+            let params = graph.memset_node_params(node)?;
+            let size = if params.height == 1 {
+                format!("{} x {} bytes", params.width, params.element_size)
+            } else {
+                format!(
+                    "{} x {} x {} bytes",
+                    params.width, params.height, params.element_size
+                )
+            };
+            format!("memset\\n{}\\nvalue {:#x}", size, params.value)
+        }
+        GraphNodeType::HostExecute => "host function".to_string(),
+        GraphNodeType::ChildGraph => "child graph".to_string(),
+        GraphNodeType::Empty => "empty".to_string(),
+        GraphNodeType::WaitEvent => "event wait".to_string(),
+        GraphNodeType::EventRecord => "event record".to_string(),
+        GraphNodeType::SemaphoreSignal => "semaphore signal".to_string(),
+        GraphNodeType::SemaphoreWait => "semaphore wait".to_string(),
+        GraphNodeType::MemoryAllocation => "memory allocation".to_string(),
+        GraphNodeType::MemoryFree => "memory free".to_string(),
+    })
+}
+
+fn location_name(location: MemcpyLocation) -> &'static str {
+    match location {
+        MemcpyLocation::Device { .. } => "device",
+        MemcpyLocation::Host { .. } => "host",
+        MemcpyLocation::Array { .. } => "array",
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -116,7 +116,7 @@
 
 use crate::context::ContextHandle;
 use crate::device::Device;
-use crate::error::{CudaResult, DropResult, ToResult};
+use crate::error::{CudaResult, DriverResult, DropResult, ToResult};
 use crate::private::Sealed;
 use crate::sys::{self as cuda, CUcontext};
 use crate::CudaApiVersion;
@@ -260,7 +260,7 @@ impl Context {
             // push again.
             let mut ctx: CUcontext = ptr::null_mut();
             cuda::cuCtxCreate_v2(&mut ctx as *mut CUcontext, flags.bits(), device.as_raw())
-                .to_result()?;
+                .to_result_from("cuCtxCreate_v2")?;
             Ok(Context { inner: ctx })
         }
     }
@@ -287,7 +287,7 @@ impl Context {
     pub fn get_api_version(&self) -> CudaResult<CudaApiVersion> {
         unsafe {
             let mut api_version = 0u32;
-            cuda::cuCtxGetApiVersion(self.inner, &mut api_version as *mut u32).to_result()?;
+            cuda::cuCtxGetApiVersion(self.inner, &mut api_version as *mut u32).to_result_from("cuCtxGetApiVersion")?;
             Ok(CudaApiVersion {
                 version: api_version as i32,
             })
@@ -351,7 +351,7 @@ impl Context {
 
         unsafe {
             let inner = mem::replace(&mut ctx.inner, ptr::null_mut());
-            match cuda::cuCtxDestroy_v2(inner).to_result() {
+            match cuda::cuCtxDestroy_v2(inner).to_result_from("cuCtxDestroy_v2") {
                 Ok(()) => {
                     mem::forget(ctx);
                     Ok(())
@@ -419,7 +419,7 @@ impl UnownedContext {
     pub fn get_api_version(&self) -> CudaResult<CudaApiVersion> {
         unsafe {
             let mut api_version = 0u32;
-            cuda::cuCtxGetApiVersion(self.inner, &mut api_version as *mut u32).to_result()?;
+            cuda::cuCtxGetApiVersion(self.inner, &mut api_version as *mut u32).to_result_from("cuCtxGetApiVersion")?;
             Ok(CudaApiVersion {
                 version: api_version as i32,
             })
@@ -453,7 +453,7 @@ impl ContextStack {
     pub fn pop() -> CudaResult<UnownedContext> {
         unsafe {
             let mut ctx: CUcontext = ptr::null_mut();
-            cuda::cuCtxPopCurrent_v2(&mut ctx as *mut CUcontext).to_result()?;
+            cuda::cuCtxPopCurrent_v2(&mut ctx as *mut CUcontext).to_result_from("cuCtxPopCurrent_v2")?;
             Ok(UnownedContext { inner: ctx })
         }
     }
@@ -478,7 +478,7 @@ impl ContextStack {
     /// ```
     pub fn push<C: ContextHandle>(ctx: &C) -> CudaResult<()> {
         unsafe {
-            cuda::cuCtxPushCurrent_v2(ctx.get_inner()).to_result()?;
+            cuda::cuCtxPushCurrent_v2(ctx.get_inner()).to_result_from("cuCtxPushCurrent_v2")?;
             Ok(())
         }
     }
@@ -526,7 +526,7 @@ impl CurrentContext {
         unsafe {
             let mut config = CacheConfig::PreferNone;
             cuda::cuCtxGetCacheConfig(&mut config as *mut CacheConfig as *mut cuda::CUfunc_cache)
-                .to_result()?;
+                .to_result_from("cuCtxGetCacheConfig")?;
             Ok(config)
         }
     }
@@ -551,7 +551,7 @@ impl CurrentContext {
     pub fn get_device() -> CudaResult<Device> {
         unsafe {
             let mut device = Device { device: 0 };
-            cuda::cuCtxGetDevice(&mut device.device as *mut cuda::CUdevice).to_result()?;
+            cuda::cuCtxGetDevice(&mut device.device as *mut cuda::CUdevice).to_result_from("cuCtxGetDevice")?;
             Ok(device)
         }
     }
@@ -576,7 +576,7 @@ impl CurrentContext {
     pub fn get_flags() -> CudaResult<ContextFlags> {
         unsafe {
             let mut flags = 0u32;
-            cuda::cuCtxGetFlags(&mut flags as *mut u32).to_result()?;
+            cuda::cuCtxGetFlags(&mut flags as *mut u32).to_result_from("cuCtxGetFlags")?;
             Ok(ContextFlags::from_bits_truncate(flags))
         }
     }
@@ -601,7 +601,7 @@ impl CurrentContext {
     pub fn get_resource_limit(resource: ResourceLimit) -> CudaResult<usize> {
         unsafe {
             let mut limit: usize = 0;
-            cuda::cuCtxGetLimit(&mut limit as *mut usize, transmute(resource)).to_result()?;
+            cuda::cuCtxGetLimit(&mut limit as *mut usize, transmute(resource)).to_result_from("cuCtxGetLimit")?;
             Ok(limit)
         }
     }
@@ -629,7 +629,7 @@ impl CurrentContext {
             cuda::cuCtxGetSharedMemConfig(
                 &mut cfg as *mut SharedMemoryConfig as *mut cuda::CUsharedconfig,
             )
-            .to_result()?;
+            .to_result_from("cuCtxGetSharedMemConfig")?;
             Ok(cfg)
         }
     }
@@ -665,7 +665,7 @@ impl CurrentContext {
                 &mut range.least as *mut i32,
                 &mut range.greatest as *mut i32,
             )
-            .to_result()?;
+            .to_result_from("cuCtxGetStreamPriorityRange")?;
             Ok(range)
         }
     }
@@ -696,7 +696,7 @@ impl CurrentContext {
     /// # }
     /// ```
     pub fn set_cache_config(cfg: CacheConfig) -> CudaResult<()> {
-        unsafe { cuda::cuCtxSetCacheConfig(transmute(cfg)).to_result() }
+        unsafe { cuda::cuCtxSetCacheConfig(transmute(cfg)).to_result_from("cuCtxSetCacheConfig") }
     }
 
     /// Sets a requested resource limit for the current context.
@@ -741,7 +741,7 @@ impl CurrentContext {
     /// ```
     pub fn set_resource_limit(resource: ResourceLimit, limit: usize) -> CudaResult<()> {
         unsafe {
-            cuda::cuCtxSetLimit(transmute(resource), limit).to_result()?;
+            cuda::cuCtxSetLimit(transmute(resource), limit).to_result_from("cuCtxSetLimit")?;
             Ok(())
         }
     }
@@ -767,7 +767,7 @@ impl CurrentContext {
     /// # }
     /// ```
     pub fn set_shared_memory_config(cfg: SharedMemoryConfig) -> CudaResult<()> {
-        unsafe { cuda::cuCtxSetSharedMemConfig(transmute(cfg)).to_result() }
+        unsafe { cuda::cuCtxSetSharedMemConfig(transmute(cfg)).to_result_from("cuCtxSetSharedMemConfig") }
     }
 
     /// Returns a non-owning handle to the current context.
@@ -790,7 +790,7 @@ impl CurrentContext {
     pub fn get_current() -> CudaResult<UnownedContext> {
         unsafe {
             let mut ctx: CUcontext = ptr::null_mut();
-            cuda::cuCtxGetCurrent(&mut ctx as *mut CUcontext).to_result()?;
+            cuda::cuCtxGetCurrent(&mut ctx as *mut CUcontext).to_result_from("cuCtxGetCurrent")?;
             Ok(UnownedContext { inner: ctx })
         }
     }
@@ -818,16 +818,13 @@ impl CurrentContext {
     /// ```
     pub fn set_current<C: ContextHandle>(c: &C) -> CudaResult<()> {
         unsafe {
-            cuda::cuCtxSetCurrent(c.get_inner()).to_result()?;
+            cuda::cuCtxSetCurrent(c.get_inner()).to_result_from("cuCtxSetCurrent")?;
             Ok(())
         }
     }
 
     /// Block to wait for a context's tasks to complete.
-    pub fn synchronize() -> CudaResult<()> {
-        unsafe {
-            cuda::cuCtxSynchronize().to_result()?;
-            Ok(())
-        }
+    pub fn synchronize() -> DriverResult<()> {
+        unsafe { cuda::cuCtxSynchronize().to_driver_result("cuCtxSynchronize") }
     }
 }
@@ -53,22 +53,33 @@
 //!
 //! Cust will try to find the CUDA libraries automatically, if it is unable to find it, you can set
 //! `CUDA_LIBRARY_PATH` to some path manually.
+//!
+//! The `mock` feature replaces the CUDA driver with an in-process emulation which keeps device
+//! memory in host memory, so code using cust can be tested on machines without a GPU. See
+//! `cust::sys::mock` for what it supports and for injecting errors.
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(feature = "backtrace", feature(backtrace))]
 
+#[cfg(feature = "nvrtc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "nvrtc")))]
+pub mod compile;
 pub mod device;
 pub mod error;
 pub mod event;
+pub mod executor;
 pub mod external;
 pub mod function;
 // WIP
 pub mod context;
-#[allow(warnings)]
-mod graph;
+pub mod graph;
+mod graph_dotfile;
 pub mod link;
 pub mod memory;
 pub mod module;
+pub mod nvtx;
 pub mod prelude;
+pub mod profile;
 pub mod stream;
 // WIP
 mod surface;
@@ -103,7 +114,7 @@ bitflags! {
 /// The `flags` parameter is used to configure the CUDA API. Currently no flags are defined, so
 /// it must be `CudaFlags::empty()`.
 pub fn init(flags: CudaFlags) -> CudaResult<()> {
-    unsafe { cuInit(flags.bits()).to_result() }
+    unsafe { cuInit(flags.bits()).to_result_from("cuInit") }
 }
 
 /// Shortcut for initializing the CUDA Driver API and creating a CUDA context with default settings
@@ -134,7 +145,7 @@ impl CudaApiVersion {
     pub fn get() -> CudaResult<CudaApiVersion> {
         unsafe {
             let mut version: i32 = 0;
-            cuDriverGetVersion(&mut version as *mut i32).to_result()?;
+            cuDriverGetVersion(&mut version as *mut i32).to_result_from("cuDriverGetVersion")?;
             Ok(CudaApiVersion { version })
         }
     }
@@ -1,11 +1,13 @@
 //! Functions for linking together multiple PTX files into a module.
 
 use std::mem::MaybeUninit;
-use std::ptr::null_mut;
+use std::os::raw::c_uint;
 
 use crate::sys as cuda;
 
 use crate::error::{CudaResult, ToResult};
+use crate::module::jit::RawJitOptions;
+use crate::module::{ModuleJitOption, ModuleLoadError, ModuleLoadReport};
 
 static UNNAMED: &str = "\0";
 
@@ -13,6 +15,8 @@ static UNNAMED: &str = "\0";
 #[derive(Debug)]
 pub struct Linker {
     raw: cuda::CUlinkState,
+    // kept alive for as long as the link state, see `Linker::with_options`.
+    options: RawJitOptions,
 }
 
 unsafe impl Send for Linker {}
@@ -21,19 +25,42 @@ unsafe impl Sync for Linker {}
 impl Linker {
     /// Creates a new linker.
     pub fn new() -> CudaResult<Self> {
+        Self::with_options(&[])
+    }
+
+    /// Creates a new linker which compiles any PTX added to it with the given JIT options, such
+    /// as [`ModuleJitOption::OptLevel`] and [`ModuleJitOption::MaxRegisters`].
+    ///
+    /// The output of the JIT compiler requested with the options, such as the error log, can be
+    /// read with [`Linker::report`] at any point, or is returned by
+    /// [`Linker::complete_with_report`].
+    pub fn with_options(options: &[ModuleJitOption]) -> CudaResult<Self> {
         // per the docs, cuda expects the options pointers to last as long as CULinkState.
-        // Therefore we use box to alloc the memory for us, then into_raw it so we now have ownership
-        // of the memory (and dont have any aliasing requirements attached either).
+        // Therefore we keep the options in the linker so they are dropped together with it.
+        let mut options = RawJitOptions::new(options);
 
         unsafe {
             let mut raw = MaybeUninit::uninit();
-            cuda::cuLinkCreate_v2(0, null_mut(), null_mut(), raw.as_mut_ptr()).to_result()?;
+            cuda::cuLinkCreate_v2(
+                options.options.len() as c_uint,
+                options.options.as_mut_ptr(),
+                options.values.as_mut_ptr(),
+                raw.as_mut_ptr(),
+            )
+            .to_result_from("cuLinkCreate_v2")?;
             Ok(Self {
                 raw: raw.assume_init(),
+                options,
             })
         }
     }
 
+    /// Returns the output of the JIT compiler so far, such as the reason [`Linker::add_ptx`]
+    /// failed.
+    pub fn report(&self) -> ModuleLoadReport {
+        self.options.report()
+    }
+
     // TODO(RDambrosio016): Support PTX compiler options and decide whether we should expose
     // them as a separate crate or as part of cust.
 
@@ -60,7 +87,7 @@ impl Linker {
                 std::ptr::null_mut(),
                 std::ptr::null_mut(),
             )
-            .to_result()
+            .to_result_from("cuLinkAddData_v2")
         }
     }
 
@@ -85,7 +112,7 @@ impl Linker {
                 std::ptr::null_mut(),
                 std::ptr::null_mut(),
             )
-            .to_result()
+            .to_result_from("cuLinkAddData_v2")
         }
     }
 
@@ -110,18 +137,35 @@ impl Linker {
                 std::ptr::null_mut(),
                 std::ptr::null_mut(),
             )
-            .to_result()
+            .to_result_from("cuLinkAddData_v2")
         }
     }
 
-    /// Runs the linker to generate the final cubin bytes. Also returns a duration
-    /// for how long it took to run the linker.
+    /// Runs the linker to generate the final cubin bytes.
     pub fn complete(self) -> CudaResult<Vec<u8>> {
+        self.complete_with_report()
+            .map_err(|e| e.error)
+            .map(|(cubin, _)| cubin)
+    }
+
+    /// Runs the linker to generate the final cubin bytes like [`Linker::complete`], also returning
+    /// the output of the JIT compiler requested with the options in [`Linker::with_options`].
+    pub fn complete_with_report(self) -> Result<(Vec<u8>, ModuleLoadReport), ModuleLoadError> {
+        let cubin = self.complete_raw();
+        let report = self.report();
+        match cubin {
+            Ok(cubin) => Ok((cubin, report)),
+            Err(error) => Err(ModuleLoadError { error, report }),
+        }
+    }
+
+    fn complete_raw(&self) -> CudaResult<Vec<u8>> {
         let mut cubin = MaybeUninit::uninit();
         let mut size = MaybeUninit::uninit();
 
         unsafe {
-            cuda::cuLinkComplete(self.raw, cubin.as_mut_ptr(), size.as_mut_ptr()).to_result()?;
+            cuda::cuLinkComplete(self.raw, cubin.as_mut_ptr(), size.as_mut_ptr())
+                .to_result_from("cuLinkComplete")?;
             // docs say that CULinkState owns the data, so clone it out before we destroy ourselves.
             let cubin = cubin.assume_init() as *const u8;
             let size = size.assume_init();
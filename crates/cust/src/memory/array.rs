@@ -185,7 +185,7 @@ impl ArrayObjectFlags {
 /// Describes a CUDA Array
 #[derive(Clone, Copy, Debug)]
 pub struct ArrayDescriptor {
-    desc: cuda::CUDA_ARRAY3D_DESCRIPTOR,
+    pub(crate) desc: cuda::CUDA_ARRAY3D_DESCRIPTOR,
 }
 
 impl ArrayDescriptor {
@@ -367,7 +367,7 @@ impl ArrayObject {
             // Exhaustively check bounds of arrays
             let device = CurrentContext::get_device()?;
 
-            let attr = |attr| Ok(1..=(device.get_attribute(attr)? as usize));
+            let attr = |attr| Ok::<_, CudaError>(1..=(device.get_attribute(attr)? as usize));
 
             let (description, bounds) = if descriptor.flags().contains(ArrayObjectFlags::CUBEMAP) {
                 if descriptor.flags().contains(ArrayObjectFlags::LAYERED) {
@@ -474,7 +474,8 @@ impl ArrayObject {
         }
 
         let mut handle = MaybeUninit::uninit();
-        unsafe { cuda::cuArray3DCreate_v2(handle.as_mut_ptr(), &descriptor.desc) }.to_result()?;
+        unsafe { cuda::cuArray3DCreate_v2(handle.as_mut_ptr(), &descriptor.desc) }
+            .to_result_from("cuArray3DCreate_v2")?;
         Ok(Self {
             handle: unsafe { handle.assume_init() },
         })
@@ -726,7 +727,7 @@ impl ArrayObject {
         // Use "zeroed" incase CUDA_ARRAY3D_DESCRIPTOR has uninitialized padding
         let mut raw_descriptor = MaybeUninit::zeroed();
         unsafe { cuda::cuArray3DGetDescriptor_v2(raw_descriptor.as_mut_ptr(), self.handle) }
-            .to_result()?;
+            .to_result_from("cuArray3DGetDescriptor_v2")?;
 
         Ok(ArrayDescriptor::from_raw(unsafe {
             raw_descriptor.assume_init()
@@ -736,7 +737,7 @@ impl ArrayObject {
     /// Try to destroy an `ArrayObject`. Can fail - if it does, returns the CUDA error and the
     /// un-destroyed array object
     pub fn drop(array: ArrayObject) -> DropResult<ArrayObject> {
-        match unsafe { cuda::cuArrayDestroy(array.handle) }.to_result() {
+        match unsafe { cuda::cuArrayDestroy(array.handle) }.to_result_from("cuArrayDestroy") {
             Ok(()) => Ok(()),
             Err(e) => Err((e, array)),
         }
@@ -759,7 +760,7 @@ impl ArrayObject {
         unsafe {
             if desc.height() == 0 && desc.depth() == 0 {
                 cuMemcpyHtoA_v2(self.handle, 0, val.as_ptr() as *const c_void, self_size)
-                    .to_result()
+                    .to_result_from("cuMemcpyHtoA_v2")
             } else if desc.depth() == 0 {
                 let desc = CUDA_MEMCPY2D {
                     Height: desc.height(),
@@ -781,7 +782,7 @@ impl ArrayObject {
                     srcXInBytes: 0,
                     srcY: 0,
                 };
-                cuMemcpy2D_v2(&desc as *const _).to_result()
+                cuMemcpy2D_v2(&desc as *const _).to_result_from("cuMemcpy2D_v2")
             } else {
                 panic!();
             }
@@ -805,7 +806,7 @@ impl ArrayObject {
         unsafe {
             if desc.height() == 0 && desc.depth() == 0 {
                 cuMemcpyAtoH_v2(val.as_mut_ptr() as *mut c_void, self.handle, 0, self_size)
-                    .to_result()
+                    .to_result_from("cuMemcpyAtoH_v2")
             } else if desc.depth() == 0 {
                 let width = desc.width() * desc.num_channels() as usize * desc.format().mem_size();
                 let desc = CUDA_MEMCPY2D {
@@ -826,7 +827,7 @@ impl ArrayObject {
                     srcXInBytes: 0,
                     srcY: 0,
                 };
-                cuMemcpy2D_v2(&desc as *const _).to_result()?;
+                cuMemcpy2D_v2(&desc as *const _).to_result_from("cuMemcpy2D_v2")?;
                 Ok(())
             } else {
                 panic!();
@@ -0,0 +1,897 @@
+//! The caching allocator used for [`DeviceBuffer`](crate::memory::DeviceBuffer)s, see [`CachingAllocator`].
+
+use crate::error::{CudaError, CudaResult, ToResult};
+use crate::memory::{DeviceCopy, DevicePointer};
+use crate::sys::{
+    self as cuda, cudaError_enum, CUcontext, CUdevice, CUdeviceptr, CUevent, CUstream,
+};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Once};
+
+/// Statistics of a [`CachingAllocator`], in bytes unless noted otherwise.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AllocatorStats {
+    /// Bytes requested by live allocations.
+    pub allocated: usize,
+    /// The highest `allocated` has been since the allocator was created or the peaks were reset.
+    pub peak_allocated: usize,
+    /// Bytes allocated from the driver, including rounding and cached blocks.
+    pub reserved: usize,
+    /// The highest `reserved` has been since the allocator was created or the peaks were reset.
+    pub peak_reserved: usize,
+    /// Bytes of freed blocks kept for reuse, including blocks still in use by other streams.
+    pub cached: usize,
+    /// The number of live allocations.
+    pub allocations: usize,
+    /// The number of allocations which reused a cached block.
+    pub cache_hits: u64,
+    /// The number of allocations which had to allocate from the driver.
+    pub cache_misses: u64,
+    /// Statistics of allocations made inside of [`tag_allocations`] scopes, by tag.
+    pub tags: HashMap<&'static str, TagStats>,
+}
+
+impl AllocatorStats {
+    /// The fraction of reserved memory which is not used by live allocations, either because it is
+    /// cached or because allocations were rounded up to their size class. Zero if nothing is reserved.
+    pub fn fragmentation(&self) -> f64 {
+        if self.reserved == 0 {
+            0.0
+        } else {
+            (self.reserved - self.allocated) as f64 / self.reserved as f64
+        }
+    }
+}
+
+/// Statistics of the allocations made with a particular tag, see [`tag_allocations`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TagStats {
+    /// Bytes requested by live allocations with this tag.
+    pub allocated: usize,
+    /// The highest `allocated` has been since the allocator was created or the peaks were reset.
+    pub peak_allocated: usize,
+    /// The number of live allocations with this tag.
+    pub allocations: usize,
+}
+
+thread_local! {
+    static TAG: Cell<Option<&'static str>> = Cell::new(None);
+}
+
+/// Tags every allocation a [`CachingAllocator`] makes on this thread with `tag` until the returned
+/// guard is dropped, which restores the previous tag. The allocator's statistics are broken down by
+/// tag in [`AllocatorStats::tags`].
+///
+/// # Example
+///
+/// ```
+/// # let _context = cust::quick_init().unwrap();
+/// use cust::memory::*;
+/// let _tag = tag_allocations("activations");
+/// let buffer = DeviceBuffer::from_slice(&[0.0f32; 64]).unwrap();
+/// ```
+pub fn tag_allocations(tag: &'static str) -> AllocationTag {
+    AllocationTag {
+        previous: TAG.with(|current| current.replace(Some(tag))),
+    }
+}
+
+/// Guard returned by [`tag_allocations`], which restores the previous tag when dropped.
+#[derive(Debug)]
+#[must_use = "allocations are only tagged until the guard is dropped"]
+pub struct AllocationTag {
+    previous: Option<&'static str>,
+}
+
+impl Drop for AllocationTag {
+    fn drop(&mut self) {
+        TAG.with(|current| current.set(self.previous));
+    }
+}
+
+/// A caching allocator for [`DeviceBuffer`]s.
+///
+/// Allocating and freeing device memory through the driver is slow, `cuMemFree` in particular
+/// may synchronize the whole device. Workloads which repeatedly allocate buffers of varying sizes,
+/// such as deep learning frameworks, usually keep freed memory around and hand it out again instead.
+/// A `CachingAllocator` does that for [`DeviceBuffer`]s.
+///
+/// The allocator is selected per context with [`Context::set_caching_allocator`] (or the legacy
+/// context equivalent), after which every `DeviceBuffer` allocated while the context is current
+/// comes from it and returns to it when dropped. The allocator is a handle which can be cloned
+/// cheaply, all clones share the same cache.
+///
+/// # Size classes
+///
+/// Allocations are rounded up to a size class, multiples of 512 bytes up to 1 MiB and eighths of
+/// the next power of two above that, and are only reused for allocations of the same class. This
+/// wastes at most 25% of an allocation but means blocks never need to be split or merged.
+///
+/// # Streams
+///
+/// Every block belongs to the stream it was allocated on, the null stream for
+/// [`DeviceBuffer::uninitialized`] and friends, or the stream passed to
+/// [`DeviceBuffer::uninitialized_async`]. A freed block is only reused by allocations on the same
+/// stream, which are ordered after any work that used the block. If a buffer is used on other
+/// streams as well, those uses must be registered with [`DeviceBuffer::record_stream`] so the block
+/// is not reused before that work is done.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let context = cust::quick_init()?;
+/// use cust::memory::*;
+/// let allocator = CachingAllocator::new();
+/// context.set_caching_allocator(Some(&allocator))?;
+///
+/// let first = DeviceBuffer::from_slice(&[0u8; 1000])?;
+/// drop(first);
+/// // reuses the memory of the first buffer.
+/// let second = DeviceBuffer::from_slice(&[1u8; 1000])?;
+/// assert_eq!(allocator.stats().cache_hits, 1);
+///
+/// drop(second);
+/// context.set_caching_allocator(None)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Context::set_caching_allocator`]: crate::context::Context::set_caching_allocator
+/// [`DeviceBuffer`]: crate::memory::DeviceBuffer
+/// [`DeviceBuffer::uninitialized`]: crate::memory::DeviceBuffer::uninitialized
+/// [`DeviceBuffer::uninitialized_async`]: crate::memory::DeviceBuffer::uninitialized_async
+/// [`DeviceBuffer::record_stream`]: crate::memory::DeviceBuffer::record_stream
+#[derive(Debug, Clone)]
+pub struct CachingAllocator {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    max_cached: Option<usize>,
+    // contexts this allocator is installed in, blocks freed in other contexts are not cached.
+    contexts: Vec<usize>,
+    live: HashMap<CUdeviceptr, Block>,
+    // freed blocks by (context, stream, size class).
+    free: HashMap<(usize, usize, usize), Vec<CUdeviceptr>>,
+    // freed blocks waiting for work on other streams to finish.
+    pending: Vec<Pending>,
+    stats: AllocatorStats,
+}
+
+#[derive(Debug)]
+struct Block {
+    context: usize,
+    stream: usize,
+    size: usize,
+    requested: usize,
+    tag: Option<&'static str>,
+    // streams other than `stream` the block was used on.
+    used_on: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    ptr: CUdeviceptr,
+    context: usize,
+    stream: usize,
+    size: usize,
+    events: Vec<CUevent>,
+}
+
+// SAFETY: the raw handles are only used while holding the lock.
+unsafe impl Send for State {}
+
+impl Default for CachingAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CachingAllocator {
+    /// Creates an allocator which caches any amount of freed memory.
+    pub fn new() -> Self {
+        Self::with_inner(None)
+    }
+
+    /// Creates an allocator which frees blocks instead of caching them once it caches `bytes` bytes.
+    pub fn with_max_cached(bytes: usize) -> Self {
+        Self::with_inner(Some(bytes))
+    }
+
+    fn with_inner(max_cached: Option<usize>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    max_cached,
+                    ..State::default()
+                }),
+            }),
+        }
+    }
+
+    /// Returns the current statistics of the allocator.
+    pub fn stats(&self) -> AllocatorStats {
+        self.inner.lock().stats.clone()
+    }
+
+    /// Resets the peaks in the statistics to the current values.
+    pub fn reset_peak_stats(&self) {
+        let mut state = self.inner.lock();
+        let stats = &mut state.stats;
+        stats.peak_allocated = stats.allocated;
+        stats.peak_reserved = stats.reserved;
+        for tag in stats.tags.values_mut() {
+            tag.peak_allocated = tag.allocated;
+        }
+    }
+
+    /// Frees all cached blocks, waiting for work on other streams which still uses some of them.
+    /// Live allocations are not affected.
+    pub fn empty_cache(&self) -> CudaResult<()> {
+        self.inner.lock().empty(None)
+    }
+
+    fn ptr_eq(&self, other: &CachingAllocator) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn allocate(&self, context: usize, bytes: usize, stream: usize) -> CudaResult<CUdeviceptr> {
+        let size = size_class(bytes);
+        let mut state = self.lock();
+        state.poll_pending();
+
+        let cached = state
+            .free
+            .get_mut(&(context, stream, size))
+            .and_then(Vec::pop);
+        let ptr = match cached {
+            Some(ptr) => {
+                state.stats.cached -= size;
+                state.stats.cache_hits += 1;
+                ptr
+            }
+            None => {
+                let ptr = match malloc(size) {
+                    Err(CudaError::OutOfMemory) => {
+                        state.empty(Some(context))?;
+                        malloc(size)?
+                    }
+                    res => res?,
+                };
+                state.stats.cache_misses += 1;
+                state.stats.reserved += size;
+                state.stats.peak_reserved = state.stats.peak_reserved.max(state.stats.reserved);
+                ptr
+            }
+        };
+
+        let tag = TAG.with(Cell::get);
+        let stats = &mut state.stats;
+        stats.allocated += bytes;
+        stats.peak_allocated = stats.peak_allocated.max(stats.allocated);
+        stats.allocations += 1;
+        if let Some(tag) = tag {
+            let tag = stats.tags.entry(tag).or_default();
+            tag.allocated += bytes;
+            tag.peak_allocated = tag.peak_allocated.max(tag.allocated);
+            tag.allocations += 1;
+        }
+        state.live.insert(
+            ptr,
+            Block {
+                context,
+                stream,
+                size,
+                requested: bytes,
+                tag,
+                used_on: Vec::new(),
+            },
+        );
+        Ok(ptr)
+    }
+
+    fn record_stream(&self, ptr: CUdeviceptr, stream: usize) {
+        if let Some(block) = self.lock().live.get_mut(&ptr) {
+            if block.stream != stream && !block.used_on.contains(&stream) {
+                block.used_on.push(stream);
+            }
+        }
+    }
+
+    fn release(&self, ptr: CUdeviceptr, stream: Option<usize>) -> CudaResult<()> {
+        let mut state = self.lock();
+        let mut block = match state.live.remove(&ptr) {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+
+        let stats = &mut state.stats;
+        stats.allocated -= block.requested;
+        stats.allocations -= 1;
+        if let Some(tag) = block.tag.and_then(|tag| stats.tags.get_mut(tag)) {
+            tag.allocated -= block.requested;
+            tag.allocations -= 1;
+        }
+
+        if let Some(stream) = stream {
+            if stream != block.stream && !block.used_on.contains(&stream) {
+                block.used_on.push(stream);
+            }
+        }
+        if block.used_on.is_empty() {
+            return state.cache(ptr, block.context, block.stream, block.size);
+        }
+
+        let mut events = Vec::with_capacity(block.used_on.len());
+        for &stream in &block.used_on {
+            match record_event(stream as CUstream) {
+                Ok(event) => events.push(event),
+                Err(_) => {
+                    // the stream is most likely gone, wait for everything instead.
+                    for event in events {
+                        destroy_event(event);
+                    }
+                    in_context(block.context, || unsafe {
+                        cuda::cuCtxSynchronize().to_result_from("cuCtxSynchronize")
+                    })?;
+                    return state.cache(ptr, block.context, block.stream, block.size);
+                }
+            }
+        }
+        state.stats.cached += block.size;
+        state.pending.push(Pending {
+            ptr,
+            context: block.context,
+            stream: block.stream,
+            size: block.size,
+            events,
+        });
+        Ok(())
+    }
+}
+
+impl State {
+    fn installed(&self, context: usize) -> bool {
+        self.contexts.contains(&context)
+    }
+
+    fn cache(
+        &mut self,
+        ptr: CUdeviceptr,
+        context: usize,
+        stream: usize,
+        size: usize,
+    ) -> CudaResult<()> {
+        let over_limit = self
+            .max_cached
+            .map_or(false, |max| self.stats.cached + size > max);
+        if !self.installed(context) || over_limit {
+            self.stats.reserved -= size;
+            return free_in(context, ptr);
+        }
+        self.stats.cached += size;
+        self.free
+            .entry((context, stream, size))
+            .or_default()
+            .push(ptr);
+        Ok(())
+    }
+
+    // moves pending blocks whose streams are done with them to the free lists.
+    fn poll_pending(&mut self) {
+        let mut i = 0;
+        while i < self.pending.len() {
+            let done = self.pending[i]
+                .events
+                .iter()
+                .all(|&event| unsafe { cuda::cuEventQuery(event) } != cudaError_enum::CUDA_ERROR_NOT_READY);
+            if done {
+                let pending = self.pending.swap_remove(i);
+                for event in pending.events {
+                    destroy_event(event);
+                }
+                self.stats.cached -= pending.size;
+                let _ = self.cache(pending.ptr, pending.context, pending.stream, pending.size);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    // frees the cached blocks of `context`, or of every context.
+    fn empty(&mut self, context: Option<usize>) -> CudaResult<()> {
+        let matches = |ctx: usize| context.map_or(true, |context| context == ctx);
+        let mut result = Ok(());
+
+        let pending = std::mem::take(&mut self.pending);
+        for pending in pending {
+            if !matches(pending.context) {
+                self.pending.push(pending);
+                continue;
+            }
+            for event in pending.events {
+                unsafe {
+                    let synced =
+                        cuda::cuEventSynchronize(event).to_result_from("cuEventSynchronize");
+                    result = result.and(synced);
+                }
+                destroy_event(event);
+            }
+            self.stats.cached -= pending.size;
+            self.stats.reserved -= pending.size;
+            result = result.and(free_in(pending.context, pending.ptr));
+        }
+
+        let keys = self
+            .free
+            .keys()
+            .filter(|(ctx, _, _)| matches(*ctx))
+            .copied()
+            .collect::<Vec<_>>();
+        for key in keys {
+            let (ctx, _, size) = key;
+            for ptr in self.free.remove(&key).unwrap_or_default() {
+                self.stats.cached -= size;
+                self.stats.reserved -= size;
+                result = result.and(free_in(ctx, ptr));
+            }
+        }
+        result
+    }
+
+    // forgets everything about a destroyed context, the driver freed its memory along with it.
+    fn forget(&mut self, context: usize) {
+        self.contexts.retain(|&ctx| ctx != context);
+        let stats = &mut self.stats;
+        self.pending.retain(|pending| {
+            if pending.context != context {
+                return true;
+            }
+            stats.cached -= pending.size;
+            stats.reserved -= pending.size;
+            false
+        });
+        self.free.retain(|&(ctx, _, size), ptrs| {
+            if ctx != context {
+                return true;
+            }
+            stats.cached -= size * ptrs.len();
+            stats.reserved -= size * ptrs.len();
+            false
+        });
+        let live = std::mem::take(&mut self.live);
+        for (ptr, block) in live {
+            if block.context != context {
+                self.live.insert(ptr, block);
+                continue;
+            }
+            stats.allocated -= block.requested;
+            stats.reserved -= block.size;
+            stats.allocations -= 1;
+            if let Some(tag) = block.tag.and_then(|tag| stats.tags.get_mut(tag)) {
+                tag.allocated -= block.requested;
+                tag.allocations -= 1;
+            }
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
+        let _ = self.lock().empty(None);
+    }
+}
+
+/// Rounds `bytes` up to its size class.
+fn size_class(bytes: usize) -> usize {
+    const SMALL: usize = 1 << 20;
+    let step = if bytes <= SMALL {
+        512
+    } else {
+        bytes.next_power_of_two() / 8
+    };
+    (bytes + step - 1) / step * step
+}
+
+fn malloc(size: usize) -> CudaResult<CUdeviceptr> {
+    let mut ptr = 0;
+    unsafe {
+        cuda::cuMemAlloc_v2(&mut ptr, size).to_result_from("cuMemAlloc_v2")?;
+    }
+    Ok(ptr)
+}
+
+// blocks may be freed while another context is current, so make theirs current for the call.
+fn in_context(context: usize, f: impl FnOnce() -> CudaResult<()>) -> CudaResult<()> {
+    unsafe {
+        cuda::cuCtxPushCurrent_v2(context as CUcontext).to_result_from("cuCtxPushCurrent_v2")?;
+        let res = f();
+        let mut popped = ptr::null_mut();
+        cuda::cuCtxPopCurrent_v2(&mut popped).to_result_from("cuCtxPopCurrent_v2")?;
+        res
+    }
+}
+
+fn free_in(context: usize, ptr: CUdeviceptr) -> CudaResult<()> {
+    in_context(context, || unsafe {
+        cuda::cuMemFree_v2(ptr).to_result_from("cuMemFree_v2")
+    })
+}
+
+fn record_event(stream: CUstream) -> CudaResult<CUevent> {
+    let mut event = ptr::null_mut();
+    unsafe {
+        cuda::cuEventCreate(
+            &mut event,
+            cuda::CUevent_flags::CU_EVENT_DISABLE_TIMING as u32,
+        )
+        .to_result_from("cuEventCreate")?;
+        if let Err(e) = cuda::cuEventRecord(event, stream).to_result_from("cuEventRecord") {
+            destroy_event(event);
+            return Err(e);
+        }
+    }
+    Ok(event)
+}
+
+fn destroy_event(event: CUevent) {
+    unsafe {
+        cuda::cuEventDestroy_v2(event);
+    }
+}
+
+// The allocator installed in each context, and the allocator of every live cached allocation so
+// they can be found again when the buffer is dropped. The counters let the allocation and free
+// paths of `DeviceBuffer` skip the lock when no allocator is in use.
+#[derive(Default)]
+struct Registry {
+    contexts: HashMap<usize, Installed>,
+    live: HashMap<CUdeviceptr, CachingAllocator>,
+}
+
+struct Installed {
+    allocator: CachingAllocator,
+    // primary contexts are retained while an allocator is installed so the cached memory cannot
+    // be freed along with the context behind the allocator's back.
+    primary_device: Option<CUdevice>,
+}
+
+static INSTALLED: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+fn registry() -> MutexGuard<'static, Registry> {
+    static INIT: Once = Once::new();
+    static mut REGISTRY: *const Mutex<Registry> = std::ptr::null();
+
+    unsafe {
+        INIT.call_once(|| REGISTRY = Box::into_raw(Box::new(Mutex::new(Registry::default()))));
+        (*REGISTRY).lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Installs `allocator` in `context`, replacing and emptying the previous allocator.
+/// `primary_device` is the device of `context` if it is a primary context.
+pub(crate) fn install(
+    context: CUcontext,
+    primary_device: Option<CUdevice>,
+    allocator: Option<&CachingAllocator>,
+) -> CudaResult<()> {
+    let key = context as usize;
+    let mut registry = registry();
+    if let Some(old) = registry.contexts.get(&key) {
+        if allocator.map_or(false, |new| new.ptr_eq(&old.allocator)) {
+            return Ok(());
+        }
+    }
+
+    if let Some(old) = registry.contexts.remove(&key) {
+        INSTALLED.fetch_sub(1, Ordering::SeqCst);
+        let mut state = old.allocator.inner.lock();
+        state.contexts.retain(|&ctx| ctx != key);
+        let emptied = state.empty(Some(key));
+        drop(state);
+        if let Some(device) = old.primary_device {
+            unsafe {
+                crate::context::release_primary_context(context, device)?;
+            }
+        }
+        emptied?;
+    }
+
+    if let Some(allocator) = allocator {
+        if let Some(device) = primary_device {
+            let mut retained = ptr::null_mut();
+            unsafe {
+                cuda::cuDevicePrimaryCtxRetain(&mut retained, device)
+                    .to_result_from("cuDevicePrimaryCtxRetain")?;
+            }
+        }
+        allocator.inner.lock().contexts.push(key);
+        registry.contexts.insert(
+            key,
+            Installed {
+                allocator: allocator.clone(),
+                primary_device,
+            },
+        );
+        INSTALLED.fetch_add(1, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+/// Returns the allocator installed in `context`.
+pub(crate) fn installed(context: CUcontext) -> Option<CachingAllocator> {
+    if INSTALLED.load(Ordering::SeqCst) == 0 {
+        return None;
+    }
+    registry()
+        .contexts
+        .get(&(context as usize))
+        .map(|installed| installed.allocator.clone())
+}
+
+/// Forgets the allocator and cached memory of a context which was destroyed.
+pub(crate) fn context_destroyed(context: CUcontext) {
+    if INSTALLED.load(Ordering::SeqCst) == 0 && LIVE.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    let key = context as usize;
+    let mut registry = registry();
+    if let Some(installed) = registry.contexts.remove(&key) {
+        INSTALLED.fetch_sub(1, Ordering::SeqCst);
+        installed.allocator.inner.lock().forget(key);
+    }
+    // live buffers of the context may belong to allocators which are no longer installed in it.
+    let before = registry.live.len();
+    registry.live.retain(|ptr, allocator| {
+        let mut state = allocator.inner.lock();
+        if state
+            .live
+            .get(ptr)
+            .map_or(false, |block| block.context == key)
+        {
+            state.forget(key);
+        }
+        state.live.contains_key(ptr)
+    });
+    LIVE.fetch_sub(before - registry.live.len(), Ordering::SeqCst);
+}
+
+/// Allocates `count` `T`s from the allocator installed in the current context, if there is one.
+pub(crate) fn allocate<T: DeviceCopy>(
+    count: usize,
+    stream: CUstream,
+) -> CudaResult<Option<DevicePointer<T>>> {
+    if INSTALLED.load(Ordering::SeqCst) == 0 {
+        return Ok(None);
+    }
+    let mut context = ptr::null_mut();
+    unsafe {
+        cuda::cuCtxGetCurrent(&mut context).to_result_from("cuCtxGetCurrent")?;
+    }
+    let allocator = match installed(context) {
+        Some(allocator) => allocator,
+        None => return Ok(None),
+    };
+    let bytes = count.checked_mul(size_of::<T>()).unwrap_or(0);
+    if bytes == 0 {
+        return Err(CudaError::InvalidMemoryAllocation);
+    }
+
+    let ptr = allocator
+        .inner
+        .allocate(context as usize, bytes, stream as usize)?;
+    registry().live.insert(ptr, allocator);
+    LIVE.fetch_add(1, Ordering::SeqCst);
+    Ok(Some(DevicePointer::from_raw(ptr)))
+}
+
+/// Returns a buffer to the allocator it came from, after the work on `stream` if there is one.
+/// Returns `None` if the buffer was not allocated by a caching allocator.
+pub(crate) fn release(ptr: CUdeviceptr, stream: Option<CUstream>) -> Option<CudaResult<()>> {
+    if LIVE.load(Ordering::SeqCst) == 0 {
+        return None;
+    }
+    let allocator = registry().live.remove(&ptr)?;
+    LIVE.fetch_sub(1, Ordering::SeqCst);
+    Some(
+        allocator
+            .inner
+            .release(ptr, stream.map(|stream| stream as usize)),
+    )
+}
+
+/// Marks a buffer from a caching allocator as used on `stream`.
+pub(crate) fn record_stream(ptr: CUdeviceptr, stream: CUstream) {
+    if LIVE.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    let allocator = registry().live.get(&ptr).cloned();
+    if let Some(allocator) = allocator {
+        allocator.inner.record_stream(ptr, stream as usize);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::legacy::{Context, ContextFlags};
+    use crate::device::Device;
+    use crate::memory::DeviceBuffer;
+    use crate::stream::{Stream, StreamFlags};
+
+    // legacy contexts are not shared with other tests, unlike the primary context.
+    fn context() -> Context {
+        crate::init(crate::CudaFlags::empty()).unwrap();
+        Context::create_and_push(ContextFlags::empty(), Device::get_device(0).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_size_class() {
+        assert_eq!(size_class(1), 512);
+        assert_eq!(size_class(512), 512);
+        assert_eq!(size_class(1000), 1024);
+        assert_eq!(size_class(1 << 20), 1 << 20);
+        assert_eq!(size_class((1 << 20) + 1), (1 << 20) + (1 << 18));
+        assert_eq!(size_class(3 << 20), 3 << 20);
+    }
+
+    #[test]
+    fn test_reuse_and_stats() {
+        let context = context();
+        let allocator = CachingAllocator::new();
+        context.set_caching_allocator(Some(&allocator)).unwrap();
+        assert!(context.caching_allocator().unwrap().ptr_eq(&allocator));
+
+        let first = DeviceBuffer::from_slice(&[0u8; 1000]).unwrap();
+        let ptr = first.as_device_ptr();
+        drop(first);
+        assert_eq!(allocator.stats().cached, 1024);
+
+        let second = DeviceBuffer::from_slice(&[0u8; 900]).unwrap();
+        assert_eq!(second.as_device_ptr(), ptr);
+        let third = DeviceBuffer::from_slice(&[0u16; 1000]).unwrap();
+        let stats = allocator.stats();
+        assert_eq!(stats.cache_hits, 1);
+        assert_eq!(stats.cache_misses, 2);
+        assert_eq!(stats.allocated, 2900);
+        assert_eq!(stats.peak_allocated, 2900);
+        assert_eq!(stats.reserved, 1024 + 2048);
+        assert_eq!(stats.cached, 0);
+        assert_eq!(stats.allocations, 2);
+        assert!((stats.fragmentation() - 172.0 / 3072.0).abs() < 1e-9);
+
+        drop(third);
+        allocator.empty_cache().unwrap();
+        let stats = allocator.stats();
+        assert_eq!(stats.reserved, 1024);
+        assert_eq!(stats.cached, 0);
+        assert_eq!(stats.peak_reserved, 1024 + 2048);
+        allocator.reset_peak_stats();
+        assert_eq!(allocator.stats().peak_reserved, 1024);
+        drop(second);
+    }
+
+    #[test]
+    fn test_streams() {
+        let context = context();
+        let allocator = CachingAllocator::new();
+        context.set_caching_allocator(Some(&allocator)).unwrap();
+        let first = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+        let second = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+
+        unsafe {
+            let buf = DeviceBuffer::<u32>::uninitialized_async(100, &first).unwrap();
+            buf.drop_async(&first).unwrap();
+            // not reused on another stream.
+            let other = DeviceBuffer::<u32>::uninitialized_async(100, &second).unwrap();
+            assert_eq!(allocator.stats().cache_hits, 0);
+            let same = DeviceBuffer::<u32>::uninitialized_async(100, &first).unwrap();
+            assert_eq!(allocator.stats().cache_hits, 1);
+
+            // used on another stream, so it waits for that stream before it is reused.
+            same.record_stream(&second);
+            drop(same);
+            assert_eq!(allocator.inner.lock().pending.len(), 1);
+            first.synchronize().unwrap();
+            second.synchronize().unwrap();
+            let _reused = DeviceBuffer::<u32>::uninitialized_async(100, &first).unwrap();
+            assert_eq!(allocator.stats().cache_hits, 2);
+            assert!(allocator.inner.lock().pending.is_empty());
+            other.drop_async(&second).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_tags() {
+        let context = context();
+        let allocator = CachingAllocator::new();
+        context.set_caching_allocator(Some(&allocator)).unwrap();
+
+        let tag = tag_allocations("weights");
+        let weights = DeviceBuffer::from_slice(&[0u64; 16]).unwrap();
+        {
+            let _inner = tag_allocations("scratch");
+            let _scratch = DeviceBuffer::from_slice(&[0u8; 10]).unwrap();
+        }
+        let more = DeviceBuffer::from_slice(&[0u64; 4]).unwrap();
+        drop(tag);
+        let _untagged = DeviceBuffer::from_slice(&[0u8; 1]).unwrap();
+
+        let stats = allocator.stats();
+        assert_eq!(
+            stats.tags["weights"],
+            TagStats {
+                allocated: 160,
+                peak_allocated: 160,
+                allocations: 2,
+            }
+        );
+        assert_eq!(
+            stats.tags["scratch"],
+            TagStats {
+                allocated: 0,
+                peak_allocated: 10,
+                allocations: 0,
+            }
+        );
+        assert_eq!(stats.tags.len(), 2);
+        drop((weights, more));
+    }
+
+    #[test]
+    fn test_uninstall_and_limit() {
+        let context = context();
+        let allocator = CachingAllocator::with_max_cached(1024);
+        context.set_caching_allocator(Some(&allocator)).unwrap();
+
+        let live = DeviceBuffer::from_slice(&[0u8; 100]).unwrap();
+        drop(DeviceBuffer::from_slice(&[0u8; 1000]).unwrap());
+        // over the limit, so it is freed instead of cached.
+        drop(DeviceBuffer::from_slice(&[0u8; 2000]).unwrap());
+        assert_eq!(allocator.stats().cached, 1024);
+        assert_eq!(allocator.stats().reserved, 512 + 1024);
+
+        context.set_caching_allocator(None).unwrap();
+        assert!(context.caching_allocator().is_none());
+        assert_eq!(allocator.stats().reserved, 512);
+        let _uncached = DeviceBuffer::from_slice(&[0u8; 100]).unwrap();
+        assert_eq!(allocator.stats().allocations, 1);
+        // allocated before the allocator was uninstalled, but not cached afterwards.
+        drop(live);
+        assert_eq!(allocator.stats().reserved, 0);
+    }
+
+    #[test]
+    fn test_context_destroyed() {
+        let context = context();
+        let allocator = CachingAllocator::new();
+        context.set_caching_allocator(Some(&allocator)).unwrap();
+        let live = DeviceBuffer::from_slice(&[0u8; 100]).unwrap();
+        drop(DeviceBuffer::from_slice(&[0u8; 1000]).unwrap());
+
+        drop(context);
+        assert_eq!(allocator.stats().reserved, 0);
+        assert_eq!(allocator.stats().allocations, 0);
+        // the memory went away with the context.
+        std::mem::forget(live);
+    }
+}
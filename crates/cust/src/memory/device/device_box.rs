@@ -162,7 +162,7 @@ impl<T: DeviceCopy + bytemuck::Zeroable> DeviceBox<T> {
             let new_box = DeviceBox::uninitialized()?;
             if mem::size_of::<T>() != 0 {
                 cuda::cuMemsetD8_v2(new_box.as_device_ptr().as_raw(), 0, mem::size_of::<T>())
-                    .to_result()?;
+                    .to_result_from("cuMemsetD8_v2")?;
             }
             Ok(new_box)
         }
@@ -207,7 +207,7 @@ impl<T: DeviceCopy + bytemuck::Zeroable> DeviceBox<T> {
                 mem::size_of::<T>(),
                 stream.as_inner(),
             )
-            .to_result()?;
+            .to_result_from("cuMemsetD8Async")?;
         }
         Ok(new_box)
     }
@@ -285,8 +285,8 @@ impl<T: DeviceCopy> DeviceBox<T> {
     /// # let _context = cust::quick_init().unwrap();
     /// use cust::memory::*;
     /// let x = DeviceBox::new(&5).unwrap();
-    /// let ptr = DeviceBox::into_device(x).as_raw_mut();
-    /// let x = unsafe { DeviceBox::from_raw(ptr) };
+    /// let ptr = DeviceBox::into_device(x).as_raw();
+    /// let x: DeviceBox<i32> = unsafe { DeviceBox::from_raw(ptr) };
     /// ```
     pub unsafe fn from_raw(ptr: cust_raw::CUdeviceptr) -> Self {
         DeviceBox {
@@ -401,6 +401,7 @@ impl<T: DeviceCopy> DeviceBox<T> {
 }
 impl<T: DeviceCopy> Drop for DeviceBox<T> {
     fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
         if self.ptr.is_null() {
             return;
         }
@@ -426,7 +427,7 @@ impl<T: DeviceCopy> CopyDestination<T> for DeviceBox<T> {
         if size != 0 {
             unsafe {
                 cuda::cuMemcpyHtoD_v2(self.ptr.as_raw(), val as *const T as *const c_void, size)
-                    .to_result()?
+                    .to_result_from("cuMemcpyHtoD_v2")?
             }
         }
         Ok(())
@@ -441,7 +442,7 @@ impl<T: DeviceCopy> CopyDestination<T> for DeviceBox<T> {
                     self.ptr.as_raw() as u64,
                     size,
                 )
-                .to_result()?
+                .to_result_from("cuMemcpyDtoH_v2")?
             }
         }
         Ok(())
@@ -451,7 +452,10 @@ impl<T: DeviceCopy> CopyDestination<DeviceBox<T>> for DeviceBox<T> {
     fn copy_from(&mut self, val: &DeviceBox<T>) -> CudaResult<()> {
         let size = mem::size_of::<T>();
         if size != 0 {
-            unsafe { cuda::cuMemcpyDtoD_v2(self.ptr.as_raw(), val.ptr.as_raw(), size).to_result()? }
+            unsafe {
+                cuda::cuMemcpyDtoD_v2(self.ptr.as_raw(), val.ptr.as_raw(), size)
+                    .to_result_from("cuMemcpyDtoD_v2")?
+            }
         }
         Ok(())
     }
@@ -459,7 +463,10 @@ impl<T: DeviceCopy> CopyDestination<DeviceBox<T>> for DeviceBox<T> {
     fn copy_to(&self, val: &mut DeviceBox<T>) -> CudaResult<()> {
         let size = mem::size_of::<T>();
         if size != 0 {
-            unsafe { cuda::cuMemcpyDtoD_v2(val.ptr.as_raw(), self.ptr.as_raw(), size).to_result()? }
+            unsafe {
+                cuda::cuMemcpyDtoD_v2(val.ptr.as_raw(), self.ptr.as_raw(), size)
+                    .to_result_from("cuMemcpyDtoD_v2")?
+            }
         }
         Ok(())
     }
@@ -474,7 +481,7 @@ impl<T: DeviceCopy> AsyncCopyDestination<T> for DeviceBox<T> {
                 size,
                 stream.as_inner(),
             )
-            .to_result()?
+            .to_result_from("cuMemcpyHtoDAsync_v2")?
         }
         Ok(())
     }
@@ -488,7 +495,7 @@ impl<T: DeviceCopy> AsyncCopyDestination<T> for DeviceBox<T> {
                 size,
                 stream.as_inner(),
             )
-            .to_result()?
+            .to_result_from("cuMemcpyDtoHAsync_v2")?
         }
         Ok(())
     }
@@ -498,7 +505,7 @@ impl<T: DeviceCopy> AsyncCopyDestination<DeviceBox<T>> for DeviceBox<T> {
         let size = mem::size_of::<T>();
         if size != 0 {
             cuda::cuMemcpyDtoDAsync_v2(self.ptr.as_raw(), val.ptr.as_raw(), size, stream.as_inner())
-                .to_result()?
+                .to_result_from("cuMemcpyDtoDAsync_v2")?
         }
         Ok(())
     }
@@ -507,7 +514,7 @@ impl<T: DeviceCopy> AsyncCopyDestination<DeviceBox<T>> for DeviceBox<T> {
         let size = mem::size_of::<T>();
         if size != 0 {
             cuda::cuMemcpyDtoDAsync_v2(val.ptr.as_raw(), self.ptr.as_raw(), size, stream.as_inner())
-                .to_result()?
+                .to_result_from("cuMemcpyDtoDAsync_v2")?
         }
         Ok(())
     }
@@ -605,4 +612,22 @@ mod test_device_box {
         let _ = format!("{:?}", x.as_device_ptr());
         let _ = format!("{:p}", x.as_device_ptr());
     }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_copy_failure_leaves_destination() {
+        use crate::error::CudaError;
+        use crate::sys::{mock, CUresult};
+
+        let _context = crate::quick_init().unwrap();
+        let mut x = DeviceBox::new(&5u64).unwrap();
+        let mut y = 0u64;
+        mock::fail_next("cuMemcpyDtoH_v2", CUresult::CUDA_ERROR_ILLEGAL_ADDRESS);
+        assert_eq!(x.copy_to(&mut y), Err(CudaError::IllegalAddress));
+        assert_eq!(y, 0);
+        mock::fail_next("cuMemcpyHtoD_v2", CUresult::CUDA_ERROR_ILLEGAL_ADDRESS);
+        assert_eq!(x.copy_from(&10u64), Err(CudaError::IllegalAddress));
+        x.copy_to(&mut y).unwrap();
+        assert_eq!(y, 5);
+    }
 }
@@ -1,7 +1,8 @@
 use crate::error::{CudaResult, DropResult, ToResult};
+use crate::memory::caching;
 use crate::memory::device::{AsyncCopyDestination, CopyDestination, DeviceSlice};
 use crate::memory::malloc::{cuda_free, cuda_malloc};
-use crate::memory::{cuda_free_async, DevicePointer};
+use crate::memory::{cuda_free_async, cuda_malloc_from_pool_async, DevicePointer, MemoryPool};
 use crate::memory::{cuda_malloc_async, DeviceCopy};
 use crate::stream::Stream;
 use crate::sys as cuda;
@@ -11,6 +12,7 @@ pub use bytemuck;
 use bytemuck::{Pod, PodCastError, Zeroable};
 use std::mem::{self, align_of, size_of, transmute, ManuallyDrop};
 use std::ops::{Deref, DerefMut};
+use std::ptr;
 
 /// Fixed-size device-side buffer. Provides basic access to device memory.
 #[derive(Debug)]
@@ -27,6 +29,9 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
     /// Allocate a new device buffer large enough to hold `size` `T`'s, but without
     /// initializing the contents.
     ///
+    /// The memory comes from the [`CachingAllocator`](crate::memory::CachingAllocator) of the
+    /// current context if it has one.
+    ///
     /// # Errors
     ///
     /// If the allocation fails, returns the error from CUDA. If `size` is large enough that
@@ -47,7 +52,10 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
     /// ```
     pub unsafe fn uninitialized(size: usize) -> CudaResult<Self> {
         let ptr = if size > 0 && size_of::<T>() > 0 {
-            cuda_malloc(size)?
+            match caching::allocate(size, ptr::null_mut())? {
+                Some(ptr) => ptr,
+                None => cuda_malloc(size)?,
+            }
         } else {
             // FIXME (AL): Do we /really/ want to allow creating an invalid buffer?
             DevicePointer::null()
@@ -60,7 +68,9 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
 
     /// Allocates device memory asynchronously on a stream, without initializing it.
     ///
-    /// This doesn't actually allocate if `T` is zero sized.
+    /// This doesn't actually allocate if `T` is zero sized. The memory comes from the
+    /// [`CachingAllocator`](crate::memory::CachingAllocator) of the current context if it has one,
+    /// in which case it is reused by allocations on the same stream after the buffer is dropped.
     ///
     /// # Safety
     ///
@@ -72,7 +82,35 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
     /// You can synchronize the stream to ensure the memory allocation operation is complete.
     pub unsafe fn uninitialized_async(size: usize, stream: &Stream) -> CudaResult<Self> {
         let ptr = if size > 0 && size_of::<T>() > 0 {
-            cuda_malloc_async(stream, size)?
+            match caching::allocate(size, stream.as_inner())? {
+                Some(ptr) => ptr,
+                None => cuda_malloc_async(stream, size)?,
+            }
+        } else {
+            DevicePointer::null()
+        };
+        Ok(DeviceBuffer {
+            buf: ptr,
+            len: size,
+        })
+    }
+
+    /// Allocates device memory asynchronously on a stream from a specific [`MemoryPool`], without
+    /// initializing it. The memory can be freed with [`DeviceBuffer::drop_async`] like any other
+    /// async allocation, which returns it to the pool.
+    ///
+    /// This doesn't actually allocate if `T` is zero sized.
+    ///
+    /// # Safety
+    ///
+    /// The allocated memory retains all of the unsafety of [`DeviceBuffer::uninitialized_async`].
+    pub unsafe fn uninitialized_async_in(
+        size: usize,
+        pool: &MemoryPool,
+        stream: &Stream,
+    ) -> CudaResult<Self> {
+        let ptr = if size > 0 && size_of::<T>() > 0 {
+            cuda_malloc_from_pool_async(pool, stream, size)?
         } else {
             DevicePointer::null()
         };
@@ -82,6 +120,16 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
         })
     }
 
+    /// Marks this buffer as used by work on `stream`.
+    ///
+    /// If the buffer was allocated by a [`CachingAllocator`](crate::memory::CachingAllocator), its
+    /// memory is not reused after the buffer is dropped until the work enqueued on `stream` before
+    /// that point has completed. This is only needed for streams other than the one the buffer was
+    /// allocated on. Does nothing for buffers allocated by the driver.
+    pub fn record_stream(&self, stream: &Stream) {
+        caching::record_stream(self.buf.as_raw(), stream.as_inner());
+    }
+
     /// Enqueues an operation to free the memory backed by this [`DeviceBuffer`] on a
     /// particular stream. The stream will free the allocation as soon as it reaches
     /// the operation in the stream. You can ensure the memory is freed by synchronizing
@@ -94,6 +142,9 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
     /// The memory inside of the pool is all freed back to the OS once the stream is synchronized unless
     /// a custom pool is configured to not do so.
     ///
+    /// Buffers allocated by a [`CachingAllocator`](crate::memory::CachingAllocator) are returned to
+    /// it instead, and are not reused before the work on `stream` is done.
+    ///
     /// # Examples
     ///
     /// ```
@@ -119,6 +170,9 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
         }
         // make sure we dont run the normal destructor, otherwise a double drop will happen
         let me = ManuallyDrop::new(self);
+        if let Some(res) = caching::release(me.buf.as_raw(), Some(stream.as_inner())) {
+            return res;
+        }
         // SAFETY: we consume the box so its not possible to use the box past its drop point unless
         // you keep around a pointer, but in that case, we cannot guarantee safety.
         unsafe { cuda_free_async(stream, me.buf) }
@@ -195,7 +249,8 @@ impl<T: DeviceCopy> DeviceBuffer<T> {
             let capacity = dev_buf.len;
             let ptr = mem::replace(&mut dev_buf.buf, DevicePointer::null());
             unsafe {
-                match cuda_free(ptr) {
+                let freed = caching::release(ptr.as_raw(), None).unwrap_or_else(|| cuda_free(ptr));
+                match freed {
                     Ok(()) => {
                         mem::forget(dev_buf);
                         Ok(())
@@ -231,7 +286,7 @@ impl<T: DeviceCopy + Zeroable> DeviceBuffer<T> {
             let new_buf = DeviceBuffer::uninitialized(size)?;
             if size_of::<T>() != 0 {
                 cuda::cuMemsetD8_v2(new_buf.as_device_ptr().as_raw(), 0, size_of::<T>() * size)
-                    .to_result()?;
+                    .to_result_from("cuMemsetD8_v2")?;
             }
             Ok(new_buf)
         }
@@ -276,7 +331,7 @@ impl<T: DeviceCopy + Zeroable> DeviceBuffer<T> {
                 size_of::<T>() * size,
                 stream.as_inner(),
             )
-            .to_result()?;
+            .to_result_from("cuMemsetD8Async")?;
         }
         Ok(new_buf)
     }
@@ -413,14 +468,17 @@ impl<T: DeviceCopy> DerefMut for DeviceBuffer<T> {
 
 impl<T: DeviceCopy> Drop for DeviceBuffer<T> {
     fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
         if self.buf.is_null() {
             return;
         }
 
         if self.len > 0 && size_of::<T>() > 0 {
             let ptr = mem::replace(&mut self.buf, DevicePointer::null());
-            unsafe {
-                let _ = cuda_free(ptr);
+            if caching::release(ptr.as_raw(), None).is_none() {
+                unsafe {
+                    let _ = cuda_free(ptr);
+                }
             }
         }
         self.len = 0;
@@ -467,6 +525,26 @@ mod test_device_buffer {
         assert_eq!(start, end);
     }
 
+    #[test]
+    fn test_copy_peer() {
+        // contexts on the same device are always peers of each other.
+        let context = crate::quick_init().unwrap();
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+        let start = [0u64, 1, 2, 3, 4, 5];
+        let src = DeviceBuffer::from_slice(&start).unwrap();
+        let mut dst = DeviceBuffer::from_slice(&[0u64; 6]).unwrap();
+        dst.copy_from_peer(&context, &src, &context).unwrap();
+        assert_eq!(dst.as_host_vec().unwrap(), start);
+
+        let mut dst = DeviceBuffer::from_slice(&[0u64; 6]).unwrap();
+        unsafe {
+            src.async_copy_to_peer(&context, &mut dst, &context, &stream)
+                .unwrap();
+        }
+        stream.synchronize().unwrap();
+        assert_eq!(dst.as_host_vec().unwrap(), start);
+    }
+
     #[test]
     #[should_panic]
     fn test_copy_to_d2h_wrong_size() {
@@ -550,4 +628,69 @@ mod test_device_buffer {
             let _ = buf.async_copy_from(&start, &stream);
         }
     }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_from_slice_copy_failure_frees() {
+        use crate::error::CudaError;
+        use crate::sys::{mock, CUresult};
+
+        let _context = crate::quick_init().unwrap();
+        let before = mock::live_allocations();
+        mock::fail_next("cuMemcpyHtoD_v2", CUresult::CUDA_ERROR_LAUNCH_FAILED);
+        let err = DeviceBuffer::from_slice(&[0u64, 1, 2, 3]).unwrap_err();
+        assert_eq!(err, CudaError::LaunchFailed);
+        assert_eq!(mock::live_allocations(), before);
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_allocation_failure() {
+        use crate::error::CudaError;
+        use crate::sys::{mock, CUresult};
+
+        let _context = crate::quick_init().unwrap();
+        let before = mock::live_allocations();
+        let err = unsafe { DeviceBuffer::<u8>::uninitialized(mock::TOTAL_MEMORY + 1) }.unwrap_err();
+        assert_eq!(err, CudaError::OutOfMemory);
+        mock::fail_next("cuMemAlloc_v2", CUresult::CUDA_ERROR_OUT_OF_MEMORY);
+        let err = unsafe { DeviceBuffer::<u64>::uninitialized(16) }.unwrap_err();
+        assert_eq!(err, CudaError::OutOfMemory);
+        assert_eq!(mock::live_allocations(), before);
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_failed_drop_returns_buffer() {
+        use crate::error::CudaError;
+        use crate::sys::{mock, CUresult};
+
+        let _context = crate::quick_init().unwrap();
+        let before = mock::live_allocations();
+        let buf = DeviceBuffer::from_slice(&[0u64, 1, 2, 3]).unwrap();
+        mock::fail_next("cuMemFree_v2", CUresult::CUDA_ERROR_LAUNCH_FAILED);
+        let (err, buf) = DeviceBuffer::drop(buf).unwrap_err();
+        assert_eq!(err, CudaError::LaunchFailed);
+        // the buffer is still alive and usable.
+        assert_eq!(mock::live_allocations(), before + 1);
+        assert_eq!(buf.as_host_vec().unwrap(), [0, 1, 2, 3]);
+        DeviceBuffer::drop(buf).unwrap();
+        assert_eq!(mock::live_allocations(), before);
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_drop_async_frees_before_stream_is_destroyed() {
+        use crate::sys::mock;
+
+        let _context = crate::quick_init().unwrap();
+        let before = mock::live_allocations();
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+        let buf = DeviceBuffer::from_slice(&[0u64, 1, 2, 3]).unwrap();
+        mock::clear_calls();
+        buf.drop_async(&stream).unwrap();
+        drop(stream);
+        assert_eq!(mock::calls(), ["cuMemFreeAsync", "cuStreamDestroy_v2"]);
+        assert_eq!(mock::live_allocations(), before);
+    }
 }
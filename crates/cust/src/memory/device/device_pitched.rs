@@ -0,0 +1,587 @@
+use crate::error::{CudaError, CudaResult, DropResult, ToResult};
+use crate::graph::{MemcpyEndpoint, MemcpyLocation, MemcpyNodeParams};
+use crate::memory::array::ArrayObject;
+use crate::memory::device::{AsyncCopyDestination, CopyDestination, DeviceSlice};
+use crate::memory::malloc::{cuda_free, cuda_malloc_pitched};
+use crate::memory::{DeviceCopy, DevicePointer};
+use crate::stream::Stream;
+use crate::sys as cuda;
+use std::ffi::c_void;
+use std::mem::{self, size_of};
+
+/// Two-dimensional device-side buffer with every row padded to be properly aligned.
+///
+/// Rows are `pitch` bytes apart instead of `width * size_of::<T>()` bytes, which makes
+/// accessing the start of a row as fast as possible. Kernels must index it with
+/// `(ptr as *const u8).add(y * pitch) as *const T` to find the start of row `y`.
+///
+/// Copies to and from host memory treat the host memory as tightly packed rows.
+///
+/// # Examples
+///
+/// ```
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// use cust::memory::*;
+///
+/// let image = [0u8; 640 * 480];
+/// let buf = DevicePitchedBuffer2D::from_slice(640, 480, &image)?;
+/// assert!(buf.pitch() >= 640);
+///
+/// let mut out = vec![1u8; 640 * 480];
+/// buf.copy_to(&mut out)?;
+/// assert_eq!(out, image);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DevicePitchedBuffer2D<T: DeviceCopy> {
+    buf: DevicePointer<T>,
+    width: usize,
+    height: usize,
+    pitch: usize,
+}
+
+unsafe impl<T: Send + DeviceCopy> Send for DevicePitchedBuffer2D<T> {}
+unsafe impl<T: Sync + DeviceCopy> Sync for DevicePitchedBuffer2D<T> {}
+
+impl<T: DeviceCopy> DevicePitchedBuffer2D<T> {
+    /// Allocates a new buffer of `height` rows of `width` `T`s, without initializing the contents.
+    ///
+    /// This doesn't actually allocate if the buffer is empty or `T` is zero-sized.
+    ///
+    /// # Errors
+    ///
+    /// If the allocation fails, returns the error from CUDA.
+    ///
+    /// # Safety
+    ///
+    /// The returned buffer contains uninitialized memory. This memory must not be read until it
+    /// has been initialized.
+    pub unsafe fn uninitialized(width: usize, height: usize) -> CudaResult<Self> {
+        let (buf, pitch) = if width > 0 && height > 0 && size_of::<T>() > 0 {
+            cuda_malloc_pitched(width, height)?
+        } else {
+            (DevicePointer::null(), width * size_of::<T>())
+        };
+        Ok(Self {
+            buf,
+            width,
+            height,
+            pitch,
+        })
+    }
+
+    /// Allocates a new buffer of `height` rows of `width` `T`s, initialized with the
+    /// tightly packed rows in `slice`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `slice` is not `width * height`.
+    pub fn from_slice(width: usize, height: usize, slice: &[T]) -> CudaResult<Self> {
+        unsafe {
+            let mut uninit = Self::uninitialized(width, height)?;
+            uninit.copy_from(slice)?;
+            Ok(uninit)
+        }
+    }
+
+    /// The width of the buffer in elements.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the buffer in rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The distance in bytes between the start of each row.
+    pub fn pitch(&self) -> usize {
+        self.pitch
+    }
+
+    /// Returns a [`DevicePointer<T>`] to the start of the buffer.
+    pub fn as_device_ptr(&self) -> DevicePointer<T> {
+        self.buf
+    }
+
+    /// Returns a slice of the row `y` of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: usize) -> DeviceSlice<T> {
+        assert!(y < self.height, "row index out of bounds");
+        unsafe {
+            let ptr = self.buf.cast::<u8>().add(y * self.pitch).cast();
+            DeviceSlice::from_raw_parts(ptr, self.width)
+        }
+    }
+
+    fn extent(&self) -> [usize; 3] {
+        [self.width * size_of::<T>(), self.height, 1]
+    }
+
+    fn location(&self) -> (MemcpyLocation, [usize; 3]) {
+        let location = MemcpyLocation::Device {
+            ptr: self.buf.cast(),
+            pitch: self.pitch,
+            height: self.height,
+        };
+        (location, self.extent())
+    }
+
+    /// Destroy a `DevicePitchedBuffer2D`, returning an error.
+    ///
+    /// Deallocating device memory can return errors from previous asynchronous work. This function
+    /// destroys the given buffer and returns the error and the un-destroyed buffer on failure.
+    pub fn drop(mut buf: DevicePitchedBuffer2D<T>) -> DropResult<DevicePitchedBuffer2D<T>> {
+        if buf.buf.is_null() {
+            return Ok(());
+        }
+
+        unsafe {
+            let ptr = mem::replace(&mut buf.buf, DevicePointer::null());
+            match cuda_free(ptr) {
+                Ok(()) => {
+                    mem::forget(buf);
+                    Ok(())
+                }
+                Err(e) => Err((
+                    e,
+                    DevicePitchedBuffer2D {
+                        buf: ptr,
+                        width: buf.width,
+                        height: buf.height,
+                        pitch: buf.pitch,
+                    },
+                )),
+            }
+        }
+    }
+}
+
+impl<T: DeviceCopy> Drop for DevicePitchedBuffer2D<T> {
+    fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
+        if self.buf.is_null() {
+            return;
+        }
+
+        let ptr = mem::replace(&mut self.buf, DevicePointer::null());
+        unsafe {
+            let _ = cuda_free(ptr);
+        }
+    }
+}
+
+/// Three-dimensional device-side buffer with every row padded to be properly aligned.
+///
+/// The buffer is laid out as `depth` slices of `height` rows, rows are `pitch` bytes apart and
+/// slices are `pitch * height` bytes apart.
+///
+/// Copies to and from host memory treat the host memory as tightly packed rows.
+#[derive(Debug)]
+pub struct DevicePitchedBuffer3D<T: DeviceCopy> {
+    buf: DevicePointer<T>,
+    width: usize,
+    height: usize,
+    depth: usize,
+    pitch: usize,
+}
+
+unsafe impl<T: Send + DeviceCopy> Send for DevicePitchedBuffer3D<T> {}
+unsafe impl<T: Sync + DeviceCopy> Sync for DevicePitchedBuffer3D<T> {}
+
+impl<T: DeviceCopy> DevicePitchedBuffer3D<T> {
+    /// Allocates a new buffer of `depth` slices of `height` rows of `width` `T`s, without
+    /// initializing the contents.
+    ///
+    /// This doesn't actually allocate if the buffer is empty or `T` is zero-sized.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CudaError::InvalidMemoryAllocation`] if `height * depth` overflows a `usize`. If
+    /// the allocation fails, returns the error from CUDA.
+    ///
+    /// # Safety
+    ///
+    /// The returned buffer contains uninitialized memory. This memory must not be read until it
+    /// has been initialized.
+    pub unsafe fn uninitialized(width: usize, height: usize, depth: usize) -> CudaResult<Self> {
+        let rows = height
+            .checked_mul(depth)
+            .ok_or(CudaError::InvalidMemoryAllocation)?;
+        let (buf, pitch) = if width > 0 && rows > 0 && size_of::<T>() > 0 {
+            cuda_malloc_pitched(width, rows)?
+        } else {
+            (DevicePointer::null(), width * size_of::<T>())
+        };
+        Ok(Self {
+            buf,
+            width,
+            height,
+            depth,
+            pitch,
+        })
+    }
+
+    /// Allocates a new buffer of `depth` slices of `height` rows of `width` `T`s, initialized with
+    /// the tightly packed rows in `slice`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `slice` is not `width * height * depth`.
+    pub fn from_slice(width: usize, height: usize, depth: usize, slice: &[T]) -> CudaResult<Self> {
+        unsafe {
+            let mut uninit = Self::uninitialized(width, height, depth)?;
+            uninit.copy_from(slice)?;
+            Ok(uninit)
+        }
+    }
+
+    /// The width of the buffer in elements.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the buffer in rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The depth of the buffer in slices.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The distance in bytes between the start of each row.
+    pub fn pitch(&self) -> usize {
+        self.pitch
+    }
+
+    /// Returns a [`DevicePointer<T>`] to the start of the buffer.
+    pub fn as_device_ptr(&self) -> DevicePointer<T> {
+        self.buf
+    }
+
+    /// Returns a slice of the row `y` of the slice `z` of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` or `z` are out of bounds.
+    pub fn row(&self, y: usize, z: usize) -> DeviceSlice<T> {
+        assert!(y < self.height && z < self.depth, "row index out of bounds");
+        unsafe {
+            let offset = (z * self.height + y) * self.pitch;
+            let ptr = self.buf.cast::<u8>().add(offset).cast();
+            DeviceSlice::from_raw_parts(ptr, self.width)
+        }
+    }
+
+    fn extent(&self) -> [usize; 3] {
+        [self.width * size_of::<T>(), self.height, self.depth]
+    }
+
+    fn location(&self) -> (MemcpyLocation, [usize; 3]) {
+        let location = MemcpyLocation::Device {
+            ptr: self.buf.cast(),
+            pitch: self.pitch,
+            height: self.height,
+        };
+        (location, self.extent())
+    }
+
+    /// Destroy a `DevicePitchedBuffer3D`, returning an error.
+    ///
+    /// Deallocating device memory can return errors from previous asynchronous work. This function
+    /// destroys the given buffer and returns the error and the un-destroyed buffer on failure.
+    pub fn drop(mut buf: DevicePitchedBuffer3D<T>) -> DropResult<DevicePitchedBuffer3D<T>> {
+        if buf.buf.is_null() {
+            return Ok(());
+        }
+
+        unsafe {
+            let ptr = mem::replace(&mut buf.buf, DevicePointer::null());
+            match cuda_free(ptr) {
+                Ok(()) => {
+                    mem::forget(buf);
+                    Ok(())
+                }
+                Err(e) => Err((
+                    e,
+                    DevicePitchedBuffer3D {
+                        buf: ptr,
+                        width: buf.width,
+                        height: buf.height,
+                        depth: buf.depth,
+                        pitch: buf.pitch,
+                    },
+                )),
+            }
+        }
+    }
+}
+
+impl<T: DeviceCopy> Drop for DevicePitchedBuffer3D<T> {
+    fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
+        if self.buf.is_null() {
+            return;
+        }
+
+        let ptr = mem::replace(&mut self.buf, DevicePointer::null());
+        unsafe {
+            let _ = cuda_free(ptr);
+        }
+    }
+}
+
+fn host_location<T>(slice: &[T]) -> (MemcpyLocation, [usize; 3]) {
+    let width = mem::size_of_val(slice);
+    let location = MemcpyLocation::Host {
+        ptr: slice.as_ptr() as *mut c_void,
+        pitch: width,
+        height: 1,
+    };
+    (location, [width, 1, 1])
+}
+
+fn slice_location<T: DeviceCopy>(slice: &DeviceSlice<T>) -> (MemcpyLocation, [usize; 3]) {
+    let width = slice.len() * size_of::<T>();
+    let location = MemcpyLocation::Device {
+        ptr: slice.as_device_ptr().cast(),
+        pitch: width,
+        height: 1,
+    };
+    (location, [width, 1, 1])
+}
+
+pub(super) unsafe fn memcpy_2d(
+    src: (MemcpyLocation, [usize; 3]),
+    dst: (MemcpyLocation, [usize; 3]),
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    let params = MemcpyNodeParams::from_locations(src, dst);
+    if params.extent.contains(&0) {
+        return Ok(());
+    }
+    let raw = params.to_raw_2d();
+    match stream {
+        Some(stream) => {
+            cuda::cuMemcpy2DAsync_v2(&raw, stream.as_inner()).to_result_from("cuMemcpy2DAsync_v2")
+        }
+        None => cuda::cuMemcpy2D_v2(&raw).to_result_from("cuMemcpy2D_v2"),
+    }
+}
+
+pub(super) unsafe fn memcpy_3d(
+    src: (MemcpyLocation, [usize; 3]),
+    dst: (MemcpyLocation, [usize; 3]),
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    let params = MemcpyNodeParams::from_locations(src, dst);
+    if params.extent.contains(&0) {
+        return Ok(());
+    }
+    let raw = params.to_raw();
+    match stream {
+        Some(stream) => {
+            cuda::cuMemcpy3DAsync_v2(&raw, stream.as_inner()).to_result_from("cuMemcpy3DAsync_v2")
+        }
+        None => cuda::cuMemcpy3D_v2(&raw).to_result_from("cuMemcpy3D_v2"),
+    }
+}
+
+// the copy impls are the same for both buffers other than the memcpy function used.
+macro_rules! impl_pitched_copies {
+    ($buffer:ident, $memcpy:ident) => {
+        impl<T: DeviceCopy> crate::private::Sealed for $buffer<T> {}
+
+        impl<T: DeviceCopy> MemcpyEndpoint for $buffer<T> {
+            fn memcpy_location(&self) -> CudaResult<(MemcpyLocation, [usize; 3])> {
+                Ok(self.location())
+            }
+        }
+
+        impl<T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> CopyDestination<I> for $buffer<T> {
+            fn copy_from(&mut self, val: &I) -> CudaResult<()> {
+                unsafe { $memcpy(host_location(val.as_ref()), self.location(), None) }
+            }
+
+            fn copy_to(&self, val: &mut I) -> CudaResult<()> {
+                unsafe { $memcpy(self.location(), host_location(val.as_mut()), None) }
+            }
+        }
+
+        impl<T: DeviceCopy> CopyDestination<DeviceSlice<T>> for $buffer<T> {
+            fn copy_from(&mut self, val: &DeviceSlice<T>) -> CudaResult<()> {
+                unsafe { $memcpy(slice_location(val), self.location(), None) }
+            }
+
+            fn copy_to(&self, val: &mut DeviceSlice<T>) -> CudaResult<()> {
+                unsafe { $memcpy(self.location(), slice_location(val), None) }
+            }
+        }
+
+        impl<T: DeviceCopy> CopyDestination<$buffer<T>> for $buffer<T> {
+            fn copy_from(&mut self, val: &$buffer<T>) -> CudaResult<()> {
+                unsafe { $memcpy(val.location(), self.location(), None) }
+            }
+
+            fn copy_to(&self, val: &mut $buffer<T>) -> CudaResult<()> {
+                unsafe { $memcpy(self.location(), val.location(), None) }
+            }
+        }
+
+        impl<T: DeviceCopy> CopyDestination<ArrayObject> for $buffer<T> {
+            fn copy_from(&mut self, val: &ArrayObject) -> CudaResult<()> {
+                unsafe { $memcpy(val.memcpy_location()?, self.location(), None) }
+            }
+
+            fn copy_to(&self, val: &mut ArrayObject) -> CudaResult<()> {
+                unsafe { $memcpy(self.location(), val.memcpy_location()?, None) }
+            }
+        }
+
+        impl<T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> AsyncCopyDestination<I>
+            for $buffer<T>
+        {
+            unsafe fn async_copy_from(&mut self, val: &I, stream: &Stream) -> CudaResult<()> {
+                $memcpy(host_location(val.as_ref()), self.location(), Some(stream))
+            }
+
+            unsafe fn async_copy_to(&self, val: &mut I, stream: &Stream) -> CudaResult<()> {
+                $memcpy(self.location(), host_location(val.as_mut()), Some(stream))
+            }
+        }
+
+        impl<T: DeviceCopy> AsyncCopyDestination<DeviceSlice<T>> for $buffer<T> {
+            unsafe fn async_copy_from(
+                &mut self,
+                val: &DeviceSlice<T>,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                $memcpy(slice_location(val), self.location(), Some(stream))
+            }
+
+            unsafe fn async_copy_to(
+                &self,
+                val: &mut DeviceSlice<T>,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                $memcpy(self.location(), slice_location(val), Some(stream))
+            }
+        }
+
+        impl<T: DeviceCopy> AsyncCopyDestination<$buffer<T>> for $buffer<T> {
+            unsafe fn async_copy_from(
+                &mut self,
+                val: &$buffer<T>,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                $memcpy(val.location(), self.location(), Some(stream))
+            }
+
+            unsafe fn async_copy_to(
+                &self,
+                val: &mut $buffer<T>,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                $memcpy(self.location(), val.location(), Some(stream))
+            }
+        }
+
+        impl<T: DeviceCopy> AsyncCopyDestination<ArrayObject> for $buffer<T> {
+            unsafe fn async_copy_from(
+                &mut self,
+                val: &ArrayObject,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                $memcpy(val.memcpy_location()?, self.location(), Some(stream))
+            }
+
+            unsafe fn async_copy_to(
+                &self,
+                val: &mut ArrayObject,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                $memcpy(self.location(), val.memcpy_location()?, Some(stream))
+            }
+        }
+    };
+}
+
+impl_pitched_copies!(DevicePitchedBuffer2D, memcpy_2d);
+impl_pitched_copies!(DevicePitchedBuffer3D, memcpy_3d);
+
+#[cfg(test)]
+mod test_device_pitched {
+    use super::*;
+    use crate::memory::array::ArrayFormat;
+    use crate::memory::DeviceBuffer;
+    use crate::stream::StreamFlags;
+    use crate::texture::Texture;
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_copy_2d() {
+        let _context = crate::quick_init().unwrap();
+        let start = (0..33 * 7).map(|x| x as u8).collect::<Vec<_>>();
+        let buf = DevicePitchedBuffer2D::from_slice(33, 7, &start).unwrap();
+        assert!(buf.pitch() >= 33);
+
+        let mut end = vec![0u8; start.len()];
+        buf.copy_to(&mut end).unwrap();
+        assert_eq!(start, end);
+
+        let mut row = [0u8; 33];
+        buf.row(2).copy_to(&mut row).unwrap();
+        assert_eq!(row, start[66..99]);
+
+        // device buffers are treated as tightly packed rows.
+        let mut packed = DeviceBuffer::from_slice(&vec![0u8; start.len()]).unwrap();
+        buf.copy_to(&mut *packed).unwrap();
+        assert_eq!(packed.as_host_vec().unwrap(), start);
+
+        let mut array = ArrayObject::new_2d([33, 7], ArrayFormat::U8, 1).unwrap();
+        buf.copy_to(&mut array).unwrap();
+        assert_eq!(array.as_host_vec::<u8>().unwrap(), start);
+
+        let _texture = unsafe { Texture::from_pitched_2d(&buf, ArrayFormat::U8, 1).unwrap() };
+    }
+
+    #[test]
+    fn test_async_copy_3d() {
+        let _context = crate::quick_init().unwrap();
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+        let start = (0..5 * 4 * 3).map(|x| x as f32).collect::<Vec<_>>();
+        let mut end = vec![0.0; start.len()];
+        unsafe {
+            let mut buf = DevicePitchedBuffer3D::uninitialized(5, 4, 3).unwrap();
+            buf.async_copy_from(&start, &stream).unwrap();
+            let mut other = DevicePitchedBuffer3D::uninitialized(5, 4, 3).unwrap();
+            other.async_copy_from(&buf, &stream).unwrap();
+            other.async_copy_to(&mut end, &stream).unwrap();
+            stream.synchronize().unwrap();
+        }
+        assert_eq!(start, end);
+    }
+
+    #[test]
+    fn test_3d_size_overflow() {
+        let _context = crate::quick_init().unwrap();
+        let res = unsafe { DevicePitchedBuffer3D::<u8>::uninitialized(1, usize::MAX, 2) };
+        assert_eq!(res.unwrap_err(), CudaError::InvalidMemoryAllocation);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_copy_wrong_size() {
+        let _context = crate::quick_init().unwrap();
+        let _ = DevicePitchedBuffer2D::from_slice(4, 4, &[0u32; 15]);
+    }
+}
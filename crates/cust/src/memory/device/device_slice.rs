@@ -1,3 +1,4 @@
+use crate::context::ContextHandle;
 use crate::error::{CudaResult, ToResult};
 use crate::memory::device::AsyncCopyDestination;
 use crate::memory::device::{CopyDestination, DeviceBuffer};
@@ -75,7 +76,7 @@ impl<T: DeviceCopy> DeviceSlice<T> {
     /// # let _context = cust::quick_init().unwrap();
     /// use cust::memory::*;
     /// let a = DeviceBuffer::from_slice(&[1, 2, 3]).unwrap();
-    /// println!("{:p}", a.as_ptr());
+    /// println!("{:p}", a.as_device_ptr());
     /// ```
     pub fn as_device_ptr(&self) -> DevicePointer<T> {
         self.ptr
@@ -228,7 +229,8 @@ impl<T: DeviceCopy + Pod> DeviceSlice<T> {
         // SAFETY: We know T can hold any value because it is `Pod`, and
         // sub-byte alignment isn't a thing so we know the alignment is right.
         unsafe {
-            cuda::cuMemsetD8_v2(self.ptr.as_raw(), value, size_of::<T>() * self.len).to_result()
+            cuda::cuMemsetD8_v2(self.ptr.as_raw(), value, size_of::<T>() * self.len)
+                .to_result_from("cuMemsetD8_v2")
         }
     }
 
@@ -252,7 +254,7 @@ impl<T: DeviceCopy + Pod> DeviceSlice<T> {
             size_of::<T>() * self.len,
             stream.as_inner(),
         )
-        .to_result()
+        .to_result_from("cuMemsetD8Async")
     }
 
     /// Sets the memory range of this buffer to contiguous `16-bit` values of `value`.
@@ -281,7 +283,10 @@ impl<T: DeviceCopy + Pod> DeviceSlice<T> {
             0,
             "Buffer pointer is not aligned to at least 2 bytes!"
         );
-        unsafe { cuda::cuMemsetD16_v2(self.ptr.as_raw(), value, data_len / 2).to_result() }
+        unsafe {
+            cuda::cuMemsetD16_v2(self.ptr.as_raw(), value, data_len / 2)
+                .to_result_from("cuMemsetD16_v2")
+        }
     }
 
     /// Sets the memory range of this buffer to contiguous `16-bit` values of `value` asynchronously.
@@ -316,7 +321,7 @@ impl<T: DeviceCopy + Pod> DeviceSlice<T> {
             "Buffer pointer is not aligned to at least 2 bytes!"
         );
         cuda::cuMemsetD16Async(self.ptr.as_raw(), value, data_len / 2, stream.as_inner())
-            .to_result()
+            .to_result_from("cuMemsetD16Async")
     }
 
     /// Sets the memory range of this buffer to contiguous `32-bit` values of `value`.
@@ -345,7 +350,10 @@ impl<T: DeviceCopy + Pod> DeviceSlice<T> {
             0,
             "Buffer pointer is not aligned to at least 4 bytes!"
         );
-        unsafe { cuda::cuMemsetD32_v2(self.ptr.as_raw(), value, data_len / 4).to_result() }
+        unsafe {
+            cuda::cuMemsetD32_v2(self.ptr.as_raw(), value, data_len / 4)
+                .to_result_from("cuMemsetD32_v2")
+        }
     }
 
     /// Sets the memory range of this buffer to contiguous `32-bit` values of `value` asynchronously.
@@ -380,7 +388,7 @@ impl<T: DeviceCopy + Pod> DeviceSlice<T> {
             "Buffer pointer is not aligned to at least 4 bytes!"
         );
         cuda::cuMemsetD32Async(self.ptr.as_raw(), value, data_len / 4, stream.as_inner())
-            .to_result()
+            .to_result_from("cuMemsetD32Async")
     }
 }
 
@@ -556,6 +564,121 @@ impl<T: DeviceCopy> DeviceSlice<T> {
     }
 }
 
+impl<T: DeviceCopy> DeviceSlice<T> {
+    /// Copies data from `source`, which is memory in the context `src_ctx`, into this slice, which
+    /// is memory in the context `dst_ctx`. `source` must be the same size as `self`.
+    ///
+    /// This works across contexts on different devices, if peer access is enabled between the
+    /// contexts the copy is done directly between the devices, otherwise it is staged through
+    /// host memory by the driver.
+    ///
+    /// # Errors
+    ///
+    /// If a CUDA error occurs, return the error.
+    pub fn copy_from_peer<D: ContextHandle, S: ContextHandle>(
+        &mut self,
+        dst_ctx: &D,
+        source: &DeviceSlice<T>,
+        src_ctx: &S,
+    ) -> CudaResult<()> {
+        assert!(
+            self.len() == source.len(),
+            "destination and source slices have different lengths"
+        );
+        let size = mem::size_of::<T>() * self.len();
+        if size != 0 {
+            unsafe {
+                cuda::cuMemcpyPeer(
+                    self.ptr.as_raw(),
+                    dst_ctx.get_inner(),
+                    source.as_device_ptr().as_raw(),
+                    src_ctx.get_inner(),
+                    size,
+                )
+                .to_result_from("cuMemcpyPeer")?
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies data from this slice, which is memory in the context `src_ctx`, into `dest`, which is
+    /// memory in the context `dst_ctx`. `dest` must be the same size as `self`.
+    ///
+    /// See [`DeviceSlice::copy_from_peer`] for more info.
+    ///
+    /// # Errors
+    ///
+    /// If a CUDA error occurs, return the error.
+    pub fn copy_to_peer<S: ContextHandle, D: ContextHandle>(
+        &self,
+        src_ctx: &S,
+        dest: &mut DeviceSlice<T>,
+        dst_ctx: &D,
+    ) -> CudaResult<()> {
+        dest.copy_from_peer(dst_ctx, self, src_ctx)
+    }
+
+    /// Asynchronously copies data from `source`, which is memory in the context `src_ctx`, into this
+    /// slice, which is memory in the context `dst_ctx`. `source` must be the same size as `self`.
+    ///
+    /// See [`DeviceSlice::copy_from_peer`] for more info.
+    ///
+    /// # Safety
+    ///
+    /// For why this function is unsafe, see [AsyncCopyDestination](trait.AsyncCopyDestination.html)
+    ///
+    /// # Errors
+    ///
+    /// If a CUDA error occurs, return the error.
+    pub unsafe fn async_copy_from_peer<D: ContextHandle, S: ContextHandle>(
+        &mut self,
+        dst_ctx: &D,
+        source: &DeviceSlice<T>,
+        src_ctx: &S,
+        stream: &Stream,
+    ) -> CudaResult<()> {
+        assert!(
+            self.len() == source.len(),
+            "destination and source slices have different lengths"
+        );
+        let size = mem::size_of::<T>() * self.len();
+        if size != 0 {
+            cuda::cuMemcpyPeerAsync(
+                self.ptr.as_raw(),
+                dst_ctx.get_inner(),
+                source.as_device_ptr().as_raw(),
+                src_ctx.get_inner(),
+                size,
+                stream.as_inner(),
+            )
+            .to_result_from("cuMemcpyPeerAsync")?
+        }
+        Ok(())
+    }
+
+    /// Asynchronously copies data from this slice, which is memory in the context `src_ctx`, into
+    /// `dest`, which is memory in the context `dst_ctx`. `dest` must be the same size as `self`.
+    ///
+    /// See [`DeviceSlice::copy_from_peer`] for more info.
+    ///
+    /// # Safety
+    ///
+    /// For why this function is unsafe, see [AsyncCopyDestination](trait.AsyncCopyDestination.html)
+    ///
+    /// # Errors
+    ///
+    /// If a CUDA error occurs, return the error.
+    pub unsafe fn async_copy_to_peer<S: ContextHandle, D: ContextHandle>(
+        &self,
+        src_ctx: &S,
+        dest: &mut DeviceSlice<T>,
+        dst_ctx: &D,
+        stream: &Stream,
+    ) -> CudaResult<()> {
+        dest.async_copy_from_peer(dst_ctx, self, src_ctx, stream)
+    }
+}
+
 impl<T: DeviceCopy> crate::private::Sealed for DeviceSlice<T> {}
 impl<T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> CopyDestination<I> for DeviceSlice<T> {
     fn copy_from(&mut self, val: &I) -> CudaResult<()> {
@@ -568,7 +691,7 @@ impl<T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> CopyDestination<I> for
         if size != 0 {
             unsafe {
                 cuda::cuMemcpyHtoD_v2(self.ptr.as_raw(), val.as_ptr() as *const c_void, size)
-                    .to_result()?
+                    .to_result_from("cuMemcpyHtoD_v2")?
             }
         }
         Ok(())
@@ -588,7 +711,7 @@ impl<T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> CopyDestination<I> for
                     self.as_device_ptr().as_raw(),
                     size,
                 )
-                .to_result()?
+                .to_result_from("cuMemcpyDtoH_v2")?
             }
         }
         Ok(())
@@ -604,7 +727,7 @@ impl<T: DeviceCopy> CopyDestination<DeviceSlice<T>> for DeviceSlice<T> {
         if size != 0 {
             unsafe {
                 cuda::cuMemcpyDtoD_v2(self.ptr.as_raw(), val.as_device_ptr().as_raw(), size)
-                    .to_result()?
+                    .to_result_from("cuMemcpyDtoD_v2")?
             }
         }
         Ok(())
@@ -623,7 +746,7 @@ impl<T: DeviceCopy> CopyDestination<DeviceSlice<T>> for DeviceSlice<T> {
                     self.as_device_ptr().as_raw(),
                     size,
                 )
-                .to_result()?
+                .to_result_from("cuMemcpyDtoD_v2")?
             }
         }
         Ok(())
@@ -655,7 +778,7 @@ impl<T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> AsyncCopyDestination<I>
                 size,
                 stream.as_inner(),
             )
-            .to_result()?
+            .to_result_from("cuMemcpyHtoDAsync_v2")?
         }
         Ok(())
     }
@@ -674,7 +797,7 @@ impl<T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> AsyncCopyDestination<I>
                 size,
                 stream.as_inner(),
             )
-            .to_result()?
+            .to_result_from("cuMemcpyDtoHAsync_v2")?
         }
         Ok(())
     }
@@ -693,7 +816,7 @@ impl<T: DeviceCopy> AsyncCopyDestination<DeviceSlice<T>> for DeviceSlice<T> {
                 size,
                 stream.as_inner(),
             )
-            .to_result()?
+            .to_result_from("cuMemcpyDtoDAsync_v2")?
         }
         Ok(())
     }
@@ -711,7 +834,7 @@ impl<T: DeviceCopy> AsyncCopyDestination<DeviceSlice<T>> for DeviceSlice<T> {
                 size,
                 stream.as_inner(),
             )
-            .to_result()?
+            .to_result_from("cuMemcpyDtoDAsync_v2")?
         }
         Ok(())
     }
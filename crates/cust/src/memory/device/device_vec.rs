@@ -0,0 +1,346 @@
+use crate::context::CurrentContext;
+use crate::device::Device;
+use crate::error::{CudaError, CudaResult, ToResult};
+use crate::memory::device::{CopyDestination, DeviceSlice};
+use crate::memory::vmm::{
+    allocation_granularity, AllocationGranularity, PhysicalAllocation, VirtualAddressRange,
+};
+use crate::memory::{DeviceCopy, DevicePointer, MemoryAccess};
+use crate::sys::{self as cuda, CUcontext};
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
+
+/// Growable device-side buffer, the device equivalent of a `Vec<T>`.
+///
+/// Unlike [`DeviceBuffer`](crate::memory::DeviceBuffer), a `DeviceVec` can grow without
+/// copying its contents. Whenever it needs to grow, it reserves the virtual addresses right after
+/// its memory and maps more physical memory there, so the device pointer of a `DeviceVec`
+/// usually stays the same. If those addresses are already in use, the existing memory is mapped
+/// again together with the new memory into a larger range somewhere else, which changes the
+/// device pointer but still does not copy anything.
+///
+/// Unmapping memory does not wait for the device, so when the vector moves or is dropped it
+/// synchronizes the context it was created in first.
+///
+/// # Examples
+///
+/// ```
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// # if cfg!(feature = "mock") { return Ok(()); }
+/// use cust::memory::DeviceVec;
+///
+/// let mut particles = DeviceVec::new()?;
+/// particles.extend_from_slice(&[1.0f32, 2.0, 3.0])?;
+/// particles.push(4.0)?;
+/// assert_eq!(particles.len(), 4);
+/// assert_eq!(particles.as_host_vec()?, [1.0, 2.0, 3.0, 4.0]);
+///
+/// particles.truncate(2);
+/// assert_eq!(particles.as_host_vec()?, [1.0, 2.0]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[repr(C)]
+pub struct DeviceVec<T: DeviceCopy> {
+    buf: DevicePointer<T>,
+    len: usize,
+    // the slice handed out by `DerefMut`. It is rebuilt from `buf` and `len` every time, so
+    // assigning to the slice cannot change which memory the vector owns.
+    view: DeviceSlice<T>,
+    context: CUcontext,
+    device: Device,
+    granularity: usize,
+    // the ranges of virtual addresses making up the vector, each right after the previous one.
+    ranges: Vec<VirtualAddressRange>,
+    // the physical memory mapped one after another into `ranges`, which it completely fills.
+    chunks: Vec<PhysicalAllocation>,
+    // the total size of the chunks in bytes.
+    mapped: usize,
+}
+
+// maps `chunks` one after another into the start of `range` and lets `device` read and write
+// them. Unmaps everything again on failure.
+fn map_chunks<'a>(
+    range: &VirtualAddressRange,
+    chunks: impl IntoIterator<Item = &'a PhysicalAllocation>,
+    device: &Device,
+) -> CudaResult<()> {
+    let mut offset = 0;
+    let mut result = Ok(());
+    for chunk in chunks {
+        result = range.map(offset, chunk);
+        if result.is_err() {
+            break;
+        }
+        offset += chunk.size();
+    }
+    let result = result.and_then(|_| range.set_access(0, offset, device, MemoryAccess::ReadWrite));
+    if result.is_err() && offset > 0 {
+        unsafe {
+            let _ = range.unmap(0, offset);
+        }
+    }
+    result
+}
+
+unsafe impl<T: Send + DeviceCopy> Send for DeviceVec<T> {}
+unsafe impl<T: Sync + DeviceCopy> Sync for DeviceVec<T> {}
+
+impl<T: DeviceCopy> DeviceVec<T> {
+    /// Creates a new empty `DeviceVec` on the device of the current context. This does not
+    /// allocate any memory until elements are added.
+    ///
+    /// # Errors
+    ///
+    /// If there is no current context, or the device does not support virtual memory
+    /// management, returns the error from CUDA.
+    pub fn new() -> CudaResult<Self> {
+        let device = CurrentContext::get_device()?;
+        let granularity = allocation_granularity(&device, AllocationGranularity::Recommended)?;
+        let mut context = std::ptr::null_mut();
+        unsafe {
+            cuda::cuCtxGetCurrent(&mut context).to_result_from("cuCtxGetCurrent")?;
+        }
+        Ok(Self {
+            buf: DevicePointer::null(),
+            len: 0,
+            view: unsafe { DeviceSlice::from_raw_parts_mut(DevicePointer::null(), 0) },
+            context,
+            device,
+            granularity,
+            ranges: Vec::new(),
+            chunks: Vec::new(),
+            mapped: 0,
+        })
+    }
+
+    /// Creates a new empty `DeviceVec` with space for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> CudaResult<Self> {
+        let mut vec = Self::new()?;
+        vec.reserve(capacity)?;
+        Ok(vec)
+    }
+
+    /// Returns the amount of elements the vector can hold without mapping more memory.
+    pub fn capacity(&self) -> usize {
+        if size_of::<T>() == 0 {
+            usize::MAX
+        } else {
+            self.mapped / size_of::<T>()
+        }
+    }
+
+    /// Makes sure the vector has space for at least `additional` more elements. Growing the
+    /// vector never copies the existing elements, but may move them to other addresses.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CudaError::OutOfMemory`] if the size of the vector in bytes overflows, or the
+    /// error from CUDA if allocating or mapping more memory fails.
+    pub fn reserve(&mut self, additional: usize) -> CudaResult<()> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(CudaError::OutOfMemory)?;
+        if required <= self.capacity() {
+            return Ok(());
+        }
+
+        let required_bytes = required
+            .checked_mul(size_of::<T>())
+            .ok_or(CudaError::OutOfMemory)?;
+        self.grow(required_bytes)
+    }
+
+    fn grow(&mut self, required_bytes: usize) -> CudaResult<()> {
+        // grow by at least double like Vec to not map tiny chunks over and over.
+        let bytes = round_up(required_bytes.max(self.mapped * 2), self.granularity);
+        let chunk = PhysicalAllocation::new(&self.device, bytes - self.mapped)?;
+
+        // try to put the chunk right after the existing memory first. The address is only a
+        // hint to the driver, so the range may end up somewhere else.
+        let end = self.buf.cast::<u8>().wrapping_add(self.mapped);
+        let range = VirtualAddressRange::reserve_at(chunk.size(), 0, end)?;
+        if !self.ranges.is_empty() && range.as_device_ptr() != end {
+            drop(range);
+            return self.relocate(chunk);
+        }
+
+        map_chunks(&range, Some(&chunk), &self.device)?;
+        if self.ranges.is_empty() {
+            self.buf = range.as_device_ptr().cast();
+        }
+        self.ranges.push(range);
+        self.chunks.push(chunk);
+        self.mapped = bytes;
+        Ok(())
+    }
+
+    // maps the existing memory followed by `chunk` into a new range large enough for all of it,
+    // and moves the vector there.
+    fn relocate(&mut self, chunk: PhysicalAllocation) -> CudaResult<()> {
+        let bytes = self.mapped + chunk.size();
+        let range = VirtualAddressRange::reserve(bytes, 0)?;
+        // physical memory can be mapped more than once, so the old ranges are only unmapped
+        // once the new one is set up.
+        map_chunks(&range, self.chunks.iter().chain(Some(&chunk)), &self.device)?;
+
+        // pending work may still use the old addresses.
+        if let Err(e) = self.synchronize() {
+            unsafe {
+                let _ = range.unmap(0, bytes);
+            }
+            return Err(e);
+        }
+        self.unmap();
+        self.buf = range.as_device_ptr().cast();
+        self.ranges.push(range);
+        self.chunks.push(chunk);
+        self.mapped = bytes;
+        Ok(())
+    }
+
+    // waits for all work in the context of the vector, which may not be the current one.
+    fn synchronize(&self) -> CudaResult<()> {
+        unsafe {
+            cuda::cuCtxPushCurrent_v2(self.context).to_result_from("cuCtxPushCurrent_v2")?;
+            let res = cuda::cuCtxSynchronize().to_result_from("cuCtxSynchronize");
+            let mut popped = std::ptr::null_mut();
+            cuda::cuCtxPopCurrent_v2(&mut popped).to_result_from("cuCtxPopCurrent_v2")?;
+            res
+        }
+    }
+
+    // unmaps and frees the ranges, without freeing the chunks.
+    fn unmap(&mut self) {
+        for range in self.ranges.drain(..) {
+            unsafe {
+                let _ = range.unmap(0, range.size());
+            }
+        }
+    }
+
+    /// Appends an element to the end of the vector, growing it if needed.
+    pub fn push(&mut self, value: T) -> CudaResult<()> {
+        self.extend_from_slice(&[value])
+    }
+
+    /// Copies all the elements of a host slice to the end of the vector, growing it if needed.
+    pub fn extend_from_slice(&mut self, values: &[T]) -> CudaResult<()> {
+        self.reserve(values.len())?;
+        unsafe {
+            let mut dst = DeviceSlice::from_raw_parts_mut(self.buf.add(self.len), values.len());
+            dst.copy_from(values)?;
+        }
+        self.len += values.len();
+        Ok(())
+    }
+
+    /// Shortens the vector to `len` elements, does nothing if the vector is already shorter.
+    ///
+    /// This does not unmap any memory, so the capacity of the vector stays the same.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+        }
+    }
+
+    /// Removes all of the elements of the vector without unmapping any memory.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Explicitly creates a [`DeviceSlice`] from this vector.
+    pub fn as_slice(&self) -> &DeviceSlice<T> {
+        self
+    }
+
+    /// Returns the device the memory of this vector is on.
+    pub fn device(&self) -> Device {
+        self.device
+    }
+}
+
+fn round_up(x: usize, multiple: usize) -> usize {
+    match x % multiple {
+        0 => x,
+        rem => x + (multiple - rem),
+    }
+}
+
+impl<T: DeviceCopy> Deref for DeviceVec<T> {
+    type Target = DeviceSlice<T>;
+
+    fn deref(&self) -> &DeviceSlice<T> {
+        // a shared reference cannot change `buf` and `len`, so it can overlay them.
+        unsafe { &*(self as *const _ as *const DeviceSlice<T>) }
+    }
+}
+
+impl<T: DeviceCopy> DerefMut for DeviceVec<T> {
+    fn deref_mut(&mut self) -> &mut DeviceSlice<T> {
+        self.view = unsafe { DeviceSlice::from_raw_parts_mut(self.buf, self.len) };
+        &mut self.view
+    }
+}
+
+impl<T: DeviceCopy> Drop for DeviceVec<T> {
+    fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
+        if self.ranges.is_empty() {
+            return;
+        }
+        // unmapping does not wait for the device like freeing memory does, so wait for any
+        // work which may still use the vector first.
+        let _ = self.synchronize();
+        self.unmap();
+        // the chunks are freed by their own destructors.
+        self.chunks.clear();
+        self.buf = DevicePointer::null();
+        self.len = 0;
+        self.mapped = 0;
+    }
+}
+
+#[cfg(test)]
+mod test_device_vec {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_grow_in_place() {
+        let _context = crate::quick_init().unwrap();
+        let mut vec = DeviceVec::<u64>::new().unwrap();
+        assert!(vec.is_empty());
+        assert_eq!(vec.capacity(), 0);
+
+        vec.push(1).unwrap();
+        let capacity = vec.capacity();
+        let data = (0..capacity as u64 * 3).collect::<Vec<_>>();
+        vec.extend_from_slice(&data).unwrap();
+        // growing maps more memory instead of reallocating, the existing elements stay intact.
+        assert!(vec.capacity() > capacity);
+        assert_eq!(vec.len(), data.len() + 1);
+
+        let host = vec.as_host_vec().unwrap();
+        assert_eq!(host[0], 1);
+        assert_eq!(&host[1..], &data[..]);
+
+        // replacing the slice handed out by `DerefMut` does not change the vector.
+        let other = DeviceVec::<u64>::with_capacity(1).unwrap();
+        let ptr = vec.as_device_ptr();
+        let slice: &mut DeviceSlice<u64> = &mut vec;
+        *slice = *other.as_slice();
+        assert_eq!(vec.as_device_ptr(), ptr);
+        assert_eq!(vec.len(), data.len() + 1);
+
+        vec.truncate(1);
+        assert_eq!(vec.as_host_vec().unwrap(), [1]);
+        vec.clear();
+        assert!(vec.is_empty());
+    }
+}
@@ -0,0 +1,1080 @@
+use super::device_pitched::{memcpy_2d, memcpy_3d};
+use crate::error::{CudaResult, ToResult};
+use crate::graph::MemcpyLocation;
+use crate::memory::device::{AsyncCopyDestination, CopyDestination, DeviceSlice};
+use crate::memory::{DeviceCopy, DevicePointer};
+use crate::stream::Stream;
+use crate::sys as cuda;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{Bound, Range, RangeBounds};
+
+/// The pointer, shape and strides of a device view, which can be passed to a kernel.
+///
+/// Shapes and strides are in elements with the outermost dimension first, so element `[i, j]` of
+/// a 2D view is at `ptr.add(i * strides[0] + j * strides[1])`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct RawDeviceView<T: DeviceCopy, const N: usize> {
+    pub ptr: DevicePointer<T>,
+    pub shape: [usize; N],
+    pub strides: [usize; N],
+}
+
+unsafe impl<T: DeviceCopy, const N: usize> DeviceCopy for RawDeviceView<T, N> {}
+
+impl<T: DeviceCopy, const N: usize> RawDeviceView<T, N> {
+    fn contiguous(ptr: DevicePointer<T>, shape: [usize; N]) -> Self {
+        let mut strides = [1usize; N];
+        for i in (1..N).rev() {
+            strides[i - 1] = strides[i].saturating_mul(shape[i]);
+        }
+        Self {
+            ptr,
+            shape,
+            strides,
+        }
+    }
+
+    // panics instead of overflowing, so that a view never claims fewer elements than it has.
+    fn len(&self) -> usize {
+        self.shape
+            .iter()
+            .try_fold(1usize, |len, &n| len.checked_mul(n))
+            .unwrap_or_else(|| panic!("view with shape {:?} has too many elements", self.shape))
+    }
+
+    fn is_standard_layout(&self) -> bool {
+        let contiguous = Self::contiguous(self.ptr, self.shape);
+        self.shape.contains(&0)
+            || (0..N).all(|i| self.shape[i] == 1 || self.strides[i] == contiguous.strides[i])
+    }
+
+    fn assert_in_bounds(&self, len: usize) {
+        // the number of elements has to fit in a usize as well, even if the strides are zero.
+        self.len();
+        if self.shape.contains(&0) {
+            return;
+        }
+        let last = self
+            .shape
+            .iter()
+            .zip(&self.strides)
+            .try_fold(0usize, |last, (&n, &stride)| {
+                (n - 1).checked_mul(stride)?.checked_add(last)
+            });
+        assert!(
+            matches!(last, Some(last) if last < len),
+            "view with shape {:?} and strides {:?} is out of bounds of a slice of length {}",
+            self.shape,
+            self.strides,
+            len
+        );
+    }
+
+    // every element must be at a different offset, so that writes to the view don't race.
+    fn assert_unique(&self) {
+        let mut dims = self
+            .shape
+            .iter()
+            .zip(&self.strides)
+            .filter(|&(&n, _)| n > 1)
+            .collect::<Vec<_>>();
+        dims.sort_by_key(|&(_, &stride)| stride);
+        let mut extent = 1;
+        for (&n, &stride) in dims {
+            assert!(
+                stride >= extent,
+                "mutable view with shape {:?} and strides {:?} has overlapping elements",
+                self.shape,
+                self.strides
+            );
+            extent += (n - 1) * stride;
+        }
+    }
+
+    fn slice(self, ranges: [Range<usize>; N]) -> Self {
+        let mut view = self;
+        let mut offset = 0;
+        for (i, range) in ranges.iter().enumerate() {
+            offset = range
+                .start
+                .checked_mul(self.strides[i])
+                .and_then(|start| start.checked_add(offset))
+                .expect("view offset overflows");
+            view.shape[i] = range.end - range.start;
+        }
+        view.ptr = self.ptr.wrapping_add(offset);
+        view
+    }
+
+    fn permute_axes(self, axes: [usize; N]) -> Self {
+        let mut seen = [false; N];
+        let mut view = self;
+        for (i, &axis) in axes.iter().enumerate() {
+            assert!(
+                axis < N && !seen[axis],
+                "{:?} is not a permutation of the axes",
+                axes
+            );
+            seen[axis] = true;
+            view.shape[i] = self.shape[axis];
+            view.strides[i] = self.strides[axis];
+        }
+        view
+    }
+
+    fn transpose(mut self) -> Self {
+        self.shape.reverse();
+        self.strides.reverse();
+        self
+    }
+
+    fn device(&self) -> (Memory, [usize; N]) {
+        (Memory::Device(self.ptr.as_raw()), self.strides)
+    }
+}
+
+fn to_range<R: RangeBounds<usize>>(range: R, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(
+        start <= end && end <= len,
+        "range {}..{} is out of bounds of an axis of length {}",
+        start,
+        end,
+        len
+    );
+    start..end
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Memory {
+    Device(u64),
+    Host(*mut c_void),
+}
+
+impl Memory {
+    fn host<T>(slice: &[T], shape: &[usize]) -> (Self, Vec<usize>) {
+        let mut strides = vec![1; shape.len()];
+        for i in (1..shape.len()).rev() {
+            strides[i - 1] = strides[i] * shape[i];
+        }
+        (Memory::Host(slice.as_ptr() as *mut c_void), strides)
+    }
+
+    fn add(self, bytes: usize) -> Self {
+        match self {
+            Memory::Device(ptr) => Memory::Device(ptr + bytes as u64),
+            Memory::Host(ptr) => Memory::Host((ptr as *mut u8).wrapping_add(bytes) as *mut c_void),
+        }
+    }
+
+    fn location(self, pitch: usize, height: usize) -> MemcpyLocation {
+        match self {
+            Memory::Device(ptr) => MemcpyLocation::Device {
+                ptr: DevicePointer::from_raw(ptr),
+                pitch,
+                height,
+            },
+            Memory::Host(ptr) => MemcpyLocation::Host { ptr, pitch, height },
+        }
+    }
+}
+
+// Copies between two strided layouts of the same shape, with as few 2D and 3D memcpys as the
+// layouts allow.
+unsafe fn copy_strided<T: DeviceCopy>(
+    shape: &[usize],
+    (src, src_strides): (Memory, &[usize]),
+    (dst, dst_strides): (Memory, &[usize]),
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    let size = size_of::<T>();
+    if shape.contains(&0) || size == 0 {
+        return Ok(());
+    }
+    // (elements, source stride, destination stride) with the strides in bytes, outermost first.
+    let mut dims = (0..shape.len())
+        .filter(|&i| shape[i] > 1)
+        .map(|i| (shape[i], src_strides[i] * size, dst_strides[i] * size))
+        .collect::<Vec<_>>();
+    // contiguous innermost dimensions are copied as one row.
+    let mut width = size;
+    while let Some(&(n, src_stride, dst_stride)) = dims.last() {
+        if src_stride != width || dst_stride != width {
+            break;
+        }
+        width *= n;
+        dims.pop();
+    }
+    // so are neighbouring dimensions which are laid out like a single one.
+    for i in (1..dims.len()).rev() {
+        let (n, src_stride, dst_stride) = dims[i];
+        let (outer, src_outer, dst_outer) = dims[i - 1];
+        if src_outer == src_stride * n && dst_outer == dst_stride * n {
+            dims[i - 1] = (outer * n, src_stride, dst_stride);
+            dims.remove(i);
+        }
+    }
+    copy_dims(&dims, width, src, dst, stream)
+}
+
+// contiguous copies don't go through cuMemcpy2D, which limits the pitch to the maximum pitch of
+// the device.
+unsafe fn memcpy_1d(
+    src: Memory,
+    dst: Memory,
+    bytes: usize,
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    match (src, dst, stream) {
+        (Memory::Host(src), Memory::Device(dst), None) => {
+            cuda::cuMemcpyHtoD_v2(dst, src, bytes).to_result_from("cuMemcpyHtoD_v2")
+        }
+        (Memory::Host(src), Memory::Device(dst), Some(stream)) => {
+            cuda::cuMemcpyHtoDAsync_v2(dst, src, bytes, stream.as_inner())
+                .to_result_from("cuMemcpyHtoDAsync_v2")
+        }
+        (Memory::Device(src), Memory::Host(dst), None) => {
+            cuda::cuMemcpyDtoH_v2(dst, src, bytes).to_result_from("cuMemcpyDtoH_v2")
+        }
+        (Memory::Device(src), Memory::Host(dst), Some(stream)) => {
+            cuda::cuMemcpyDtoHAsync_v2(dst, src, bytes, stream.as_inner())
+                .to_result_from("cuMemcpyDtoHAsync_v2")
+        }
+        (Memory::Device(src), Memory::Device(dst), None) => {
+            cuda::cuMemcpyDtoD_v2(dst, src, bytes).to_result_from("cuMemcpyDtoD_v2")
+        }
+        (Memory::Device(src), Memory::Device(dst), Some(stream)) => {
+            cuda::cuMemcpyDtoDAsync_v2(dst, src, bytes, stream.as_inner())
+                .to_result_from("cuMemcpyDtoDAsync_v2")
+        }
+        (Memory::Host(_), Memory::Host(_), _) => unreachable!("views are always on the device"),
+    }
+}
+
+unsafe fn copy_dims(
+    dims: &[(usize, usize, usize)],
+    width: usize,
+    src: Memory,
+    dst: Memory,
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    // 3D memcpys need the slices to be a whole number of rows apart.
+    let fits_3d = |outer: usize, pitch: usize, rows: usize| {
+        pitch >= width && outer % pitch == 0 && outer / pitch >= rows
+    };
+    match *dims {
+        [] => memcpy_1d(src, dst, width, stream),
+        [(rows, src_pitch, dst_pitch)] if src_pitch >= width && dst_pitch >= width => memcpy_2d(
+            (src.location(src_pitch, rows), [width, rows, 1]),
+            (dst.location(dst_pitch, rows), [width, rows, 1]),
+            stream,
+        ),
+        [(depth, src_outer, dst_outer), (rows, src_pitch, dst_pitch)]
+            if fits_3d(src_outer, src_pitch, rows) && fits_3d(dst_outer, dst_pitch, rows) =>
+        {
+            let extent = [width, rows, depth];
+            memcpy_3d(
+                (src.location(src_pitch, src_outer / src_pitch), extent),
+                (dst.location(dst_pitch, dst_outer / dst_pitch), extent),
+                stream,
+            )
+        }
+        _ => {
+            let (n, src_stride, dst_stride) = dims[0];
+            for i in 0..n {
+                copy_dims(
+                    &dims[1..],
+                    width,
+                    src.add(i * src_stride),
+                    dst.add(i * dst_stride),
+                    stream,
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// the methods which are the same for every view, `$n` is the number of dimensions and `$unique`
+// whether the elements of the view must not overlap.
+macro_rules! impl_view {
+    ($view:ident, $n:literal, $unique:expr $(, $mut:tt)?) => {
+        impl<'a, T: DeviceCopy> $view<'a, T> {
+            /// Creates a view of `slice` with the given shape, laid out in row-major order.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the number of elements in the shape is not the length of the slice.
+            pub fn new(slice: &'a $($mut)? DeviceSlice<T>, shape: [usize; $n]) -> Self {
+                let raw = RawDeviceView::contiguous(slice.as_device_ptr(), shape);
+                assert_eq!(
+                    raw.len(),
+                    slice.len(),
+                    "view with shape {:?} does not have the length of the slice",
+                    shape
+                );
+                Self {
+                    raw,
+                    _marker: PhantomData,
+                }
+            }
+
+            /// Creates a view of `slice` with the given shape and strides, in elements.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the view would reach past the end of the slice, if it has more than
+            /// `usize::MAX` elements, or if it is a mutable view with elements which overlap.
+            pub fn with_strides(
+                slice: &'a $($mut)? DeviceSlice<T>,
+                shape: [usize; $n],
+                strides: [usize; $n],
+            ) -> Self {
+                let raw = RawDeviceView {
+                    ptr: slice.as_device_ptr(),
+                    shape,
+                    strides,
+                };
+                raw.assert_in_bounds(slice.len());
+                if $unique {
+                    raw.assert_unique();
+                }
+                Self {
+                    raw,
+                    _marker: PhantomData,
+                }
+            }
+
+            /// Creates a view from its raw parts.
+            ///
+            /// # Safety
+            ///
+            /// Every element of the view must be in the same allocation, which must outlive the
+            /// view. For mutable views, no two elements may overlap and no other view may be used
+            /// to access them for as long as the view is alive.
+            pub unsafe fn from_raw_parts(
+                ptr: DevicePointer<T>,
+                shape: [usize; $n],
+                strides: [usize; $n],
+            ) -> Self {
+                Self {
+                    raw: RawDeviceView {
+                        ptr,
+                        shape,
+                        strides,
+                    },
+                    _marker: PhantomData,
+                }
+            }
+
+            /// The number of elements in every dimension, outermost first.
+            pub fn shape(&self) -> [usize; $n] {
+                self.raw.shape
+            }
+
+            /// The distance between consecutive elements of every dimension, in elements.
+            pub fn strides(&self) -> [usize; $n] {
+                self.raw.strides
+            }
+
+            /// The number of elements in the view.
+            pub fn len(&self) -> usize {
+                self.raw.len()
+            }
+
+            /// Returns `true` if the view has no elements.
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// A pointer to the first element of the view.
+            pub fn as_device_ptr(&self) -> DevicePointer<T> {
+                self.raw.ptr
+            }
+
+            /// Returns `true` if the view is laid out in row-major order without gaps, like a
+            /// view made with `new`.
+            pub fn is_standard_layout(&self) -> bool {
+                self.raw.is_standard_layout()
+            }
+
+            /// Reverses the order of the axes, which transposes a 2D view.
+            pub fn transpose(self) -> Self {
+                Self {
+                    raw: self.raw.transpose(),
+                    _marker: PhantomData,
+                }
+            }
+
+            /// Reorders the axes, axis `i` of the new view is axis `axes[i]` of this view.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `axes` is not a permutation of the axes.
+            pub fn permute_axes(self, axes: [usize; $n]) -> Self {
+                Self {
+                    raw: self.raw.permute_axes(axes),
+                    _marker: PhantomData,
+                }
+            }
+        }
+
+        impl<'a, T: DeviceCopy + Default + Clone> $view<'a, T> {
+            /// Copies the view to a new vector, in row-major order.
+            pub fn as_host_vec(&self) -> CudaResult<Vec<T>> {
+                let mut vec = vec![T::default(); self.len()];
+                unsafe { copy_to_host(&self.raw, &mut vec, None)? };
+                Ok(vec)
+            }
+        }
+
+        unsafe impl<'a, T: Send + DeviceCopy> Send for $view<'a, T> {}
+        unsafe impl<'a, T: Sync + DeviceCopy> Sync for $view<'a, T> {}
+    };
+}
+
+unsafe fn copy_to_host<T: DeviceCopy, const N: usize>(
+    view: &RawDeviceView<T, N>,
+    host: &mut [T],
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    assert!(
+        view.len() == host.len(),
+        "destination and source slices have different lengths"
+    );
+    let (host, host_strides) = Memory::host(host, &view.shape);
+    let (device, strides) = view.device();
+    copy_strided::<T>(
+        &view.shape,
+        (device, &strides),
+        (host, &host_strides),
+        stream,
+    )
+}
+
+unsafe fn copy_from_host<T: DeviceCopy, const N: usize>(
+    view: &RawDeviceView<T, N>,
+    host: &[T],
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    assert!(
+        view.len() == host.len(),
+        "destination and source slices have different lengths"
+    );
+    let (host, host_strides) = Memory::host(host, &view.shape);
+    let (device, strides) = view.device();
+    copy_strided::<T>(
+        &view.shape,
+        (host, &host_strides),
+        (device, &strides),
+        stream,
+    )
+}
+
+unsafe fn copy_view<T: DeviceCopy, const N: usize>(
+    src: &RawDeviceView<T, N>,
+    dst: &RawDeviceView<T, N>,
+    stream: Option<&Stream>,
+) -> CudaResult<()> {
+    assert_eq!(
+        src.shape, dst.shape,
+        "destination and source views have different shapes"
+    );
+    let (src_device, src_strides) = src.device();
+    let (dst_device, dst_strides) = dst.device();
+    copy_strided::<T>(
+        &src.shape,
+        (src_device, &src_strides),
+        (dst_device, &dst_strides),
+        stream,
+    )
+}
+
+// the methods of views which only read, `$mut_view` is the mutable version of the view.
+macro_rules! impl_read_view {
+    ($view:ident, $mut_view:ident, $n:literal) => {
+        impl<'a, T: DeviceCopy> $view<'a, T> {
+            /// The pointer, shape and strides of the view, for passing it to a kernel which only
+            /// reads from it.
+            pub fn as_raw(&self) -> RawDeviceView<T, $n> {
+                self.raw
+            }
+
+            /// Copies the view to `dest` in row-major order. `dest` must have the same number of
+            /// elements as the view.
+            ///
+            /// # Errors
+            ///
+            /// If a CUDA error occurs, return the error.
+            pub fn copy_to<I: AsMut<[T]> + ?Sized>(&self, dest: &mut I) -> CudaResult<()> {
+                unsafe { copy_to_host(&self.raw, dest.as_mut(), None) }
+            }
+
+            /// Asynchronously copies the view to `dest` in row-major order. `dest` must have the
+            /// same number of elements as the view, and should be page-locked.
+            ///
+            /// # Safety
+            ///
+            /// For why this function is unsafe, see [AsyncCopyDestination](trait.AsyncCopyDestination.html)
+            ///
+            /// # Errors
+            ///
+            /// If a CUDA error occurs, return the error.
+            pub unsafe fn async_copy_to<I: AsMut<[T]> + ?Sized>(
+                &self,
+                dest: &mut I,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                copy_to_host(&self.raw, dest.as_mut(), Some(stream))
+            }
+        }
+
+        impl<'a, T: DeviceCopy> Clone for $view<'a, T> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<'a, T: DeviceCopy> Copy for $view<'a, T> {}
+
+        impl<'a, T: DeviceCopy> From<$mut_view<'a, T>> for $view<'a, T> {
+            fn from(view: $mut_view<'a, T>) -> Self {
+                Self {
+                    raw: view.raw,
+                    _marker: PhantomData,
+                }
+            }
+        }
+
+        impl<'a, T: DeviceCopy> $mut_view<'a, T> {
+            /// Borrows the view as a view which only reads.
+            pub fn as_view(&self) -> $view<'_, T> {
+                $view {
+                    raw: self.raw,
+                    _marker: PhantomData,
+                }
+            }
+
+            /// Reborrows the view, for passing it somewhere without giving it up.
+            pub fn reborrow(&mut self) -> $mut_view<'_, T> {
+                $mut_view {
+                    raw: self.raw,
+                    _marker: PhantomData,
+                }
+            }
+
+            /// The pointer, shape and strides of the view, for passing it to a kernel.
+            pub fn as_raw(&mut self) -> RawDeviceView<T, $n> {
+                self.raw
+            }
+
+            /// Copies the elements of `source`, which must have the same shape, into the view.
+            ///
+            /// # Errors
+            ///
+            /// If a CUDA error occurs, return the error.
+            pub fn copy_from_view(&mut self, source: &$view<T>) -> CudaResult<()> {
+                unsafe { copy_view(&source.raw, &self.raw, None) }
+            }
+
+            /// Asynchronously copies the elements of `source`, which must have the same shape,
+            /// into the view.
+            ///
+            /// # Safety
+            ///
+            /// For why this function is unsafe, see [AsyncCopyDestination](trait.AsyncCopyDestination.html)
+            ///
+            /// # Errors
+            ///
+            /// If a CUDA error occurs, return the error.
+            pub unsafe fn async_copy_from_view(
+                &mut self,
+                source: &$view<T>,
+                stream: &Stream,
+            ) -> CudaResult<()> {
+                copy_view(&source.raw, &self.raw, Some(stream))
+            }
+        }
+
+        impl<'a, T: DeviceCopy> crate::private::Sealed for $mut_view<'a, T> {}
+
+        impl<'a, T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> CopyDestination<I>
+            for $mut_view<'a, T>
+        {
+            fn copy_from(&mut self, val: &I) -> CudaResult<()> {
+                unsafe { copy_from_host(&self.raw, val.as_ref(), None) }
+            }
+
+            fn copy_to(&self, val: &mut I) -> CudaResult<()> {
+                unsafe { copy_to_host(&self.raw, val.as_mut(), None) }
+            }
+        }
+
+        impl<'a, T: DeviceCopy, I: AsRef<[T]> + AsMut<[T]> + ?Sized> AsyncCopyDestination<I>
+            for $mut_view<'a, T>
+        {
+            unsafe fn async_copy_from(&mut self, val: &I, stream: &Stream) -> CudaResult<()> {
+                copy_from_host(&self.raw, val.as_ref(), Some(stream))
+            }
+
+            unsafe fn async_copy_to(&self, val: &mut I, stream: &Stream) -> CudaResult<()> {
+                copy_to_host(&self.raw, val.as_mut(), Some(stream))
+            }
+        }
+    };
+}
+
+/// Two-dimensional view of device memory with arbitrary strides, such as a matrix stored in a
+/// [`DeviceSlice`].
+///
+/// The shape and strides are in elements with the outermost dimension first, so a view of a
+/// row-major matrix has the shape `[rows, columns]` and the strides `[columns, 1]`. Views can be
+/// narrowed down with [`slice`](Self::slice), [`row`](Self::row) and [`column`](Self::column) and
+/// transposed without touching the memory, and passed to kernels with [`as_raw`](Self::as_raw).
+///
+/// Copies to and from host memory treat the host memory as a row-major array of the shape of the
+/// view and use as few `cuMemcpy2D` calls as the strides allow, or a plain memcpy if the view is
+/// contiguous.
+///
+/// # Examples
+///
+/// ```
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// use cust::memory::*;
+///
+/// let buf = DeviceBuffer::from_slice(&[1, 2, 3, 4, 5, 6])?;
+/// let matrix = DeviceView2D::new(&buf, [2, 3]);
+/// assert_eq!(matrix.column(1).as_host_vec()?, [2, 5]);
+/// assert_eq!(matrix.transpose().as_host_vec()?, [1, 4, 2, 5, 3, 6]);
+/// assert_eq!(matrix.slice(.., 1..).as_host_vec()?, [2, 3, 5, 6]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DeviceView2D<'a, T: DeviceCopy> {
+    raw: RawDeviceView<T, 2>,
+    _marker: PhantomData<&'a DeviceSlice<T>>,
+}
+
+/// Mutable version of [`DeviceView2D`], which can be copied into.
+///
+/// The elements of a mutable view cannot overlap.
+///
+/// # Examples
+///
+/// ```
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// use cust::memory::*;
+///
+/// let mut buf = DeviceBuffer::<u32>::zeroed(9)?;
+/// let mut matrix = DeviceViewMut2D::new(&mut buf, [3, 3]);
+/// matrix.slice_mut(1.., 1..).copy_from(&[1, 2, 3, 4])?;
+/// assert_eq!(buf.as_host_vec()?, [0, 0, 0, 0, 1, 2, 0, 3, 4]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct DeviceViewMut2D<'a, T: DeviceCopy> {
+    raw: RawDeviceView<T, 2>,
+    _marker: PhantomData<&'a mut DeviceSlice<T>>,
+}
+
+/// Three-dimensional view of device memory with arbitrary strides, see [`DeviceView2D`].
+///
+/// A view of a row-major volume has the shape `[depth, height, width]`. Copies to and from host
+/// memory use `cuMemcpy3D` where the strides allow it.
+#[derive(Debug)]
+pub struct DeviceView3D<'a, T: DeviceCopy> {
+    raw: RawDeviceView<T, 3>,
+    _marker: PhantomData<&'a DeviceSlice<T>>,
+}
+
+/// Mutable version of [`DeviceView3D`], which can be copied into.
+///
+/// The elements of a mutable view cannot overlap.
+#[derive(Debug)]
+pub struct DeviceViewMut3D<'a, T: DeviceCopy> {
+    raw: RawDeviceView<T, 3>,
+    _marker: PhantomData<&'a mut DeviceSlice<T>>,
+}
+
+impl_view!(DeviceView2D, 2, false);
+impl_view!(DeviceViewMut2D, 2, true, mut);
+impl_view!(DeviceView3D, 3, false);
+impl_view!(DeviceViewMut3D, 3, true, mut);
+impl_read_view!(DeviceView2D, DeviceViewMut2D, 2);
+impl_read_view!(DeviceView3D, DeviceViewMut3D, 3);
+
+impl<'a, T: DeviceCopy> DeviceView2D<'a, T> {
+    /// Returns the part of the view in the given rows and columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a range is out of bounds.
+    pub fn slice<R, C>(&self, rows: R, columns: C) -> DeviceView2D<'a, T>
+    where
+        R: RangeBounds<usize>,
+        C: RangeBounds<usize>,
+    {
+        let [height, width] = self.raw.shape;
+        Self {
+            raw: self
+                .raw
+                .slice([to_range(rows, height), to_range(columns, width)]),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns row `i` as a view with a single row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn row(&self, i: usize) -> DeviceView2D<'a, T> {
+        self.slice(i..=i, ..)
+    }
+
+    /// Returns column `j` as a view with a single column.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `j` is out of bounds.
+    pub fn column(&self, j: usize) -> DeviceView2D<'a, T> {
+        self.slice(.., j..=j)
+    }
+}
+
+impl<'a, T: DeviceCopy> DeviceViewMut2D<'a, T> {
+    /// Returns the part of the view in the given rows and columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a range is out of bounds.
+    pub fn slice_mut<R, C>(&mut self, rows: R, columns: C) -> DeviceViewMut2D<'_, T>
+    where
+        R: RangeBounds<usize>,
+        C: RangeBounds<usize>,
+    {
+        let [height, width] = self.raw.shape;
+        DeviceViewMut2D {
+            raw: self
+                .raw
+                .slice([to_range(rows, height), to_range(columns, width)]),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns row `i` as a view with a single row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn row_mut(&mut self, i: usize) -> DeviceViewMut2D<'_, T> {
+        self.slice_mut(i..=i, ..)
+    }
+
+    /// Returns column `j` as a view with a single column.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `j` is out of bounds.
+    pub fn column_mut(&mut self, j: usize) -> DeviceViewMut2D<'_, T> {
+        self.slice_mut(.., j..=j)
+    }
+}
+
+// the 2D view of index `index` along `axis`.
+fn index_axis<T: DeviceCopy>(
+    raw: &RawDeviceView<T, 3>,
+    axis: usize,
+    index: usize,
+) -> RawDeviceView<T, 2> {
+    assert!(axis < 3, "axis {} is out of bounds of a 3D view", axis);
+    assert!(
+        index < raw.shape[axis],
+        "index {} is out of bounds of an axis of length {}",
+        index,
+        raw.shape[axis]
+    );
+    let others = [(axis + 1) % 3, (axis + 2) % 3];
+    let [a, b] = [others[0].min(others[1]), others[0].max(others[1])];
+    RawDeviceView {
+        ptr: raw.ptr.wrapping_add(index * raw.strides[axis]),
+        shape: [raw.shape[a], raw.shape[b]],
+        strides: [raw.strides[a], raw.strides[b]],
+    }
+}
+
+impl<'a, T: DeviceCopy> DeviceView3D<'a, T> {
+    /// Returns the part of the view in the given ranges of every axis.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a range is out of bounds.
+    pub fn slice<Z, Y, X>(&self, z: Z, y: Y, x: X) -> DeviceView3D<'a, T>
+    where
+        Z: RangeBounds<usize>,
+        Y: RangeBounds<usize>,
+        X: RangeBounds<usize>,
+    {
+        let [depth, height, width] = self.raw.shape;
+        Self {
+            raw: self
+                .raw
+                .slice([to_range(z, depth), to_range(y, height), to_range(x, width)]),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the 2D view of the elements with index `index` along `axis`, made of the other
+    /// two axes in order. Index `i` along axis 0 is the `i`th plane of a row-major volume.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `axis` or `index` is out of bounds.
+    pub fn index_axis(&self, axis: usize, index: usize) -> DeviceView2D<'a, T> {
+        DeviceView2D {
+            raw: index_axis(&self.raw, axis, index),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: DeviceCopy> DeviceViewMut3D<'a, T> {
+    /// Returns the part of the view in the given ranges of every axis.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a range is out of bounds.
+    pub fn slice_mut<Z, Y, X>(&mut self, z: Z, y: Y, x: X) -> DeviceViewMut3D<'_, T>
+    where
+        Z: RangeBounds<usize>,
+        Y: RangeBounds<usize>,
+        X: RangeBounds<usize>,
+    {
+        let [depth, height, width] = self.raw.shape;
+        DeviceViewMut3D {
+            raw: self
+                .raw
+                .slice([to_range(z, depth), to_range(y, height), to_range(x, width)]),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the 2D view of the elements with index `index` along `axis`, see
+    /// [`DeviceView3D::index_axis`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `axis` or `index` is out of bounds.
+    pub fn index_axis_mut(&mut self, axis: usize, index: usize) -> DeviceViewMut2D<'_, T> {
+        DeviceViewMut2D {
+            raw: index_axis(&self.raw, axis, index),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::DeviceBuffer;
+    use std::error::Error;
+
+    fn assert_device_copy<T: DeviceCopy>() {}
+
+    #[cfg(feature = "mock")]
+    fn memcpy_calls() -> Vec<&'static str> {
+        crate::sys::mock::calls()
+            .into_iter()
+            .filter(|call| call.starts_with("cuMemcpy"))
+            .collect()
+    }
+
+    #[test]
+    fn test_view_2d() -> Result<(), Box<dyn Error>> {
+        let _ctx = crate::quick_init()?;
+        let buf = DeviceBuffer::from_slice(&(0..12).collect::<Vec<u32>>())?;
+        let view = DeviceView2D::new(&buf, [3, 4]);
+        assert_eq!(view.strides(), [4, 1]);
+        assert!(view.is_standard_layout());
+        assert_eq!(view.as_host_vec()?, (0..12).collect::<Vec<_>>());
+
+        let t = view.transpose();
+        assert_eq!((t.shape(), t.strides()), ([4, 3], [1, 4]));
+        assert!(!t.is_standard_layout());
+        assert_eq!(t.as_host_vec()?, [0, 4, 8, 1, 5, 9, 2, 6, 10, 3, 7, 11]);
+
+        let sub = view.slice(1..3, 1..=2);
+        assert_eq!(sub.as_host_vec()?, [5, 6, 9, 10]);
+        assert_eq!(sub.transpose().as_host_vec()?, [5, 9, 6, 10]);
+        assert_eq!(view.row(1).as_host_vec()?, [4, 5, 6, 7]);
+        assert!(view.row(1).is_standard_layout());
+        assert_eq!(view.column(2).as_host_vec()?, [2, 6, 10]);
+        assert!(view.slice(1..1, ..).is_empty());
+
+        // every other column.
+        let strided = DeviceView2D::with_strides(&buf, [3, 2], [4, 2]);
+        let mut host = [0; 6];
+        strided.copy_to(&mut host)?;
+        assert_eq!(host, [0, 2, 4, 6, 8, 10]);
+
+        assert_device_copy::<RawDeviceView<u32, 2>>();
+        assert_eq!(
+            view.slice(1.., 2..).as_raw(),
+            RawDeviceView {
+                ptr: buf.as_device_ptr().wrapping_add(6),
+                shape: [2, 2],
+                strides: [4, 1]
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_view_3d() -> Result<(), Box<dyn Error>> {
+        let _ctx = crate::quick_init()?;
+        let buf = DeviceBuffer::from_slice(&(0..24).collect::<Vec<u32>>())?;
+        let view = DeviceView3D::new(&buf, [2, 3, 4]);
+        assert_eq!(view.strides(), [12, 4, 1]);
+        assert_eq!(
+            view.index_axis(0, 1).as_host_vec()?,
+            (12..24).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            view.index_axis(1, 2).as_host_vec()?,
+            [8, 9, 10, 11, 20, 21, 22, 23]
+        );
+        assert_eq!(view.index_axis(2, 3).as_host_vec()?, [3, 7, 11, 15, 19, 23]);
+        assert_eq!(
+            view.slice(.., 1.., 1..3).as_host_vec()?,
+            [5, 6, 9, 10, 17, 18, 21, 22]
+        );
+
+        let permuted = view.permute_axes([2, 0, 1]);
+        assert_eq!(permuted.shape(), [4, 2, 3]);
+        assert_eq!(
+            permuted.index_axis(0, 1).as_host_vec()?,
+            [1, 5, 9, 13, 17, 21]
+        );
+        assert_eq!(view.transpose().as_host_vec()?[..6], [0, 12, 4, 16, 8, 20]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_view_mut() -> Result<(), Box<dyn Error>> {
+        let _ctx = crate::quick_init()?;
+        let mut buf = DeviceBuffer::<u32>::zeroed(16)?;
+        let mut view = DeviceViewMut2D::new(&mut buf, [4, 4]);
+        view.slice_mut(1..3, 1..3).copy_from(&[1, 2, 3, 4])?;
+        view.column_mut(0).copy_from(&[5, 6, 7, 8])?;
+        view.row_mut(3)
+            .slice_mut(.., 1..)
+            .transpose()
+            .copy_from(&[9, 10, 11])?;
+        assert_eq!(
+            view.as_view().as_host_vec()?,
+            [5, 0, 0, 0, 6, 1, 2, 0, 7, 3, 4, 0, 8, 9, 10, 11]
+        );
+
+        let source = DeviceBuffer::from_slice(&(0..16).collect::<Vec<u32>>())?;
+        let source = DeviceView2D::new(&source, [4, 4]);
+        view.copy_from_view(&source.transpose())?;
+        let mut host = [0; 16];
+        view.copy_to(&mut host)?;
+        assert_eq!(host, [0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10, 14, 3, 7, 11, 15]);
+
+        let mut buf = DeviceBuffer::<u32>::zeroed(24)?;
+        let mut volume = DeviceViewMut3D::new(&mut buf, [2, 3, 4]);
+        volume.index_axis_mut(2, 1).copy_from(&[1, 2, 3, 4, 5, 6])?;
+        volume.slice_mut(1.., 2.., ..).copy_from(&[7; 4])?;
+        assert_eq!(
+            DeviceView3D::from(volume).as_host_vec()?,
+            [
+                0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, //
+                0, 4, 0, 0, 0, 5, 0, 0, 7, 7, 7, 7
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_copy_calls() -> Result<(), Box<dyn Error>> {
+        let _ctx = crate::quick_init()?;
+        let buf = DeviceBuffer::from_slice(&(0..60).collect::<Vec<u32>>())?;
+        let mut host = vec![0; 60];
+
+        crate::sys::mock::clear_calls();
+        let volume = DeviceView3D::new(&buf, [3, 4, 5]);
+        volume.copy_to(&mut host)?;
+        assert_eq!(memcpy_calls(), ["cuMemcpyDtoH_v2"]);
+
+        crate::sys::mock::clear_calls();
+        let mut other = DeviceBuffer::<u32>::zeroed(60)?;
+        let mut other = DeviceViewMut3D::new(&mut other, [3, 4, 5]);
+        other.copy_from(&host)?;
+        other.copy_from_view(&volume)?;
+        assert_eq!(memcpy_calls(), ["cuMemcpyHtoD_v2", "cuMemcpyDtoD_v2"]);
+
+        crate::sys::mock::clear_calls();
+        let mut host = vec![0; 3 * 3 * 2];
+        volume.slice(.., 1.., ..2).copy_to(&mut host)?;
+        assert_eq!(memcpy_calls(), ["cuMemcpy3D_v2"]);
+        assert_eq!(
+            host,
+            [5, 6, 10, 11, 15, 16, 25, 26, 30, 31, 35, 36, 45, 46, 50, 51, 55, 56]
+        );
+
+        // the columns of a transposed matrix are copied one by one.
+        crate::sys::mock::clear_calls();
+        let mut host = vec![0; 20];
+        DeviceView2D::new(&buf.index(..20), [4, 5])
+            .transpose()
+            .copy_to(&mut host)?;
+        assert_eq!(memcpy_calls(), ["cuMemcpy2D_v2"; 5]);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_out_of_bounds() {
+        let _ctx = crate::quick_init().unwrap();
+        let buf = DeviceBuffer::<u32>::zeroed(12).unwrap();
+        let _ = DeviceView2D::with_strides(&buf, [3, 4], [5, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_many_elements() {
+        let _ctx = crate::quick_init().unwrap();
+        let buf = DeviceBuffer::<u32>::zeroed(1).unwrap();
+        let _ = DeviceView2D::with_strides(&buf, [1 << 32, 1 << 32], [0, 0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_overlapping_mut() {
+        let _ctx = crate::quick_init().unwrap();
+        let mut buf = DeviceBuffer::<u32>::zeroed(12).unwrap();
+        let _ = DeviceViewMut2D::with_strides(&mut buf, [3, 4], [2, 1]);
+    }
+}
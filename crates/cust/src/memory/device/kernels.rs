@@ -0,0 +1,814 @@
+//! `DeviceSlice` operations which have no driver API equivalent and are implemented with small
+//! kernels embedded in cust instead.
+//!
+//! The PTX is generated from `resources/kernels.ll` and is loaded into a context the first time one
+//! of the kernels is launched in it. All operations enqueue a single kernel launch per call (except
+//! for filling with values bigger than 256 bytes), the synchronous versions run on the default
+//! stream and wait for it to finish.
+
+use crate::error::{CudaError, CudaResult, ToResult};
+use crate::memory::device::DeviceSlice;
+use crate::memory::DeviceCopy;
+use crate::stream::Stream;
+use crate::sys::{self as cuda, CUcontext, CUdeviceptr, CUfunction, CUmodule, CUstream};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+static PTX: &str = include_str!("../../../resources/kernels.ptx");
+
+const BLOCK_SIZE: usize = 256;
+// every kernel uses a grid-stride loop, launching more blocks than a device can keep resident
+// only adds scheduling overhead.
+const MAX_BLOCKS: usize = 1024;
+// the fill kernels take the value by value, bigger values are filled one part at a time.
+const MAX_FILL_SIZE: usize = 256;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Element types which [`DeviceSlice::iota`] can generate sequences of.
+pub trait Iota: DeviceCopy + private::Sealed {
+    #[doc(hidden)]
+    const KERNEL: &'static str;
+}
+
+macro_rules! impl_iota {
+    ($($ty:ty => $kernel:literal),* $(,)?) => {
+        $(
+            impl private::Sealed for $ty {}
+            impl Iota for $ty {
+                const KERNEL: &'static str = $kernel;
+            }
+        )*
+    };
+}
+
+// signed integers wrap the same way as unsigned ones, so they share kernels.
+impl_iota! {
+    u32 => "cust_iota_u32",
+    i32 => "cust_iota_u32",
+    u64 => "cust_iota_u64",
+    i64 => "cust_iota_u64",
+    f32 => "cust_iota_f32",
+    f64 => "cust_iota_f64",
+}
+
+// The kernel module loaded into each context, keyed by context handle. The modules are never unloaded,
+// they are destroyed along with their context, which removes them from here. That includes primary
+// contexts destroyed by releasing or resetting them, so a cached module is always still loaded.
+static MODULES: Lazy<Mutex<HashMap<usize, usize>>> = Lazy::new(Default::default);
+
+fn modules() -> MutexGuard<'static, HashMap<usize, usize>> {
+    MODULES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Forgets the kernel module of a context which was destroyed.
+pub(crate) fn context_destroyed(context: CUcontext) {
+    modules().remove(&(context as usize));
+}
+
+fn function(name: &str) -> CudaResult<CUfunction> {
+    let name = CString::new(name).expect("kernel names do not contain nul bytes");
+    let mut ctx = ptr::null_mut();
+    unsafe {
+        cuda::cuCtxGetCurrent(&mut ctx).to_result_from("cuCtxGetCurrent")?;
+    }
+    if ctx.is_null() {
+        return Err(CudaError::InvalidContext);
+    }
+
+    let mut modules = modules();
+    let module = match modules.get(&(ctx as usize)) {
+        Some(&module) => module as CUmodule,
+        None => {
+            let image = CString::new(PTX).expect("PTX does not contain nul bytes");
+            let mut module = ptr::null_mut();
+            unsafe {
+                cuda::cuModuleLoadData(&mut module, image.as_ptr() as *const c_void)
+                    .to_result_from("cuModuleLoadData")?;
+            }
+            modules.insert(ctx as usize, module as usize);
+            module
+        }
+    };
+
+    let mut func = ptr::null_mut();
+    unsafe {
+        cuda::cuModuleGetFunction(&mut func, module, name.as_ptr())
+            .to_result_from("cuModuleGetFunction")?;
+    }
+    Ok(func)
+}
+
+fn arg<T>(value: &mut T) -> *mut c_void {
+    value as *mut T as *mut c_void
+}
+
+/// Launches the kernel `name` with enough threads for `work` items on `stream`.
+unsafe fn launch(
+    name: &str,
+    work: usize,
+    stream: CUstream,
+    args: &mut [*mut c_void],
+) -> CudaResult<()> {
+    let func = function(name)?;
+    let blocks = ((work + BLOCK_SIZE - 1) / BLOCK_SIZE).clamp(1, MAX_BLOCKS);
+    cuda::cuLaunchKernel(
+        func,
+        blocks as u32,
+        1,
+        1,
+        BLOCK_SIZE as u32,
+        1,
+        1,
+        0,
+        stream,
+        args.as_mut_ptr(),
+        ptr::null_mut(),
+    )
+    .to_result_from("cuLaunchKernel")
+}
+
+fn synchronize_default_stream() -> CudaResult<()> {
+    unsafe { cuda::cuStreamSynchronize(ptr::null_mut()).to_result_from("cuStreamSynchronize") }
+}
+
+/// Returns the unit (in bytes) elements of `size` bytes at `ptrs` are copied in, 4 if everything is
+/// word aligned, otherwise 1.
+fn unit(size: usize, ptrs: &[CUdeviceptr]) -> usize {
+    if size % 4 == 0 && ptrs.iter().all(|ptr| ptr % 4 == 0) {
+        4
+    } else {
+        1
+    }
+}
+
+fn kernel(base: &'static str, unit: usize) -> String {
+    format!("{}_{}", base, if unit == 4 { "b32" } else { "b8" })
+}
+
+impl<T: DeviceCopy> DeviceSlice<T> {
+    /// Sets every element of this slice to `value`.
+    ///
+    /// Unlike `set_8` and friends this works for values of any size, values of 1, 2 or 4 bytes are
+    /// set with a memset, bigger values with a kernel.
+    pub fn fill(&mut self, value: T) -> CudaResult<()> {
+        unsafe {
+            self.fill_on(value, ptr::null_mut())?;
+        }
+        synchronize_default_stream()
+    }
+
+    /// Sets every element of this slice to `value` asynchronously.
+    ///
+    /// # Safety
+    ///
+    /// This operation is async so it does not complete immediately, it uses stream-ordering semantics.
+    /// Therefore you should not read/write from/to the memory range until the operation is complete.
+    pub unsafe fn fill_async(&mut self, value: T, stream: &Stream) -> CudaResult<()> {
+        self.fill_on(value, stream.as_inner())
+    }
+
+    unsafe fn fill_on(&mut self, value: T, stream: CUstream) -> CudaResult<()> {
+        let size = size_of::<T>();
+        if self.is_empty() || size == 0 {
+            return Ok(());
+        }
+        let ptr = self.as_device_ptr().as_raw();
+        let bytes = &value as *const T as *const u8;
+        let mut part = [0u32; MAX_FILL_SIZE / 4];
+        let part_bytes = part.as_mut_ptr() as *mut u8;
+
+        if size <= 4 {
+            ptr::copy_nonoverlapping(bytes, part_bytes, size);
+            match size {
+                1 => {
+                    return cuda::cuMemsetD8Async(ptr, *part_bytes, self.len(), stream)
+                        .to_result_from("cuMemsetD8Async")
+                }
+                2 if ptr % 2 == 0 => {
+                    return cuda::cuMemsetD16Async(
+                        ptr,
+                        *(part_bytes as *const u16),
+                        self.len(),
+                        stream,
+                    )
+                    .to_result_from("cuMemsetD16Async")
+                }
+                4 if ptr % 4 == 0 => {
+                    return cuda::cuMemsetD32Async(ptr, part[0], self.len(), stream)
+                        .to_result_from("cuMemsetD32Async")
+                }
+                _ => {}
+            }
+        }
+
+        let unit = unit(size, &[ptr]);
+        let name = kernel("cust_fill", unit);
+        for offset in (0..size).step_by(MAX_FILL_SIZE) {
+            let width = (size - offset).min(MAX_FILL_SIZE);
+            ptr::copy_nonoverlapping(bytes.add(offset), part_bytes, width);
+
+            let mut dst = ptr + offset as CUdeviceptr;
+            let mut len = (self.len() * width / unit) as u64;
+            let mut width = (width / unit) as u64;
+            let mut pitch = (size / unit) as u64;
+            launch(
+                &name,
+                len as usize,
+                stream,
+                &mut [
+                    arg(&mut dst),
+                    arg(&mut len),
+                    arg(&mut part),
+                    arg(&mut width),
+                    arg(&mut pitch),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Copies `count` elements from every `src_stride`th element of `src` to every `dst_stride`th
+    /// element of this slice, `self[i * dst_stride] = src[i * src_stride]`.
+    ///
+    /// The result is unspecified if `src` overlaps with this slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either strided range does not fit in its slice.
+    #[track_caller]
+    pub fn copy_strided_from(
+        &mut self,
+        dst_stride: usize,
+        src: &DeviceSlice<T>,
+        src_stride: usize,
+        count: usize,
+    ) -> CudaResult<()> {
+        unsafe {
+            self.copy_strided_on(dst_stride, src, src_stride, count, ptr::null_mut())?;
+        }
+        synchronize_default_stream()
+    }
+
+    /// Copies `count` elements from every `src_stride`th element of `src` to every `dst_stride`th
+    /// element of this slice asynchronously, `self[i * dst_stride] = src[i * src_stride]`.
+    ///
+    /// The result is unspecified if `src` overlaps with this slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either strided range does not fit in its slice.
+    ///
+    /// # Safety
+    ///
+    /// This operation is async so it does not complete immediately, it uses stream-ordering semantics.
+    /// Therefore you should not read/write from/to either memory range until the operation is complete.
+    #[track_caller]
+    pub unsafe fn copy_strided_from_async(
+        &mut self,
+        dst_stride: usize,
+        src: &DeviceSlice<T>,
+        src_stride: usize,
+        count: usize,
+        stream: &Stream,
+    ) -> CudaResult<()> {
+        self.copy_strided_on(dst_stride, src, src_stride, count, stream.as_inner())
+    }
+
+    #[track_caller]
+    unsafe fn copy_strided_on(
+        &mut self,
+        dst_stride: usize,
+        src: &DeviceSlice<T>,
+        src_stride: usize,
+        count: usize,
+        stream: CUstream,
+    ) -> CudaResult<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        let fits = |stride: usize, len: usize| {
+            (count - 1)
+                .checked_mul(stride)
+                .map_or(false, |last| last < len)
+        };
+        assert!(
+            fits(dst_stride, self.len()),
+            "strided destination range is out of bounds"
+        );
+        assert!(
+            fits(src_stride, src.len()),
+            "strided source range is out of bounds"
+        );
+        let size = size_of::<T>();
+        if size == 0 {
+            return Ok(());
+        }
+        // a single element never steps by the strides, so they are not bounded by the lengths and
+        // could overflow below. Otherwise `stride * size` is less than the size of the slice.
+        let (dst_stride, src_stride) = if count == 1 {
+            (0, 0)
+        } else {
+            (dst_stride, src_stride)
+        };
+
+        let mut dst = self.as_device_ptr().as_raw();
+        let mut src = src.as_device_ptr().as_raw();
+        let unit = unit(size, &[dst, src]);
+        let mut dst_pitch = (dst_stride * size / unit) as u64;
+        let mut src_pitch = (src_stride * size / unit) as u64;
+        let mut count = count as u64;
+        let mut size = (size / unit) as u64;
+        launch(
+            &kernel("cust_copy_strided", unit),
+            count as usize,
+            stream,
+            &mut [
+                arg(&mut dst),
+                arg(&mut dst_pitch),
+                arg(&mut src),
+                arg(&mut src_pitch),
+                arg(&mut count),
+                arg(&mut size),
+            ],
+        )
+    }
+
+    /// Sets every element of this slice to the element of `src` at the same position in `indices`,
+    /// `self[i] = src[indices[i]]`. Elements with an index outside of `src` are left unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `indices` is not the same length as this slice.
+    #[track_caller]
+    pub fn gather_from(
+        &mut self,
+        src: &DeviceSlice<T>,
+        indices: &DeviceSlice<u32>,
+    ) -> CudaResult<()> {
+        unsafe {
+            self.gather_on(src, indices, ptr::null_mut())?;
+        }
+        synchronize_default_stream()
+    }
+
+    /// Sets every element of this slice to the element of `src` at the same position in `indices`
+    /// asynchronously, `self[i] = src[indices[i]]`. Elements with an index outside of `src` are left
+    /// unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `indices` is not the same length as this slice.
+    ///
+    /// # Safety
+    ///
+    /// This operation is async so it does not complete immediately, it uses stream-ordering semantics.
+    /// Therefore you should not read/write from/to the memory ranges until the operation is complete.
+    #[track_caller]
+    pub unsafe fn gather_from_async(
+        &mut self,
+        src: &DeviceSlice<T>,
+        indices: &DeviceSlice<u32>,
+        stream: &Stream,
+    ) -> CudaResult<()> {
+        self.gather_on(src, indices, stream.as_inner())
+    }
+
+    #[track_caller]
+    unsafe fn gather_on(
+        &mut self,
+        src: &DeviceSlice<T>,
+        indices: &DeviceSlice<u32>,
+        stream: CUstream,
+    ) -> CudaResult<()> {
+        assert_eq!(
+            self.len(),
+            indices.len(),
+            "destination and indices slices have different lengths"
+        );
+        let size = size_of::<T>();
+        if self.is_empty() || size == 0 {
+            return Ok(());
+        }
+
+        let mut dst = self.as_device_ptr().as_raw();
+        let mut src_len = src.len() as u64;
+        let mut src = src.as_device_ptr().as_raw();
+        let mut indices = indices.as_device_ptr().as_raw();
+        let unit = unit(size, &[dst, src]);
+        let mut count = self.len() as u64;
+        let mut size = (size / unit) as u64;
+        launch(
+            &kernel("cust_gather", unit),
+            count as usize,
+            stream,
+            &mut [
+                arg(&mut dst),
+                arg(&mut src),
+                arg(&mut src_len),
+                arg(&mut indices),
+                arg(&mut count),
+                arg(&mut size),
+            ],
+        )
+    }
+
+    /// Copies every element of `src` to the element of this slice at the same position in
+    /// `indices`, `self[indices[i]] = src[i]`. Elements with an index outside of this slice are
+    /// skipped. If an index appears more than once, which of the elements is written is unspecified.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `indices` is not the same length as `src`.
+    #[track_caller]
+    pub fn scatter_from(
+        &mut self,
+        src: &DeviceSlice<T>,
+        indices: &DeviceSlice<u32>,
+    ) -> CudaResult<()> {
+        unsafe {
+            self.scatter_on(src, indices, ptr::null_mut())?;
+        }
+        synchronize_default_stream()
+    }
+
+    /// Copies every element of `src` to the element of this slice at the same position in
+    /// `indices` asynchronously, `self[indices[i]] = src[i]`. Elements with an index outside of
+    /// this slice are skipped. If an index appears more than once, which of the elements is written
+    /// is unspecified.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `indices` is not the same length as `src`.
+    ///
+    /// # Safety
+    ///
+    /// This operation is async so it does not complete immediately, it uses stream-ordering semantics.
+    /// Therefore you should not read/write from/to the memory ranges until the operation is complete.
+    #[track_caller]
+    pub unsafe fn scatter_from_async(
+        &mut self,
+        src: &DeviceSlice<T>,
+        indices: &DeviceSlice<u32>,
+        stream: &Stream,
+    ) -> CudaResult<()> {
+        self.scatter_on(src, indices, stream.as_inner())
+    }
+
+    #[track_caller]
+    unsafe fn scatter_on(
+        &mut self,
+        src: &DeviceSlice<T>,
+        indices: &DeviceSlice<u32>,
+        stream: CUstream,
+    ) -> CudaResult<()> {
+        assert_eq!(
+            src.len(),
+            indices.len(),
+            "source and indices slices have different lengths"
+        );
+        let size = size_of::<T>();
+        if src.is_empty() || size == 0 {
+            return Ok(());
+        }
+
+        let mut dst = self.as_device_ptr().as_raw();
+        let mut dst_len = self.len() as u64;
+        let mut src_ptr = src.as_device_ptr().as_raw();
+        let mut indices = indices.as_device_ptr().as_raw();
+        let unit = unit(size, &[dst, src_ptr]);
+        let mut count = src.len() as u64;
+        let mut size = (size / unit) as u64;
+        launch(
+            &kernel("cust_scatter", unit),
+            count as usize,
+            stream,
+            &mut [
+                arg(&mut dst),
+                arg(&mut dst_len),
+                arg(&mut src_ptr),
+                arg(&mut indices),
+                arg(&mut count),
+                arg(&mut size),
+            ],
+        )
+    }
+}
+
+impl<T: Iota> DeviceSlice<T> {
+    /// Sets the elements of this slice to the sequence starting at `start` and increasing by
+    /// `step`, `self[i] = start + i * step`. Integer sequences wrap around on overflow.
+    pub fn iota(&mut self, start: T, step: T) -> CudaResult<()> {
+        unsafe {
+            self.iota_on(start, step, ptr::null_mut())?;
+        }
+        synchronize_default_stream()
+    }
+
+    /// Sets the elements of this slice to the sequence starting at `start` and increasing by
+    /// `step` asynchronously, `self[i] = start + i * step`. Integer sequences wrap around on overflow.
+    ///
+    /// # Safety
+    ///
+    /// This operation is async so it does not complete immediately, it uses stream-ordering semantics.
+    /// Therefore you should not read/write from/to the memory range until the operation is complete.
+    pub unsafe fn iota_async(&mut self, start: T, step: T, stream: &Stream) -> CudaResult<()> {
+        self.iota_on(start, step, stream.as_inner())
+    }
+
+    unsafe fn iota_on(&mut self, mut start: T, mut step: T, stream: CUstream) -> CudaResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut dst = self.as_device_ptr().as_raw();
+        let mut len = self.len() as u64;
+        launch(
+            T::KERNEL,
+            self.len(),
+            stream,
+            &mut [
+                arg(&mut dst),
+                arg(&mut len),
+                arg(&mut start),
+                arg(&mut step),
+            ],
+        )
+    }
+}
+
+#[cfg(feature = "impl_half")]
+impl DeviceSlice<half::f16> {
+    /// Converts every element of `src` to a half and stores it in this slice, rounding to the
+    /// nearest value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` is not the same length as this slice.
+    #[track_caller]
+    #[cfg_attr(docsrs, doc(cfg(feature = "impl_half")))]
+    pub fn convert_from_f32(&mut self, src: &DeviceSlice<f32>) -> CudaResult<()> {
+        unsafe {
+            convert("cust_convert_f32_f16", self, src, ptr::null_mut())?;
+        }
+        synchronize_default_stream()
+    }
+
+    /// Converts every element of `src` to a half and stores it in this slice asynchronously,
+    /// rounding to the nearest value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` is not the same length as this slice.
+    ///
+    /// # Safety
+    ///
+    /// This operation is async so it does not complete immediately, it uses stream-ordering semantics.
+    /// Therefore you should not read/write from/to the memory ranges until the operation is complete.
+    #[track_caller]
+    #[cfg_attr(docsrs, doc(cfg(feature = "impl_half")))]
+    pub unsafe fn convert_from_f32_async(
+        &mut self,
+        src: &DeviceSlice<f32>,
+        stream: &Stream,
+    ) -> CudaResult<()> {
+        convert("cust_convert_f32_f16", self, src, stream.as_inner())
+    }
+}
+
+#[cfg(feature = "impl_half")]
+impl DeviceSlice<f32> {
+    /// Converts every half in `src` to a float and stores it in this slice. The conversion is exact.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` is not the same length as this slice.
+    #[track_caller]
+    #[cfg_attr(docsrs, doc(cfg(feature = "impl_half")))]
+    pub fn convert_from_f16(&mut self, src: &DeviceSlice<half::f16>) -> CudaResult<()> {
+        unsafe {
+            convert("cust_convert_f16_f32", self, src, ptr::null_mut())?;
+        }
+        synchronize_default_stream()
+    }
+
+    /// Converts every half in `src` to a float and stores it in this slice asynchronously. The
+    /// conversion is exact.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` is not the same length as this slice.
+    ///
+    /// # Safety
+    ///
+    /// This operation is async so it does not complete immediately, it uses stream-ordering semantics.
+    /// Therefore you should not read/write from/to the memory ranges until the operation is complete.
+    #[track_caller]
+    #[cfg_attr(docsrs, doc(cfg(feature = "impl_half")))]
+    pub unsafe fn convert_from_f16_async(
+        &mut self,
+        src: &DeviceSlice<half::f16>,
+        stream: &Stream,
+    ) -> CudaResult<()> {
+        convert("cust_convert_f16_f32", self, src, stream.as_inner())
+    }
+}
+
+#[cfg(feature = "impl_half")]
+#[track_caller]
+unsafe fn convert<D: DeviceCopy, S: DeviceCopy>(
+    kernel: &str,
+    dst: &mut DeviceSlice<D>,
+    src: &DeviceSlice<S>,
+    stream: CUstream,
+) -> CudaResult<()> {
+    assert_eq!(
+        dst.len(),
+        src.len(),
+        "destination and source slices have different lengths"
+    );
+    if dst.is_empty() {
+        return Ok(());
+    }
+    let mut dst_ptr = dst.as_device_ptr().as_raw();
+    let mut src_ptr = src.as_device_ptr().as_raw();
+    let mut len = dst.len() as u64;
+    launch(
+        kernel,
+        dst.len(),
+        stream,
+        &mut [arg(&mut dst_ptr), arg(&mut src_ptr), arg(&mut len)],
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::DeviceBuffer;
+    use crate::stream::StreamFlags;
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    #[repr(C)]
+    struct Big {
+        values: [u64; 32],
+        tag: u8,
+    }
+    unsafe impl DeviceCopy for Big {}
+
+    #[test]
+    fn test_fill_memset_sizes() {
+        let _context = crate::quick_init().unwrap();
+        let mut bytes = DeviceBuffer::from_slice(&[0u8; 7]).unwrap();
+        bytes.fill(3).unwrap();
+        assert_eq!(bytes.as_host_vec().unwrap(), [3; 7]);
+
+        let mut halves = DeviceBuffer::from_slice(&[0i16; 5]).unwrap();
+        halves.fill(-2).unwrap();
+        assert_eq!(halves.as_host_vec().unwrap(), [-2; 5]);
+
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+        let mut floats = DeviceBuffer::from_slice(&[0.0f32; 9]).unwrap();
+        unsafe {
+            floats.fill_async(1.5, &stream).unwrap();
+        }
+        stream.synchronize().unwrap();
+        assert_eq!(floats.as_host_vec().unwrap(), [1.5; 9]);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "kernels do not run on the mock driver")]
+    fn test_fill() {
+        let _context = crate::quick_init().unwrap();
+        let mut vectors = DeviceBuffer::from_slice(&[[0.0f32; 3]; 100]).unwrap();
+        vectors.fill([1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(vectors.as_host_vec().unwrap(), vec![[1.0, 2.0, 3.0]; 100]);
+
+        // at an odd address, so it is filled byte by byte.
+        let bytes = DeviceBuffer::from_slice(&[0u8; 23]).unwrap();
+        let mut pairs = unsafe {
+            DeviceSlice::from_raw_parts_mut(bytes.as_device_ptr().add(1).cast::<u16>(), 11)
+        };
+        pairs.fill(0x0102).unwrap();
+        let host = bytes.as_host_vec().unwrap();
+        assert_eq!(host[0], 0);
+        assert!(host[1..]
+            .chunks(2)
+            .all(|pair| u16::from_ne_bytes([pair[0], pair[1]]) == 0x0102));
+
+        let big = Big {
+            values: [7; 32],
+            tag: 9,
+        };
+        let mut bigs = DeviceBuffer::from_slice(&[Big::default(); 10]).unwrap();
+        bigs.fill(big).unwrap();
+        assert_eq!(bigs.as_host_vec().unwrap(), vec![big; 10]);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "kernels do not run on the mock driver")]
+    fn test_iota() {
+        let _context = crate::quick_init().unwrap();
+        let mut ints = DeviceBuffer::from_slice(&[0i32; 1000]).unwrap();
+        ints.iota(-10, 3).unwrap();
+        let expected = (0..1000).map(|i| -10 + i * 3).collect::<Vec<_>>();
+        assert_eq!(ints.as_host_vec().unwrap(), expected);
+
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+        let mut floats = DeviceBuffer::from_slice(&[0.0f64; 4]).unwrap();
+        unsafe {
+            floats.iota_async(0.5, 0.25, &stream).unwrap();
+        }
+        stream.synchronize().unwrap();
+        assert_eq!(floats.as_host_vec().unwrap(), [0.5, 0.75, 1.0, 1.25]);
+    }
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "kernels do not run on the mock driver")]
+    fn test_strided_gather_scatter() {
+        let _context = crate::quick_init().unwrap();
+        let src = DeviceBuffer::from_slice(&[0u64, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+        let mut dst = DeviceBuffer::from_slice(&[0u64; 4]).unwrap();
+        dst.copy_strided_from(1, &src, 2, 4).unwrap();
+        assert_eq!(dst.as_host_vec().unwrap(), [0, 2, 4, 6]);
+
+        let indices = DeviceBuffer::from_slice(&[7u32, 100, 0, 3]).unwrap();
+        let mut dst = DeviceBuffer::from_slice(&[9u64; 4]).unwrap();
+        dst.gather_from(&src, &indices).unwrap();
+        assert_eq!(dst.as_host_vec().unwrap(), [7, 9, 0, 3]);
+
+        let values = DeviceBuffer::from_slice(&[10u64, 11, 12, 13]).unwrap();
+        let mut dst = DeviceBuffer::from_slice(&[0u64; 8]).unwrap();
+        dst.scatter_from(&values, &indices).unwrap();
+        assert_eq!(dst.as_host_vec().unwrap(), [12, 0, 0, 13, 0, 0, 0, 10]);
+    }
+
+    #[test]
+    fn test_copy_strided_single_element() {
+        let _context = crate::quick_init().unwrap();
+        let src = DeviceBuffer::from_slice(&[7u64]).unwrap();
+        let mut dst = DeviceBuffer::from_slice(&[0u64]).unwrap();
+        dst.copy_strided_from(usize::MAX, &src, usize::MAX, 1)
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "strided source range is out of bounds")]
+    fn test_copy_strided_out_of_bounds() {
+        let _context = crate::quick_init().unwrap();
+        let src = DeviceBuffer::from_slice(&[0u32; 8]).unwrap();
+        let mut dst = DeviceBuffer::from_slice(&[0u32; 4]).unwrap();
+        let _ = dst.copy_strided_from(1, &src, 3, 4);
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_module_loaded_once_per_context() {
+        use crate::sys::mock;
+
+        let context = crate::quick_init().unwrap();
+        let mut buf = DeviceBuffer::from_slice(&[[0u8; 3]; 16]).unwrap();
+        mock::clear_calls();
+        buf.fill([1, 2, 3]).unwrap();
+        buf.fill([4, 5, 6]).unwrap();
+        let loads = || {
+            mock::calls()
+                .into_iter()
+                .filter(|&call| call == "cuModuleLoadData")
+                .count()
+        };
+        assert_eq!(loads(), 1);
+
+        drop(buf);
+        drop(context);
+        let _context = crate::quick_init().unwrap();
+        let mut buf = DeviceBuffer::from_slice(&[[0u8; 3]; 16]).unwrap();
+        buf.fill([1, 2, 3]).unwrap();
+        assert_eq!(loads(), 2);
+    }
+
+    #[test]
+    fn test_context_destroyed() {
+        use crate::context::legacy::{Context, ContextFlags};
+        use crate::context::ContextHandle;
+        use crate::device::Device;
+
+        crate::init(crate::CudaFlags::empty()).unwrap();
+        let context =
+            Context::create_and_push(ContextFlags::empty(), Device::get_device(0).unwrap())
+                .unwrap();
+        let key = context.get_inner() as usize;
+        let mut buf = DeviceBuffer::from_slice(&[[0u8; 3]; 16]).unwrap();
+        buf.fill([1, 2, 3]).unwrap();
+        assert!(modules().contains_key(&key));
+
+        drop(buf);
+        drop(context);
+        assert!(!modules().contains_key(&key));
+    }
+}
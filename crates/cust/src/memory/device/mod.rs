@@ -3,13 +3,21 @@ use crate::stream::Stream;
 
 mod device_box;
 mod device_buffer;
+mod device_pitched;
 mod device_slice;
 mod device_variable;
+mod device_vec;
+mod device_view;
+pub(crate) mod kernels;
 
 pub use self::device_box::*;
 pub use self::device_buffer::*;
+pub use self::device_pitched::*;
 pub use self::device_slice::*;
 pub use self::device_variable::*;
+pub use self::device_vec::*;
+pub use self::device_view::*;
+pub use self::kernels::Iota;
 
 /// Sealed trait implemented by types which can be the source or destination when copying data
 /// to/from the device or from one device allocation to another.
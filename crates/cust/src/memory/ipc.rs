@@ -0,0 +1,213 @@
+use crate::error::{CudaResult, DropResult, ToResult};
+use crate::memory::device::{DeviceBuffer, DeviceSlice};
+use crate::memory::{DeviceCopy, DevicePointer};
+use crate::sys as cuda;
+use std::convert::TryInto;
+use std::mem::{self, size_of};
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_char;
+
+/// A handle to a [`DeviceBuffer`] which can be sent to another process and opened with
+/// [`IpcMemory::open`] to access the same device memory from that process.
+///
+/// The handle can be converted to and from bytes to send it over something like a Unix socket or
+/// a pipe. The handle only stays valid as long as the buffer it was made from is alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpcMemHandle {
+    handle: [u8; 64],
+    size: u64,
+}
+
+impl IpcMemHandle {
+    /// The size in bytes of a handle converted to bytes with [`IpcMemHandle::to_bytes`].
+    pub const SIZE: usize = 72;
+
+    /// Converts this handle to bytes which can be sent to another process.
+    pub fn to_bytes(&self) -> [u8; IpcMemHandle::SIZE] {
+        let mut bytes = [0; IpcMemHandle::SIZE];
+        bytes[..64].copy_from_slice(&self.handle);
+        bytes[64..].copy_from_slice(&self.size.to_le_bytes());
+        bytes
+    }
+
+    /// Converts bytes made by [`IpcMemHandle::to_bytes`] back into a handle.
+    pub fn from_bytes(bytes: [u8; IpcMemHandle::SIZE]) -> Self {
+        let mut handle = [0; 64];
+        handle.copy_from_slice(&bytes[..64]);
+        let size = u64::from_le_bytes(bytes[64..].try_into().unwrap());
+        Self { handle, size }
+    }
+
+    /// The size in bytes of the memory this handle refers to.
+    pub fn size_in_bytes(&self) -> usize {
+        self.size as usize
+    }
+
+    fn to_raw(self) -> cuda::CUipcMemHandle {
+        cuda::CUipcMemHandle {
+            reserved: self.handle.map(|b| b as c_char),
+        }
+    }
+}
+
+impl<T: DeviceCopy> DeviceBuffer<T> {
+    /// Creates a handle to this buffer which can be used by another process to access the
+    /// memory of the buffer with [`IpcMemory::open`].
+    ///
+    /// Only buffers allocated with [`DeviceBuffer::uninitialized`] (or anything built on it
+    /// such as [`DeviceBuffer::from_slice`]) can be shared, memory allocated from a stream-ordered
+    /// pool cannot.
+    ///
+    /// # Errors
+    ///
+    /// If the buffer is empty, or the memory cannot be shared, returns the error from CUDA.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let _ctx = quick_init()?;
+    /// # if cfg!(feature = "mock") { return Ok(()); }
+    /// use cust::memory::{DeviceBuffer, IpcMemHandle};
+    ///
+    /// let frames = DeviceBuffer::from_slice(&[0u8; 1024])?;
+    /// let bytes = frames.ipc_handle()?.to_bytes();
+    /// // send `bytes` to the other process ...
+    /// # let _ = IpcMemHandle::from_bytes(bytes);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ipc_handle(&self) -> CudaResult<IpcMemHandle> {
+        unsafe {
+            let mut raw = cuda::CUipcMemHandle { reserved: [0; 64] };
+            cuda::cuIpcGetMemHandle(&mut raw as *mut _, self.as_device_ptr().as_raw())
+                .to_result_from("cuIpcGetMemHandle")?;
+            Ok(IpcMemHandle {
+                handle: raw.reserved.map(|b| b as u8),
+                size: (self.len() * size_of::<T>()) as u64,
+            })
+        }
+    }
+}
+
+/// Device memory owned by another process and opened from an [`IpcMemHandle`].
+///
+/// `IpcMemory` borrows the memory, it derefs to a [`DeviceSlice`] to use it like any other device
+/// memory, and dropping it closes the handle without freeing the memory.
+#[derive(Debug)]
+#[repr(C)]
+pub struct IpcMemory<T: DeviceCopy> {
+    buf: DevicePointer<T>,
+    len: usize,
+}
+
+unsafe impl<T: Send + DeviceCopy> Send for IpcMemory<T> {}
+unsafe impl<T: Sync + DeviceCopy> Sync for IpcMemory<T> {}
+
+impl<T: DeviceCopy> IpcMemory<T> {
+    /// Opens a handle made by another process with [`DeviceBuffer::ipc_handle`]. Peer access is
+    /// enabled automatically if the memory is on a different device than the current context.
+    ///
+    /// A process cannot open handles to its own memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the memory is not a multiple of the size of `T`.
+    ///
+    /// # Safety
+    ///
+    /// The memory must contain valid values of `T`. The other process may read and write the
+    /// memory at any time, so any synchronization between the processes (for example with
+    /// [`IpcEventHandle`](crate::event::IpcEventHandle)s) must be done by the caller.
+    pub unsafe fn open(handle: IpcMemHandle) -> CudaResult<Self> {
+        let size = handle.size_in_bytes();
+        let len = if size_of::<T>() == 0 {
+            0
+        } else {
+            assert_eq!(
+                size % size_of::<T>(),
+                0,
+                "memory size is not a multiple of the size of T"
+            );
+            size / size_of::<T>()
+        };
+
+        let mut ptr = 0;
+        cuda::cuIpcOpenMemHandle_v2(
+            &mut ptr as *mut _,
+            handle.to_raw(),
+            cuda::CUipcMem_flags::CU_IPC_MEM_LAZY_ENABLE_PEER_ACCESS as u32,
+        )
+        .to_result_from("cuIpcOpenMemHandle_v2")?;
+        Ok(Self {
+            buf: DevicePointer::from_raw(ptr),
+            len,
+        })
+    }
+
+    /// Explicitly creates a [`DeviceSlice`] from this memory.
+    pub fn as_slice(&self) -> &DeviceSlice<T> {
+        self
+    }
+
+    /// Close an `IpcMemory`, returning an error.
+    pub fn drop(mut mem: IpcMemory<T>) -> DropResult<IpcMemory<T>> {
+        if mem.buf.is_null() {
+            return Ok(());
+        }
+
+        unsafe {
+            let buf = mem::replace(&mut mem.buf, DevicePointer::null());
+            match cuda::cuIpcCloseMemHandle(buf.as_raw()).to_result_from("cuIpcCloseMemHandle") {
+                Ok(()) => {
+                    mem::forget(mem);
+                    Ok(())
+                }
+                Err(e) => Err((e, IpcMemory { buf, len: mem.len })),
+            }
+        }
+    }
+}
+
+impl<T: DeviceCopy> Deref for IpcMemory<T> {
+    type Target = DeviceSlice<T>;
+
+    fn deref(&self) -> &DeviceSlice<T> {
+        unsafe { &*(self as *const _ as *const DeviceSlice<T>) }
+    }
+}
+
+impl<T: DeviceCopy> DerefMut for IpcMemory<T> {
+    fn deref_mut(&mut self) -> &mut DeviceSlice<T> {
+        unsafe { &mut *(self as *mut _ as *mut DeviceSlice<T>) }
+    }
+}
+
+impl<T: DeviceCopy> Drop for IpcMemory<T> {
+    fn drop(&mut self) {
+        if self.buf.is_null() {
+            return;
+        }
+
+        unsafe {
+            cuda::cuIpcCloseMemHandle(self.buf.as_raw());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_mem_handle_bytes_roundtrip() {
+        let _context = crate::quick_init().unwrap();
+        let buf = DeviceBuffer::from_slice(&[0u32; 16]).unwrap();
+        let handle = buf.ipc_handle().unwrap();
+        assert_eq!(handle.size_in_bytes(), 64);
+        assert_eq!(IpcMemHandle::from_bytes(handle.to_bytes()), handle);
+    }
+}
@@ -1,5 +1,7 @@
+mod host_registration;
 mod locked_box;
 mod locked_buffer;
 
+pub use host_registration::*;
 pub use locked_box::*;
 pub use locked_buffer::*;
@@ -0,0 +1,213 @@
+use super::locked_buffer::host_device_pointer;
+use crate::error::{CudaResult, DropResult, ToResult};
+use crate::memory::{DeviceCopy, DevicePointer};
+use crate::sys as cuda;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_void;
+
+bitflags::bitflags! {
+    /// Bit flags for registering existing host memory with [`HostRegistration`].
+    pub struct HostRegisterFlags: u32 {
+        /// No flags set, the memory is page-locked for the current context.
+        const DEFAULT = 0x0;
+
+        /// The memory is page-locked for all contexts, not just the one current when registering it.
+        const PORTABLE = 0x1;
+
+        /// Maps the memory into the address space of the device, so that kernels can read and
+        /// write it directly through the pointer returned by [`HostRegistration::device_pointer`].
+        const DEVICE_MAP = 0x2;
+
+        /// The memory is I/O memory, such as memory mapped from a third-party PCIe device, and
+        /// is mapped into the address space of the device like [`HostRegisterFlags::DEVICE_MAP`].
+        const IO_MEMORY = 0x4;
+
+        /// The memory is only read from by the device. This is required to register memory
+        /// which the host can only read, such as a read-only memory-mapped file.
+        const READ_ONLY = 0x8;
+    }
+}
+
+/// Existing host memory which has been page-locked with `cuMemHostRegister`.
+///
+/// Registering memory makes it usable for the same fast and asynchronous copies as a
+/// [`LockedBuffer`](crate::memory::LockedBuffer) without allocating new page-locked memory and
+/// copying the data into it. The memory is unregistered once the `HostRegistration` is dropped,
+/// and it borrows the memory until then, so it cannot be freed or reallocated while it is registered.
+///
+/// Registering memory is expensive, so it is best used for large buffers which are used for
+/// many copies.
+///
+/// # Examples
+///
+/// ```
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// use cust::memory::*;
+///
+/// let mut samples = vec![0.5f32; 4096];
+/// let registered = HostRegistration::new(&mut samples, HostRegisterFlags::DEFAULT)?;
+/// let device = DeviceBuffer::from_slice(&registered)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HostRegistration<'a, T: DeviceCopy> {
+    slice: &'a mut [T],
+    // empty slices are never registered with the driver.
+    registered: bool,
+}
+
+unsafe impl<'a, T: Send + DeviceCopy> Send for HostRegistration<'a, T> {}
+unsafe impl<'a, T: Sync + DeviceCopy> Sync for HostRegistration<'a, T> {}
+
+impl<'a, T: DeviceCopy> HostRegistration<'a, T> {
+    /// Page-locks the memory of a slice (or `Vec`) until the returned `HostRegistration` is dropped.
+    ///
+    /// # Errors
+    ///
+    /// If the memory is already registered, or registering it fails, returns the error from CUDA.
+    pub fn new(slice: &'a mut [T], flags: HostRegisterFlags) -> CudaResult<Self> {
+        let size = mem::size_of_val(slice);
+        if size == 0 {
+            return Ok(Self {
+                slice,
+                registered: false,
+            });
+        }
+
+        unsafe {
+            cuda::cuMemHostRegister_v2(slice.as_mut_ptr() as *mut c_void, size, flags.bits())
+                .to_result_from("cuMemHostRegister_v2")?;
+        }
+        Ok(Self {
+            slice,
+            registered: true,
+        })
+    }
+
+    /// Returns the device pointer kernels can use to access the registered memory directly,
+    /// without copying it to the device. The memory must have been registered with
+    /// [`HostRegisterFlags::DEVICE_MAP`], or on systems with unified addressing, any
+    /// registered memory is mapped.
+    ///
+    /// # Errors
+    ///
+    /// If the memory is not mapped into the address space of the device, returns the error from
+    /// CUDA.
+    pub fn device_pointer(&self) -> CudaResult<DevicePointer<T>> {
+        if !self.registered {
+            return Ok(DevicePointer::null());
+        }
+        unsafe { host_device_pointer(self.slice.as_ptr() as *mut T) }
+    }
+
+    /// Returns the registered slice.
+    pub fn as_slice(&self) -> &[T] {
+        self.slice
+    }
+
+    /// Returns the registered slice mutably.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.slice
+    }
+
+    /// Unregister the memory, returning an error.
+    ///
+    /// Deallocating host memory returns an error if the memory is still registered, so this
+    /// is a way to handle failed unregistration before the memory is freed.
+    pub fn drop(reg: HostRegistration<'a, T>) -> DropResult<HostRegistration<'a, T>> {
+        if !reg.registered {
+            return Ok(());
+        }
+
+        unsafe {
+            match cuda::cuMemHostUnregister(reg.slice.as_mut_ptr() as *mut c_void)
+                .to_result_from("cuMemHostUnregister")
+            {
+                Ok(()) => {
+                    mem::forget(reg);
+                    Ok(())
+                }
+                Err(e) => Err((e, reg)),
+            }
+        }
+    }
+}
+
+impl<'a, T: DeviceCopy> AsRef<[T]> for HostRegistration<'a, T> {
+    fn as_ref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+impl<'a, T: DeviceCopy> AsMut<[T]> for HostRegistration<'a, T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self.slice
+    }
+}
+
+impl<'a, T: DeviceCopy> Deref for HostRegistration<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+impl<'a, T: DeviceCopy> DerefMut for HostRegistration<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.slice
+    }
+}
+
+impl<'a, T: DeviceCopy> Drop for HostRegistration<'a, T> {
+    fn drop(&mut self) {
+        if !self.registered {
+            return;
+        }
+
+        unsafe {
+            cuda::cuMemHostUnregister(self.slice.as_mut_ptr() as *mut c_void);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::{AsyncCopyDestination, DeviceBuffer};
+    use crate::stream::{Stream, StreamFlags};
+
+    #[test]
+    fn test_register_vec() {
+        let _context = crate::quick_init().unwrap();
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None).unwrap();
+        let mut host = (0..1024u32).collect::<Vec<_>>();
+        let mut device = DeviceBuffer::from_slice(&[0u32; 1024]).unwrap();
+        {
+            let mut registered =
+                HostRegistration::new(&mut host, HostRegisterFlags::PORTABLE).unwrap();
+            unsafe {
+                device.async_copy_from(&registered, &stream).unwrap();
+                stream.synchronize().unwrap();
+                registered.iter_mut().for_each(|x| *x = 0);
+                device.async_copy_to(&mut registered, &stream).unwrap();
+            }
+            stream.synchronize().unwrap();
+            HostRegistration::drop(registered).unwrap();
+        }
+        assert_eq!(host, (0..1024u32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_register_empty() {
+        let _context = crate::quick_init().unwrap();
+        let mut host = Vec::<u64>::new();
+        let registered = HostRegistration::new(&mut host, HostRegisterFlags::DEVICE_MAP).unwrap();
+        assert!(registered.device_pointer().unwrap().is_null());
+    }
+}
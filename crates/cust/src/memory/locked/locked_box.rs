@@ -93,6 +93,7 @@ impl<T: DeviceCopy> LockedBox<T> {
 
 impl<T: DeviceCopy> Drop for LockedBox<T> {
     fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
         if self.ptr.is_null() {
             return;
         }
@@ -1,11 +1,35 @@
 use crate::error::*;
-use crate::memory::malloc::{cuda_free_locked, cuda_malloc_locked};
-use crate::memory::DeviceCopy;
+use crate::memory::malloc::{cuda_free_locked, cuda_malloc_locked, cuda_malloc_locked_with_flags};
+use crate::memory::{DeviceCopy, DevicePointer};
+use crate::sys as cuda;
 use std::mem;
 use std::ops;
+use std::os::raw::c_void;
 use std::ptr;
 use std::slice;
 
+bitflags::bitflags! {
+    /// Bit flags for allocating page-locked memory.
+    pub struct LockedMemoryFlags: u32 {
+        /// No flags set, this is the same as allocating with [`cuda_malloc_locked`].
+        const DEFAULT = 0x0;
+
+        /// The memory is page-locked for all contexts, not just the one current when allocating it.
+        const PORTABLE = 0x1;
+
+        /// Maps the memory into the address space of the device, so that kernels can read and
+        /// write it directly through the pointer returned by `device_pointer`. The current
+        /// context must have been created with `ContextFlags::MAP_HOST` on systems without
+        /// unified addressing.
+        const DEVICE_MAP = 0x2;
+
+        /// Allocates the memory as write-combined, which makes transfers to the device faster,
+        /// but makes reading the memory from the host extremely slow. This should only be
+        /// used for memory the host only writes to.
+        const WRITE_COMBINED = 0x4;
+    }
+}
+
 /// Fixed-size host-side buffer in page-locked memory.
 ///
 /// See the [`module-level documentation`](../memory/index.html) for more details on page-locked
@@ -108,6 +132,69 @@ impl<T: DeviceCopy> LockedBuffer<T> {
         })
     }
 
+    /// Allocate a new page-locked buffer large enough to hold `size` `T`'s with the given
+    /// flags, but without initializing the contents.
+    ///
+    /// # Errors
+    ///
+    /// If the allocation fails, returns the error from CUDA. If `size` is large enough that
+    /// `size * mem::sizeof::<T>()` overflows usize, then returns InvalidMemoryAllocation.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the contents of the buffer are initialized before reading from
+    /// the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let _context = cust::quick_init().unwrap();
+    /// use cust::memory::*;
+    /// let mut buffer = unsafe {
+    ///     LockedBuffer::uninitialized_with_flags(5, LockedMemoryFlags::WRITE_COMBINED).unwrap()
+    /// };
+    /// for i in buffer.iter_mut() {
+    ///     *i = 0u64;
+    /// }
+    /// ```
+    pub unsafe fn uninitialized_with_flags(
+        size: usize,
+        flags: LockedMemoryFlags,
+    ) -> CudaResult<Self> {
+        let ptr: *mut T = if size > 0 && mem::size_of::<T>() > 0 {
+            cuda_malloc_locked_with_flags(size, flags)?
+        } else {
+            ptr::NonNull::dangling().as_ptr()
+        };
+        Ok(LockedBuffer {
+            buf: ptr,
+            capacity: size,
+        })
+    }
+
+    /// Returns the device pointer kernels can use to access this buffer directly, without copying
+    /// it to the device. The buffer must have been allocated with [`LockedMemoryFlags::DEVICE_MAP`],
+    /// or on systems with unified addressing, any page-locked memory is mapped.
+    ///
+    /// # Errors
+    ///
+    /// If the memory is not mapped into the address space of the device, returns the error from
+    /// CUDA.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # let _context = cust::quick_init().unwrap();
+    /// use cust::memory::*;
+    /// let mut buffer = unsafe {
+    ///     LockedBuffer::<f32>::uninitialized_with_flags(5, LockedMemoryFlags::DEVICE_MAP).unwrap()
+    /// };
+    /// let ptr = buffer.device_pointer().unwrap();
+    /// ```
+    pub fn device_pointer(&self) -> CudaResult<DevicePointer<T>> {
+        unsafe { host_device_pointer(self.buf) }
+    }
+
     /// Extracts a slice containing the entire buffer.
     ///
     /// Equivalent to `&s[..]`.
@@ -227,6 +314,15 @@ impl<T: DeviceCopy> LockedBuffer<T> {
     }
 }
 
+pub(crate) unsafe fn host_device_pointer<T: DeviceCopy>(
+    ptr: *mut T,
+) -> CudaResult<DevicePointer<T>> {
+    let mut device_ptr = 0;
+    cuda::cuMemHostGetDevicePointer_v2(&mut device_ptr, ptr as *mut c_void, 0)
+        .to_result_from("cuMemHostGetDevicePointer_v2")?;
+    Ok(DevicePointer::from_raw(device_ptr))
+}
+
 impl<T: DeviceCopy> AsRef<[T]> for LockedBuffer<T> {
     fn as_ref(&self) -> &[T] {
         self
@@ -257,6 +353,7 @@ impl<T: DeviceCopy> ops::DerefMut for LockedBuffer<T> {
 }
 impl<T: DeviceCopy> Drop for LockedBuffer<T> {
     fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
         if self.buf.is_null() {
             return;
         }
@@ -287,6 +384,19 @@ mod test {
         buffer[0] = 1;
     }
 
+    #[test]
+    fn test_mapped_device_pointer() {
+        let _context = crate::quick_init().unwrap();
+        let buffer = unsafe {
+            LockedBuffer::<u64>::uninitialized_with_flags(
+                16,
+                LockedMemoryFlags::DEVICE_MAP | LockedMemoryFlags::WRITE_COMBINED,
+            )
+            .unwrap()
+        };
+        assert!(!buffer.device_pointer().unwrap().is_null());
+    }
+
     #[test]
     fn test_from_slice() {
         let _context = crate::quick_init().unwrap();
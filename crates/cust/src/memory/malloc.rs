@@ -1,6 +1,8 @@
 use super::DeviceCopy;
 use crate::error::*;
 use crate::memory::DevicePointer;
+use crate::memory::LockedMemoryFlags;
+use crate::memory::MemoryPool;
 use crate::memory::UnifiedPointer;
 use crate::prelude::Stream;
 use crate::sys as cuda;
@@ -20,7 +22,7 @@ use std::ptr;
 ///
 /// If allocating memory fails, returns the CUDA error value.
 /// If the number of bytes to allocate is zero (either because count is zero or because T is a
-/// zero-sized type), or if the size of the allocation would overflow a usize, returns InvalidValue.
+/// zero-sized type), or if the size of the allocation would overflow a usize, returns InvalidMemoryAllocation.
 ///
 /// # Safety
 ///
@@ -46,10 +48,48 @@ pub unsafe fn cuda_malloc<T: DeviceCopy>(count: usize) -> CudaResult<DevicePoint
     }
 
     let mut ptr = 0;
-    cuda::cuMemAlloc_v2(&mut ptr, size).to_result()?;
+    cuda::cuMemAlloc_v2(&mut ptr, size).to_result_from("cuMemAlloc_v2")?;
     Ok(DevicePointer::from_raw(ptr))
 }
 
+/// Unsafe wrapper around `cuMemAllocPitch` which allocates `height` rows of `width` `T`s, with
+/// every row padded to be properly aligned. Returns the pointer and the pitch, the distance in
+/// bytes between the start of each row.
+///
+/// Retains all of the unsafe semantics of [`cuda_malloc`], the memory can be freed with
+/// [`cuda_free`].
+///
+/// # Errors
+///
+/// If allocating zero bytes, returns `InvalidMemoryAllocation`, otherwise returns the
+/// error from CUDA.
+///
+/// # Safety
+///
+/// The returned memory is uninitialized and must be initialized before being read.
+pub unsafe fn cuda_malloc_pitched<T: DeviceCopy>(
+    width: usize,
+    height: usize,
+) -> CudaResult<(DevicePointer<T>, usize)> {
+    let width_in_bytes = width.checked_mul(mem::size_of::<T>()).unwrap_or(0);
+    if width_in_bytes == 0 || height == 0 {
+        return Err(CudaError::InvalidMemoryAllocation);
+    }
+    // the element size is the size of the accesses the pitch is optimized for,
+    // it must be 4, 8, or 16 bytes.
+    let element_size = match mem::size_of::<T>() {
+        0..=4 => 4,
+        5..=8 => 8,
+        _ => 16,
+    };
+
+    let mut ptr = 0;
+    let mut pitch = 0;
+    cuda::cuMemAllocPitch_v2(&mut ptr, &mut pitch, width_in_bytes, height, element_size)
+        .to_result_from("cuMemAllocPitch_v2")?;
+    Ok((DevicePointer::from_raw(ptr), pitch))
+}
+
 /// Unsafe wrapper around `cuMemAllocAsync` which queues a memory allocation operation on a stream.
 /// Retains all of the unsafe semantics of [`cuda_malloc`] with the extra requirement that the memory
 /// must not be used until it is allocated on the stream. Therefore, proper stream ordering semantics must be
@@ -74,11 +114,34 @@ pub unsafe fn cuda_malloc_async<T: DeviceCopy>(
         size,
         stream.as_inner(),
     )
-    .to_result()?;
+    .to_result_from("cuMemAllocAsync")?;
     let ptr = ptr as *mut T;
     Ok(DevicePointer::from_raw(ptr as cuda::CUdeviceptr))
 }
 
+/// Unsafe wrapper around `cuMemAllocFromPoolAsync` which queues a memory allocation operation
+/// from a specific [`MemoryPool`] on a stream. Retains all of the unsafe semantics of [`cuda_malloc_async`].
+///
+/// # Safety
+///
+/// The memory behind the returned pointer must not be used in any way until the
+/// allocation actually takes place in the stream.
+pub unsafe fn cuda_malloc_from_pool_async<T: DeviceCopy>(
+    pool: &MemoryPool,
+    stream: &Stream,
+    count: usize,
+) -> CudaResult<DevicePointer<T>> {
+    let size = count.checked_mul(mem::size_of::<T>()).unwrap_or(0);
+    if size == 0 {
+        return Err(CudaError::InvalidMemoryAllocation);
+    }
+
+    let mut ptr = 0;
+    cuda::cuMemAllocFromPoolAsync(&mut ptr, size, pool.as_raw(), stream.as_inner())
+        .to_result_from("cuMemAllocFromPoolAsync")?;
+    Ok(DevicePointer::from_raw(ptr))
+}
+
 /// Unsafe wrapper around `cuMemFreeAsync` which queues a memory allocation free operation on a stream.
 /// Retains all of the unsafe semantics of [`cuda_free`] with the extra requirement that the memory
 /// must not be used after it is dropped. Therefore, proper stream ordering semantics must be
@@ -95,7 +158,7 @@ pub unsafe fn cuda_free_async<T: DeviceCopy>(
         return Err(CudaError::InvalidMemoryAllocation);
     }
 
-    cuda::cuMemFreeAsync(p.as_raw(), stream.as_inner()).to_result()
+    cuda::cuMemFreeAsync(p.as_raw(), stream.as_inner()).to_result_from("cuMemFreeAsync")
 }
 
 /// Unsafe wrapper around the `cuMemAllocManaged` function, which allocates some unified memory and
@@ -110,7 +173,7 @@ pub unsafe fn cuda_free_async<T: DeviceCopy>(
 ///
 /// If allocating memory fails, returns the CUDA error value.
 /// If the number of bytes to allocate is zero (either because count is zero or because T is a
-/// zero-sized type), or if the size of the allocation would overflow a usize, returns InvalidValue.
+/// zero-sized type), or if the size of the allocation would overflow a usize, returns InvalidMemoryAllocation.
 ///
 /// # Safety
 ///
@@ -143,7 +206,7 @@ pub unsafe fn cuda_malloc_unified<T: DeviceCopy>(count: usize) -> CudaResult<Uni
         size,
         cuda::CUmemAttach_flags_enum::CU_MEM_ATTACH_GLOBAL as u32,
     )
-    .to_result()?;
+    .to_result_from("cuMemAllocManaged")?;
     let ptr = ptr as *mut T;
     Ok(UnifiedPointer::wrap(ptr as *mut T))
 }
@@ -153,7 +216,7 @@ pub unsafe fn cuda_malloc_unified<T: DeviceCopy>(count: usize) -> CudaResult<Uni
 /// # Errors
 ///
 /// If freeing memory fails, returns the CUDA error value. If the given pointer is null, returns
-/// InvalidValue.
+/// InvalidMemoryAllocation.
 ///
 /// # Safety
 ///
@@ -176,7 +239,7 @@ pub unsafe fn cuda_free<T: DeviceCopy>(ptr: DevicePointer<T>) -> CudaResult<()>
         return Err(CudaError::InvalidMemoryAllocation);
     }
 
-    cuda::cuMemFree_v2(ptr.as_raw()).to_result()?;
+    cuda::cuMemFree_v2(ptr.as_raw()).to_result_from("cuMemFree_v2")?;
     Ok(())
 }
 
@@ -185,7 +248,7 @@ pub unsafe fn cuda_free<T: DeviceCopy>(ptr: DevicePointer<T>) -> CudaResult<()>
 /// # Errors
 ///
 /// If freeing memory fails, returns the CUDA error value. If the given pointer is null, returns
-/// InvalidValue.
+/// InvalidMemoryAllocation.
 ///
 /// # Safety
 ///
@@ -209,7 +272,7 @@ pub unsafe fn cuda_free_unified<T: DeviceCopy>(mut p: UnifiedPointer<T>) -> Cuda
         return Err(CudaError::InvalidMemoryAllocation);
     }
 
-    cuda::cuMemFree_v2(ptr as u64).to_result()?;
+    cuda::cuMemFree_v2(ptr as u64).to_result_from("cuMemFree_v2")?;
     Ok(())
 }
 
@@ -225,7 +288,7 @@ pub unsafe fn cuda_free_unified<T: DeviceCopy>(mut p: UnifiedPointer<T>) -> Cuda
 ///
 /// If allocating memory fails, returns the CUDA error value.
 /// If the number of bytes to allocate is zero (either because count is zero or because T is a
-/// zero-sized type), or if the size of the allocation would overflow a usize, returns InvalidValue.
+/// zero-sized type), or if the size of the allocation would overflow a usize, returns InvalidMemoryAllocation.
 ///
 /// # Safety
 ///
@@ -251,17 +314,50 @@ pub unsafe fn cuda_malloc_locked<T>(count: usize) -> CudaResult<*mut T> {
     }
 
     let mut ptr: *mut c_void = ptr::null_mut();
-    cuda::cuMemAllocHost_v2(&mut ptr as *mut *mut c_void, size).to_result()?;
+    cuda::cuMemAllocHost_v2(&mut ptr as *mut *mut c_void, size)
+        .to_result_from("cuMemAllocHost_v2")?;
     let ptr = ptr as *mut T;
     Ok(ptr as *mut T)
 }
 
+/// Unsafe wrapper around the `cuMemHostAlloc` function, which is the same as
+/// [`cuda_malloc_locked`] but allows choosing how the memory is allocated with [`LockedMemoryFlags`].
+///
+/// Memory buffers allocated using `cuda_malloc_locked_with_flags` must be freed using
+/// [`cuda_free_locked`](fn.cuda_free_locked.html).
+///
+/// # Errors
+///
+/// If allocating memory fails, returns the CUDA error value.
+/// If the number of bytes to allocate is zero (either because count is zero or because T is a
+/// zero-sized type), or if the size of the allocation would overflow a usize, returns InvalidMemoryAllocation.
+///
+/// # Safety
+///
+/// Since the allocated memory is not initialized, the caller must ensure that it is initialized
+/// before reading from it in any way. Additionally, the caller must ensure that the memory
+/// allocated is freed using `cuda_free_locked`, or the memory will be leaked.
+pub unsafe fn cuda_malloc_locked_with_flags<T>(
+    count: usize,
+    flags: LockedMemoryFlags,
+) -> CudaResult<*mut T> {
+    let size = count.checked_mul(mem::size_of::<T>()).unwrap_or(0);
+    if size == 0 {
+        return Err(CudaError::InvalidMemoryAllocation);
+    }
+
+    let mut ptr: *mut c_void = ptr::null_mut();
+    cuda::cuMemHostAlloc(&mut ptr as *mut *mut c_void, size, flags.bits())
+        .to_result_from("cuMemHostAlloc")?;
+    Ok(ptr as *mut T)
+}
+
 /// Free page-locked memory allocated with [`cuda_malloc_host`](fn.cuda_malloc_host.html).
 ///
 /// # Errors
 ///
 /// If freeing memory fails, returns the CUDA error value. If the given pointer is null, returns
-/// InvalidValue.
+/// InvalidMemoryAllocation.
 ///
 /// # Safety
 ///
@@ -284,7 +380,7 @@ pub unsafe fn cuda_free_locked<T>(ptr: *mut T) -> CudaResult<()> {
         return Err(CudaError::InvalidMemoryAllocation);
     }
 
-    cuda::cuMemFreeHost(ptr as *mut c_void).to_result()?;
+    cuda::cuMemFreeHost(ptr as *mut c_void).to_result_from("cuMemFreeHost")?;
     Ok(())
 }
 
@@ -444,6 +540,32 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_cuda_malloc_locked_with_flags_zero_bytes() {
+        let _context = crate::quick_init().unwrap();
+        unsafe {
+            assert_eq!(
+                CudaError::InvalidMemoryAllocation,
+                cuda_malloc_locked_with_flags::<u64>(0, LockedMemoryFlags::empty()).unwrap_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_cuda_malloc_locked_with_flags_overflow() {
+        let _context = crate::quick_init().unwrap();
+        unsafe {
+            assert_eq!(
+                CudaError::InvalidMemoryAllocation,
+                cuda_malloc_locked_with_flags::<u64>(
+                    ::std::usize::MAX - 1,
+                    LockedMemoryFlags::empty()
+                )
+                .unwrap_err()
+            );
+        }
+    }
+
     #[test]
     fn test_cuda_free_locked_null() {
         let _context = crate::quick_init().unwrap();
@@ -74,17 +74,27 @@
 //! ensure that the memory allocation is safely cleaned up.
 
 pub mod array;
+pub mod vmm;
 
+pub(crate) mod caching;
 mod device;
+mod ipc;
 mod locked;
 mod malloc;
 mod pointer;
+mod pool;
 mod unified;
 
+pub use self::caching::{
+    tag_allocations, AllocationTag, AllocatorStats, CachingAllocator, TagStats,
+};
+pub(crate) use self::device::kernels;
 pub use self::device::*;
+pub use self::ipc::*;
 pub use self::locked::*;
 pub use self::malloc::*;
 pub use self::pointer::*;
+pub use self::pool::*;
 pub use self::unified::*;
 
 use crate::error::*;
@@ -115,6 +125,16 @@ impl<T: DeviceCopy> GpuBuffer<T> for DeviceBuffer<T> {
     }
 }
 
+impl<T: DeviceCopy> GpuBuffer<T> for DeviceVec<T> {
+    fn as_device_ptr(&self) -> DevicePointer<T> {
+        self.as_slice().as_device_ptr()
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
 impl<T: DeviceCopy> GpuBuffer<T> for UnifiedBuffer<T> {
     fn as_device_ptr(&self) -> DevicePointer<T> {
         DevicePointer::from_raw(self.as_ptr() as u64)
@@ -183,6 +203,16 @@ impl<T: DeviceCopy> DeviceMemory for DeviceBuffer<T> {
     }
 }
 
+impl<T: DeviceCopy> DeviceMemory for DeviceVec<T> {
+    fn as_raw_ptr(&self) -> cust_raw::CUdeviceptr {
+        self.as_device_ptr().as_raw()
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        std::mem::size_of::<T>() * self.len()
+    }
+}
+
 impl<T: DeviceCopy> DeviceMemory for DeviceSlice<T> {
     fn as_raw_ptr(&self) -> cust_raw::CUdeviceptr {
         self.as_device_ptr().as_raw()
@@ -194,11 +224,12 @@ impl<T: DeviceCopy> DeviceMemory for DeviceSlice<T> {
 }
 
 mod private {
-    use super::{DeviceBox, DeviceBuffer, DeviceCopy, UnifiedBox, UnifiedBuffer};
+    use super::{DeviceBox, DeviceBuffer, DeviceCopy, DeviceVec, UnifiedBox, UnifiedBuffer};
 
     pub trait Sealed {}
     impl<T: DeviceCopy> Sealed for UnifiedBuffer<T> {}
     impl<T: DeviceCopy> Sealed for DeviceBuffer<T> {}
+    impl<T: DeviceCopy> Sealed for DeviceVec<T> {}
     impl<T: DeviceCopy> Sealed for UnifiedBox<T> {}
     impl<T: DeviceCopy> Sealed for DeviceBox<T> {}
 }
@@ -210,7 +241,7 @@ pub unsafe fn memcpy_htod(
     src_ptr: *const c_void,
     size: usize,
 ) -> CudaResult<()> {
-    crate::sys::cuMemcpyHtoD_v2(d_ptr, src_ptr, size).to_result()?;
+    crate::sys::cuMemcpyHtoD_v2(d_ptr, src_ptr, size).to_result_from("cuMemcpyHtoD_v2")?;
     Ok(())
 }
 
@@ -221,7 +252,7 @@ pub unsafe fn memcpy_dtoh(
     src_ptr: cust_raw::CUdeviceptr,
     size: usize,
 ) -> CudaResult<()> {
-    crate::sys::cuMemcpyDtoH_v2(d_ptr, src_ptr, size).to_result()?;
+    crate::sys::cuMemcpyDtoH_v2(d_ptr, src_ptr, size).to_result_from("cuMemcpyDtoH_v2")?;
     Ok(())
 }
 
@@ -235,7 +266,8 @@ pub fn mem_get_info() -> CudaResult<(usize, usize)> {
     let mut mem_free = 0;
     let mut mem_total = 0;
     unsafe {
-        crate::sys::cuMemGetInfo_v2(&mut mem_free, &mut mem_total).to_result()?;
+        crate::sys::cuMemGetInfo_v2(&mut mem_free, &mut mem_total)
+            .to_result_from("cuMemGetInfo_v2")?;
     }
     Ok((mem_free, mem_total))
 }
@@ -69,11 +69,8 @@ impl<T: ?Sized + DeviceCopy> DevicePointer<T> {
     /// ```
     /// # let _context = cust::quick_init().unwrap();
     /// use cust::memory::*;
-    /// use std::ptr;
-    /// unsafe {
-    ///     let null : *mut u64 = ptr::null_mut();
-    ///     assert!(DevicePointer::wrap(null).is_null());
-    /// }
+    /// let null = DevicePointer::<u64>::from_raw(0);
+    /// assert!(null.is_null());
     /// ```
     pub fn is_null(self) -> bool {
         self.ptr == 0
@@ -129,7 +126,9 @@ impl<T: ?Sized + DeviceCopy> DevicePointer<T> {
     where
         T: Sized,
     {
-        let ptr = self.ptr + (count as usize * size_of::<T>()) as u64;
+        let ptr = self
+            .ptr
+            .wrapping_add((count * size_of::<T>() as isize) as u64);
         Self {
             ptr,
             marker: PhantomData,
@@ -172,7 +171,7 @@ impl<T: ?Sized + DeviceCopy> DevicePointer<T> {
     {
         let ptr = self
             .ptr
-            .wrapping_add((count as usize * size_of::<T>()) as u64);
+            .wrapping_add(count.wrapping_mul(size_of::<T>() as isize) as u64);
         Self {
             ptr,
             marker: PhantomData,
@@ -253,6 +252,7 @@ impl<T: ?Sized + DeviceCopy> DevicePointer<T> {
     ///     let offset = dev_ptr.add(4).sub(3); // Points to the 2nd u64 in the buffer
     ///     cuda_free(dev_ptr); // Must free the buffer using the original pointer
     /// }
+    /// ```
     #[allow(clippy::should_implement_trait)]
     pub unsafe fn sub(self, count: usize) -> Self
     where
@@ -0,0 +1,294 @@
+use crate::device::Device;
+use crate::error::{CudaResult, DropResult, ToResult};
+use crate::sys as cuda;
+use std::mem::{self, MaybeUninit};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// How a device may access a piece of memory.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryAccess {
+    /// The memory cannot be accessed.
+    None = 0,
+    /// The memory can be read from but not written to.
+    Read = 1,
+    /// The memory can be read from and written to.
+    ReadWrite = 3,
+}
+
+impl MemoryAccess {
+    pub(crate) fn from_raw(raw: cuda::CUmemAccess_flags) -> Self {
+        match raw {
+            cuda::CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READ => Self::Read,
+            cuda::CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE => Self::ReadWrite,
+            _ => Self::None,
+        }
+    }
+
+    pub(crate) fn to_raw(self) -> cuda::CUmemAccess_flags {
+        match self {
+            Self::None => cuda::CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_NONE,
+            Self::Read => cuda::CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READ,
+            Self::ReadWrite => cuda::CUmemAccess_flags::CU_MEM_ACCESS_FLAGS_PROT_READWRITE,
+        }
+    }
+}
+
+pub(crate) fn device_location(device: &Device) -> cuda::CUmemLocation {
+    cuda::CUmemLocation {
+        type_: cuda::CUmemLocationType::CU_MEM_LOCATION_TYPE_DEVICE,
+        id: device.as_raw(),
+    }
+}
+
+/// A pool of device memory used for stream-ordered allocations.
+///
+/// Stream-ordered allocations such as [`DeviceBuffer::uninitialized_async`](crate::memory::DeviceBuffer::uninitialized_async)
+/// reserve memory from a pool, and freeing them returns the memory to the pool where it can be
+/// reused by further allocations without going through the driver. Every device has a default pool,
+/// which is used for stream-ordered allocations unless a pool is specified, and custom pools
+/// can be made with [`MemoryPool::new`].
+///
+/// When a stream, event, or context is synchronized, the pool tries to release memory it is not using
+/// back to the OS until it holds at most its release threshold of memory. The release threshold is
+/// `0` by default, which means all unused memory is released on every synchronization, setting a
+/// higher threshold with [`MemoryPool::set_release_threshold`] avoids the cost of reallocating
+/// memory for workloads which allocate the same amount of memory over and over.
+#[derive(Debug)]
+pub struct MemoryPool {
+    inner: cuda::CUmemoryPool,
+    // the default pool of a device is owned by the driver and must not be destroyed.
+    owned: bool,
+}
+
+unsafe impl Send for MemoryPool {}
+unsafe impl Sync for MemoryPool {}
+
+impl MemoryPool {
+    /// Creates a new memory pool which allocates memory on a device.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let _ctx = quick_init()?;
+    /// # if cfg!(feature = "mock") { return Ok(()); }
+    /// use cust::device::Device;
+    /// use cust::memory::MemoryPool;
+    ///
+    /// let pool = MemoryPool::new(&Device::get_device(0)?)?;
+    /// pool.set_release_threshold(u64::MAX)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(device: &Device) -> CudaResult<Self> {
+        let props = cuda::CUmemPoolProps {
+            allocType: cuda::CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED,
+            handleTypes: cuda::CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_NONE,
+            location: device_location(device),
+            win32SecurityAttributes: ptr::null_mut(),
+            reserved: [0; 64],
+        };
+
+        unsafe {
+            let mut inner = ptr::null_mut();
+            cuda::cuMemPoolCreate(&mut inner as *mut _, &props as *const _)
+                .to_result_from("cuMemPoolCreate")?;
+            Ok(Self { inner, owned: true })
+        }
+    }
+
+    /// Returns the default memory pool of a device. This pool is owned by the driver,
+    /// dropping the returned pool does not destroy it.
+    pub fn device_default(device: &Device) -> CudaResult<Self> {
+        unsafe {
+            let mut inner = ptr::null_mut();
+            cuda::cuDeviceGetDefaultMemPool(&mut inner as *mut _, device.as_raw())
+                .to_result_from("cuDeviceGetDefaultMemPool")?;
+            Ok(Self {
+                inner,
+                owned: false,
+            })
+        }
+    }
+
+    fn get_attribute<T>(&self, attr: cuda::CUmemPool_attribute) -> CudaResult<T> {
+        unsafe {
+            let mut value = MaybeUninit::<T>::uninit();
+            cuda::cuMemPoolGetAttribute(self.inner, attr, value.as_mut_ptr().cast())
+                .to_result_from("cuMemPoolGetAttribute")?;
+            Ok(value.assume_init())
+        }
+    }
+
+    fn set_attribute<T>(&self, attr: cuda::CUmemPool_attribute, mut value: T) -> CudaResult<()> {
+        unsafe {
+            cuda::cuMemPoolSetAttribute(self.inner, attr, &mut value as *mut T as *mut c_void)
+                .to_result_from("cuMemPoolSetAttribute")
+        }
+    }
+
+    /// Returns the amount of reserved memory in bytes this pool holds onto before trying to
+    /// release memory back to the OS.
+    pub fn release_threshold(&self) -> CudaResult<u64> {
+        self.get_attribute(cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD)
+    }
+
+    /// Sets the amount of reserved memory in bytes this pool holds onto before trying to
+    /// release memory back to the OS. Use `u64::MAX` to never release memory on synchronization.
+    pub fn set_release_threshold(&self, threshold: u64) -> CudaResult<()> {
+        self.set_attribute(
+            cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_RELEASE_THRESHOLD,
+            threshold,
+        )
+    }
+
+    /// Releases memory back to the OS until this pool holds at most `min_bytes_to_keep` bytes
+    /// of reserved memory. Memory backing allocations which are still alive is never released.
+    pub fn trim_to(&self, min_bytes_to_keep: usize) -> CudaResult<()> {
+        unsafe {
+            cuda::cuMemPoolTrimTo(self.inner, min_bytes_to_keep).to_result_from("cuMemPoolTrimTo")
+        }
+    }
+
+    /// Returns the amount of memory in bytes currently reserved by this pool from the OS.
+    pub fn reserved_mem_current(&self) -> CudaResult<u64> {
+        self.get_attribute(cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_CURRENT)
+    }
+
+    /// Returns the highest amount of memory in bytes reserved by this pool since the
+    /// last reset.
+    pub fn reserved_mem_high(&self) -> CudaResult<u64> {
+        self.get_attribute(cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH)
+    }
+
+    /// Resets the high watermark of reserved memory to the current amount of reserved memory.
+    pub fn reset_reserved_mem_high(&self) -> CudaResult<()> {
+        self.set_attribute(
+            cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_RESERVED_MEM_HIGH,
+            0u64,
+        )
+    }
+
+    /// Returns the amount of memory in bytes from this pool currently used by allocations.
+    pub fn used_mem_current(&self) -> CudaResult<u64> {
+        self.get_attribute(cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_CURRENT)
+    }
+
+    /// Returns the highest amount of memory in bytes from this pool used by allocations
+    /// since the last reset.
+    pub fn used_mem_high(&self) -> CudaResult<u64> {
+        self.get_attribute(cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_HIGH)
+    }
+
+    /// Resets the high watermark of used memory to the current amount of used memory.
+    pub fn reset_used_mem_high(&self) -> CudaResult<()> {
+        self.set_attribute(
+            cuda::CUmemPool_attribute::CU_MEMPOOL_ATTR_USED_MEM_HIGH,
+            0u64,
+        )
+    }
+
+    /// Returns how a device can access the memory allocated from this pool.
+    pub fn access(&self, device: &Device) -> CudaResult<MemoryAccess> {
+        unsafe {
+            let mut location = device_location(device);
+            let mut flags = MaybeUninit::uninit();
+            cuda::cuMemPoolGetAccess(flags.as_mut_ptr(), self.inner, &mut location as *mut _)
+                .to_result_from("cuMemPoolGetAccess")?;
+            Ok(MemoryAccess::from_raw(flags.assume_init()))
+        }
+    }
+
+    /// Sets how a device can access the memory allocated from this pool, this applies to
+    /// all allocations from the pool, including the ones which already exist.
+    ///
+    /// The device the pool allocates memory on always has read-write access, and
+    /// other devices must be able to access the memory of this device as a peer.
+    pub fn set_access(&self, device: &Device, access: MemoryAccess) -> CudaResult<()> {
+        let desc = cuda::CUmemAccessDesc {
+            location: device_location(device),
+            flags: access.to_raw(),
+        };
+        unsafe {
+            cuda::cuMemPoolSetAccess(self.inner, &desc as *const _, 1)
+                .to_result_from("cuMemPoolSetAccess")
+        }
+    }
+
+    /// Returns the raw handle of this pool.
+    pub fn as_raw(&self) -> cuda::CUmemoryPool {
+        self.inner
+    }
+
+    /// Destroy a `MemoryPool`, returning an error.
+    ///
+    /// If allocations from the pool are still alive, the pool is destroyed once all of them
+    /// are freed.
+    pub fn drop(mut pool: MemoryPool) -> DropResult<MemoryPool> {
+        if pool.inner.is_null() || !pool.owned {
+            return Ok(());
+        }
+
+        unsafe {
+            let inner = mem::replace(&mut pool.inner, ptr::null_mut());
+            match cuda::cuMemPoolDestroy(inner).to_result_from("cuMemPoolDestroy") {
+                Ok(()) => {
+                    mem::forget(pool);
+                    Ok(())
+                }
+                Err(e) => Err((e, MemoryPool { inner, owned: true })),
+            }
+        }
+    }
+}
+
+impl Drop for MemoryPool {
+    fn drop(&mut self) {
+        if self.inner.is_null() || !self.owned {
+            return;
+        }
+
+        unsafe {
+            cuda::cuMemPoolDestroy(self.inner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::DeviceBuffer;
+    use crate::quick_init;
+    use crate::stream::{Stream, StreamFlags};
+    use std::error::Error;
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_pool_stats() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let device = Device::get_device(0)?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let pool = MemoryPool::new(&device)?;
+        pool.set_release_threshold(u64::MAX)?;
+        assert_eq!(pool.release_threshold()?, u64::MAX);
+        assert_eq!(pool.access(&device)?, MemoryAccess::ReadWrite);
+
+        let buf = unsafe { DeviceBuffer::<u32>::uninitialized_async_in(1024, &pool, &stream)? };
+        stream.synchronize()?;
+        assert!(pool.used_mem_current()? >= 4096);
+        assert!(pool.reserved_mem_current()? >= pool.used_mem_current()?);
+
+        buf.drop_async(&stream)?;
+        stream.synchronize()?;
+        assert_eq!(pool.used_mem_current()?, 0);
+        // the release threshold keeps the memory reserved until the pool is trimmed.
+        assert!(pool.reserved_mem_current()? > 0);
+        pool.trim_to(0)?;
+        assert_eq!(pool.reserved_mem_current()?, 0);
+        Ok(())
+    }
+}
@@ -245,6 +245,7 @@ impl<T: DeviceCopy> UnifiedBox<T> {
 }
 impl<T: DeviceCopy> Drop for UnifiedBox<T> {
     fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
         if !self.ptr.is_null() {
             let ptr = mem::replace(&mut self.ptr, UnifiedPointer::null());
             unsafe {
@@ -586,6 +587,7 @@ impl<T: DeviceCopy> DerefMut for UnifiedBuffer<T> {
 }
 impl<T: DeviceCopy> Drop for UnifiedBuffer<T> {
     fn drop(&mut self) {
+        let _quiet = crate::error::suppress_last_error();
         if self.buf.is_null() {
             return;
         }
@@ -644,7 +646,7 @@ pub trait MemoryAdvise<T: DeviceCopy>: private::Sealed {
                 -1, // CU_DEVICE_CPU #define
                 stream.as_inner(),
             )
-            .to_result()?;
+            .to_result_from("cuMemPrefetchAsync")?;
         }
         Ok(())
     }
@@ -681,7 +683,7 @@ pub trait MemoryAdvise<T: DeviceCopy>: private::Sealed {
                 device.as_raw(),
                 stream.as_inner(),
             )
-            .to_result()?;
+            .to_result_from("cuMemPrefetchAsync")?;
         }
         Ok(())
     }
@@ -708,7 +710,7 @@ pub trait MemoryAdvise<T: DeviceCopy>: private::Sealed {
 
         unsafe {
             cuda::cuMemAdvise(slice.as_ptr() as cuda::CUdeviceptr, mem_size, advice, 0)
-                .to_result()?;
+                .to_result_from("cuMemAdvise")?;
         }
         Ok(())
     }
@@ -743,7 +745,7 @@ pub trait MemoryAdvise<T: DeviceCopy>: private::Sealed {
                 cuda::CUmem_advise::CU_MEM_ADVISE_SET_PREFERRED_LOCATION,
                 preferred_location.map(|d| d.as_raw()).unwrap_or(-1),
             )
-            .to_result()?;
+            .to_result_from("cuMemAdvise")?;
         }
         Ok(())
     }
@@ -760,7 +762,7 @@ pub trait MemoryAdvise<T: DeviceCopy>: private::Sealed {
                 cuda::CUmem_advise::CU_MEM_ADVISE_UNSET_PREFERRED_LOCATION,
                 0,
             )
-            .to_result()?;
+            .to_result_from("cuMemAdvise")?;
         }
         Ok(())
     }
@@ -0,0 +1,336 @@
+//! Low level virtual memory management.
+//!
+//! Regular allocations such as [`DeviceBuffer`](crate::memory::DeviceBuffer) tie together a range of
+//! virtual addresses and the physical memory backing it. The virtual memory management functions
+//! split this up into three steps:
+//! - Reserving a range of virtual addresses with [`VirtualAddressRange::reserve`].
+//! - Allocating physical memory on a device with [`PhysicalAllocation::new`].
+//! - Mapping physical memory into a range of virtual addresses with [`VirtualAddressRange::map`],
+//!   then making it accessible with [`VirtualAddressRange::set_access`].
+//!
+//! Because physical memory can be mapped at the end of an existing mapping, this allows
+//! growing an allocation without reallocating and copying its contents, this is how
+//! [`DeviceVec`](crate::memory::DeviceVec) is implemented.
+//!
+//! Every size and offset used with these functions must be a multiple of the
+//! [allocation granularity](allocation_granularity) of the device.
+
+use crate::device::Device;
+use crate::error::{CudaResult, DropResult, ToResult};
+use crate::memory::pool::device_location;
+use crate::memory::{DevicePointer, MemoryAccess};
+use crate::sys as cuda;
+use std::mem;
+use std::ptr;
+
+/// Which granularity to query with [`allocation_granularity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllocationGranularity {
+    /// The smallest granularity sizes and offsets must be a multiple of.
+    Minimum,
+    /// The granularity which should be used for the best performance.
+    Recommended,
+}
+
+impl AllocationGranularity {
+    fn to_raw(self) -> cuda::CUmemAllocationGranularity_flags {
+        match self {
+            Self::Minimum => {
+                cuda::CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_MINIMUM
+            }
+            Self::Recommended => {
+                cuda::CUmemAllocationGranularity_flags::CU_MEM_ALLOC_GRANULARITY_RECOMMENDED
+            }
+        }
+    }
+}
+
+fn allocation_prop(device: &Device) -> cuda::CUmemAllocationProp {
+    cuda::CUmemAllocationProp {
+        type_: cuda::CUmemAllocationType::CU_MEM_ALLOCATION_TYPE_PINNED,
+        requestedHandleTypes: cuda::CUmemAllocationHandleType::CU_MEM_HANDLE_TYPE_NONE,
+        location: device_location(device),
+        win32HandleMetaData: ptr::null_mut(),
+        allocFlags: Default::default(),
+    }
+}
+
+/// Returns the granularity in bytes of physical allocations on a device. Sizes and
+/// offsets of allocations, reservations, and mappings must be a multiple of it.
+pub fn allocation_granularity(
+    device: &Device,
+    granularity: AllocationGranularity,
+) -> CudaResult<usize> {
+    let prop = allocation_prop(device);
+    let mut size = 0;
+    unsafe {
+        cuda::cuMemGetAllocationGranularity(
+            &mut size as *mut _,
+            &prop as *const _,
+            granularity.to_raw(),
+        )
+        .to_result_from("cuMemGetAllocationGranularity")?;
+    }
+    Ok(size)
+}
+
+/// A chunk of physical memory on a device. The memory cannot be used until it is mapped into
+/// a [`VirtualAddressRange`].
+///
+/// Mappings keep the memory alive, so the allocation may be dropped while it is still mapped,
+/// in which case the memory is freed once it is unmapped.
+#[derive(Debug)]
+pub struct PhysicalAllocation {
+    handle: cuda::CUmemGenericAllocationHandle,
+    size: usize,
+}
+
+unsafe impl Send for PhysicalAllocation {}
+unsafe impl Sync for PhysicalAllocation {}
+
+impl PhysicalAllocation {
+    /// Allocates `size` bytes of physical memory on a device, `size` must be a multiple of the
+    /// [allocation granularity](allocation_granularity) of the device.
+    pub fn new(device: &Device, size: usize) -> CudaResult<Self> {
+        let prop = allocation_prop(device);
+        let mut handle = 0;
+        unsafe {
+            cuda::cuMemCreate(&mut handle as *mut _, size, &prop as *const _, 0)
+                .to_result_from("cuMemCreate")?;
+        }
+        Ok(Self { handle, size })
+    }
+
+    /// The size of this allocation in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the raw handle of this allocation.
+    pub fn as_raw(&self) -> cuda::CUmemGenericAllocationHandle {
+        self.handle
+    }
+
+    /// Release a `PhysicalAllocation`, returning an error.
+    ///
+    /// Mappings of the allocation keep the memory alive until they are unmapped.
+    pub fn drop(mut alloc: PhysicalAllocation) -> DropResult<PhysicalAllocation> {
+        if alloc.handle == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            let handle = mem::replace(&mut alloc.handle, 0);
+            match cuda::cuMemRelease(handle).to_result_from("cuMemRelease") {
+                Ok(()) => {
+                    mem::forget(alloc);
+                    Ok(())
+                }
+                Err(e) => Err((
+                    e,
+                    PhysicalAllocation {
+                        handle,
+                        size: alloc.size,
+                    },
+                )),
+            }
+        }
+    }
+}
+
+impl Drop for PhysicalAllocation {
+    fn drop(&mut self) {
+        if self.handle == 0 {
+            return;
+        }
+
+        unsafe {
+            cuda::cuMemRelease(self.handle);
+        }
+    }
+}
+
+/// A reserved range of virtual device addresses which physical memory can be mapped into.
+///
+/// Everything mapped into the range must be unmapped before the range is dropped.
+#[derive(Debug)]
+pub struct VirtualAddressRange {
+    ptr: DevicePointer<u8>,
+    size: usize,
+}
+
+unsafe impl Send for VirtualAddressRange {}
+unsafe impl Sync for VirtualAddressRange {}
+
+impl VirtualAddressRange {
+    /// Reserves `size` bytes of virtual addresses aligned to `alignment` bytes, an `alignment` of
+    /// `0` uses the allocation granularity. `size` must be a multiple of the
+    /// [allocation granularity](allocation_granularity).
+    ///
+    /// Reserving addresses does not use any device memory, so it is fine to reserve far more than
+    /// will actually be used.
+    pub fn reserve(size: usize, alignment: usize) -> CudaResult<Self> {
+        Self::reserve_at(size, alignment, DevicePointer::null())
+    }
+
+    /// Same as [`VirtualAddressRange::reserve`], but requests the range to start at `addr`. The driver
+    /// treats this as a hint, the returned range may start somewhere else.
+    pub fn reserve_at(size: usize, alignment: usize, addr: DevicePointer<u8>) -> CudaResult<Self> {
+        let mut ptr = 0;
+        unsafe {
+            cuda::cuMemAddressReserve(&mut ptr as *mut _, size, alignment, addr.as_raw(), 0)
+                .to_result_from("cuMemAddressReserve")?;
+        }
+        Ok(Self {
+            ptr: DevicePointer::from_raw(ptr),
+            size,
+        })
+    }
+
+    /// The address of the start of the range.
+    pub fn as_device_ptr(&self) -> DevicePointer<u8> {
+        self.ptr
+    }
+
+    /// The size of the range in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Maps the whole of a physical allocation into this range starting at `offset` bytes.
+    ///
+    /// The mapped memory cannot be accessed until access is granted with
+    /// [`VirtualAddressRange::set_access`].
+    pub fn map(&self, offset: usize, alloc: &PhysicalAllocation) -> CudaResult<()> {
+        assert!(
+            offset <= self.size && alloc.size <= self.size - offset,
+            "mapping out of bounds of the address range"
+        );
+        unsafe {
+            cuda::cuMemMap(
+                self.ptr.as_raw() + offset as u64,
+                alloc.size,
+                0,
+                alloc.handle,
+                0,
+            )
+            .to_result_from("cuMemMap")
+        }
+    }
+
+    /// Unmaps `size` bytes starting at `offset` bytes, the range must exactly cover one or
+    /// more whole mappings.
+    ///
+    /// # Safety
+    ///
+    /// The unmapped memory may be freed, so it must not be used by any pending or future
+    /// operation.
+    pub unsafe fn unmap(&self, offset: usize, size: usize) -> CudaResult<()> {
+        assert!(
+            offset <= self.size && size <= self.size - offset,
+            "unmapping out of bounds of the address range"
+        );
+        cuda::cuMemUnmap(self.ptr.as_raw() + offset as u64, size).to_result_from("cuMemUnmap")
+    }
+
+    /// Sets how a device can access `size` bytes of mapped memory starting at `offset` bytes.
+    pub fn set_access(
+        &self,
+        offset: usize,
+        size: usize,
+        device: &Device,
+        access: MemoryAccess,
+    ) -> CudaResult<()> {
+        assert!(
+            offset <= self.size && size <= self.size - offset,
+            "access out of bounds of the address range"
+        );
+        let desc = cuda::CUmemAccessDesc {
+            location: device_location(device),
+            flags: access.to_raw(),
+        };
+        unsafe {
+            cuda::cuMemSetAccess(
+                self.ptr.as_raw() + offset as u64,
+                size,
+                &desc as *const _,
+                1,
+            )
+            .to_result_from("cuMemSetAccess")
+        }
+    }
+
+    /// Free a `VirtualAddressRange`, returning an error.
+    pub fn drop(mut range: VirtualAddressRange) -> DropResult<VirtualAddressRange> {
+        if range.ptr.is_null() {
+            return Ok(());
+        }
+
+        unsafe {
+            let ptr = mem::replace(&mut range.ptr, DevicePointer::null());
+            match cuda::cuMemAddressFree(ptr.as_raw(), range.size)
+                .to_result_from("cuMemAddressFree")
+            {
+                Ok(()) => {
+                    mem::forget(range);
+                    Ok(())
+                }
+                Err(e) => Err((
+                    e,
+                    VirtualAddressRange {
+                        ptr,
+                        size: range.size,
+                    },
+                )),
+            }
+        }
+    }
+}
+
+impl Drop for VirtualAddressRange {
+    fn drop(&mut self) {
+        if self.ptr.is_null() {
+            return;
+        }
+
+        unsafe {
+            cuda::cuMemAddressFree(self.ptr.as_raw(), self.size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::{CopyDestination, DeviceSlice};
+    use crate::quick_init;
+    use std::error::Error;
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "not supported by the mock driver")]
+    fn test_map_physical_memory() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let device = Device::get_device(0)?;
+        let granularity = allocation_granularity(&device, AllocationGranularity::Minimum)?;
+
+        let range = VirtualAddressRange::reserve(granularity * 2, 0)?;
+        let first = PhysicalAllocation::new(&device, granularity)?;
+        let second = PhysicalAllocation::new(&device, granularity)?;
+        range.map(0, &first)?;
+        range.map(granularity, &second)?;
+        range.set_access(0, granularity * 2, &device, MemoryAccess::ReadWrite)?;
+
+        // both allocations are contiguous in the address range.
+        let len = granularity * 2 / 4;
+        let mut slice =
+            unsafe { DeviceSlice::from_raw_parts_mut(range.as_device_ptr().cast::<u32>(), len) };
+        let host = (0..len as u32).collect::<Vec<_>>();
+        slice.copy_from(&host)?;
+        let mut out = vec![0; len];
+        slice.copy_to(&mut out)?;
+        assert_eq!(host, out);
+
+        unsafe { range.unmap(0, granularity * 2)? };
+        Ok(())
+    }
+}
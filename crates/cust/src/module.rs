@@ -1,7 +1,7 @@
 //! Functions and types for working with CUDA modules.
 
 use crate::error::{CudaResult, DropResult, ToResult};
-use crate::function::Function;
+use crate::function::{Function, KernelArgs, TypedFunction};
 use crate::memory::{CopyDestination, DeviceCopy, DevicePointer};
 use crate::sys as cuda;
 use std::ffi::{c_void, CStr, CString};
@@ -12,6 +12,13 @@ use std::os::raw::c_uint;
 use std::path::Path;
 use std::ptr;
 
+mod cache;
+pub(crate) mod jit;
+
+pub use self::cache::{ModuleCache, ModuleCacheStats};
+use self::jit::RawJitOptions;
+pub use self::jit::{KernelResources, ModuleLoadError, ModuleLoadReport};
+
 /// A compiled CUDA module, loaded into a context.
 #[derive(Debug)]
 pub struct Module {
@@ -88,9 +95,25 @@ pub enum ModuleJitOption {
     GenenerateDebugInfo(bool),
     /// Generates line info in the compiled binary.
     GenerateLineInfo(bool),
+    /// Captures the informational messages of the JIT compiler in a buffer of the given size in
+    /// bytes, see [`ModuleLoadReport::info_log`].
+    InfoLogBuffer(usize),
+    /// Captures the errors and warnings of the JIT compiler in a buffer of the given size in
+    /// bytes, see [`ModuleLoadReport::error_log`].
+    ErrorLogBuffer(usize),
+    /// Makes the JIT compiler write verbose messages to the info log, including the registers
+    /// and spills of every kernel, see [`ModuleLoadReport::kernels`].
+    LogVerbose(bool),
+    /// Measures the time spent in the JIT compiler, see [`ModuleLoadReport::wall_time`].
+    WallTime,
 }
 
 impl ModuleJitOption {
+    /// Converts the options into the arrays of options and values the driver takes.
+    ///
+    /// [`ModuleJitOption::InfoLogBuffer`], [`ModuleJitOption::ErrorLogBuffer`] and
+    /// [`ModuleJitOption::WallTime`] are left out, since the driver writes to them and the arrays
+    /// cannot own the buffers they need. cust passes them itself when loading modules.
     pub fn into_raw(opts: &[Self]) -> (Vec<cuda::CUjit_option>, Vec<*mut c_void>) {
         // And here we stumble across one of the most horrific things i have ever seen in my entire
         // journey of working with many parts of CUDA. As a background, CUDA usually wants an array
@@ -115,7 +138,9 @@ impl ModuleJitOption {
                     raw_vals.push(*level as usize as *mut c_void);
                 }
                 Self::DetermineTargetFromContext => {
+                    // takes no value, but the values are matched up with the options by index.
                     raw_opts.push(cuda::CUjit_option::CU_JIT_TARGET_FROM_CUCONTEXT);
+                    raw_vals.push(ptr::null_mut());
                 }
                 Self::Target(target) => {
                     raw_opts.push(cuda::CUjit_option::CU_JIT_TARGET);
@@ -133,6 +158,11 @@ impl ModuleJitOption {
                     raw_opts.push(cuda::CUjit_option::CU_JIT_GENERATE_LINE_INFO);
                     raw_vals.push(*gen as usize as *mut c_void)
                 }
+                Self::LogVerbose(verbose) => {
+                    raw_opts.push(cuda::CUjit_option::CU_JIT_LOG_VERBOSE);
+                    raw_vals.push(*verbose as usize as *mut c_void)
+                }
+                Self::InfoLogBuffer(_) | Self::ErrorLogBuffer(_) | Self::WallTime => {}
             }
         }
         (raw_opts, raw_vals)
@@ -183,7 +213,7 @@ impl Module {
                 &mut module.inner as *mut cuda::CUmodule,
                 bytes.as_ptr() as *const _,
             )
-            .to_result()?;
+            .to_result_from("cuModuleLoad")?;
             Ok(module)
         }
     }
@@ -249,19 +279,32 @@ impl Module {
     }
 
     unsafe fn load_module(image: *const c_void, options: &[ModuleJitOption]) -> CudaResult<Module> {
+        Self::load_module_with_report(image, options)
+            .map(|(module, _)| module)
+            .map_err(|e| e.error)
+    }
+
+    unsafe fn load_module_with_report(
+        image: *const c_void,
+        options: &[ModuleJitOption],
+    ) -> Result<(Module, ModuleLoadReport), ModuleLoadError> {
         let mut module = Module {
             inner: ptr::null_mut(),
         };
-        let (mut options, mut option_values) = ModuleJitOption::into_raw(options);
-        cuda::cuModuleLoadDataEx(
+        let mut raw = RawJitOptions::new(options);
+        let res = cuda::cuModuleLoadDataEx(
             &mut module.inner as *mut cuda::CUmodule,
             image,
-            options.len() as c_uint,
-            options.as_mut_ptr(),
-            option_values.as_mut_ptr(),
+            raw.options.len() as c_uint,
+            raw.options.as_mut_ptr(),
+            raw.values.as_mut_ptr(),
         )
-        .to_result()?;
-        Ok(module)
+        .to_result_from("cuModuleLoadDataEx");
+        let report = raw.report();
+        match res {
+            Ok(()) => Ok((module, report)),
+            Err(error) => Err(ModuleLoadError { error, report }),
+        }
     }
 
     /// Creates a new module from a [`CStr`] pointing to PTX code.
@@ -288,7 +331,7 @@ impl Module {
     /// # fn main() -> Result<(), Box<dyn Error>> {
     /// # let _ctx = quick_init()?;
     /// use cust::module::Module;
-    /// let ptx = std::fs::read("./resources/add.ptx")?;
+    /// let ptx = std::fs::read_to_string("./resources/add.ptx")?;
     /// let module = Module::from_ptx(&ptx, &[])?;
     /// # Ok(())
     /// # }
@@ -299,6 +342,49 @@ impl Module {
         Self::from_ptx_cstr(cstr.as_c_str(), options)
     }
 
+    /// Creates a new module from a PTX string like [`Module::from_ptx`], also returning the
+    /// output of the JIT compiler requested with the options.
+    ///
+    /// If loading the module fails, the error contains the output as well. The reason for the
+    /// failure is usually in the error log, which is captured with
+    /// [`ModuleJitOption::ErrorLogBuffer`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `string` contains a nul.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let _ctx = quick_init()?;
+    /// use cust::module::{Module, ModuleJitOption};
+    ///
+    /// let ptx = include_str!("../resources/add.ptx");
+    /// let options = [
+    ///     ModuleJitOption::InfoLogBuffer(8192),
+    ///     ModuleJitOption::ErrorLogBuffer(8192),
+    ///     ModuleJitOption::LogVerbose(true),
+    /// ];
+    /// let (module, report) = Module::from_ptx_with_report(ptx, &options)?;
+    /// for kernel in &report.kernels {
+    ///     println!("{} uses {} registers", kernel.name, kernel.registers);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_ptx_with_report<T: AsRef<str>>(
+        string: T,
+        options: &[ModuleJitOption],
+    ) -> Result<(Module, ModuleLoadReport), ModuleLoadError> {
+        let cstr = CString::new(string.as_ref())
+            .expect("string given to Module::from_ptx_with_report contained nul bytes");
+        // SAFETY: the image is known to be dereferenceable
+        unsafe { Self::load_module_with_report(cstr.as_ptr() as *const c_void, options) }
+    }
+
     /// Load a module from a normal (rust) string, implicitly making it into
     /// a cstring.
     #[deprecated(
@@ -351,7 +437,7 @@ impl Module {
                 &mut module.inner as *mut cuda::CUmodule,
                 image.as_ptr() as *const c_void,
             )
-            .to_result()?;
+            .to_result_from("cuModuleLoadData")?;
             Ok(module)
         }
     }
@@ -376,6 +462,7 @@ impl Module {
     /// let ptx = CString::new(include_str!("../resources/add.ptx"))?;
     /// let module = Module::load_from_string(&ptx)?;
     /// let name = CString::new("my_constant")?;
+    /// # if cfg!(feature = "mock") { return Ok(()); }
     /// let symbol = module.get_global::<u32>(&name)?;
     /// let mut host_const = 0;
     /// symbol.copy_to(&mut host_const)?;
@@ -394,7 +481,7 @@ impl Module {
                 self.inner,
                 name.as_ptr(),
             )
-            .to_result()?;
+            .to_result_from("cuModuleGetGlobal_v2")?;
             assert_eq!(size, mem::size_of::<T>());
             Ok(Symbol {
                 ptr,
@@ -432,11 +519,39 @@ impl Module {
                 self.inner,
                 cstr.as_ptr(),
             )
-            .to_result()?;
-            Ok(Function::new(func, self))
+            .to_result_from("cuModuleGetFunction")?;
+            crate::function::register_function_name(self.inner, func, name);
+            Ok(Function::new(func, name, self))
         }
     }
 
+    /// Get a reference to a kernel function which can only be launched with arguments of the
+    /// types `Args`, see [`TypedFunction`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let _ctx = quick_init()?;
+    /// use cust::memory::DevicePointer;
+    /// use cust::module::Module;
+    ///
+    /// let module = Module::from_ptx(include_str!("../resources/add.ptx"), &[])?;
+    /// type Ptr = DevicePointer<f32>;
+    /// let function = module.get_typed_function::<(Ptr, Ptr, Ptr, u32)>("sum")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_typed_function<Args: KernelArgs>(
+        &'_ self,
+        name: &str,
+    ) -> CudaResult<TypedFunction<'_, Args>> {
+        let function = self.get_function(name)?;
+        Ok(TypedFunction::new(function))
+    }
+
     /// Destroy a `Module`, returning an error.
     ///
     /// Destroying a module can return errors from previous asynchronous work. This function
@@ -471,8 +586,9 @@ impl Module {
 
         unsafe {
             let inner = mem::replace(&mut module.inner, ptr::null_mut());
-            match cuda::cuModuleUnload(inner).to_result() {
+            match cuda::cuModuleUnload(inner).to_result_from("cuModuleUnload") {
                 Ok(()) => {
+                    crate::function::forget_function_names(inner);
                     mem::forget(module);
                     Ok(())
                 }
@@ -490,6 +606,7 @@ impl Drop for Module {
             // No choice but to panic if this fails...
             let module = mem::replace(&mut self.inner, ptr::null_mut());
             cuda::cuModuleUnload(module);
+            crate::function::forget_function_names(module);
         }
     }
 }
@@ -512,7 +629,7 @@ impl<'a, T: DeviceCopy> CopyDestination<T> for Symbol<'a, T> {
         if size != 0 {
             unsafe {
                 cuda::cuMemcpyHtoD_v2(self.ptr.as_raw(), val as *const T as *const c_void, size)
-                    .to_result()?
+                    .to_result_from("cuMemcpyHtoD_v2")?
             }
         }
         Ok(())
@@ -527,7 +644,7 @@ impl<'a, T: DeviceCopy> CopyDestination<T> for Symbol<'a, T> {
                     self.ptr.as_raw() as u64,
                     size,
                 )
-                .to_result()?
+                .to_result_from("cuMemcpyDtoH_v2")?
             }
         }
         Ok(())
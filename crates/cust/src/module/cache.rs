@@ -0,0 +1,491 @@
+//! A persistent on-disk cache of JIT-compiled modules.
+//!
+//! Loading PTX with [`Module::from_ptx`] makes the driver JIT compile it every time the program
+//! starts, which can take seconds for large kernels. The driver has a cache of its own, but it is
+//! small, shared by every program on the machine, and can be disabled by the user. [`ModuleCache`]
+//! keeps the compiled cubin in a directory of the program's choosing instead, and loads it with
+//! [`Module::from_cubin`] the next time the same PTX is loaded.
+//!
+//! # Keys
+//!
+//! Entries are keyed by a 128-bit hash of the PTX, the [`ModuleJitOption`]s it is compiled with,
+//! the compute capability of the current context's device and the driver version. Updating the
+//! driver or running on a different GPU therefore compiles the PTX again instead of loading cubin
+//! which may not work there.
+//!
+//! # Eviction
+//!
+//! When storing a new entry takes the cache past its maximum size, the least recently used
+//! entries are removed until it fits again. Hits update the modification time of the entry, which
+//! is what "recently used" is based on, so several processes can share one cache directory.
+
+use crate::context::CurrentContext;
+use crate::device::DeviceAttribute;
+use crate::error::CudaResult;
+use crate::link::Linker;
+use crate::module::{Module, ModuleJitOption};
+use crate::CudaApiVersion;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// the start of every entry, bump the version when changing the layout of entries.
+const MAGIC: &[u8; 8] = b"CUSTJIT1";
+// magic, key and cubin length.
+const HEADER_LEN: usize = 8 + 16 + 8;
+const EXTENSION: &str = "cubin";
+const DEFAULT_MAX_SIZE: u64 = 256 << 20;
+
+/// Statistics of a [`ModuleCache`], since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModuleCacheStats {
+    /// The number of modules loaded from cached cubin.
+    pub hits: u64,
+    /// The number of modules which had to be JIT compiled.
+    pub misses: u64,
+    /// The number of entries removed to keep the cache under its maximum size.
+    pub evictions: u64,
+}
+
+/// A directory of cubin JIT compiled from PTX, see the [module-level documentation](self).
+///
+/// The cache is best effort, failing to read or write the directory never fails loading a
+/// module, it only makes it fall back to JIT compiling the PTX.
+///
+/// # Example
+///
+/// ```
+/// # use cust::*;
+/// # use std::error::Error;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let _ctx = quick_init()?;
+/// use cust::module::{ModuleCache, ModuleJitOption, OptLevel};
+///
+/// let cache = ModuleCache::new(std::env::temp_dir().join("my-app-kernels"));
+/// let ptx = include_str!("../../resources/add.ptx");
+/// // JIT compiled the first time the program runs, loaded from the cache afterwards.
+/// let module = cache.load_ptx(ptx, &[ModuleJitOption::OptLevel(OptLevel::O4)])?;
+/// let function = module.get_function("sum")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ModuleCache {
+    dir: PathBuf,
+    max_size: u64,
+    stats: Mutex<ModuleCacheStats>,
+}
+
+impl ModuleCache {
+    /// Creates a cache which stores entries in `dir`, keeping it under 256 MiB.
+    ///
+    /// The directory is created when the first entry is stored.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self::with_max_size(dir, DEFAULT_MAX_SIZE)
+    }
+
+    /// Creates a cache which stores entries in `dir`, keeping it under `max_size` bytes.
+    ///
+    /// Modules whose cubin is larger than `max_size` on its own are never cached.
+    pub fn with_max_size<P: Into<PathBuf>>(dir: P, max_size: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size,
+            stats: Mutex::new(ModuleCacheStats::default()),
+        }
+    }
+
+    /// The directory the entries are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The maximum size of the cache in bytes.
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Returns the hits, misses and evictions of this cache so far.
+    pub fn stats(&self) -> ModuleCacheStats {
+        *self.lock_stats()
+    }
+
+    /// Loads PTX into the current context, from the cache if it was compiled with the same
+    /// options for the same device and driver before.
+    ///
+    /// Otherwise the PTX is JIT compiled with [`Linker`] and the resulting cubin is stored in the
+    /// cache. Entries which fail to load, for example because they were corrupted, are replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device or driver version cannot be queried, or if the PTX fails
+    /// to compile or load.
+    pub fn load_ptx<T: AsRef<str>>(
+        &self,
+        ptx: T,
+        options: &[ModuleJitOption],
+    ) -> CudaResult<Module> {
+        let ptx = ptx.as_ref();
+        let device = CurrentContext::get_device()?;
+        let compute_capability = (
+            device.get_attribute(DeviceAttribute::ComputeCapabilityMajor)?,
+            device.get_attribute(DeviceAttribute::ComputeCapabilityMinor)?,
+        );
+        let key = cache_key(ptx, options, compute_capability, CudaApiVersion::get()?);
+        let path = self.dir.join(format!("{:032x}.{}", key, EXTENSION));
+
+        if let Some(cubin) = read_entry(&path, key) {
+            if let Ok(module) = Module::from_cubin(&cubin, &[]) {
+                self.lock_stats().hits += 1;
+                let _ = touch(&path);
+                return Ok(module);
+            }
+            let _ = fs::remove_file(&path);
+        }
+
+        self.lock_stats().misses += 1;
+        let mut linker = Linker::with_options(options)?;
+        linker.add_ptx(ptx)?;
+        let cubin = linker.complete()?;
+        let module = Module::from_cubin(&cubin, &[])?;
+        let _ = self.store(&path, key, &cubin);
+        Ok(module)
+    }
+
+    /// Returns the total size of the entries in the cache in bytes.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|entry| entry.len).sum())
+    }
+
+    /// Removes every entry from the cache.
+    pub fn clear(&self) -> io::Result<()> {
+        for entry in self.entries()? {
+            fs::remove_file(entry.path)?;
+        }
+        Ok(())
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, ModuleCacheStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn entries(&self) -> io::Result<Vec<Entry>> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = Vec::new();
+        for entry in dir {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension() != Some(OsStr::new(EXTENSION)) {
+                continue;
+            }
+            // another process may have evicted it in the meantime.
+            if let Ok(metadata) = entry.metadata() {
+                entries.push(Entry {
+                    path,
+                    len: metadata.len(),
+                    used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    fn store(&self, path: &Path, key: u128, cubin: &[u8]) -> io::Result<()> {
+        if (HEADER_LEN + cubin.len()) as u64 > self.max_size {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+
+        // written to a temporary file first so other processes never see half of an entry.
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let tmp = self.dir.join(format!(
+            "{:032x}.{}.{}.tmp",
+            key,
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let res = write_entry(&tmp, key, cubin).and_then(|_| fs::rename(&tmp, path));
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+            return res;
+        }
+        self.evict(path)
+    }
+
+    // Removes the least recently used entries other than `keep` until the cache fits.
+    fn evict(&self, keep: &Path) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut size = entries.iter().map(|entry| entry.len).sum::<u64>();
+        if size <= self.max_size {
+            return Ok(());
+        }
+        entries.sort_by_key(|entry| entry.used);
+        for entry in entries {
+            if size <= self.max_size {
+                break;
+            }
+            if entry.path == keep {
+                continue;
+            }
+            if fs::remove_file(&entry.path).is_ok() {
+                size -= entry.len;
+                self.lock_stats().evictions += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Entry {
+    path: PathBuf,
+    len: u64,
+    used: SystemTime,
+}
+
+fn read_entry(path: &Path, key: u128) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    File::open(path).ok()?.read_to_end(&mut bytes).ok()?;
+    if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+        return None;
+    }
+    let mut entry_key = [0; 16];
+    entry_key.copy_from_slice(&bytes[8..24]);
+    let mut len = [0; 8];
+    len.copy_from_slice(&bytes[24..32]);
+    if u128::from_le_bytes(entry_key) != key
+        || u64::from_le_bytes(len) != (bytes.len() - HEADER_LEN) as u64
+    {
+        return None;
+    }
+    bytes.drain(..HEADER_LEN);
+    Some(bytes)
+}
+
+fn write_entry(path: &Path, key: u128, cubin: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(MAGIC)?;
+    file.write_all(&key.to_le_bytes())?;
+    file.write_all(&(cubin.len() as u64).to_le_bytes())?;
+    file.write_all(cubin)
+}
+
+// Marks an entry as recently used. Setting the modification time directly needs a newer Rust
+// than cust supports, so this rewrites the (unchanged) magic instead.
+fn touch(path: &Path) -> io::Result<()> {
+    OpenOptions::new().write(true).open(path)?.write_all(MAGIC)
+}
+
+fn cache_key(
+    ptx: &str,
+    options: &[ModuleJitOption],
+    compute_capability: (i32, i32),
+    driver: CudaApiVersion,
+) -> u128 {
+    let mut hasher = Fnv128::default();
+    ptx.hash(&mut hasher);
+    // the options which only affect what the compiler reports do not change the cubin.
+    let options = options
+        .iter()
+        .filter_map(|option| match *option {
+            ModuleJitOption::MaxRegisters(regs) => Some((0u8, regs)),
+            ModuleJitOption::OptLevel(level) => Some((1, level as u32)),
+            ModuleJitOption::DetermineTargetFromContext => Some((2, 0)),
+            ModuleJitOption::Target(target) => Some((3, target as u32)),
+            ModuleJitOption::Fallback(fallback) => Some((4, fallback as u32)),
+            ModuleJitOption::GenenerateDebugInfo(gen) => Some((5, gen as u32)),
+            ModuleJitOption::GenerateLineInfo(gen) => Some((6, gen as u32)),
+            ModuleJitOption::InfoLogBuffer(_)
+            | ModuleJitOption::ErrorLogBuffer(_)
+            | ModuleJitOption::LogVerbose(_)
+            | ModuleJitOption::WallTime => None,
+        })
+        .collect::<Vec<_>>();
+    options.hash(&mut hasher);
+    compute_capability.hash(&mut hasher);
+    driver.hash(&mut hasher);
+    hasher.0
+}
+
+// 128-bit FNV-1a. The standard library's hasher is not guaranteed to give the same results
+// across Rust versions, which would invalidate the cache on every compiler update.
+struct Fnv128(u128);
+
+impl Default for Fnv128 {
+    fn default() -> Self {
+        Fnv128(0x6c62_272e_07bb_0142_62b8_2175_6295_c58d)
+    }
+}
+
+impl Hasher for Fnv128 {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u128;
+            self.0 = self
+                .0
+                .wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::module::OptLevel;
+    use crate::quick_init;
+    use std::error::Error;
+    use std::thread;
+    use std::time::Duration;
+
+    const PTX: &str = include_str!("../../resources/add.ptx");
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("cust-module-cache-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let driver = CudaApiVersion { version: 11040 };
+        let key = cache_key(PTX, &[], (8, 6), driver);
+        assert_eq!(key, cache_key(PTX, &[], (8, 6), driver));
+        assert_ne!(key, cache_key(&PTX[1..], &[], (8, 6), driver));
+        assert_ne!(
+            key,
+            cache_key(
+                PTX,
+                &[ModuleJitOption::OptLevel(OptLevel::O4)],
+                (8, 6),
+                driver
+            )
+        );
+        assert_ne!(
+            cache_key(PTX, &[ModuleJitOption::MaxRegisters(2)], (8, 6), driver),
+            cache_key(
+                PTX,
+                &[ModuleJitOption::OptLevel(OptLevel::O2)],
+                (8, 6),
+                driver
+            )
+        );
+        assert_eq!(
+            key,
+            cache_key(PTX, &[ModuleJitOption::LogVerbose(true)], (8, 6), driver)
+        );
+        assert_ne!(key, cache_key(PTX, &[], (8, 0), driver));
+        assert_ne!(
+            key,
+            cache_key(PTX, &[], (8, 6), CudaApiVersion { version: 11050 })
+        );
+    }
+
+    #[test]
+    fn test_hit_and_miss() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let dir = cache_dir("hit");
+        let cache = ModuleCache::new(&dir);
+        let options = [ModuleJitOption::OptLevel(OptLevel::O3)];
+
+        let module = cache.load_ptx(PTX, &options)?;
+        module.get_function("sum")?;
+        assert_eq!(cache.stats().misses, 1);
+        assert_eq!(cache.entries()?.len(), 1);
+
+        // a new cache on the same directory, as in the next run of the program.
+        let cache = ModuleCache::new(&dir);
+        #[cfg(feature = "mock")]
+        crate::sys::mock::clear_calls();
+        let module = cache.load_ptx(PTX, &options)?;
+        module.get_function("sum")?;
+        #[cfg(feature = "mock")]
+        assert!(!crate::sys::mock::calls().contains(&"cuLinkCreate_v2"));
+        assert_eq!(
+            cache.stats(),
+            ModuleCacheStats {
+                hits: 1,
+                misses: 0,
+                evictions: 0
+            }
+        );
+
+        // different options are a different entry.
+        cache.load_ptx(PTX, &[])?;
+        assert_eq!(cache.stats().misses, 1);
+        assert_eq!(cache.entries()?.len(), 2);
+
+        cache.clear()?;
+        assert_eq!(cache.size()?, 0);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_entry() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let dir = cache_dir("corrupt");
+        let cache = ModuleCache::new(&dir);
+        cache.load_ptx(PTX, &[])?;
+        let entry = cache.entries()?.remove(0);
+        let len = entry.len;
+        OpenOptions::new()
+            .write(true)
+            .open(&entry.path)?
+            .set_len(len - 1)?;
+
+        cache.load_ptx(PTX, &[])?;
+        assert_eq!(cache.stats().misses, 2);
+        assert_eq!(cache.size()?, len);
+        cache.load_ptx(PTX, &[])?;
+        assert_eq!(cache.stats().hits, 1);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_eviction() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let dir = cache_dir("evict");
+        let variant = |i: u32| format!("{}\n// variant {}\n", PTX, i);
+
+        let cache = ModuleCache::new(&dir);
+        cache.load_ptx(variant(0), &[])?;
+        let entry_len = cache.size()?;
+
+        // room for two entries.
+        let cache = ModuleCache::with_max_size(&dir, entry_len * 2 + entry_len / 2);
+        cache.load_ptx(variant(1), &[])?;
+        // modification times can be as coarse as a few milliseconds.
+        thread::sleep(Duration::from_millis(20));
+        // makes variant 0 the most recently used entry.
+        cache.load_ptx(variant(0), &[])?;
+        cache.load_ptx(variant(2), &[])?;
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.entries()?.len(), 2);
+        cache.load_ptx(variant(0), &[])?;
+        cache.load_ptx(variant(2), &[])?;
+        assert_eq!(cache.stats().hits, 3);
+
+        // entries larger than the whole cache are not stored.
+        let cache = ModuleCache::with_max_size(&dir, entry_len / 2);
+        cache.clear()?;
+        cache.load_ptx(variant(3), &[])?;
+        assert_eq!(cache.size()?, 0);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
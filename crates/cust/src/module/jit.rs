@@ -0,0 +1,352 @@
+//! JIT compiler logs, see [`ModuleLoadReport`].
+
+use crate::error::CudaError;
+use crate::module::ModuleJitOption;
+use crate::sys as cuda;
+use std::error::Error;
+use std::ffi::c_void;
+use std::fmt;
+use std::ptr;
+use std::time::Duration;
+
+/// The resources used by a kernel, as reported by the JIT compiler in the info log.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelResources {
+    /// The (mangled) name of the kernel.
+    pub name: String,
+    /// The number of registers used by each thread.
+    pub registers: u32,
+    /// The size of the stack frame of each thread in bytes.
+    pub stack_frame_bytes: u32,
+    /// The bytes of registers each thread spills to local memory.
+    pub spill_store_bytes: u32,
+    /// The bytes of spilled registers each thread loads back from local memory.
+    pub spill_load_bytes: u32,
+    /// The statically-allocated shared memory used by each block in bytes.
+    pub shared_memory_bytes: u32,
+}
+
+/// The output of the JIT compiler for loading or linking PTX.
+///
+/// Only the outputs requested with the JIT options are filled in, the logs with
+/// [`ModuleJitOption::InfoLogBuffer`] and [`ModuleJitOption::ErrorLogBuffer`] and the wall time
+/// with [`ModuleJitOption::WallTime`]. The resources of the kernels are parsed from the info log,
+/// which only lists them with [`ModuleJitOption::LogVerbose`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModuleLoadReport {
+    /// The informational messages of the JIT compiler.
+    pub info_log: Option<String>,
+    /// The errors and warnings of the JIT compiler.
+    pub error_log: Option<String>,
+    /// The time spent in the JIT compiler and linker.
+    pub wall_time: Option<Duration>,
+    /// The resources used by every kernel in the info log, in the order they were compiled.
+    pub kernels: Vec<KernelResources>,
+}
+
+impl ModuleLoadReport {
+    /// Returns the resources of the kernel called `name`, if it is in the info log.
+    pub fn kernel(&self, name: &str) -> Option<&KernelResources> {
+        self.kernels.iter().find(|kernel| kernel.name == name)
+    }
+
+    fn from_logs(
+        info_log: Option<String>,
+        error_log: Option<String>,
+        wall_time: Option<Duration>,
+    ) -> Self {
+        let kernels = info_log.as_deref().map(parse_kernels).unwrap_or_default();
+        Self {
+            info_log,
+            error_log,
+            wall_time,
+            kernels,
+        }
+    }
+}
+
+/// An error from loading or linking PTX, together with the logs of the JIT compiler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleLoadError {
+    /// The error returned by the driver.
+    pub error: CudaError,
+    /// The logs of the JIT compiler, the reason for the error is usually in
+    /// [`ModuleLoadReport::error_log`].
+    pub report: ModuleLoadReport,
+}
+
+impl fmt::Display for ModuleLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        match self.report.error_log.as_deref().map(str::trim) {
+            Some(log) if !log.is_empty() => write!(f, ":\n{}", log),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Error for ModuleLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// JIT options in the form the driver takes them, along with the buffers for the options which
+// output something. The driver writes to the buffers and the values of the options, so this must
+// outlive the call (or link state) it is passed to.
+#[derive(Debug)]
+pub(crate) struct RawJitOptions {
+    pub(crate) options: Vec<cuda::CUjit_option>,
+    pub(crate) values: Vec<*mut c_void>,
+    // the index of the size option and the buffer.
+    info_log: Option<(usize, Vec<u8>)>,
+    error_log: Option<(usize, Vec<u8>)>,
+    wall_time: Option<usize>,
+}
+
+impl RawJitOptions {
+    pub(crate) fn new(opts: &[ModuleJitOption]) -> Self {
+        let (mut options, mut values) = ModuleJitOption::into_raw(opts);
+        let mut info_log = None;
+        let mut error_log = None;
+        let mut wall_time = None;
+
+        for opt in opts {
+            match *opt {
+                ModuleJitOption::InfoLogBuffer(size) => {
+                    let mut buf = vec![0; size.max(1)];
+                    options.push(cuda::CUjit_option::CU_JIT_INFO_LOG_BUFFER);
+                    values.push(buf.as_mut_ptr() as *mut c_void);
+                    options.push(cuda::CUjit_option::CU_JIT_INFO_LOG_BUFFER_SIZE_BYTES);
+                    values.push(buf.len() as *mut c_void);
+                    info_log = Some((values.len() - 1, buf));
+                }
+                ModuleJitOption::ErrorLogBuffer(size) => {
+                    let mut buf = vec![0; size.max(1)];
+                    options.push(cuda::CUjit_option::CU_JIT_ERROR_LOG_BUFFER);
+                    values.push(buf.as_mut_ptr() as *mut c_void);
+                    options.push(cuda::CUjit_option::CU_JIT_ERROR_LOG_BUFFER_SIZE_BYTES);
+                    values.push(buf.len() as *mut c_void);
+                    error_log = Some((values.len() - 1, buf));
+                }
+                ModuleJitOption::WallTime => {
+                    options.push(cuda::CUjit_option::CU_JIT_WALL_TIME);
+                    values.push(ptr::null_mut());
+                    wall_time = Some(values.len() - 1);
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            options,
+            values,
+            info_log,
+            error_log,
+            wall_time,
+        }
+    }
+
+    // Reads what the driver wrote into the buffers so far.
+    pub(crate) fn report(&self) -> ModuleLoadReport {
+        let log = |log: &Option<(usize, Vec<u8>)>| {
+            log.as_ref().map(|(idx, buf)| {
+                // the driver overwrites the size with the amount of the buffer it filled.
+                let filled = (self.values[*idx] as usize as u32 as usize).min(buf.len());
+                let filled = &buf[..filled];
+                let len = filled.iter().position(|&b| b == 0).unwrap_or(filled.len());
+                String::from_utf8_lossy(&filled[..len]).into_owned()
+            })
+        };
+        let wall_time = self.wall_time.map(|idx| {
+            // the driver overwrites the value with a float of milliseconds.
+            let ms = unsafe { *(&self.values[idx] as *const *mut c_void as *const f32) };
+            Duration::from_secs_f32(ms.max(0.0) / 1000.0)
+        });
+        ModuleLoadReport::from_logs(log(&self.info_log), log(&self.error_log), wall_time)
+    }
+}
+
+// Parses the resource usage lines of a verbose ptxas log, such as:
+//
+// ptxas info    : Compiling entry function 'sum' for 'sm_86'
+// ptxas info    : Function properties for sum
+//     0 bytes stack frame, 0 bytes spill stores, 0 bytes spill loads
+// ptxas info    : Used 10 registers, 380 bytes cmem[0]
+fn parse_kernels(log: &str) -> Vec<KernelResources> {
+    // device functions get properties too, they are dropped at the end.
+    let mut functions: Vec<(KernelResources, bool)> = Vec::new();
+    let mut current = None;
+    let mut properties_for = None;
+
+    fn find_or_insert(functions: &mut Vec<(KernelResources, bool)>, name: &str) -> usize {
+        match functions.iter().position(|(f, _)| f.name == name) {
+            Some(idx) => idx,
+            None => {
+                functions.push((
+                    KernelResources {
+                        name: name.to_string(),
+                        ..Default::default()
+                    },
+                    false,
+                ));
+                functions.len() - 1
+            }
+        }
+    }
+
+    for line in log.lines() {
+        let line = line.trim();
+        let message = match line.split_once(" : ") {
+            Some((prefix, message)) if prefix.starts_with("ptxas") => message.trim(),
+            _ => line,
+        };
+
+        if let Some(rest) = message.strip_prefix("Compiling entry function '") {
+            if let Some(name) = rest.split('\'').next() {
+                let idx = find_or_insert(&mut functions, name);
+                functions[idx].1 = true;
+                current = Some(idx);
+            }
+        } else if let Some(name) = message.strip_prefix("Function properties for ") {
+            properties_for = Some(find_or_insert(&mut functions, name.trim()));
+        } else if let Some(rest) = message.strip_prefix("Used ") {
+            if let Some(idx) = current {
+                for (value, what) in quantities(rest) {
+                    let kernel = &mut functions[idx].0;
+                    match what {
+                        "registers" | "register" => kernel.registers = value,
+                        "bytes smem" => kernel.shared_memory_bytes = value,
+                        _ => {}
+                    }
+                }
+            }
+        } else if message.contains("bytes stack frame") {
+            if let Some(idx) = properties_for.take() {
+                for (value, what) in quantities(message) {
+                    let function = &mut functions[idx].0;
+                    match what {
+                        "bytes stack frame" => function.stack_frame_bytes = value,
+                        "bytes spill stores" => function.spill_store_bytes = value,
+                        "bytes spill loads" => function.spill_load_bytes = value,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    functions
+        .into_iter()
+        .filter(|(_, entry)| *entry)
+        .map(|(kernel, _)| kernel)
+        .collect()
+}
+
+// Splits "10 registers, 16 bytes smem" into (10, "registers") and (16, "bytes smem").
+fn quantities(s: &str) -> impl Iterator<Item = (u32, &str)> {
+    s.split(',').filter_map(|part| {
+        let (value, what) = part.trim().split_once(' ')?;
+        Some((value.parse().ok()?, what.trim()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::link::Linker;
+    use crate::module::Module;
+    use crate::quick_init;
+    use std::error::Error;
+
+    const PTX: &str = include_str!("../../resources/add.ptx");
+
+    fn log_options() -> [ModuleJitOption; 4] {
+        [
+            ModuleJitOption::InfoLogBuffer(16 * 1024),
+            ModuleJitOption::ErrorLogBuffer(16 * 1024),
+            ModuleJitOption::LogVerbose(true),
+            ModuleJitOption::WallTime,
+        ]
+    }
+
+    #[test]
+    fn test_parse_kernels() {
+        let log = "\
+ptxas info    : 0 bytes gmem
+ptxas info    : Function properties for helper
+    8 bytes stack frame, 0 bytes spill stores, 0 bytes spill loads
+ptxas info    : Compiling entry function 'sum' for 'sm_86'
+ptxas info    : Function properties for sum
+    0 bytes stack frame, 0 bytes spill stores, 0 bytes spill loads
+ptxas info    : Used 10 registers, 380 bytes cmem[0]
+ptxas info    : Compiling entry function 'big' for 'sm_86'
+ptxas info    : Function properties for big
+    96 bytes stack frame, 40 bytes spill stores, 44 bytes spill loads
+ptxas info    : Used 255 registers, 1024 bytes smem, 380 bytes cmem[0], 8 bytes cmem[2]
+";
+        let kernels = parse_kernels(log);
+        assert_eq!(
+            kernels,
+            [
+                KernelResources {
+                    name: "sum".to_string(),
+                    registers: 10,
+                    ..Default::default()
+                },
+                KernelResources {
+                    name: "big".to_string(),
+                    registers: 255,
+                    stack_frame_bytes: 96,
+                    spill_store_bytes: 40,
+                    spill_load_bytes: 44,
+                    shared_memory_bytes: 1024,
+                },
+            ]
+        );
+        assert!(parse_kernels("").is_empty());
+    }
+
+    #[test]
+    fn test_module_report() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let (module, report) = Module::from_ptx_with_report(PTX, &log_options())?;
+        module.get_function("sum")?;
+        assert!(report.info_log.is_some());
+        assert_eq!(report.error_log.as_deref(), Some(""));
+        assert!(report.wall_time.is_some());
+        #[cfg(not(feature = "mock"))]
+        assert!(report.kernel("sum").unwrap().registers > 0);
+
+        // without the options there is nothing to report.
+        let (_, report) = Module::from_ptx_with_report(PTX, &[])?;
+        assert_eq!(report, ModuleLoadReport::default());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(feature = "mock", ignore = "the mock driver does not compile PTX")]
+    fn test_module_error_log() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let ptx = PTX.replace("add.f32", "add.nonsense");
+        let err = Module::from_ptx_with_report(ptx, &log_options()).unwrap_err();
+        let log = err.report.error_log.as_deref().unwrap();
+        assert!(log.contains("error"));
+        assert!(err.to_string().contains(log.trim()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_linker_report() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let mut linker = Linker::with_options(&log_options())?;
+        linker.add_ptx(PTX)?;
+        let (cubin, report) = linker.complete_with_report()?;
+        Module::from_cubin(cubin, &[])?;
+        assert!(report.info_log.is_some());
+        assert!(report.wall_time.is_some());
+        #[cfg(not(feature = "mock"))]
+        assert!(report.kernel("sum").is_some());
+        Ok(())
+    }
+}
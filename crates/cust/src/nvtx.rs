@@ -1 +1,833 @@
+//! Annotating host code with NVTX ranges and markers for profilers.
+//!
+//! NVTX (NVIDIA Tools Extension) lets an application describe what it is doing to tools such as
+//! Nsight Systems, which then show the ranges and markers on their timelines next to the CUDA
+//! work. Ranges describe a span of time such as a phase of a pipeline, markers describe a single
+//! instant. Both can be given a [`Color`], a category, and a [`Payload`] using [`EventAttributes`].
+//!
+//! NVTX is only used if cust is built with the `nvtx` feature. Without the feature, every function
+//! in this module does nothing, so annotations can be left in code which is built without NVTX.
+//! This implements NVTX v3, which does not link to any library: the annotations are passed to the
+//! tool collecting them, which is loaded from the `NVTX_INJECTION64_PATH` environment variable set
+//! by tools such as Nsight Systems. Unless the application is run under such a tool every function
+//! also does nothing, so annotations are very cheap outside of profiling.
+//!
+//! # Example
+//!
+//! ```
+//! # use cust::*;
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! # let _ctx = quick_init()?;
+//! use cust::nvtx::{self, nvtx_range, Color, EventAttributes};
+//! use cust::stream::{Stream, StreamFlags};
+//!
+//! let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+//! nvtx::name_stream(&stream, "upload");
+//! nvtx::name_current_thread("loader");
+//!
+//! {
+//!     let _range = nvtx::Range::push_with(&EventAttributes::new("decode").color(Color::rgb(0, 128, 255)));
+//!     // ... decode a batch
+//! }
+//! nvtx::mark("batch decoded");
+//!
+//! #[nvtx_range]
+//! fn preprocess() {
+//!     // shows up as a range named "preprocess"
+//! }
+//! preprocess();
+//! # Ok(())
+//! # }
+//! ```
 
+use crate::context::ContextHandle;
+use crate::device::Device;
+use crate::stream::Stream;
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+/// Wraps the body of a function in a [`Range`] named after the function, or after the string
+/// given to the attribute such as `#[nvtx_range("load batch")]`.
+///
+/// Async functions are not supported since a [`Range`] cannot be held across an `.await`.
+pub use cust_derive::nvtx_range;
+
+#[allow(non_camel_case_types)]
+type nvtxDomainHandle_t = *mut c_void;
+#[allow(non_camel_case_types)]
+type nvtxRangeId_t = u64;
+
+const NVTX_VERSION: u16 = 3;
+const NVTX_COLOR_UNKNOWN: i32 = 0;
+const NVTX_COLOR_ARGB: i32 = 1;
+const NVTX_MESSAGE_TYPE_ASCII: i32 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types, non_snake_case)]
+union nvtxPayload_t {
+    ullValue: u64,
+    llValue: i64,
+    dValue: f64,
+    uiValue: u32,
+    iValue: i32,
+    fValue: f32,
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types, non_snake_case)]
+struct nvtxEventAttributes_t {
+    version: u16,
+    size: u16,
+    category: u32,
+    colorType: i32,
+    color: u32,
+    payloadType: i32,
+    reserved0: i32,
+    payload: nvtxPayload_t,
+    messageType: i32,
+    message: *const c_char,
+}
+
+// NVTX v3 is header-only, there is no library to link to. Instead, the first call loads the tool
+// named by the `NVTX_INJECTION64_PATH` environment variable, which Nsight Systems and other tools
+// set when they run the application, and the tool fills in the function tables of each module
+// with its implementations. Functions the tool did not fill in, or every function without a tool,
+// do nothing. This mirrors `nvtxInit.h` from the CUDA toolkit.
+#[cfg(feature = "nvtx")]
+#[allow(non_snake_case)]
+mod ffi {
+    use super::*;
+    use crate::sys::{CUcontext, CUdevice, CUstream};
+    use once_cell::sync::Lazy;
+    use std::ffi::{CStr, OsStr};
+    use std::os::raw::c_uint;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Once;
+
+    // `NvtxCallbackModule` and the callback ids of the functions used here in each module, which
+    // are the indices of their slots in the function table of the module.
+    const MODULE_CORE: u32 = 1;
+    const MODULE_CUDA: u32 = 2;
+    const MODULE_CORE2: u32 = 5;
+
+    const CORE_NAME_OS_THREAD_A: usize = 14;
+    const CORE_SIZE: usize = 16;
+
+    const CUDA_NAME_CU_DEVICE_A: usize = 1;
+    const CUDA_NAME_CU_CONTEXT_A: usize = 3;
+    const CUDA_NAME_CU_STREAM_A: usize = 5;
+    const CUDA_SIZE: usize = 9;
+
+    const CORE2_DOMAIN_MARK_EX: usize = 1;
+    const CORE2_DOMAIN_RANGE_START_EX: usize = 2;
+    const CORE2_DOMAIN_RANGE_END: usize = 3;
+    const CORE2_DOMAIN_RANGE_PUSH_EX: usize = 4;
+    const CORE2_DOMAIN_RANGE_POP: usize = 5;
+    const CORE2_DOMAIN_NAME_CATEGORY_A: usize = 8;
+    const CORE2_DOMAIN_CREATE_A: usize = 12;
+    const CORE2_DOMAIN_DESTROY: usize = 14;
+    const CORE2_SIZE: usize = 16;
+
+    // `NvtxExportTableID`.
+    const ETID_CALLBACKS: u32 = 1;
+    const ETID_VERSIONINFO: u32 = 3;
+
+    pub(super) struct Module {
+        // the function pointers filled in by the tool, indexed by callback id. Slot 0 is unused.
+        slots: Vec<AtomicUsize>,
+        // the function table handed to the tool, pointers to each slot starting with a null
+        // pointer for slot 0 and ending with a null pointer.
+        table: Vec<*const AtomicUsize>,
+    }
+
+    unsafe impl Send for Module {}
+    unsafe impl Sync for Module {}
+
+    impl Module {
+        fn new(size: usize) -> Self {
+            let slots = (0..size).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+            let mut table = vec![ptr::null()];
+            table.extend(slots[1..].iter().map(|slot| slot as *const AtomicUsize));
+            table.push(ptr::null());
+            Self { slots, table }
+        }
+
+        pub(super) fn function(&self, id: usize) -> usize {
+            INIT.call_once(|| unsafe { load_injection() });
+            self.slots[id].load(Ordering::Acquire)
+        }
+
+        #[cfg(test)]
+        pub(super) fn set_function(&self, id: usize, function: usize) {
+            self.slots[id].store(function, Ordering::Release);
+        }
+    }
+
+    static INIT: Once = Once::new();
+    pub(super) static CORE: Lazy<Module> = Lazy::new(|| Module::new(CORE_SIZE));
+    pub(super) static CUDA: Lazy<Module> = Lazy::new(|| Module::new(CUDA_SIZE));
+    pub(super) static CORE2: Lazy<Module> = Lazy::new(|| Module::new(CORE2_SIZE));
+
+    #[repr(C)]
+    struct ExportTableCallbacks {
+        struct_size: usize,
+        get_module_function_table:
+            unsafe extern "C" fn(u32, *mut *const *const AtomicUsize, *mut c_uint) -> c_int,
+    }
+
+    #[repr(C)]
+    struct ExportTableVersionInfo {
+        struct_size: usize,
+        version: u32,
+        reserved0: u32,
+        set_injection_nvtx_version: unsafe extern "C" fn(u32),
+    }
+
+    static CALLBACKS: ExportTableCallbacks = ExportTableCallbacks {
+        struct_size: mem::size_of::<ExportTableCallbacks>(),
+        get_module_function_table,
+    };
+
+    static VERSION_INFO: ExportTableVersionInfo = ExportTableVersionInfo {
+        struct_size: mem::size_of::<ExportTableVersionInfo>(),
+        version: NVTX_VERSION as u32,
+        reserved0: 0,
+        set_injection_nvtx_version,
+    };
+
+    unsafe extern "C" fn get_export_table(id: u32) -> *const c_void {
+        match id {
+            ETID_CALLBACKS => &CALLBACKS as *const _ as *const c_void,
+            ETID_VERSIONINFO => &VERSION_INFO as *const _ as *const c_void,
+            _ => ptr::null(),
+        }
+    }
+
+    pub(super) unsafe extern "C" fn get_module_function_table(
+        module: u32,
+        table: *mut *const *const AtomicUsize,
+        size: *mut c_uint,
+    ) -> c_int {
+        let module: &Module = match module {
+            MODULE_CORE => &CORE,
+            MODULE_CUDA => &CUDA,
+            MODULE_CORE2 => &CORE2,
+            _ => return 0,
+        };
+        if !table.is_null() {
+            *table = module.table.as_ptr();
+        }
+        if !size.is_null() {
+            *size = module.slots.len() as c_uint;
+        }
+        1
+    }
+
+    unsafe extern "C" fn set_injection_nvtx_version(_version: u32) {}
+
+    unsafe fn load_injection() {
+        let path = match std::env::var_os("NVTX_INJECTION64_PATH") {
+            Some(path) => path,
+            None => return,
+        };
+        let symbol = CStr::from_bytes_with_nul(b"InitializeInjectionNvtx2\0").unwrap();
+        let initialize = match load_symbol(&path, symbol) {
+            Some(initialize) => initialize,
+            None => return,
+        };
+        let initialize: unsafe extern "C" fn(unsafe extern "C" fn(u32) -> *const c_void) -> c_int =
+            mem::transmute(initialize);
+        if initialize(get_export_table) == 0 {
+            // a tool which failed to initialize may have filled in some of the functions.
+            for module in [&*CORE, &*CUDA, &*CORE2].iter() {
+                for slot in &module.slots {
+                    slot.store(0, Ordering::Release);
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    unsafe fn load_symbol(path: &OsStr, symbol: &CStr) -> Option<usize> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(path.as_bytes()).ok()?;
+        let library = libc::dlopen(path.as_ptr(), libc::RTLD_LAZY);
+        if library.is_null() {
+            return None;
+        }
+        let function = libc::dlsym(library, symbol.as_ptr());
+        if function.is_null() {
+            None
+        } else {
+            Some(function as usize)
+        }
+    }
+
+    #[cfg(windows)]
+    unsafe fn load_symbol(path: &OsStr, symbol: &CStr) -> Option<usize> {
+        use std::os::windows::ffi::OsStrExt;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn LoadLibraryW(name: *const u16) -> *mut c_void;
+            fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+        }
+
+        let path = path.encode_wide().chain(Some(0)).collect::<Vec<_>>();
+        let library = LoadLibraryW(path.as_ptr());
+        if library.is_null() {
+            return None;
+        }
+        let function = GetProcAddress(library, symbol.as_ptr());
+        if function.is_null() {
+            None
+        } else {
+            Some(function as usize)
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    unsafe fn load_symbol(_path: &OsStr, _symbol: &CStr) -> Option<usize> {
+        None
+    }
+
+    // Defines each function to call the function the tool filled in for it, or to return the
+    // default value if there is none.
+    macro_rules! dispatch {
+        ($(fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)? = $module:ident[$id:ident] $(or $default:expr)?;)*) => {
+            $(
+                pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                    match $module.function($id) {
+                        0 => { $($default)? }
+                        function => {
+                            let function: unsafe extern "C" fn($($ty),*) $(-> $ret)? =
+                                mem::transmute(function);
+                            function($($arg),*)
+                        }
+                    }
+                }
+            )*
+        };
+    }
+
+    dispatch! {
+        fn nvtxDomainCreateA(name: *const c_char) -> nvtxDomainHandle_t =
+            CORE2[CORE2_DOMAIN_CREATE_A] or ptr::null_mut();
+        fn nvtxDomainDestroy(domain: nvtxDomainHandle_t) = CORE2[CORE2_DOMAIN_DESTROY];
+        fn nvtxDomainMarkEx(domain: nvtxDomainHandle_t, attr: *const nvtxEventAttributes_t) =
+            CORE2[CORE2_DOMAIN_MARK_EX];
+        fn nvtxDomainRangeStartEx(
+            domain: nvtxDomainHandle_t,
+            attr: *const nvtxEventAttributes_t
+        ) -> nvtxRangeId_t = CORE2[CORE2_DOMAIN_RANGE_START_EX] or 0;
+        fn nvtxDomainRangeEnd(domain: nvtxDomainHandle_t, id: nvtxRangeId_t) =
+            CORE2[CORE2_DOMAIN_RANGE_END];
+        fn nvtxDomainRangePushEx(
+            domain: nvtxDomainHandle_t,
+            attr: *const nvtxEventAttributes_t
+        ) -> c_int = CORE2[CORE2_DOMAIN_RANGE_PUSH_EX] or 0;
+        fn nvtxDomainRangePop(domain: nvtxDomainHandle_t) -> c_int =
+            CORE2[CORE2_DOMAIN_RANGE_POP] or 0;
+        fn nvtxDomainNameCategoryA(domain: nvtxDomainHandle_t, id: u32, name: *const c_char) =
+            CORE2[CORE2_DOMAIN_NAME_CATEGORY_A];
+        fn nvtxNameOsThreadA(id: u32, name: *const c_char) = CORE[CORE_NAME_OS_THREAD_A];
+        fn nvtxNameCuDeviceA(device: CUdevice, name: *const c_char) =
+            CUDA[CUDA_NAME_CU_DEVICE_A];
+        fn nvtxNameCuContextA(context: CUcontext, name: *const c_char) =
+            CUDA[CUDA_NAME_CU_CONTEXT_A];
+        fn nvtxNameCuStreamA(stream: CUstream, name: *const c_char) =
+            CUDA[CUDA_NAME_CU_STREAM_A];
+    }
+
+    #[cfg(target_os = "linux")]
+    pub unsafe fn current_thread_id() -> Option<u32> {
+        Some(libc::syscall(libc::SYS_gettid) as u32)
+    }
+
+    #[cfg(target_os = "windows")]
+    pub unsafe fn current_thread_id() -> Option<u32> {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentThreadId() -> u32;
+        }
+        Some(GetCurrentThreadId())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    pub unsafe fn current_thread_id() -> Option<u32> {
+        None
+    }
+}
+
+// without NVTX every call is a no-op with the same signature, so the safe wrappers below do
+// not need to care whether NVTX is enabled.
+#[cfg(not(feature = "nvtx"))]
+#[allow(non_snake_case)]
+mod ffi {
+    use super::*;
+    use crate::sys::{CUcontext, CUdevice, CUstream};
+
+    pub unsafe fn nvtxDomainCreateA(_name: *const c_char) -> nvtxDomainHandle_t {
+        ptr::null_mut()
+    }
+    pub unsafe fn nvtxDomainDestroy(_domain: nvtxDomainHandle_t) {}
+    pub unsafe fn nvtxDomainMarkEx(
+        _domain: nvtxDomainHandle_t,
+        _attr: *const nvtxEventAttributes_t,
+    ) {
+    }
+    pub unsafe fn nvtxDomainRangeStartEx(
+        _domain: nvtxDomainHandle_t,
+        _attr: *const nvtxEventAttributes_t,
+    ) -> nvtxRangeId_t {
+        0
+    }
+    pub unsafe fn nvtxDomainRangeEnd(_domain: nvtxDomainHandle_t, _id: nvtxRangeId_t) {}
+    pub unsafe fn nvtxDomainRangePushEx(
+        _domain: nvtxDomainHandle_t,
+        _attr: *const nvtxEventAttributes_t,
+    ) -> c_int {
+        0
+    }
+    pub unsafe fn nvtxDomainRangePop(_domain: nvtxDomainHandle_t) -> c_int {
+        0
+    }
+    pub unsafe fn nvtxDomainNameCategoryA(
+        _domain: nvtxDomainHandle_t,
+        _id: u32,
+        _name: *const c_char,
+    ) {
+    }
+    pub unsafe fn nvtxNameOsThreadA(_id: u32, _name: *const c_char) {}
+    pub unsafe fn nvtxNameCuDeviceA(_device: CUdevice, _name: *const c_char) {}
+    pub unsafe fn nvtxNameCuContextA(_context: CUcontext, _name: *const c_char) {}
+    pub unsafe fn nvtxNameCuStreamA(_stream: CUstream, _name: *const c_char) {}
+    pub unsafe fn current_thread_id() -> Option<u32> {
+        None
+    }
+}
+
+// NVTX strings end at the first nul, so cut the string there instead of failing.
+fn to_cstring(s: &str) -> CString {
+    let bytes = s.as_bytes();
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    CString::new(&bytes[..end]).unwrap()
+}
+
+/// An ARGB color for ranges and markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color(u32);
+
+impl Color {
+    /// Creates an opaque color from its red, green and blue components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::argb(0xff, r, g, b)
+    }
+
+    /// Creates a color from its alpha, red, green and blue components.
+    pub const fn argb(a: u8, r: u8, g: u8, b: u8) -> Self {
+        Self(u32::from_be_bytes([a, r, g, b]))
+    }
+
+    /// Creates a color from a packed `0xAARRGGBB` value.
+    pub const fn from_argb(argb: u32) -> Self {
+        Self(argb)
+    }
+
+    /// Returns the color packed as `0xAARRGGBB`.
+    pub const fn to_argb(self) -> u32 {
+        self.0
+    }
+}
+
+/// A value attached to a range or marker, which tools can show or plot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Payload {
+    /// An unsigned 64-bit integer.
+    U64(u64),
+    /// A signed 64-bit integer.
+    I64(i64),
+    /// A 64-bit float.
+    F64(f64),
+    /// An unsigned 32-bit integer.
+    U32(u32),
+    /// A signed 32-bit integer.
+    I32(i32),
+    /// A 32-bit float.
+    F32(f32),
+}
+
+impl Payload {
+    fn to_raw(self) -> (i32, nvtxPayload_t) {
+        match self {
+            Payload::U64(x) => (1, nvtxPayload_t { ullValue: x }),
+            Payload::I64(x) => (2, nvtxPayload_t { llValue: x }),
+            Payload::F64(x) => (3, nvtxPayload_t { dValue: x }),
+            Payload::U32(x) => (4, nvtxPayload_t { uiValue: x }),
+            Payload::I32(x) => (5, nvtxPayload_t { iValue: x }),
+            Payload::F32(x) => (6, nvtxPayload_t { fValue: x }),
+        }
+    }
+}
+
+/// The message and optional color, category and payload of a range or marker.
+///
+/// # Example
+///
+/// ```
+/// use cust::nvtx::{Color, EventAttributes, Payload};
+///
+/// let attributes = EventAttributes::new("upload")
+///     .color(Color::rgb(255, 0, 0))
+///     .category(1)
+///     .payload(Payload::U64(4096));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventAttributes {
+    message: CString,
+    color: Option<Color>,
+    category: u32,
+    payload: Option<Payload>,
+}
+
+impl EventAttributes {
+    /// Creates attributes with a message and nothing else. The message ends at the first nul
+    /// character if it contains any.
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: to_cstring(message),
+            color: None,
+            category: 0,
+            payload: None,
+        }
+    }
+
+    /// Sets the color tools use to draw the range or marker.
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Sets the category, which can be named with [`name_category`]. `0` means no category.
+    pub fn category(mut self, category: u32) -> Self {
+        self.category = category;
+        self
+    }
+
+    /// Sets the payload.
+    pub fn payload(mut self, payload: Payload) -> Self {
+        self.payload = Some(payload);
+        self
+    }
+
+    fn to_raw(&self) -> nvtxEventAttributes_t {
+        let (payload_type, payload) = self
+            .payload
+            .map(Payload::to_raw)
+            .unwrap_or((0, nvtxPayload_t { ullValue: 0 }));
+        nvtxEventAttributes_t {
+            version: NVTX_VERSION,
+            size: mem::size_of::<nvtxEventAttributes_t>() as u16,
+            category: self.category,
+            colorType: if self.color.is_some() {
+                NVTX_COLOR_ARGB
+            } else {
+                NVTX_COLOR_UNKNOWN
+            },
+            color: self.color.map(Color::to_argb).unwrap_or(0),
+            payloadType: payload_type,
+            reserved0: 0,
+            payload,
+            messageType: NVTX_MESSAGE_TYPE_ASCII,
+            message: self.message.as_ptr(),
+        }
+    }
+}
+
+/// A named group of ranges, markers and categories, which tools show separately from
+/// everything else. Libraries should annotate in their own domain to keep their annotations
+/// apart from the annotations of the application.
+///
+/// The free functions in this module and ranges made with [`Range::push`] or [`StartedRange::start`]
+/// use the global domain.
+#[derive(Debug)]
+pub struct Domain {
+    handle: nvtxDomainHandle_t,
+}
+
+unsafe impl Send for Domain {}
+unsafe impl Sync for Domain {}
+
+impl Domain {
+    /// Creates a new domain with a name.
+    pub fn new(name: &str) -> Self {
+        let name = to_cstring(name);
+        Self {
+            handle: unsafe { ffi::nvtxDomainCreateA(name.as_ptr()) },
+        }
+    }
+
+    /// Marks an instant in this domain.
+    pub fn mark(&self, message: &str) {
+        self.mark_with(&EventAttributes::new(message))
+    }
+
+    /// Marks an instant in this domain with attributes.
+    pub fn mark_with(&self, attributes: &EventAttributes) {
+        let raw = attributes.to_raw();
+        unsafe { ffi::nvtxDomainMarkEx(self.handle, &raw as *const _) }
+    }
+
+    /// Pushes a range in this domain on the current thread, see [`Range`].
+    pub fn push_range(&self, message: &str) -> Range<'_> {
+        self.push_range_with(&EventAttributes::new(message))
+    }
+
+    /// Pushes a range with attributes in this domain on the current thread, see [`Range`].
+    pub fn push_range_with(&self, attributes: &EventAttributes) -> Range<'_> {
+        Range::push_in(self.handle, attributes)
+    }
+
+    /// Starts a range in this domain which can end on any thread, see [`StartedRange`].
+    pub fn start_range(&self, message: &str) -> StartedRange<'_> {
+        self.start_range_with(&EventAttributes::new(message))
+    }
+
+    /// Starts a range with attributes in this domain which can end on any thread, see
+    /// [`StartedRange`].
+    pub fn start_range_with(&self, attributes: &EventAttributes) -> StartedRange<'_> {
+        StartedRange::start_in(self.handle, attributes)
+    }
+
+    /// Names a category of this domain.
+    pub fn name_category(&self, category: u32, name: &str) {
+        let name = to_cstring(name);
+        unsafe { ffi::nvtxDomainNameCategoryA(self.handle, category, name.as_ptr()) }
+    }
+}
+
+impl Drop for Domain {
+    fn drop(&mut self) {
+        if self.handle.is_null() {
+            return;
+        }
+
+        unsafe {
+            ffi::nvtxDomainDestroy(self.handle);
+        }
+    }
+}
+
+/// A range on the current thread which ends when it is dropped.
+///
+/// Ranges pushed on the same thread nest, so the range must be dropped on the thread which pushed
+/// it, and before any range pushed before it. Use a [`StartedRange`] for ranges which do not
+/// follow this.
+#[derive(Debug)]
+#[must_use = "the range ends as soon as it is dropped"]
+pub struct Range<'a> {
+    domain: nvtxDomainHandle_t,
+    // ranges are popped on the thread which pushed them, so they must not be sent to other threads.
+    _marker: PhantomData<(&'a Domain, *const ())>,
+}
+
+impl Range<'static> {
+    /// Pushes a range in the global domain on the current thread.
+    pub fn push(message: &str) -> Self {
+        Self::push_with(&EventAttributes::new(message))
+    }
+
+    /// Pushes a range with attributes in the global domain on the current thread.
+    pub fn push_with(attributes: &EventAttributes) -> Self {
+        Self::push_in(ptr::null_mut(), attributes)
+    }
+}
+
+impl<'a> Range<'a> {
+    fn push_in(domain: nvtxDomainHandle_t, attributes: &EventAttributes) -> Self {
+        let raw = attributes.to_raw();
+        unsafe {
+            ffi::nvtxDomainRangePushEx(domain, &raw as *const _);
+        }
+        Self {
+            domain,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Drop for Range<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::nvtxDomainRangePop(self.domain);
+        }
+    }
+}
+
+/// A range which can end on any thread, and may overlap with other ranges in any way.
+/// The range ends when [`StartedRange::end`] is called or it is dropped.
+#[derive(Debug)]
+#[must_use = "the range ends as soon as it is dropped"]
+pub struct StartedRange<'a> {
+    domain: nvtxDomainHandle_t,
+    id: nvtxRangeId_t,
+    _marker: PhantomData<&'a Domain>,
+}
+
+unsafe impl Send for StartedRange<'_> {}
+unsafe impl Sync for StartedRange<'_> {}
+
+impl StartedRange<'static> {
+    /// Starts a range in the global domain.
+    pub fn start(message: &str) -> Self {
+        Self::start_with(&EventAttributes::new(message))
+    }
+
+    /// Starts a range with attributes in the global domain.
+    pub fn start_with(attributes: &EventAttributes) -> Self {
+        Self::start_in(ptr::null_mut(), attributes)
+    }
+}
+
+impl<'a> StartedRange<'a> {
+    fn start_in(domain: nvtxDomainHandle_t, attributes: &EventAttributes) -> Self {
+        let raw = attributes.to_raw();
+        let id = unsafe { ffi::nvtxDomainRangeStartEx(domain, &raw as *const _) };
+        Self {
+            domain,
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Ends the range, this is the same as dropping it.
+    pub fn end(self) {}
+}
+
+impl Drop for StartedRange<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::nvtxDomainRangeEnd(self.domain, self.id);
+        }
+    }
+}
+
+/// Marks an instant in the global domain.
+pub fn mark(message: &str) {
+    mark_with(&EventAttributes::new(message))
+}
+
+/// Marks an instant with attributes in the global domain.
+pub fn mark_with(attributes: &EventAttributes) {
+    let raw = attributes.to_raw();
+    unsafe { ffi::nvtxDomainMarkEx(ptr::null_mut(), &raw as *const _) }
+}
+
+/// Names a category of the global domain.
+pub fn name_category(category: u32, name: &str) {
+    let name = to_cstring(name);
+    unsafe { ffi::nvtxDomainNameCategoryA(ptr::null_mut(), category, name.as_ptr()) }
+}
+
+/// Names the OS thread with the given id, such as the id returned by `gettid` on Linux.
+pub fn name_os_thread(thread_id: u32, name: &str) {
+    let name = to_cstring(name);
+    unsafe { ffi::nvtxNameOsThreadA(thread_id, name.as_ptr()) }
+}
+
+/// Names the current OS thread. This does nothing on platforms other than Linux and Windows.
+pub fn name_current_thread(name: &str) {
+    if let Some(id) = unsafe { ffi::current_thread_id() } {
+        name_os_thread(id, name);
+    }
+}
+
+/// Names a stream.
+pub fn name_stream(stream: &Stream, name: &str) {
+    let name = to_cstring(name);
+    unsafe { ffi::nvtxNameCuStreamA(stream.as_inner(), name.as_ptr()) }
+}
+
+/// Names a context.
+pub fn name_context<C: ContextHandle>(context: &C, name: &str) {
+    let name = to_cstring(name);
+    unsafe { ffi::nvtxNameCuContextA(context.get_inner(), name.as_ptr()) }
+}
+
+/// Names a device.
+pub fn name_device(device: &Device, name: &str) {
+    let name = to_cstring(name);
+    unsafe { ffi::nvtxNameCuDeviceA(device.as_raw(), name.as_ptr()) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_attributes_layout() {
+        let raw = EventAttributes::new("a\0b")
+            .color(Color::rgb(1, 2, 3))
+            .payload(Payload::F64(1.5))
+            .to_raw();
+        // the layout of nvtxEventAttributes_v2 on 64-bit targets.
+        assert_eq!(raw.size, 48);
+        assert_eq!(raw.color, 0xff010203);
+        assert_eq!(raw.payloadType, 3);
+        assert_eq!(unsafe { raw.payload.dValue }, 1.5);
+        assert_eq!(EventAttributes::new("a\0b").message.as_bytes(), b"a");
+    }
+
+    #[test]
+    fn test_ranges() {
+        let domain = Domain::new("cust");
+        domain.name_category(1, "test");
+        let outer = Range::push("outer");
+        let started = StartedRange::start_with(&EventAttributes::new("started").category(1));
+        {
+            let _inner = domain.push_range("inner");
+            let _overlapping = domain.start_range("overlapping");
+            domain.mark("inside");
+        }
+        drop(outer);
+        std::thread::spawn(move || started.end()).join().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "nvtx")]
+    fn test_injection() {
+        use once_cell::sync::Lazy;
+        use std::ffi::CStr;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        static NAMES: Lazy<Mutex<Vec<(u32, String)>>> = Lazy::new(Default::default);
+
+        unsafe extern "C" fn name_category(_: nvtxDomainHandle_t, id: u32, name: *const c_char) {
+            let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+            NAMES.lock().unwrap().push((id, name));
+        }
+
+        // fill in the function table the way a tool does.
+        let mut table: *const *const AtomicUsize = ptr::null();
+        let mut size = 0;
+        unsafe {
+            assert_eq!(ffi::get_module_function_table(5, &mut table, &mut size), 1);
+            assert_eq!(size, 16);
+            assert!((*table).is_null());
+            assert!((*table.add(16)).is_null());
+            let function: unsafe extern "C" fn(_, _, _) = name_category;
+            (**table.add(8)).store(function as usize, Ordering::Release);
+        }
+
+        Domain::new("cust").name_category(7, "injected");
+        ffi::CORE2.set_function(8, 0);
+        assert!(NAMES.lock().unwrap().contains(&(7, "injected".to_string())));
+    }
+}
@@ -9,6 +9,7 @@ pub use crate::event::{Event, EventFlags, EventStatus};
 pub use crate::external::*;
 pub use crate::function::Function;
 pub use crate::launch;
+pub use crate::launch_cooperative;
 pub use crate::memory::{
     CopyDestination, DeviceBuffer, DevicePointer, DeviceSlice, DeviceVariable, UnifiedBuffer,
 };
@@ -0,0 +1,888 @@
+//! Lightweight timing of kernel launches and copies with events.
+//!
+//! [`Profiler`] records an [`Event`] before and after every launch or copy it wraps, and once the
+//! work is done turns the time between them into per-kernel statistics and a timeline. It only
+//! adds two event records to each operation, so unlike Nsight it can be left on in release builds
+//! and report on production workloads.
+//!
+//! The statistics are aggregated by name into a count and the minimum, mean, maximum and total
+//! duration. The timeline can be exported as a [Chrome trace] for `chrome://tracing` or
+//! [Perfetto], and the statistics as CSV.
+//!
+//! Events are timestamped when the device reaches them, so the durations include any time the
+//! work spent waiting on other work in the same stream, but not the time spent in the launch
+//! queue. Start times on the timeline lose precision the longer the profiler is used, see
+//! [`TraceEvent::start`].
+//!
+//! # Example
+//!
+//! ```
+//! # use cust::*;
+//! # use std::error::Error;
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! # let _ctx = quick_init()?;
+//! use cust::memory::*;
+//! use cust::module::Module;
+//! use cust::profile::Profiler;
+//! use cust::stream::{Stream, StreamFlags};
+//!
+//! let module = Module::from_ptx(include_str!("../resources/add.ptx"), &[])?;
+//! let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+//! let profiler = Profiler::new();
+//!
+//! let host = LockedBuffer::new(&1.0f32, 1024)?;
+//! let mut a = DeviceBuffer::<f32>::zeroed(1024)?;
+//! let b = DeviceBuffer::<f32>::zeroed(1024)?;
+//! let c = DeviceBuffer::<f32>::zeroed(1024)?;
+//!
+//! // launches and copies on `profiled` are timed.
+//! let profiled = profiler.on(&stream);
+//! profiled.copy("upload a", |stream| unsafe { a.async_copy_from(&host, stream) })?;
+//! unsafe {
+//!     launch!(module.sum<<<4, 256, 0, profiled>>>(
+//!         a.as_device_ptr(),
+//!         b.as_device_ptr(),
+//!         c.as_device_ptr(),
+//!         1024u32
+//!     ))?;
+//! }
+//!
+//! let report = profiler.report()?;
+//! let sum = report.stats("sum").unwrap();
+//! println!("sum ran {} times, {:?} on average", sum.count, sum.mean());
+//! std::fs::write("trace.json", report.to_chrome_trace())?;
+//! # std::fs::remove_file("trace.json")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [Chrome trace]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+//! [Perfetto]: https://ui.perfetto.dev
+
+use crate::error::{CudaError, CudaResult, DriverResult, ToResult};
+use crate::event::{Event, EventFlags, EventStatus};
+use crate::function::{BlockSize, Function, GridSize, KernelArgs, TypedFunction};
+use crate::stream::Stream;
+use crate::sys as cuda;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::c_void;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+const DEFAULT_TRACE_CAPACITY: usize = 1 << 16;
+
+/// What kind of work a timed operation is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ActivityKind {
+    /// A kernel launch.
+    Kernel,
+    /// A memory copy or memset.
+    Copy,
+    /// Anything else timed with [`Profiler::time`].
+    Other,
+}
+
+impl ActivityKind {
+    /// The lowercase name of the kind, used as the category in traces and in CSV.
+    pub fn name(self) -> &'static str {
+        match self {
+            ActivityKind::Kernel => "kernel",
+            ActivityKind::Copy => "copy",
+            ActivityKind::Other => "other",
+        }
+    }
+}
+
+/// The aggregated durations of every operation with the same name and kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivityStats {
+    /// The name of the kernel or operation.
+    pub name: String,
+    /// The kind of the operation.
+    pub kind: ActivityKind,
+    /// The number of times it ran.
+    pub count: u64,
+    /// The sum of the durations.
+    pub total: Duration,
+    /// The shortest duration.
+    pub min: Duration,
+    /// The longest duration.
+    pub max: Duration,
+}
+
+impl ActivityStats {
+    /// The mean duration.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+    }
+
+    fn add(&mut self, duration: Duration) {
+        if self.count == 0 || duration < self.min {
+            self.min = duration;
+        }
+        self.max = self.max.max(duration);
+        self.total += duration;
+        self.count += 1;
+    }
+}
+
+/// A single timed operation on the timeline of a [`ProfileReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// The name of the kernel or operation.
+    pub name: String,
+    /// The kind of the operation.
+    pub kind: ActivityKind,
+    /// The index of the context the operation ran in, in the order contexts were first used with
+    /// the profiler.
+    pub context: usize,
+    /// The index of the stream the operation ran on, in the order streams were first used with
+    /// the profiler.
+    pub stream: usize,
+    /// When the operation started, relative to the earliest operation on the timeline in its
+    /// context.
+    ///
+    /// Start times are measured from an event recorded when the profiler is first used in the
+    /// context, and the driver only gives the time between events as an `f32` in milliseconds.
+    /// Once the profiler has been in use for a few seconds that is coarser than the roughly half a
+    /// microsecond resolution of events, and after a minute start times are only accurate to a few
+    /// microseconds. Durations are measured from the operation's own events and stay precise.
+    pub start: Duration,
+    /// How long the operation took.
+    pub duration: Duration,
+}
+
+/// The statistics and timeline collected by a [`Profiler`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    /// The statistics of every operation, sorted by total duration with the longest first.
+    pub stats: Vec<ActivityStats>,
+    /// The operations on the timeline, sorted by start time. Only the most recent operations are
+    /// kept, see [`Profiler::with_trace_capacity`].
+    pub events: Vec<TraceEvent>,
+    /// The number of operations which were dropped from the timeline to stay under its capacity.
+    /// They are still included in the statistics.
+    pub dropped_events: u64,
+}
+
+impl ProfileReport {
+    /// Returns the statistics of the operation called `name`, preferring kernels if there are
+    /// operations of several kinds with that name.
+    pub fn stats(&self, name: &str) -> Option<&ActivityStats> {
+        self.stats
+            .iter()
+            .filter(|stats| stats.name == name)
+            .min_by_key(|stats| stats.kind)
+    }
+
+    /// Writes the timeline in the Chrome trace event format, which can be opened in
+    /// `chrome://tracing` or Perfetto. Every context is shown as a process and every stream as a
+    /// thread.
+    pub fn write_chrome_trace<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.to_chrome_trace().as_bytes())
+    }
+
+    /// Returns the timeline in the Chrome trace event format, see
+    /// [`ProfileReport::write_chrome_trace`].
+    pub fn to_chrome_trace(&self) -> String {
+        let mut out = String::from("{\"displayTimeUnit\":\"ns\",\"traceEvents\":[");
+        let mut first = true;
+        let mut separator = |out: &mut String| {
+            if !first {
+                out.push(',');
+            }
+            first = false;
+        };
+
+        let mut named = Vec::new();
+        for event in &self.events {
+            if named.contains(&(event.context, event.stream)) {
+                continue;
+            }
+            named.push((event.context, event.stream));
+            separator(&mut out);
+            let _ = write!(
+                out,
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":\"stream {}\"}}}}",
+                event.context, event.stream, event.stream
+            );
+        }
+        for event in &self.events {
+            separator(&mut out);
+            out.push_str("{\"name\":");
+            push_json_string(&mut out, &event.name);
+            let _ = write!(
+                out,
+                ",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":{},\"tid\":{}}}",
+                event.kind.name(),
+                micros(event.start),
+                micros(event.duration),
+                event.context,
+                event.stream
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Writes the statistics as CSV, with a header and one row per operation. Durations are in
+    /// microseconds.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.to_csv().as_bytes())
+    }
+
+    /// Returns the statistics as CSV, see [`ProfileReport::write_csv`].
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("name,kind,count,total_us,min_us,mean_us,max_us\n");
+        for stats in &self.stats {
+            push_csv_field(&mut out, &stats.name);
+            let _ = writeln!(
+                out,
+                ",{},{},{:.3},{:.3},{:.3},{:.3}",
+                stats.kind.name(),
+                stats.count,
+                micros(stats.total),
+                micros(stats.min),
+                micros(stats.mean()),
+                micros(stats.max)
+            );
+        }
+        out
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_nanos() as f64 / 1000.0
+}
+
+fn push_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn push_csv_field(out: &mut String, s: &str) {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        out.push('"');
+        out.push_str(&s.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(s);
+    }
+}
+
+struct Pending {
+    name: String,
+    kind: ActivityKind,
+    context: usize,
+    stream: usize,
+    start: Event,
+    end: Event,
+}
+
+struct State {
+    enabled: bool,
+    trace_capacity: usize,
+    // events of finished operations, reused so timing does not create events all the time.
+    free_events: Vec<Event>,
+    pending: VecDeque<Pending>,
+    stats: HashMap<(ActivityKind, String), ActivityStats>,
+    // the operations on the timeline and when they started in nanoseconds after the base event
+    // of their context, see `timeline`.
+    events: VecDeque<(i64, TraceEvent)>,
+    dropped_events: u64,
+    // the context handle and the event recorded before anything else in it, which start times
+    // are measured from.
+    contexts: Vec<(usize, Event)>,
+    // context index and stream handle.
+    streams: Vec<(usize, usize)>,
+}
+
+impl State {
+    fn event(&mut self) -> CudaResult<Event> {
+        match self.free_events.pop() {
+            Some(event) => Ok(event),
+            None => Event::new(EventFlags::DEFAULT),
+        }
+    }
+
+    // Resolves the operations at the front of the queue which are done, without blocking.
+    fn poll(&mut self) -> CudaResult<()> {
+        while let Some(pending) = self.pending.front() {
+            // the base event may be on another stream, which is usually but not always done.
+            let base = &self.contexts[pending.context].1;
+            if pending.end.query()? == EventStatus::NotReady
+                || base.query()? == EventStatus::NotReady
+            {
+                break;
+            }
+            let pending = self.pending.pop_front().unwrap();
+            self.resolve(pending)?;
+        }
+        Ok(())
+    }
+
+    // Records a finished operation, its events and the base event of its context must be complete.
+    fn resolve(&mut self, pending: Pending) -> CudaResult<()> {
+        let duration = pending.end.elapsed(&pending.start)?;
+        let base = &self.contexts[pending.context].1;
+        let offset = (pending.start.elapsed_time_f32(base)? as f64 * 1e6) as i64;
+
+        self.stats
+            .entry((pending.kind, pending.name.clone()))
+            .or_insert_with(|| ActivityStats {
+                name: pending.name.clone(),
+                kind: pending.kind,
+                count: 0,
+                total: Duration::ZERO,
+                min: Duration::ZERO,
+                max: Duration::ZERO,
+            })
+            .add(duration);
+        if self.trace_capacity != 0 {
+            if self.events.len() == self.trace_capacity {
+                self.events.pop_front();
+                self.dropped_events += 1;
+            }
+            self.events.push_back((
+                offset,
+                TraceEvent {
+                    name: pending.name,
+                    kind: pending.kind,
+                    context: pending.context,
+                    stream: pending.stream,
+                    start: Duration::ZERO,
+                    duration,
+                },
+            ));
+        } else {
+            self.dropped_events += 1;
+        }
+        self.free_events.push(pending.start);
+        self.free_events.push(pending.end);
+        Ok(())
+    }
+}
+
+// Turns the offsets of the operations from the base event of their context into start times
+// relative to the earliest operation of each context. The base event is recorded on the first
+// stream used in the context, so operations on other streams can start before it and have a
+// negative offset.
+fn timeline<'a>(events: impl Iterator<Item = &'a (i64, TraceEvent)> + Clone) -> Vec<TraceEvent> {
+    let mut earliest = HashMap::new();
+    for (offset, event) in events.clone() {
+        let min = earliest.entry(event.context).or_insert(*offset);
+        *min = (*min).min(*offset);
+    }
+    events
+        .map(|(offset, event)| TraceEvent {
+            start: Duration::from_nanos((offset - earliest[&event.context]) as u64),
+            ..event.clone()
+        })
+        .collect()
+}
+
+/// Times launches and copies on streams with events, see the [module-level documentation](self).
+///
+/// The profiler holds events in the contexts it was used in, so it must be dropped before them.
+pub struct Profiler {
+    state: Mutex<State>,
+}
+
+impl std::fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Profiler")
+            .field("enabled", &state.enabled)
+            .field("trace_capacity", &state.trace_capacity)
+            .field("pending", &state.pending.len())
+            .finish()
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// Creates an enabled profiler which keeps the last 65536 operations for the timeline.
+    pub fn new() -> Self {
+        Self::with_trace_capacity(DEFAULT_TRACE_CAPACITY)
+    }
+
+    /// Creates an enabled profiler which keeps the last `capacity` operations for the timeline.
+    /// The statistics include every operation regardless, a capacity of zero only collects
+    /// statistics.
+    pub fn with_trace_capacity(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                enabled: true,
+                trace_capacity: capacity,
+                free_events: Vec::new(),
+                pending: VecDeque::new(),
+                stats: HashMap::new(),
+                events: VecDeque::new(),
+                dropped_events: 0,
+                contexts: Vec::new(),
+                streams: Vec::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Enables or disables timing. Operations run while the profiler is disabled are not timed
+    /// and cost nothing extra.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.lock().enabled = enabled;
+    }
+
+    /// Whether operations are currently timed.
+    pub fn is_enabled(&self) -> bool {
+        self.lock().enabled
+    }
+
+    /// Returns a wrapper around `stream` which times the launches and copies made through it.
+    ///
+    /// The wrapper can be passed to [`launch!`](crate::launch) in place of the stream.
+    pub fn on<'a>(&'a self, stream: &'a Stream) -> ProfiledStream<'a> {
+        ProfiledStream {
+            profiler: self,
+            stream,
+        }
+    }
+
+    /// Times the work `f` submits to `stream`, recording it as `name`.
+    ///
+    /// `f` should only submit work to `stream`, anything it submits elsewhere is not included in
+    /// the duration. The work is not timed if `f` fails.
+    pub fn time<R>(
+        &self,
+        stream: &Stream,
+        name: &str,
+        kind: ActivityKind,
+        f: impl FnOnce(&Stream) -> CudaResult<R>,
+    ) -> CudaResult<R> {
+        self.time_with(stream, name, kind, f)
+    }
+
+    // `Profiler::time` for any error a `CudaError` converts into, so that timed launches keep
+    // their `DriverError`.
+    fn time_with<R, E: From<CudaError>>(
+        &self,
+        stream: &Stream,
+        name: &str,
+        kind: ActivityKind,
+        f: impl FnOnce(&Stream) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let (start, end, context, stream_idx) = {
+            let mut state = self.lock();
+            if !state.enabled {
+                drop(state);
+                return f(stream);
+            }
+            state.poll()?;
+
+            let mut ctx = ptr::null_mut();
+            unsafe { cuda::cuCtxGetCurrent(&mut ctx).to_result_from("cuCtxGetCurrent")? };
+            let context = match state.contexts.iter().position(|(c, _)| *c == ctx as usize) {
+                Some(idx) => idx,
+                None => {
+                    let base = state.event()?;
+                    base.record(stream)?;
+                    state.contexts.push((ctx as usize, base));
+                    state.contexts.len() - 1
+                }
+            };
+            let key = (context, stream.as_inner() as usize);
+            let stream_idx = match state.streams.iter().position(|s| *s == key) {
+                Some(idx) => idx,
+                None => {
+                    state.streams.push(key);
+                    state.streams.len() - 1
+                }
+            };
+            (state.event()?, state.event()?, context, stream_idx)
+        };
+
+        let recycle = |start, end| {
+            let mut state = self.lock();
+            state.free_events.push(start);
+            state.free_events.push(end);
+        };
+        if let Err(e) = start.record(stream) {
+            recycle(start, end);
+            return Err(e.into());
+        }
+        let res = match f(stream) {
+            Ok(res) => res,
+            Err(e) => {
+                recycle(start, end);
+                return Err(e);
+            }
+        };
+        if let Err(e) = end.record(stream) {
+            recycle(start, end);
+            return Err(e.into());
+        }
+
+        self.lock().pending.push_back(Pending {
+            name: name.to_string(),
+            kind,
+            context,
+            stream: stream_idx,
+            start,
+            end,
+        });
+        Ok(res)
+    }
+
+    /// Collects the timings of the operations which are done so far, without blocking.
+    ///
+    /// This is also done whenever an operation is timed, so that the events of finished
+    /// operations can be reused.
+    pub fn poll(&self) -> CudaResult<()> {
+        self.lock().poll()
+    }
+
+    /// Waits for every timed operation to finish and returns the statistics and timeline of
+    /// everything timed since the profiler was created or [`Profiler::reset`] was called.
+    pub fn report(&self) -> CudaResult<ProfileReport> {
+        let mut state = self.lock();
+        while let Some(pending) = state.pending.pop_front() {
+            let base = &state.contexts[pending.context].1;
+            if let Err(e) = pending.end.synchronize().and_then(|()| base.synchronize()) {
+                state.pending.push_front(pending);
+                return Err(e.into());
+            }
+            state.resolve(pending)?;
+        }
+
+        let mut stats = state.stats.values().cloned().collect::<Vec<_>>();
+        stats.sort_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| a.kind.cmp(&b.kind))
+        });
+        let mut events = timeline(state.events.iter());
+        events.sort_by_key(|event| (event.context, event.start));
+        Ok(ProfileReport {
+            stats,
+            events,
+            dropped_events: state.dropped_events,
+        })
+    }
+
+    /// Clears the statistics and timeline collected so far. Operations which have not finished
+    /// yet are still collected afterwards.
+    pub fn reset(&self) {
+        let mut state = self.lock();
+        state.stats.clear();
+        state.events.clear();
+        state.dropped_events = 0;
+    }
+}
+
+/// A [`Stream`] whose launches and copies are timed by a [`Profiler`], created with
+/// [`Profiler::on`].
+#[derive(Debug, Clone, Copy)]
+pub struct ProfiledStream<'a> {
+    profiler: &'a Profiler,
+    stream: &'a Stream,
+}
+
+impl<'a> ProfiledStream<'a> {
+    /// The stream being profiled.
+    pub fn stream(&self) -> &'a Stream {
+        self.stream
+    }
+
+    /// Times the copies or memsets `f` submits to the stream as `name`, see
+    /// [`Profiler::time`].
+    pub fn copy<R>(&self, name: &str, f: impl FnOnce(&Stream) -> CudaResult<R>) -> CudaResult<R> {
+        self.profiler.time(self.stream, name, ActivityKind::Copy, f)
+    }
+
+    /// Times the work `f` submits to the stream as `name`, see [`Profiler::time`].
+    pub fn time<R>(&self, name: &str, f: impl FnOnce(&Stream) -> CudaResult<R>) -> CudaResult<R> {
+        self.profiler
+            .time(self.stream, name, ActivityKind::Other, f)
+    }
+
+    /// Launches `function` on the stream with the arguments `args`, timed as a kernel named after
+    /// the function, see [`TypedFunction::launch`].
+    ///
+    /// # Safety
+    ///
+    /// The same as [`TypedFunction::launch`].
+    pub unsafe fn launch_typed<Args: KernelArgs, G, B>(
+        &self,
+        function: &TypedFunction<'_, Args>,
+        grid_size: G,
+        block_size: B,
+        shared_mem_bytes: u32,
+        args: Args,
+    ) -> DriverResult<()>
+    where
+        G: Into<GridSize>,
+        B: Into<BlockSize>,
+    {
+        self.profiler.time_with(
+            self.stream,
+            function.name(),
+            ActivityKind::Kernel,
+            |stream| function.launch(stream, grid_size, block_size, shared_mem_bytes, args),
+        )
+    }
+
+    // Used by the `launch!` macro like `Stream::launch`. The launch is named after the function.
+    #[doc(hidden)]
+    pub unsafe fn launch<G, B>(
+        &self,
+        func: &Function,
+        grid_size: G,
+        block_size: B,
+        shared_mem_bytes: u32,
+        args: &[*mut c_void],
+    ) -> DriverResult<()>
+    where
+        G: Into<GridSize>,
+        B: Into<BlockSize>,
+    {
+        self.profiler
+            .time_with(self.stream, func.name(), ActivityKind::Kernel, |stream| {
+                stream.launch(func, grid_size, block_size, shared_mem_bytes, args)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::{AsyncCopyDestination, DeviceBuffer, DevicePointer, LockedBuffer};
+    use crate::module::Module;
+    use crate::quick_init;
+    use crate::stream::StreamFlags;
+    use std::error::Error;
+
+    fn report() -> ProfileReport {
+        let stats = |name: &str, kind, count, total| ActivityStats {
+            name: name.to_string(),
+            kind,
+            count,
+            total: Duration::from_micros(total),
+            min: Duration::from_micros(1),
+            max: Duration::from_micros(total - 1),
+        };
+        let event = |name: &str, kind, stream, start, duration| TraceEvent {
+            name: name.to_string(),
+            kind,
+            context: 0,
+            stream,
+            start: Duration::from_nanos(start),
+            duration: Duration::from_nanos(duration),
+        };
+        ProfileReport {
+            stats: vec![
+                stats("sum", ActivityKind::Kernel, 2, 10),
+                stats("upload \"a\", b", ActivityKind::Copy, 1, 4),
+            ],
+            events: vec![
+                event("upload \"a\", b", ActivityKind::Copy, 0, 0, 4000),
+                event("sum", ActivityKind::Kernel, 1, 4500, 1000),
+            ],
+            dropped_events: 0,
+        }
+    }
+
+    #[test]
+    fn test_chrome_trace() {
+        assert_eq!(
+            report().to_chrome_trace(),
+            concat!(
+                r#"{"displayTimeUnit":"ns","traceEvents":["#,
+                r#"{"name":"thread_name","ph":"M","pid":0,"tid":0,"args":{"name":"stream 0"}},"#,
+                r#"{"name":"thread_name","ph":"M","pid":0,"tid":1,"args":{"name":"stream 1"}},"#,
+                r#"{"name":"upload \"a\", b","cat":"copy","ph":"X","ts":0.000,"dur":4.000,"pid":0,"tid":0},"#,
+                r#"{"name":"sum","cat":"kernel","ph":"X","ts":4.500,"dur":1.000,"pid":0,"tid":1}"#,
+                "]}"
+            )
+        );
+        assert_eq!(
+            ProfileReport::default().to_chrome_trace(),
+            r#"{"displayTimeUnit":"ns","traceEvents":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            report().to_csv(),
+            "name,kind,count,total_us,min_us,mean_us,max_us\n\
+             sum,kernel,2,10.000,1.000,5.000,9.000\n\
+             \"upload \"\"a\"\", b\",copy,1,4.000,1.000,4.000,3.000\n"
+        );
+    }
+
+    #[test]
+    fn test_profiler() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let module = Module::from_ptx(include_str!("../resources/add.ptx"), &[])?;
+        let sum = module.get_function("sum")?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let other = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let profiler = Profiler::new();
+
+        let host = LockedBuffer::new(&1.0f32, 256)?;
+        let mut a = DeviceBuffer::<f32>::zeroed(256)?;
+        let b = DeviceBuffer::<f32>::zeroed(256)?;
+        let c = DeviceBuffer::<f32>::zeroed(256)?;
+
+        let profiled = profiler.on(&stream);
+        let other = profiler.on(&other);
+        for _ in 0..3 {
+            profiled.copy("upload", |s| unsafe { a.async_copy_from(&host, s) })?;
+            unsafe {
+                crate::launch!(sum<<<1, 256, 0, profiled>>>(
+                    a.as_device_ptr(),
+                    b.as_device_ptr(),
+                    c.as_device_ptr(),
+                    256u32
+                ))?;
+            }
+        }
+        let typed = module.get_typed_function::<(
+            DevicePointer<f32>,
+            DevicePointer<f32>,
+            DevicePointer<f32>,
+            u32,
+        )>("sum")?;
+        unsafe {
+            profiled.launch_typed(
+                &typed,
+                1,
+                256,
+                0,
+                (a.as_device_ptr(), b.as_device_ptr(), c.as_device_ptr(), 256),
+            )?;
+        }
+        other.time("nothing", |_| Ok(()))?;
+
+        let report = profiler.report()?;
+        assert_eq!(report.stats.len(), 3);
+        let stats = report.stats("sum").unwrap();
+        assert_eq!((stats.kind, stats.count), (ActivityKind::Kernel, 4));
+        assert!(stats.min <= stats.mean() && stats.mean() <= stats.max);
+        assert_eq!(report.stats("upload").unwrap().count, 3);
+        assert_eq!(report.events.len(), 8);
+        assert!(report
+            .events
+            .iter()
+            .any(|e| e.name == "nothing" && e.stream == 1));
+        assert!(report
+            .events
+            .windows(2)
+            .all(|pair| pair[0].start <= pair[1].start));
+
+        // failed operations are not timed.
+        let err = profiled.time("failing", |_| {
+            Err::<(), _>(crate::error::CudaError::InvalidValue)
+        });
+        assert!(err.is_err());
+        profiler.set_enabled(false);
+        profiled.time("disabled", |_| Ok(()))?;
+        profiler.reset();
+        assert_eq!(profiler.report()?, ProfileReport::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeline() {
+        let event = |context, offset: i64| {
+            (
+                offset,
+                TraceEvent {
+                    name: offset.to_string(),
+                    kind: ActivityKind::Other,
+                    context,
+                    stream: 0,
+                    start: Duration::ZERO,
+                    duration: Duration::from_nanos(10),
+                },
+            )
+        };
+        // the first operation on another stream started before the base event.
+        let events = [event(0, 1000), event(0, -500), event(1, 200), event(1, 300)];
+        let starts = timeline(events.iter())
+            .into_iter()
+            .map(|e| (e.context, e.start.as_nanos()))
+            .collect::<Vec<_>>();
+        assert_eq!(starts, [(0, 1500), (0, 0), (1, 0), (1, 100)]);
+    }
+
+    #[test]
+    fn test_trace_capacity() -> Result<(), Box<dyn Error>> {
+        let _ctx = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let profiler = Profiler::with_trace_capacity(2);
+        for i in 0..5 {
+            profiler.time(&stream, &i.to_string(), ActivityKind::Other, |_| Ok(()))?;
+        }
+        let report = profiler.report()?;
+        assert_eq!(report.stats.len(), 5);
+        assert_eq!(report.dropped_events, 3);
+        let names = report
+            .events
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["3", "4"]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_poll_waits_for_base_event() -> Result<(), Box<dyn Error>> {
+        use crate::sys::{mock, CUresult};
+
+        let _ctx = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let profiler = Profiler::new();
+        profiler.time(&stream, "op", ActivityKind::Other, |_| Ok(()))?;
+
+        // the operation is done, but the base event of the context is not.
+        mock::clear_calls();
+        mock::fail_nth("cuEventQuery", 1, CUresult::CUDA_ERROR_NOT_READY);
+        profiler.poll()?;
+        assert_eq!(mock::calls(), ["cuEventQuery", "cuEventQuery"]);
+        assert_eq!(profiler.lock().pending.len(), 1);
+
+        profiler.poll()?;
+        assert!(profiler.lock().pending.is_empty());
+        assert_eq!(profiler.report()?.events.len(), 1);
+        Ok(())
+    }
+}
@@ -10,14 +10,20 @@
 //! are not currently supported by cust. Finally, the host can wait for all work scheduled in
 //! a stream to be completed.
 
-use crate::error::{CudaResult, DropResult, ToResult};
+use crate::error::{CudaResult, DriverResult, DropResult, ToResult};
 use crate::event::Event;
 use crate::function::{BlockSize, Function, GridSize};
+use crate::graph::Graph;
 use crate::sys::{self as cuda, CUstream};
+use std::borrow::Borrow;
 use std::ffi::c_void;
+use std::future::Future;
 use std::mem;
 use std::panic;
+use std::pin::Pin;
 use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
 
 bitflags::bitflags! {
     /// Bit flags for configuring a CUDA Stream.
@@ -52,6 +58,65 @@ bitflags::bitflags! {
     }
 }
 
+/// How a stream capture interacts with potentially unsafe CUDA API calls made while the
+/// capture is active.
+///
+/// Some API calls such as `cuMalloc` may implicitly synchronize with the device, which is not
+/// legal during a capture. The capture mode decides which threads such calls are prohibited in.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamCaptureMode {
+    /// Potentially unsafe API calls are prohibited in every thread while this capture is active,
+    /// unless the thread opted out with [`Stream::exchange_capture_mode`].
+    Global = 0,
+    /// Potentially unsafe API calls are only prohibited in the thread which began the capture.
+    ThreadLocal = 1,
+    /// Potentially unsafe API calls are not prohibited, it is up to the user to not make any
+    /// calls which would be illegal during the capture.
+    Relaxed = 2,
+}
+
+impl StreamCaptureMode {
+    fn from_raw(raw: cuda::CUstreamCaptureMode) -> Self {
+        match raw {
+            cuda::CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_GLOBAL => Self::Global,
+            cuda::CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_THREAD_LOCAL => Self::ThreadLocal,
+            cuda::CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_RELAXED => Self::Relaxed,
+        }
+    }
+
+    fn to_raw(self) -> cuda::CUstreamCaptureMode {
+        match self {
+            Self::Global => cuda::CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_GLOBAL,
+            Self::ThreadLocal => cuda::CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_THREAD_LOCAL,
+            Self::Relaxed => cuda::CUstreamCaptureMode::CU_STREAM_CAPTURE_MODE_RELAXED,
+        }
+    }
+}
+
+/// The capture status of a stream.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamCaptureStatus {
+    /// The stream is not capturing.
+    None = 0,
+    /// The stream is capturing.
+    Active = 1,
+    /// The stream is capturing but the capture was invalidated by an error, it must
+    /// be ended with [`Stream::end_capture`] which will return an error.
+    Invalidated = 2,
+}
+
+impl StreamCaptureStatus {
+    fn from_raw(raw: cuda::CUstreamCaptureStatus) -> Self {
+        match raw {
+            cuda::CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_NONE => Self::None,
+            cuda::CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_ACTIVE => Self::Active,
+            cuda::CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_INVALIDATED => Self::Invalidated,
+        }
+    }
+}
+
 /// A stream of work for the device to perform.
 ///
 /// See the module-level documentation for more information.
@@ -101,7 +166,7 @@ impl Stream {
                 flags.bits(),
                 priority.unwrap_or(0),
             )
-            .to_result()?;
+            .to_result_from("cuStreamCreateWithPriority")?;
             Ok(stream)
         }
     }
@@ -110,7 +175,8 @@ impl Stream {
     pub fn get_flags(&self) -> CudaResult<StreamFlags> {
         unsafe {
             let mut bits = 0u32;
-            cuda::cuStreamGetFlags(self.inner, &mut bits as *mut u32).to_result()?;
+            cuda::cuStreamGetFlags(self.inner, &mut bits as *mut u32)
+                .to_result_from("cuStreamGetFlags")?;
             Ok(StreamFlags::from_bits_truncate(bits))
         }
     }
@@ -138,7 +204,8 @@ impl Stream {
     pub fn get_priority(&self) -> CudaResult<i32> {
         unsafe {
             let mut priority = 0i32;
-            cuda::cuStreamGetPriority(self.inner, &mut priority as *mut i32).to_result()?;
+            cuda::cuStreamGetPriority(self.inner, &mut priority as *mut i32)
+                .to_result_from("cuStreamGetPriority")?;
             Ok(priority)
         }
     }
@@ -181,7 +248,7 @@ impl Stream {
                 Some(callback_wrapper::<T>),
                 Box::into_raw(callback) as *mut c_void,
             )
-            .to_result()
+            .to_result_from("cuLaunchHostFunc")
         }
     }
 
@@ -207,8 +274,42 @@ impl Stream {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn synchronize(&self) -> CudaResult<()> {
-        unsafe { cuda::cuStreamSynchronize(self.inner).to_result() }
+    pub fn synchronize(&self) -> DriverResult<()> {
+        unsafe { cuda::cuStreamSynchronize(self.inner).to_driver_result("cuStreamSynchronize") }
+    }
+
+    /// Wait until a stream's tasks are completed without blocking the current thread.
+    ///
+    /// Returns a future which completes once the device has completed all operations scheduled
+    /// for this stream before this call. Unlike [`Stream::synchronize`], this does not block
+    /// the thread, the future is woken by a callback on the stream, so it is suitable for use
+    /// inside of async runtimes. Work scheduled on the stream after this call is not waited for.
+    ///
+    /// # Errors
+    ///
+    /// If the callback cannot be added to the stream, returns the error from CUDA. If the work on
+    /// the stream fails, the future resolves to the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # async fn run() -> Result<(), Box<dyn Error>> {
+    /// # let _ctx = quick_init()?;
+    /// use cust::stream::{Stream, StreamFlags};
+    ///
+    /// let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+    ///
+    /// // ... queue up some work on the stream
+    ///
+    /// // Wait for the work to be completed while letting other tasks run.
+    /// stream.synchronize_async()?.await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn synchronize_async(&self) -> CudaResult<SynchronizeFuture> {
+        SynchronizeFuture::new(self)
     }
 
     /// Make the stream wait on an event.
@@ -217,6 +318,9 @@ impl Stream {
     /// complete. Synchronization is performed on the device, if possible. The
     /// event may originate from different context or device than the stream.
     ///
+    /// The event can be passed by value or by reference, so the same event can be waited on
+    /// by several streams.
+    ///
     /// # Example
     ///
     /// ```
@@ -241,8 +345,106 @@ impl Stream {
     /// # Ok(())
     /// }
     /// ```
-    pub fn wait_event(&self, event: Event, flags: StreamWaitEventFlags) -> CudaResult<()> {
-        unsafe { cuda::cuStreamWaitEvent(self.inner, event.as_inner(), flags.bits()).to_result() }
+    pub fn wait_event<E: Borrow<Event>>(
+        &self,
+        event: E,
+        flags: StreamWaitEventFlags,
+    ) -> CudaResult<()> {
+        unsafe {
+            cuda::cuStreamWaitEvent(self.inner, event.borrow().as_inner(), flags.bits())
+                .to_result_from("cuStreamWaitEvent")
+        }
+    }
+
+    /// Begin capturing the work submitted to this stream into a [`Graph`] instead of executing it.
+    ///
+    /// While capturing, work such as kernel launches and async memory copies will not be executed,
+    /// it will be recorded into a graph which is returned by [`Stream::end_capture`]. Other streams
+    /// can join the capture by waiting on an event recorded in this stream.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use cust::*;
+    /// # use std::error::Error;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let _ctx = quick_init()?;
+    /// # if cfg!(feature = "mock") { return Ok(()); }
+    /// use cust::stream::{Stream, StreamCaptureMode, StreamFlags};
+    ///
+    /// let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+    /// stream.begin_capture(StreamCaptureMode::Global)?;
+    ///
+    /// // ... queue up some work on the stream
+    ///
+    /// let mut graph = stream.end_capture()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn begin_capture(&self, mode: StreamCaptureMode) -> CudaResult<()> {
+        unsafe {
+            cuda::cuStreamBeginCapture_v2(self.inner, mode.to_raw())
+                .to_result_from("cuStreamBeginCapture_v2")
+        }
+    }
+
+    /// End capturing work on this stream, returning the captured graph.
+    ///
+    /// This must be called from the same thread which began the capture if the capture mode
+    /// is not [`StreamCaptureMode::Relaxed`]. If the capture was invalidated this returns
+    /// [`StreamCaptureInvalidated`](crate::error::CudaError::StreamCaptureInvalidated).
+    pub fn end_capture(&self) -> CudaResult<Graph> {
+        unsafe {
+            let mut graph = ptr::null_mut();
+            cuda::cuStreamEndCapture(self.inner, &mut graph as *mut _)
+                .to_result_from("cuStreamEndCapture")?;
+            Ok(Graph::from_raw(graph))
+        }
+    }
+
+    /// Return the capture status of this stream.
+    pub fn capture_status(&self) -> CudaResult<StreamCaptureStatus> {
+        unsafe {
+            let mut status = mem::MaybeUninit::uninit();
+            cuda::cuStreamIsCapturing(self.inner, status.as_mut_ptr())
+                .to_result_from("cuStreamIsCapturing")?;
+            Ok(StreamCaptureStatus::from_raw(status.assume_init()))
+        }
+    }
+
+    /// Whether this stream is currently capturing, this includes captures which were invalidated.
+    pub fn is_capturing(&self) -> CudaResult<bool> {
+        Ok(self.capture_status()? != StreamCaptureStatus::None)
+    }
+
+    /// Return the unique id of the capture this stream is currently part of, or `None` if the
+    /// stream is not capturing. Every capture in a process has a different id.
+    pub fn capture_id(&self) -> CudaResult<Option<u64>> {
+        unsafe {
+            let mut status = mem::MaybeUninit::uninit();
+            let mut id = 0;
+            cuda::cuStreamGetCaptureInfo(self.inner, status.as_mut_ptr(), &mut id as *mut u64)
+                .to_result_from("cuStreamGetCaptureInfo")?;
+            match StreamCaptureStatus::from_raw(status.assume_init()) {
+                StreamCaptureStatus::None => Ok(None),
+                _ => Ok(Some(id)),
+            }
+        }
+    }
+
+    /// Set the capture mode of the calling thread, returning the previous mode.
+    ///
+    /// A thread which sets its mode to [`StreamCaptureMode::Relaxed`] can make potentially
+    /// unsafe API calls while another thread is capturing with [`StreamCaptureMode::Global`].
+    /// The previous mode should be restored afterwards, modes are expected to be set and restored
+    /// in a push-pop fashion.
+    pub fn exchange_capture_mode(mode: StreamCaptureMode) -> CudaResult<StreamCaptureMode> {
+        unsafe {
+            let mut mode = mode.to_raw();
+            cuda::cuThreadExchangeStreamCaptureMode(&mut mode as *mut _)
+                .to_result_from("cuThreadExchangeStreamCaptureMode")?;
+            Ok(StreamCaptureMode::from_raw(mode))
+        }
     }
 
     // Hidden implementation detail function. Highly unsafe. Use the `launch!` macro instead.
@@ -254,7 +456,7 @@ impl Stream {
         block_size: B,
         shared_mem_bytes: u32,
         args: &[*mut c_void],
-    ) -> CudaResult<()>
+    ) -> DriverResult<()>
     where
         G: Into<GridSize>,
         B: Into<BlockSize>,
@@ -275,7 +477,7 @@ impl Stream {
             args.as_ptr() as *mut _,
             ptr::null_mut(),
         )
-        .to_result()
+        .to_driver_result("cuLaunchKernel")
     }
 
     // Get the inner `CUstream` from the `Stream`. If you use this handle elsewhere,
@@ -316,7 +518,7 @@ impl Stream {
 
         unsafe {
             let inner = mem::replace(&mut stream.inner, ptr::null_mut());
-            match cuda::cuStreamDestroy_v2(inner).to_result() {
+            match cuda::cuStreamDestroy_v2(inner).to_result_from("cuStreamDestroy_v2") {
                 Ok(()) => {
                     mem::forget(stream);
                     Ok(())
@@ -349,3 +551,151 @@ where
         callback();
     });
 }
+
+/// A future which completes once a stream or event has completed its work, returned by
+/// [`Stream::synchronize_async`] and [`Event::synchronize_async`].
+///
+/// The future is completed by a callback from the driver, polling it never blocks or queries
+/// the device.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct SynchronizeFuture {
+    state: Arc<Mutex<SynchronizeState>>,
+}
+
+#[derive(Debug, Default)]
+struct SynchronizeState {
+    result: Option<CudaResult<()>>,
+    waker: Option<Waker>,
+}
+
+impl SynchronizeFuture {
+    pub(crate) fn new(stream: &Stream) -> CudaResult<Self> {
+        let state = Arc::new(Mutex::new(SynchronizeState::default()));
+        let data = Arc::into_raw(state.clone()) as *mut c_void;
+        unsafe {
+            // cuStreamAddCallback is used instead of cuLaunchHostFunc because host functions
+            // are not called if the stream fails, which would leave the future pending forever.
+            if let Err(e) =
+                cuda::cuStreamAddCallback(stream.inner, Some(synchronize_callback), data, 0)
+                    .to_result_from("cuStreamAddCallback")
+            {
+                drop(Arc::from_raw(data as *const Mutex<SynchronizeState>));
+                return Err(e);
+            }
+        }
+        Ok(Self { state })
+    }
+}
+
+impl Future for SynchronizeFuture {
+    type Output = CudaResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn synchronize_callback(
+    _stream: CUstream,
+    status: cuda::CUresult,
+    data: *mut c_void,
+) {
+    let state = Arc::from_raw(data as *const Mutex<SynchronizeState>);
+    // Stop panics from unwinding across the FFI
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let waker = {
+            let mut state = state.lock().unwrap();
+            state.result = Some(status.to_result());
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::EventFlags;
+    use crate::memory::{AsyncCopyDestination, DeviceBuffer, LockedBuffer};
+    use crate::quick_init;
+    use std::error::Error;
+    use std::task::Wake;
+    use std::thread::{self, Thread};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_synchronize_async() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let host = LockedBuffer::from_slice(&[1u32; 4096])?;
+        let mut device = DeviceBuffer::from_slice(&[0u32; 4096])?;
+        let mut out = LockedBuffer::new(&0u32, 4096)?;
+        unsafe {
+            device.async_copy_from(&host, &stream)?;
+            device.async_copy_to(&mut out, &stream)?;
+        }
+        block_on(stream.synchronize_async()?)?;
+        assert!(out.iter().all(|&x| x == 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_synchronize_async() -> Result<(), Box<dyn Error>> {
+        let _context = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let waiter = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        let event = Event::new(EventFlags::DISABLE_TIMING)?;
+        event.record(&stream)?;
+        block_on(event.synchronize_async(&waiter)?)?;
+        assert_eq!(event.query()?, crate::event::EventStatus::Ready);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn test_failed_drop_returns_stream() -> Result<(), Box<dyn Error>> {
+        use crate::error::CudaError;
+        use crate::sys::{mock, CUresult};
+
+        let _context = quick_init()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
+        mock::fail_next("cuStreamDestroy_v2", CUresult::CUDA_ERROR_NOT_READY);
+        let (err, stream) = Stream::drop(stream).unwrap_err();
+        assert_eq!(err, CudaError::NotReady);
+        // the stream was not destroyed and can still be used.
+        stream.synchronize()?;
+        mock::clear_calls();
+        Stream::drop(stream).map_err(|(e, _)| e)?;
+        assert_eq!(mock::calls(), ["cuStreamDestroy_v2"]);
+        Ok(())
+    }
+}
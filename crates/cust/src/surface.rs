@@ -47,7 +47,8 @@ impl Surface {
         let raw = resource_desc.into_raw();
         unsafe {
             let mut uninit = MaybeUninit::<CUsurfObject>::uninit();
-            cuSurfObjectCreate(uninit.as_mut_ptr(), &raw as *const _).to_result()?;
+            cuSurfObjectCreate(uninit.as_mut_ptr(), &raw as *const _)
+                .to_result_from("cuSurfObjectCreate")?;
             Ok(Self {
                 handle: uninit.assume_init(),
                 _destroy_array_on_drop: true,
@@ -68,6 +69,8 @@ impl Surface {
         self._destroy_array_on_drop = false;
         Ok(match desc.ty {
             ResourceType::Array { array } => Some(array),
+            // surfaces can only be made from arrays.
+            ResourceType::Pitch2d(_) => None,
         })
     }
 
@@ -75,7 +78,8 @@ impl Surface {
     unsafe fn resource_desc(&mut self) -> CudaResult<ManuallyDrop<ResourceDescriptor>> {
         let raw = {
             let mut uninit = MaybeUninit::<CUDA_RESOURCE_DESC>::uninit();
-            cuSurfObjectGetResourceDesc(uninit.as_mut_ptr(), self.handle).to_result()?;
+            cuSurfObjectGetResourceDesc(uninit.as_mut_ptr(), self.handle)
+                .to_result_from("cuSurfObjectGetResourceDesc")?;
             uninit.assume_init()
         };
         Ok(ManuallyDrop::new(ResourceDescriptor::from_raw(raw)))
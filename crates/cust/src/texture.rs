@@ -3,16 +3,18 @@ use crate::error::ToResult;
 use crate::memory::array::ArrayDescriptor;
 use crate::memory::array::ArrayFormat;
 use crate::memory::array::ArrayObject;
+use crate::memory::{DeviceCopy, DevicePitchedBuffer2D, DevicePointer};
 use crate::sys::cuTexObjectCreate;
 use crate::sys::cuTexObjectGetResourceDesc;
 use crate::sys::{
     self as cuda, cuTexObjectDestroy, CUDA_RESOURCE_DESC_st__bindgen_ty_1,
-    CUDA_RESOURCE_DESC_st__bindgen_ty_1__bindgen_ty_1, CUresourcetype, CUtexObject,
+    CUDA_RESOURCE_DESC_st__bindgen_ty_1__bindgen_ty_1,
+    CUDA_RESOURCE_DESC_st__bindgen_ty_1__bindgen_ty_4, CUresourcetype, CUtexObject,
     CUDA_RESOURCE_DESC, CUDA_RESOURCE_VIEW_DESC, CUDA_TEXTURE_DESC,
 };
-use std::mem::transmute;
 use std::mem::ManuallyDrop;
 use std::mem::MaybeUninit;
+use std::mem::{self, transmute};
 use std::os::raw::c_ulonglong;
 use std::os::raw::{c_float, c_uint};
 use std::ptr;
@@ -316,8 +318,10 @@ bitflags::bitflags! {
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum ResourceType {
-    Array { array: ArrayObject },
-    // TODO: validate the soundness of linear and pitch2, they require some pointer to memory, but
+    Array {
+        array: ArrayObject,
+    },
+    // TODO: validate the soundness of linear, it requires some pointer to memory, but
     // it might be possible to cause unsoundness by allocating some type then allocating a texture, and reading back
     // the texture to host memory. Causing GPU UB is probably fine, but using that to cause host UB is not acceptable.
 
@@ -326,13 +330,111 @@ pub enum ResourceType {
     //     num_channels: u32,
     //     size: usize,
     // },
-    // Pitch2d {
-    //     format: ArrayFormat,
-    //     num_channels: u32,
-    //     width: usize,
-    //     height: usize,
-    //     pitch_in_bytes: usize,
-    // },
+    /// Pitched 2D memory such as a [`DevicePitchedBuffer2D`](crate::memory::DevicePitchedBuffer2D).
+    Pitch2d(Pitch2dResource),
+}
+
+impl ResourceType {
+    /// Makes a resource for a [`DevicePitchedBuffer2D`], treating every element of the buffer as
+    /// `num_channels` values of `format`.
+    ///
+    /// # Safety
+    ///
+    /// The texture does not keep the buffer alive, the buffer must outlive any use of textures
+    /// made from the resource, including by kernels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of `T` is not the size of `num_channels` values of `format`.
+    pub unsafe fn pitch_2d<T: DeviceCopy>(
+        buffer: &DevicePitchedBuffer2D<T>,
+        format: ArrayFormat,
+        num_channels: u32,
+    ) -> Self {
+        assert_eq!(
+            format.mem_size() * num_channels as usize,
+            mem::size_of::<T>(),
+            "the size of the buffer elements does not match the format"
+        );
+        Self::Pitch2d(Pitch2dResource::new(
+            buffer.as_device_ptr().cast(),
+            format,
+            num_channels,
+            buffer.width(),
+            buffer.height(),
+            buffer.pitch(),
+        ))
+    }
+}
+
+/// The pitched 2D memory of a [`ResourceType::Pitch2d`].
+///
+/// This only holds a pointer to the memory, so it can only be made unsafely.
+#[derive(Debug, Clone, Copy)]
+pub struct Pitch2dResource {
+    ptr: DevicePointer<u8>,
+    format: ArrayFormat,
+    num_channels: u32,
+    width: usize,
+    height: usize,
+    pitch_in_bytes: usize,
+}
+
+impl Pitch2dResource {
+    /// Describes `height` rows starting at `ptr`, each `pitch_in_bytes` bytes apart and
+    /// holding `width` elements of `format` with `num_channels` channels.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid for the described rows, and must outlive any use of textures
+    /// made from the resource, including by kernels.
+    pub unsafe fn new(
+        ptr: DevicePointer<u8>,
+        format: ArrayFormat,
+        num_channels: u32,
+        width: usize,
+        height: usize,
+        pitch_in_bytes: usize,
+    ) -> Self {
+        Self {
+            ptr,
+            format,
+            num_channels,
+            width,
+            height,
+            pitch_in_bytes,
+        }
+    }
+
+    /// The start of the memory.
+    pub fn as_device_ptr(&self) -> DevicePointer<u8> {
+        self.ptr
+    }
+
+    /// The format of every channel of the elements.
+    pub fn format(&self) -> ArrayFormat {
+        self.format
+    }
+
+    /// The number of channels of every element.
+    pub fn num_channels(&self) -> u32 {
+        self.num_channels
+    }
+
+    /// The width of every row in elements.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The distance between the starts of two rows in bytes.
+    pub fn pitch(&self) -> usize {
+        self.pitch_in_bytes
+    }
 }
 
 #[derive(Debug)]
@@ -346,7 +448,7 @@ impl ResourceDescriptor {
         let ty = match self.ty {
             ResourceType::Array { .. } => CUresourcetype::CU_RESOURCE_TYPE_ARRAY,
             // ResourceType::Linear { .. } => CUresourcetype::CU_RESOURCE_TYPE_LINEAR,
-            // ResourceType::Pitch2d { .. } => CUresourcetype::CU_RESOURCE_TYPE_PITCH2D,
+            ResourceType::Pitch2d(_) => CUresourcetype::CU_RESOURCE_TYPE_PITCH2D,
         };
 
         // we can't just use `array.handle`, this will cause the array object to call `Drop` and destroy the
@@ -359,6 +461,16 @@ impl ResourceDescriptor {
                 },
             },
             // ResourceType::Linear { format, num_channels, size }
+            ResourceType::Pitch2d(pitch_2d) => CUDA_RESOURCE_DESC_st__bindgen_ty_1 {
+                pitch2D: CUDA_RESOURCE_DESC_st__bindgen_ty_1__bindgen_ty_4 {
+                    devPtr: pitch_2d.ptr.as_raw(),
+                    format: pitch_2d.format.to_raw(),
+                    numChannels: pitch_2d.num_channels,
+                    width: pitch_2d.width,
+                    height: pitch_2d.height,
+                    pitchInBytes: pitch_2d.pitch_in_bytes,
+                },
+            },
         };
 
         CUDA_RESOURCE_DESC {
@@ -380,6 +492,21 @@ impl ResourceDescriptor {
                     },
                 },
             },
+            cuda::CUresourcetype_enum::CU_RESOURCE_TYPE_PITCH2D => {
+                let pitch_2d = unsafe { raw.res.pitch2D };
+                Self {
+                    flags: ResourceDescriptorFlags::from_bits(raw.flags)
+                        .expect("invalid resource descriptor flags"),
+                    ty: ResourceType::Pitch2d(Pitch2dResource {
+                        ptr: DevicePointer::from_raw(pitch_2d.devPtr),
+                        format: ArrayFormat::from_raw(pitch_2d.format),
+                        num_channels: pitch_2d.numChannels,
+                        width: pitch_2d.width,
+                        height: pitch_2d.height,
+                        pitch_in_bytes: pitch_2d.pitchInBytes,
+                    }),
+                }
+            }
             _ => panic!("Unsupported resource descriptor"),
         }
     }
@@ -440,7 +567,7 @@ impl Texture {
                 texture_desc as *const _,
                 resource_view_desc as *const _,
             )
-            .to_result()?;
+            .to_result_from("cuTexObjectCreate")?;
             if !resource_view_desc.is_null() {
                 let _ = Box::from_raw(resource_view_desc);
             }
@@ -460,11 +587,30 @@ impl Texture {
         Self::new(resource_desc, Default::default(), None)
     }
 
+    /// Creates a texture reading from a [`DevicePitchedBuffer2D`], see [`ResourceType::pitch_2d`].
+    ///
+    /// # Safety
+    ///
+    /// The texture does not keep the buffer alive, the buffer must outlive any use of the
+    /// texture, including by kernels.
+    pub unsafe fn from_pitched_2d<T: DeviceCopy>(
+        buffer: &DevicePitchedBuffer2D<T>,
+        format: ArrayFormat,
+        num_channels: u32,
+    ) -> CudaResult<Self> {
+        let resource_desc = ResourceDescriptor {
+            flags: ResourceDescriptorFlags::empty(),
+            ty: ResourceType::pitch_2d(buffer, format, num_channels),
+        };
+        Self::new(resource_desc, Default::default(), None)
+    }
+
     pub fn into_array(mut self) -> CudaResult<Option<ArrayObject>> {
         let desc = unsafe { ManuallyDrop::take(&mut self.resource_desc()?) };
         self._destroy_array_on_destruct = false;
         Ok(match desc.ty {
             ResourceType::Array { array } => Some(array),
+            ResourceType::Pitch2d(_) => None,
         })
     }
 
@@ -480,7 +626,8 @@ impl Texture {
     unsafe fn resource_desc(&mut self) -> CudaResult<ManuallyDrop<ResourceDescriptor>> {
         let raw = {
             let mut uninit = MaybeUninit::<CUDA_RESOURCE_DESC>::uninit();
-            cuTexObjectGetResourceDesc(uninit.as_mut_ptr(), self.handle).to_result()?;
+            cuTexObjectGetResourceDesc(uninit.as_mut_ptr(), self.handle)
+                .to_result_from("cuTexObjectGetResourceDesc")?;
             uninit.assume_init()
         };
         Ok(ManuallyDrop::new(ResourceDescriptor::from_raw(raw)))
@@ -489,7 +636,7 @@ impl Texture {
     // pub fn resource_view_desc(&self) -> CudaResult<ResourceViewDescriptor> {
     //     let raw = unsafe {
     //         let ptr = ptr::null_mut();
-    //         cuTexObjectGetResourceViewDesc(ptr, self.handle).to_result()?;
+    //         cuTexObjectGetResourceViewDesc(ptr, self.handle).to_result_from("cuTexObjectGetResourceViewDesc")?;
     //         *ptr
     //     };
     //     Ok(ResourceViewDescriptor::)
@@ -6,8 +6,8 @@ extern crate syn;
 
 use proc_macro2::{Ident, Span, TokenStream};
 use syn::{
-    parse_str, Data, DataEnum, DataStruct, DataUnion, DeriveInput, Field, Fields, Generics,
-    TypeParamBound,
+    parse_quote, parse_str, Block, Data, DataEnum, DataStruct, DataUnion, DeriveInput, Error,
+    Field, Fields, Generics, ItemFn, LitStr, TypeParamBound,
 };
 
 #[proc_macro_derive(DeviceCopyCore)]
@@ -25,6 +25,41 @@ pub fn device_copy(input: BaseTokenStream) -> BaseTokenStream {
 
 use proc_macro::TokenStream as BaseTokenStream;
 
+#[proc_macro_attribute]
+pub fn nvtx_range(attr: BaseTokenStream, item: BaseTokenStream) -> BaseTokenStream {
+    let mut func: ItemFn = match syn::parse(item) {
+        Ok(func) => func,
+        Err(e) => return BaseTokenStream::from(e.to_compile_error()),
+    };
+    if let Some(asyncness) = func.sig.asyncness {
+        return BaseTokenStream::from(
+            Error::new_spanned(
+                asyncness,
+                "#[nvtx_range] does not support async functions, ranges cannot be held across awaits",
+            )
+            .to_compile_error(),
+        );
+    }
+
+    // the range is named after the function unless a name is given like #[nvtx_range("name")]
+    let name = if attr.is_empty() {
+        func.sig.ident.to_string()
+    } else {
+        match syn::parse::<LitStr>(attr) {
+            Ok(name) => name.value(),
+            Err(e) => return BaseTokenStream::from(e.to_compile_error()),
+        }
+    };
+
+    let block = &func.block;
+    let wrapped: Block = parse_quote!({
+        let _nvtx_range = ::cust::nvtx::Range::push(#name);
+        #block
+    });
+    *func.block = wrapped;
+    BaseTokenStream::from(quote!(#func))
+}
+
 fn impl_device_copy(input: &DeriveInput, import: TokenStream) -> TokenStream {
     let input_type = &input.ident;
 
@@ -2,3 +2,6 @@
 
 mod cuda;
 pub use cuda::*;
+
+#[cfg(feature = "mock")]
+pub mod mock;
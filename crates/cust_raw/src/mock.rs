@@ -0,0 +1,2841 @@
+//! An in-process emulation of the CUDA driver API, enabled with the `mock` feature.
+//!
+//! With the feature enabled this crate no longer links to `libcuda`, instead this module defines
+//! the driver functions itself. "Device" memory is kept in host memory and all work submitted to
+//! streams runs synchronously on the calling thread. This allows code built on the driver API to
+//! be tested on machines without a GPU or a CUDA installation.
+//!
+//! The emulation covers:
+//!
+//! - Initialization and device queries, with [`DEVICE_COUNT`] devices.
+//! - Contexts, including primary contexts and the per-thread context stack. Destroying a context
+//!   frees the memory and arrays allocated in it.
+//! - Streams, events and host callbacks, the callbacks run immediately.
+//! - Context cache and shared memory configurations and resource limits, which are only stored.
+//! - Device, pitched, managed and page-locked allocations, host registration, and memcpy and memset
+//!   on all of them.
+//! - Arrays and copies to and from them. The texture size limits are those of a compute
+//!   capability 8.6 device, and NV12 arrays are not supported.
+//! - Module loading and kernel launches, which validate their arguments but do not run anything.
+//!   Occupancy only depends on the block size and dynamic shared memory, every kernel is treated
+//!   as using no registers or static shared memory.
+//! - Linking, which produces a fake cubin made of the inputs, which can be loaded as a module.
+//! - External semaphores imported from file descriptors. Waiting for a value that was not signaled
+//!   yet fails, since nothing else could ever signal it. Importing external memory validates the
+//!   handle but always fails with `CUDA_ERROR_NOT_SUPPORTED` for valid ones.
+//!
+//! Every other driver function used by `cust` returns `CUDA_ERROR_NOT_SUPPORTED`.
+//!
+//! Any driver function can be made to fail with [`fail_next`], [`fail_nth`] and [`fail_always`]
+//! to exercise error paths, and [`calls`] returns the driver functions called so far, which is
+//! useful for checking the order things are dropped in. Faults and the call log are kept per
+//! thread, so tests running in parallel do not interfere with each other.
+//!
+//! ```
+//! use cust_raw::mock;
+//! use cust_raw::*;
+//!
+//! unsafe {
+//!     assert_eq!(cuInit(0), CUresult::CUDA_SUCCESS);
+//!     mock::fail_next("cuDeviceGetCount", CUresult::CUDA_ERROR_NO_DEVICE);
+//!     let mut count = 0;
+//!     assert_eq!(cuDeviceGetCount(&mut count), CUresult::CUDA_ERROR_NO_DEVICE);
+//!     assert_eq!(cuDeviceGetCount(&mut count), CUresult::CUDA_SUCCESS);
+//!     assert_eq!(count, mock::DEVICE_COUNT);
+//! }
+//! ```
+
+use crate::cuda::cudaError_enum::*;
+use crate::cuda::*;
+use std::alloc::{self, Layout};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ushort, c_void};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, Once};
+use std::thread::{self, ThreadId};
+use std::time::Instant;
+
+/// The number of devices reported by the emulated driver.
+pub const DEVICE_COUNT: c_int = 1;
+
+/// The amount of memory reported by every emulated device. Allocations past it fail with
+/// `CUDA_ERROR_OUT_OF_MEMORY`.
+pub const TOTAL_MEMORY: usize = 4 << 30;
+
+/// The driver version reported by the emulated driver, 11.4.
+pub const DRIVER_VERSION: c_int = 11040;
+
+const DEVICE_NAME: &str = "cust mock device";
+// the driver guarantees device allocations are aligned to at least 256 bytes.
+const ALLOCATION_ALIGNMENT: usize = 256;
+const PITCH_ALIGNMENT: usize = 512;
+const MAX_THREADS_PER_BLOCK: c_uint = 1024;
+const MAX_SHARED_MEMORY_PER_BLOCK: usize = 48 * 1024;
+const MULTIPROCESSOR_COUNT: c_int = 16;
+const MAX_THREADS_PER_MULTIPROCESSOR: c_uint = 2048;
+const MAX_BLOCKS_PER_MULTIPROCESSOR: c_uint = 32;
+const MAX_SHARED_MEMORY_PER_MULTIPROCESSOR: usize = 100 * 1024;
+
+type MockResult<T = ()> = Result<T, CUresult>;
+
+struct Fault {
+    function: &'static str,
+    skip: usize,
+    error: CUresult,
+    persistent: bool,
+}
+
+thread_local! {
+    static FAULTS: RefCell<Vec<Fault>> = RefCell::new(Vec::new());
+    static CALLS: RefCell<Vec<&'static str>> = RefCell::new(Vec::new());
+    static CONTEXT_STACK: RefCell<Vec<usize>> = RefCell::new(Vec::new());
+}
+
+/// Makes the next call to the driver function `function` on this thread fail with `error`.
+///
+/// `function` is the name of the symbol including any version suffix, such as `"cuMemAlloc_v2"`.
+pub fn fail_next(function: &'static str, error: CUresult) {
+    fail_nth(function, 0, error);
+}
+
+/// Makes the `n`th call to `function` on this thread fail with `error`, counting from zero. The
+/// calls before it behave as usual.
+pub fn fail_nth(function: &'static str, n: usize, error: CUresult) {
+    FAULTS.with(|faults| {
+        faults.borrow_mut().push(Fault {
+            function,
+            skip: n,
+            error,
+            persistent: false,
+        })
+    });
+}
+
+/// Makes every call to `function` on this thread fail with `error` until [`clear_faults`] is
+/// called.
+pub fn fail_always(function: &'static str, error: CUresult) {
+    FAULTS.with(|faults| {
+        faults.borrow_mut().push(Fault {
+            function,
+            skip: 0,
+            error,
+            persistent: true,
+        })
+    });
+}
+
+/// Removes every fault injected on this thread which has not been triggered yet.
+pub fn clear_faults() {
+    FAULTS.with(|faults| faults.borrow_mut().clear());
+}
+
+/// Returns the names of the driver functions called on this thread, in the order they were
+/// called, since the thread started or [`clear_calls`] was last called.
+pub fn calls() -> Vec<&'static str> {
+    CALLS.with(|calls| calls.borrow().clone())
+}
+
+/// Clears the log of driver functions called on this thread.
+pub fn clear_calls() {
+    CALLS.with(|calls| calls.borrow_mut().clear());
+}
+
+/// Returns the number of device, managed and page-locked allocations made on this thread which
+/// have not been freed yet, either explicitly or by destroying their context.
+pub fn live_allocations() -> usize {
+    let current = thread::current().id();
+    driver()
+        .allocations
+        .values()
+        .filter(|alloc| alloc.owner == current && alloc.layout.is_some())
+        .count()
+}
+
+fn take_fault(function: &'static str) -> Option<CUresult> {
+    FAULTS.with(|faults| {
+        let mut faults = faults.borrow_mut();
+        let idx = faults.iter().position(|fault| fault.function == function)?;
+        let fault = &mut faults[idx];
+        if fault.skip > 0 {
+            fault.skip -= 1;
+            return None;
+        }
+        let error = fault.error;
+        if !fault.persistent {
+            faults.remove(idx);
+        }
+        Some(error)
+    })
+}
+
+// Logs a call to a driver function and runs it, unless a fault was injected for it.
+fn call(function: &'static str, f: impl FnOnce() -> MockResult) -> CUresult {
+    CALLS.with(|calls| calls.borrow_mut().push(function));
+    if let Some(error) = take_fault(function) {
+        return error;
+    }
+    match f() {
+        Ok(()) => CUDA_SUCCESS,
+        Err(error) => error,
+    }
+}
+
+fn check(condition: bool, error: CUresult) -> MockResult {
+    if condition {
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
+
+unsafe fn write<T>(out: *mut T, value: T) -> MockResult {
+    check(!out.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+    out.write(value);
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MemoryKind {
+    Device,
+    Managed,
+    Host,
+    Registered,
+}
+
+struct Allocation {
+    size: usize,
+    kind: MemoryKind,
+    // registered memory is owned by the caller and has no layout.
+    layout: Option<Layout>,
+    context: usize,
+    owner: ThreadId,
+}
+
+struct ContextState {
+    device: CUdevice,
+    flags: c_uint,
+    cache_config: CUfunc_cache,
+    shared_config: CUsharedconfig,
+    // limits that were never set keep their default value.
+    limits: HashMap<CUlimit, usize>,
+}
+
+struct ArrayState {
+    descriptor: CUDA_ARRAY3D_DESCRIPTOR,
+    // every layer, row and element is stored densely, in that order.
+    data: Vec<u8>,
+    context: usize,
+}
+
+struct PrimaryContext {
+    handle: usize,
+    retained: u32,
+}
+
+struct StreamState {
+    flags: c_uint,
+    priority: c_int,
+}
+
+struct EventState {
+    flags: c_uint,
+    recorded: Option<Instant>,
+}
+
+struct SemaphoreState {
+    timeline: bool,
+    // the last signaled value, binary semaphores are either 0 or 1.
+    value: u64,
+}
+
+struct LinkState {
+    inputs: usize,
+    // stays allocated until the link state is destroyed, like the output of the real linker.
+    image: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Driver {
+    initialized: bool,
+    next_handle: usize,
+    contexts: HashMap<usize, ContextState>,
+    primary_contexts: HashMap<CUdevice, PrimaryContext>,
+    primary_flags: HashMap<CUdevice, c_uint>,
+    streams: HashMap<usize, StreamState>,
+    events: HashMap<usize, EventState>,
+    // module -> context
+    modules: HashMap<usize, usize>,
+    // function -> module
+    functions: HashMap<usize, usize>,
+    links: HashMap<usize, LinkState>,
+    semaphores: HashMap<usize, SemaphoreState>,
+    allocations: BTreeMap<usize, Allocation>,
+    arrays: HashMap<usize, ArrayState>,
+    used_memory: usize,
+    // the heap buffers of the strings never move, so pointers to them stay valid.
+    error_strings: HashMap<(CUresult, bool), CString>,
+}
+
+fn driver() -> MutexGuard<'static, Driver> {
+    static INIT: Once = Once::new();
+    static mut DRIVER: *const Mutex<Driver> = ptr::null();
+
+    unsafe {
+        INIT.call_once(|| DRIVER = Box::into_raw(Box::new(Mutex::new(Driver::default()))));
+        // a panicking test should not take down every other test with it.
+        (*DRIVER).lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Locks the driver, failing if `cuInit` has not been called yet.
+fn initialized() -> MockResult<MutexGuard<'static, Driver>> {
+    let driver = driver();
+    check(driver.initialized, CUDA_ERROR_NOT_INITIALIZED)?;
+    Ok(driver)
+}
+
+impl Driver {
+    fn new_handle(&mut self) -> usize {
+        // the null, legacy and per-thread streams are 0, 1 and 2, keep clear of them.
+        self.next_handle += 0x10;
+        self.next_handle
+    }
+
+    fn check_device(&self, dev: CUdevice) -> MockResult {
+        check((0..DEVICE_COUNT).contains(&dev), CUDA_ERROR_INVALID_DEVICE)
+    }
+
+    fn current_context(&self) -> MockResult<usize> {
+        let ctx = CONTEXT_STACK.with(|stack| stack.borrow().last().copied());
+        match ctx {
+            Some(ctx) if self.contexts.contains_key(&ctx) => Ok(ctx),
+            _ => Err(CUDA_ERROR_INVALID_CONTEXT),
+        }
+    }
+
+    fn check_context(&self, ctx: CUcontext) -> MockResult {
+        check(
+            self.contexts.contains_key(&(ctx as usize)),
+            CUDA_ERROR_INVALID_CONTEXT,
+        )
+    }
+
+    fn check_stream(&self, stream: CUstream) -> MockResult {
+        let stream = stream as usize;
+        check(
+            stream <= 2 || self.streams.contains_key(&stream),
+            CUDA_ERROR_INVALID_HANDLE,
+        )
+    }
+
+    fn check_function(&self, func: CUfunction) -> MockResult {
+        check(
+            self.functions.contains_key(&(func as usize)),
+            CUDA_ERROR_INVALID_HANDLE,
+        )
+    }
+
+    fn create_context(&mut self, device: CUdevice, flags: c_uint) -> usize {
+        let handle = self.new_handle();
+        self.contexts.insert(
+            handle,
+            ContextState {
+                device,
+                flags,
+                cache_config: CUfunc_cache::CU_FUNC_CACHE_PREFER_NONE,
+                shared_config: CUsharedconfig::CU_SHARED_MEM_CONFIG_FOUR_BYTE_BANK_SIZE,
+                limits: HashMap::new(),
+            },
+        );
+        handle
+    }
+
+    fn destroy_context(&mut self, ctx: usize) {
+        self.contexts.remove(&ctx);
+        let freed = self
+            .allocations
+            .iter()
+            .filter(|(_, alloc)| alloc.context == ctx)
+            .map(|(&ptr, _)| ptr)
+            .collect::<Vec<_>>();
+        for ptr in freed {
+            self.release(ptr);
+        }
+        let arrays = self
+            .arrays
+            .iter()
+            .filter(|(_, array)| array.context == ctx)
+            .map(|(&handle, _)| handle)
+            .collect::<Vec<_>>();
+        for handle in arrays {
+            self.destroy_array(handle);
+        }
+        self.modules.retain(|_, owner| *owner != ctx);
+        let modules = &self.modules;
+        self.functions
+            .retain(|_, module| modules.contains_key(module));
+        CONTEXT_STACK.with(|stack| stack.borrow_mut().retain(|c| *c != ctx));
+    }
+
+    fn allocate(&mut self, size: usize, kind: MemoryKind) -> MockResult<usize> {
+        let context = self.current_context()?;
+        check(size != 0, CUDA_ERROR_INVALID_VALUE)?;
+        if kind != MemoryKind::Host {
+            check(
+                size <= TOTAL_MEMORY - self.used_memory,
+                CUDA_ERROR_OUT_OF_MEMORY,
+            )?;
+        }
+        let layout = Layout::from_size_align(size, ALLOCATION_ALIGNMENT)
+            .map_err(|_| CUDA_ERROR_OUT_OF_MEMORY)?;
+        // zeroed so that reading memory nothing was written to is not undefined behavior.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) } as usize;
+        check(ptr != 0, CUDA_ERROR_OUT_OF_MEMORY)?;
+        if kind != MemoryKind::Host {
+            self.used_memory += size;
+        }
+        self.allocations.insert(
+            ptr,
+            Allocation {
+                size,
+                kind,
+                layout: Some(layout),
+                context,
+                owner: thread::current().id(),
+            },
+        );
+        Ok(ptr)
+    }
+
+    fn free(&mut self, ptr: usize, kinds: &[MemoryKind]) -> MockResult {
+        self.current_context()?;
+        match self.allocations.get(&ptr) {
+            Some(alloc) if kinds.contains(&alloc.kind) => {
+                self.release(ptr);
+                Ok(())
+            }
+            _ => Err(CUDA_ERROR_INVALID_VALUE),
+        }
+    }
+
+    fn release(&mut self, ptr: usize) {
+        if let Some(alloc) = self.allocations.remove(&ptr) {
+            if let Some(layout) = alloc.layout {
+                unsafe { alloc::dealloc(ptr as *mut u8, layout) };
+            }
+            if alloc.kind != MemoryKind::Host && alloc.kind != MemoryKind::Registered {
+                self.used_memory -= alloc.size;
+            }
+        }
+    }
+
+    // Finds the allocation containing the `len` bytes starting at `ptr`.
+    fn find(&self, ptr: usize, len: usize) -> Option<(usize, &Allocation)> {
+        let (&start, alloc) = self.allocations.range(..=ptr).next_back()?;
+        let end = ptr.checked_add(len)?;
+        if end <= start + alloc.size {
+            Some((start, alloc))
+        } else {
+            None
+        }
+    }
+
+    // Checks that `len` bytes of memory at `ptr` can be accessed from a device, returning them
+    // as a host pointer. All kinds of memory are accessible because of unified addressing.
+    fn device_memory(&self, ptr: CUdeviceptr, len: usize) -> MockResult<*mut u8> {
+        if len != 0 {
+            self.find(ptr as usize, len)
+                .ok_or(CUDA_ERROR_INVALID_VALUE)?;
+        }
+        Ok(ptr as usize as *mut u8)
+    }
+
+    fn destroy_array(&mut self, handle: usize) -> Option<ArrayState> {
+        let array = self.arrays.remove(&handle)?;
+        self.used_memory -= array.data.len();
+        Some(array)
+    }
+
+    fn array(&self, array: CUarray) -> MockResult<&ArrayState> {
+        self.arrays
+            .get(&(array as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)
+    }
+
+    // Checks that `len` bytes at `offset` lie inside `array`, returning them as a host pointer.
+    fn array_memory(&mut self, array: CUarray, offset: usize, len: usize) -> MockResult<*mut u8> {
+        let array = self
+            .arrays
+            .get_mut(&(array as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        check(
+            offset
+                .checked_add(len)
+                .map_or(false, |end| end <= array.data.len()),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        Ok(unsafe { array.data.as_mut_ptr().add(offset) })
+    }
+}
+
+fn device_attribute(attrib: CUdevice_attribute) -> c_int {
+    use CUdevice_attribute::*;
+
+    match attrib {
+        CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_BLOCK => MAX_THREADS_PER_BLOCK as c_int,
+        CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_X | CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Y => 1024,
+        CU_DEVICE_ATTRIBUTE_MAX_BLOCK_DIM_Z => 64,
+        CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_X => c_int::MAX,
+        CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Y | CU_DEVICE_ATTRIBUTE_MAX_GRID_DIM_Z => 65535,
+        CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_BLOCK => MAX_SHARED_MEMORY_PER_BLOCK as c_int,
+        CU_DEVICE_ATTRIBUTE_TOTAL_CONSTANT_MEMORY => 64 * 1024,
+        CU_DEVICE_ATTRIBUTE_WARP_SIZE => 32,
+        CU_DEVICE_ATTRIBUTE_MAX_PITCH => c_int::MAX,
+        CU_DEVICE_ATTRIBUTE_MAX_REGISTERS_PER_BLOCK => 64 * 1024,
+        CU_DEVICE_ATTRIBUTE_CLOCK_RATE => 1_500_000,
+        CU_DEVICE_ATTRIBUTE_TEXTURE_ALIGNMENT => PITCH_ALIGNMENT as c_int,
+        CU_DEVICE_ATTRIBUTE_MULTIPROCESSOR_COUNT => MULTIPROCESSOR_COUNT,
+        CU_DEVICE_ATTRIBUTE_MAX_THREADS_PER_MULTIPROCESSOR => {
+            MAX_THREADS_PER_MULTIPROCESSOR as c_int
+        }
+        CU_DEVICE_ATTRIBUTE_MAX_BLOCKS_PER_MULTIPROCESSOR => MAX_BLOCKS_PER_MULTIPROCESSOR as c_int,
+        CU_DEVICE_ATTRIBUTE_MAX_SHARED_MEMORY_PER_MULTIPROCESSOR => {
+            MAX_SHARED_MEMORY_PER_MULTIPROCESSOR as c_int
+        }
+        CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MAJOR => 8,
+        CU_DEVICE_ATTRIBUTE_COMPUTE_CAPABILITY_MINOR => 6,
+        CU_DEVICE_ATTRIBUTE_CAN_MAP_HOST_MEMORY
+        | CU_DEVICE_ATTRIBUTE_UNIFIED_ADDRESSING
+        | CU_DEVICE_ATTRIBUTE_MANAGED_MEMORY
+        | CU_DEVICE_ATTRIBUTE_CONCURRENT_MANAGED_ACCESS
+        | CU_DEVICE_ATTRIBUTE_COOPERATIVE_LAUNCH => 1,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_WIDTH
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_WIDTH => 131072,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_HEIGHT => 65536,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_WIDTH
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_HEIGHT
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_DEPTH => 16384,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_WIDTH_ALTERNATE
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_HEIGHT_ALTERNATE => 8192,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE3D_DEPTH_ALTERNATE => 32768,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_LAYERED_WIDTH
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LAYERED_WIDTH
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LAYERED_HEIGHT
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_GATHER_WIDTH
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_GATHER_HEIGHT
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURECUBEMAP_WIDTH
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURECUBEMAP_LAYERED_WIDTH => 32768,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE1D_LAYERED_LAYERS
+        | CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURE2D_LAYERED_LAYERS => 2048,
+        CU_DEVICE_ATTRIBUTE_MAXIMUM_TEXTURECUBEMAP_LAYERED_LAYERS => 2046,
+        _ => 0,
+    }
+}
+
+fn default_limit(limit: CUlimit) -> usize {
+    use CUlimit::*;
+
+    match limit {
+        CU_LIMIT_STACK_SIZE => 1024,
+        CU_LIMIT_PRINTF_FIFO_SIZE => 1 << 20,
+        CU_LIMIT_MALLOC_HEAP_SIZE => 8 << 20,
+        CU_LIMIT_DEV_RUNTIME_SYNC_DEPTH => 2,
+        CU_LIMIT_DEV_RUNTIME_PENDING_LAUNCH_COUNT => 2048,
+        CU_LIMIT_MAX_L2_FETCH_GRANULARITY => 64,
+        CU_LIMIT_PERSISTING_L2_CACHE_SIZE | CU_LIMIT_MAX => 0,
+    }
+}
+
+// The size of one element of an array, or `None` for formats the mock does not store.
+fn array_element_size(format: CUarray_format) -> Option<usize> {
+    use CUarray_format::*;
+
+    match format {
+        CU_AD_FORMAT_UNSIGNED_INT8 | CU_AD_FORMAT_SIGNED_INT8 => Some(1),
+        CU_AD_FORMAT_UNSIGNED_INT16 | CU_AD_FORMAT_SIGNED_INT16 | CU_AD_FORMAT_HALF => Some(2),
+        CU_AD_FORMAT_UNSIGNED_INT32 | CU_AD_FORMAT_SIGNED_INT32 | CU_AD_FORMAT_FLOAT => Some(4),
+        CU_AD_FORMAT_NV12 => None,
+    }
+}
+
+fn error_description(error: CUresult) -> Option<&'static str> {
+    Some(match error {
+        CUDA_SUCCESS => "no error",
+        CUDA_ERROR_INVALID_VALUE => "invalid argument",
+        CUDA_ERROR_OUT_OF_MEMORY => "out of memory",
+        CUDA_ERROR_NOT_INITIALIZED => "initialization error",
+        CUDA_ERROR_DEINITIALIZED => "driver shutting down",
+        CUDA_ERROR_NO_DEVICE => "no CUDA-capable device is detected",
+        CUDA_ERROR_INVALID_DEVICE => "invalid device ordinal",
+        CUDA_ERROR_INVALID_IMAGE => "device kernel image is invalid",
+        CUDA_ERROR_INVALID_CONTEXT => "invalid device context",
+        CUDA_ERROR_FILE_NOT_FOUND => "file not found",
+        CUDA_ERROR_INVALID_HANDLE => "invalid resource handle",
+        CUDA_ERROR_NOT_FOUND => "named symbol not found",
+        CUDA_ERROR_NOT_READY => "device not ready",
+        CUDA_ERROR_ILLEGAL_ADDRESS => "an illegal memory access was encountered",
+        CUDA_ERROR_LAUNCH_OUT_OF_RESOURCES => "too many resources requested for launch",
+        CUDA_ERROR_LAUNCH_FAILED => "unspecified launch failure",
+        CUDA_ERROR_COOPERATIVE_LAUNCH_TOO_LARGE => "too many blocks in cooperative launch",
+        CUDA_ERROR_HOST_MEMORY_ALREADY_REGISTERED => {
+            "part or all of the requested memory range is already mapped"
+        }
+        CUDA_ERROR_HOST_MEMORY_NOT_REGISTERED => {
+            "pointer does not correspond to a registered memory region"
+        }
+        CUDA_ERROR_NOT_PERMITTED => "operation not permitted",
+        CUDA_ERROR_NOT_SUPPORTED => "operation not supported",
+        CUDA_ERROR_UNKNOWN => "unknown error",
+        _ => return None,
+    })
+}
+
+unsafe fn error_string(error: CUresult, describe: bool, out: *mut *const c_char) -> MockResult {
+    let mut driver = driver();
+    let string = driver
+        .error_strings
+        .entry((error, describe))
+        .or_insert_with(|| {
+            let name = format!("{:?}", error);
+            let text = if describe {
+                error_description(error).map(str::to_string).unwrap_or(name)
+            } else {
+                name
+            };
+            CString::new(text).unwrap()
+        });
+    write(out, string.as_ptr())
+}
+
+// ---------------- Initialization and devices ----------------
+
+#[no_mangle]
+unsafe extern "C" fn cuInit(flags: c_uint) -> CUresult {
+    call("cuInit", || {
+        check(flags == 0, CUDA_ERROR_INVALID_VALUE)?;
+        driver().initialized = true;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDriverGetVersion(version: *mut c_int) -> CUresult {
+    call("cuDriverGetVersion", || write(version, DRIVER_VERSION))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuGetErrorName(error: CUresult, out: *mut *const c_char) -> CUresult {
+    call("cuGetErrorName", || error_string(error, false, out))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuGetErrorString(error: CUresult, out: *mut *const c_char) -> CUresult {
+    call("cuGetErrorString", || error_string(error, true, out))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDeviceGetCount(count: *mut c_int) -> CUresult {
+    call("cuDeviceGetCount", || {
+        initialized()?;
+        write(count, DEVICE_COUNT)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDeviceGet(device: *mut CUdevice, ordinal: c_int) -> CUresult {
+    call("cuDeviceGet", || {
+        initialized()?.check_device(ordinal)?;
+        write(device, ordinal)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDeviceGetName(name: *mut c_char, len: c_int, dev: CUdevice) -> CUresult {
+    call("cuDeviceGetName", || {
+        initialized()?.check_device(dev)?;
+        check(!name.is_null() && len > 0, CUDA_ERROR_INVALID_VALUE)?;
+        let bytes = DEVICE_NAME.as_bytes();
+        let count = bytes.len().min(len as usize - 1);
+        ptr::copy_nonoverlapping(bytes.as_ptr(), name as *mut u8, count);
+        *name.add(count) = 0;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDeviceTotalMem_v2(bytes: *mut usize, dev: CUdevice) -> CUresult {
+    call("cuDeviceTotalMem_v2", || {
+        initialized()?.check_device(dev)?;
+        write(bytes, TOTAL_MEMORY)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDeviceGetAttribute(
+    pi: *mut c_int,
+    attrib: CUdevice_attribute,
+    dev: CUdevice,
+) -> CUresult {
+    call("cuDeviceGetAttribute", || {
+        initialized()?.check_device(dev)?;
+        write(pi, device_attribute(attrib))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDeviceGetUuid(uuid: *mut CUuuid, dev: CUdevice) -> CUresult {
+    call("cuDeviceGetUuid", || {
+        initialized()?.check_device(dev)?;
+        let mut bytes = [0; 16];
+        bytes[15] = dev as c_char;
+        write(uuid, CUuuid { bytes })
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDeviceCanAccessPeer(
+    can_access: *mut c_int,
+    dev: CUdevice,
+    peer: CUdevice,
+) -> CUresult {
+    call("cuDeviceCanAccessPeer", || {
+        let driver = initialized()?;
+        driver.check_device(dev)?;
+        driver.check_device(peer)?;
+        write(can_access, 0)
+    })
+}
+
+// ---------------- Contexts ----------------
+
+#[no_mangle]
+unsafe extern "C" fn cuDevicePrimaryCtxRetain(pctx: *mut CUcontext, dev: CUdevice) -> CUresult {
+    call("cuDevicePrimaryCtxRetain", || {
+        let mut driver = initialized()?;
+        driver.check_device(dev)?;
+        check(!pctx.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let handle = match driver.primary_contexts.get_mut(&dev) {
+            Some(primary) => {
+                primary.retained += 1;
+                primary.handle
+            }
+            None => {
+                let flags = driver.primary_flags.get(&dev).copied().unwrap_or(0);
+                let handle = driver.create_context(dev, flags);
+                driver.primary_contexts.insert(
+                    dev,
+                    PrimaryContext {
+                        handle,
+                        retained: 1,
+                    },
+                );
+                handle
+            }
+        };
+        write(pctx, handle as CUcontext)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDevicePrimaryCtxRelease_v2(dev: CUdevice) -> CUresult {
+    call("cuDevicePrimaryCtxRelease_v2", || {
+        let mut driver = initialized()?;
+        driver.check_device(dev)?;
+        let primary = driver
+            .primary_contexts
+            .get_mut(&dev)
+            .ok_or(CUDA_ERROR_INVALID_CONTEXT)?;
+        primary.retained -= 1;
+        if primary.retained == 0 {
+            let handle = primary.handle;
+            driver.primary_contexts.remove(&dev);
+            driver.destroy_context(handle);
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDevicePrimaryCtxSetFlags_v2(dev: CUdevice, flags: c_uint) -> CUresult {
+    call("cuDevicePrimaryCtxSetFlags_v2", || {
+        let mut driver = initialized()?;
+        driver.check_device(dev)?;
+        driver.primary_flags.insert(dev, flags);
+        if let Some(handle) = driver.primary_contexts.get(&dev).map(|p| p.handle) {
+            if let Some(ctx) = driver.contexts.get_mut(&handle) {
+                ctx.flags = flags;
+            }
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDevicePrimaryCtxGetState(
+    dev: CUdevice,
+    flags: *mut c_uint,
+    active: *mut c_int,
+) -> CUresult {
+    call("cuDevicePrimaryCtxGetState", || {
+        let driver = initialized()?;
+        driver.check_device(dev)?;
+        check(
+            !flags.is_null() && !active.is_null(),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        write(flags, driver.primary_flags.get(&dev).copied().unwrap_or(0))?;
+        write(active, driver.primary_contexts.contains_key(&dev) as c_int)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDevicePrimaryCtxReset_v2(dev: CUdevice) -> CUresult {
+    call("cuDevicePrimaryCtxReset_v2", || {
+        let mut driver = initialized()?;
+        driver.check_device(dev)?;
+        driver.primary_flags.remove(&dev);
+        if let Some(primary) = driver.primary_contexts.remove(&dev) {
+            driver.destroy_context(primary.handle);
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxCreate_v2(
+    pctx: *mut CUcontext,
+    flags: c_uint,
+    dev: CUdevice,
+) -> CUresult {
+    call("cuCtxCreate_v2", || {
+        let mut driver = initialized()?;
+        driver.check_device(dev)?;
+        check(!pctx.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let handle = driver.create_context(dev, flags);
+        CONTEXT_STACK.with(|stack| stack.borrow_mut().push(handle));
+        write(pctx, handle as CUcontext)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxDestroy_v2(ctx: CUcontext) -> CUresult {
+    call("cuCtxDestroy_v2", || {
+        let mut driver = initialized()?;
+        driver.check_context(ctx)?;
+        let is_primary = driver
+            .primary_contexts
+            .values()
+            .any(|primary| primary.handle == ctx as usize);
+        check(!is_primary, CUDA_ERROR_INVALID_CONTEXT)?;
+        driver.destroy_context(ctx as usize);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetCurrent(pctx: *mut CUcontext) -> CUresult {
+    call("cuCtxGetCurrent", || {
+        initialized()?;
+        let ctx = CONTEXT_STACK.with(|stack| stack.borrow().last().copied().unwrap_or(0));
+        write(pctx, ctx as CUcontext)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxSetCurrent(ctx: CUcontext) -> CUresult {
+    call("cuCtxSetCurrent", || {
+        let driver = initialized()?;
+        if !ctx.is_null() {
+            driver.check_context(ctx)?;
+        }
+        CONTEXT_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            stack.pop();
+            if !ctx.is_null() {
+                stack.push(ctx as usize);
+            }
+        });
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxPushCurrent_v2(ctx: CUcontext) -> CUresult {
+    call("cuCtxPushCurrent_v2", || {
+        initialized()?.check_context(ctx)?;
+        CONTEXT_STACK.with(|stack| stack.borrow_mut().push(ctx as usize));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxPopCurrent_v2(pctx: *mut CUcontext) -> CUresult {
+    call("cuCtxPopCurrent_v2", || {
+        initialized()?.current_context()?;
+        let ctx = CONTEXT_STACK.with(|stack| stack.borrow_mut().pop().unwrap_or(0));
+        if !pctx.is_null() {
+            *pctx = ctx as CUcontext;
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxSynchronize() -> CUresult {
+    call("cuCtxSynchronize", || {
+        initialized()?.current_context()?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetDevice(device: *mut CUdevice) -> CUresult {
+    call("cuCtxGetDevice", || {
+        let driver = initialized()?;
+        let ctx = driver.current_context()?;
+        write(device, driver.contexts[&ctx].device)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetFlags(flags: *mut c_uint) -> CUresult {
+    call("cuCtxGetFlags", || {
+        let driver = initialized()?;
+        let ctx = driver.current_context()?;
+        write(flags, driver.contexts[&ctx].flags)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetApiVersion(ctx: CUcontext, version: *mut c_uint) -> CUresult {
+    call("cuCtxGetApiVersion", || {
+        let driver = initialized()?;
+        if ctx.is_null() {
+            driver.current_context()?;
+        } else {
+            driver.check_context(ctx)?;
+        }
+        write(version, 3020)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetStreamPriorityRange(
+    least: *mut c_int,
+    greatest: *mut c_int,
+) -> CUresult {
+    call("cuCtxGetStreamPriorityRange", || {
+        initialized()?.current_context()?;
+        if !least.is_null() {
+            *least = 0;
+        }
+        if !greatest.is_null() {
+            *greatest = -5;
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetCacheConfig(config: *mut CUfunc_cache) -> CUresult {
+    call("cuCtxGetCacheConfig", || {
+        let driver = initialized()?;
+        let ctx = driver.current_context()?;
+        write(config, driver.contexts[&ctx].cache_config)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxSetCacheConfig(config: CUfunc_cache) -> CUresult {
+    call("cuCtxSetCacheConfig", || {
+        let mut driver = initialized()?;
+        let ctx = driver.current_context()?;
+        driver.contexts.get_mut(&ctx).unwrap().cache_config = config;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetSharedMemConfig(config: *mut CUsharedconfig) -> CUresult {
+    call("cuCtxGetSharedMemConfig", || {
+        let driver = initialized()?;
+        let ctx = driver.current_context()?;
+        write(config, driver.contexts[&ctx].shared_config)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxSetSharedMemConfig(config: CUsharedconfig) -> CUresult {
+    call("cuCtxSetSharedMemConfig", || {
+        let mut driver = initialized()?;
+        let ctx = driver.current_context()?;
+        // like devices with a fixed bank size, the default just keeps the current one.
+        if config != CUsharedconfig::CU_SHARED_MEM_CONFIG_DEFAULT_BANK_SIZE {
+            driver.contexts.get_mut(&ctx).unwrap().shared_config = config;
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxGetLimit(value: *mut usize, limit: CUlimit) -> CUresult {
+    call("cuCtxGetLimit", || {
+        let driver = initialized()?;
+        let ctx = driver.current_context()?;
+        check(limit != CUlimit::CU_LIMIT_MAX, CUDA_ERROR_INVALID_VALUE)?;
+        let set = driver.contexts[&ctx].limits.get(&limit).copied();
+        write(value, set.unwrap_or_else(|| default_limit(limit)))
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuCtxSetLimit(limit: CUlimit, value: usize) -> CUresult {
+    call("cuCtxSetLimit", || {
+        let mut driver = initialized()?;
+        let ctx = driver.current_context()?;
+        check(limit != CUlimit::CU_LIMIT_MAX, CUDA_ERROR_INVALID_VALUE)?;
+        driver
+            .contexts
+            .get_mut(&ctx)
+            .unwrap()
+            .limits
+            .insert(limit, value);
+        Ok(())
+    })
+}
+
+// ---------------- Streams ----------------
+
+unsafe fn create_stream(stream: *mut CUstream, flags: c_uint, priority: c_int) -> MockResult {
+    let mut driver = initialized()?;
+    driver.current_context()?;
+    check(!stream.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+    let handle = driver.new_handle();
+    // out of range priorities are clamped, like the driver does.
+    let priority = priority.clamp(-5, 0);
+    driver
+        .streams
+        .insert(handle, StreamState { flags, priority });
+    write(stream, handle as CUstream)
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamCreate(stream: *mut CUstream, flags: c_uint) -> CUresult {
+    call("cuStreamCreate", || create_stream(stream, flags, 0))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamCreateWithPriority(
+    stream: *mut CUstream,
+    flags: c_uint,
+    priority: c_int,
+) -> CUresult {
+    call("cuStreamCreateWithPriority", || {
+        create_stream(stream, flags, priority)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamDestroy_v2(stream: CUstream) -> CUresult {
+    call("cuStreamDestroy_v2", || {
+        let mut driver = initialized()?;
+        driver
+            .streams
+            .remove(&(stream as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamSynchronize(stream: CUstream) -> CUresult {
+    call("cuStreamSynchronize", || {
+        initialized()?.check_stream(stream)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamQuery(stream: CUstream) -> CUresult {
+    call("cuStreamQuery", || initialized()?.check_stream(stream))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamWaitEvent(
+    stream: CUstream,
+    event: CUevent,
+    flags: c_uint,
+) -> CUresult {
+    call("cuStreamWaitEvent", || {
+        let driver = initialized()?;
+        driver.check_stream(stream)?;
+        check(
+            driver.events.contains_key(&(event as usize)),
+            CUDA_ERROR_INVALID_HANDLE,
+        )?;
+        check(flags == 0, CUDA_ERROR_INVALID_VALUE)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamGetFlags(stream: CUstream, flags: *mut c_uint) -> CUresult {
+    call("cuStreamGetFlags", || {
+        let driver = initialized()?;
+        driver.check_stream(stream)?;
+        let value = driver
+            .streams
+            .get(&(stream as usize))
+            .map_or(0, |s| s.flags);
+        write(flags, value)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamGetPriority(stream: CUstream, priority: *mut c_int) -> CUresult {
+    call("cuStreamGetPriority", || {
+        let driver = initialized()?;
+        driver.check_stream(stream)?;
+        let value = driver
+            .streams
+            .get(&(stream as usize))
+            .map_or(0, |s| s.priority);
+        write(priority, value)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamIsCapturing(
+    stream: CUstream,
+    status: *mut CUstreamCaptureStatus,
+) -> CUresult {
+    call("cuStreamIsCapturing", || {
+        initialized()?.check_stream(stream)?;
+        write(status, CUstreamCaptureStatus::CU_STREAM_CAPTURE_STATUS_NONE)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuStreamAddCallback(
+    stream: CUstream,
+    callback: CUstreamCallback,
+    user_data: *mut c_void,
+    flags: c_uint,
+) -> CUresult {
+    call("cuStreamAddCallback", || {
+        // the driver must not be locked while running the callback, it may call into it.
+        initialized()?.check_stream(stream)?;
+        check(flags == 0, CUDA_ERROR_INVALID_VALUE)?;
+        let callback = callback.ok_or(CUDA_ERROR_INVALID_VALUE)?;
+        callback(stream, CUDA_SUCCESS, user_data);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuLaunchHostFunc(
+    stream: CUstream,
+    func: CUhostFn,
+    user_data: *mut c_void,
+) -> CUresult {
+    call("cuLaunchHostFunc", || {
+        initialized()?.check_stream(stream)?;
+        let func = func.ok_or(CUDA_ERROR_INVALID_VALUE)?;
+        func(user_data);
+        Ok(())
+    })
+}
+
+// ---------------- Events ----------------
+
+#[no_mangle]
+unsafe extern "C" fn cuEventCreate(event: *mut CUevent, flags: c_uint) -> CUresult {
+    call("cuEventCreate", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        check(!event.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let handle = driver.new_handle();
+        driver.events.insert(
+            handle,
+            EventState {
+                flags,
+                recorded: None,
+            },
+        );
+        write(event, handle as CUevent)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuEventDestroy_v2(event: CUevent) -> CUresult {
+    call("cuEventDestroy_v2", || {
+        initialized()?
+            .events
+            .remove(&(event as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuEventRecord(event: CUevent, stream: CUstream) -> CUresult {
+    call("cuEventRecord", || {
+        let mut driver = initialized()?;
+        driver.check_stream(stream)?;
+        let event = driver
+            .events
+            .get_mut(&(event as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        event.recorded = Some(Instant::now());
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuEventQuery(event: CUevent) -> CUresult {
+    call("cuEventQuery", || {
+        let driver = initialized()?;
+        check(
+            driver.events.contains_key(&(event as usize)),
+            CUDA_ERROR_INVALID_HANDLE,
+        )
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuEventSynchronize(event: CUevent) -> CUresult {
+    call("cuEventSynchronize", || {
+        let driver = initialized()?;
+        check(
+            driver.events.contains_key(&(event as usize)),
+            CUDA_ERROR_INVALID_HANDLE,
+        )
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuEventElapsedTime(ms: *mut f32, start: CUevent, end: CUevent) -> CUresult {
+    call("cuEventElapsedTime", || {
+        let driver = initialized()?;
+        let recorded = |event: CUevent| -> MockResult<Instant> {
+            let state = driver
+                .events
+                .get(&(event as usize))
+                .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+            let timed = state.flags & CUevent_flags::CU_EVENT_DISABLE_TIMING as c_uint == 0;
+            check(timed, CUDA_ERROR_INVALID_HANDLE)?;
+            state.recorded.ok_or(CUDA_ERROR_INVALID_HANDLE)
+        };
+        let (start, end) = (recorded(start)?, recorded(end)?);
+        let elapsed = if end >= start {
+            end.duration_since(start).as_secs_f32()
+        } else {
+            -start.duration_since(end).as_secs_f32()
+        };
+        write(ms, elapsed * 1000.0)
+    })
+}
+
+// ---------------- Memory management ----------------
+
+#[no_mangle]
+unsafe extern "C" fn cuMemAlloc_v2(dptr: *mut CUdeviceptr, bytesize: usize) -> CUresult {
+    call("cuMemAlloc_v2", || {
+        check(!dptr.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let ptr = initialized()?.allocate(bytesize, MemoryKind::Device)?;
+        write(dptr, ptr as CUdeviceptr)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemAllocAsync(
+    dptr: *mut CUdeviceptr,
+    bytesize: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemAllocAsync", || {
+        check(!dptr.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let mut driver = initialized()?;
+        driver.check_stream(stream)?;
+        let ptr = driver.allocate(bytesize, MemoryKind::Device)?;
+        write(dptr, ptr as CUdeviceptr)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemAllocPitch_v2(
+    dptr: *mut CUdeviceptr,
+    pitch: *mut usize,
+    width_in_bytes: usize,
+    height: usize,
+    element_size_bytes: c_uint,
+) -> CUresult {
+    call("cuMemAllocPitch_v2", || {
+        check(
+            !dptr.is_null() && !pitch.is_null() && matches!(element_size_bytes, 4 | 8 | 16),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        let padded = width_in_bytes
+            .checked_add(PITCH_ALIGNMENT - 1)
+            .ok_or(CUDA_ERROR_INVALID_VALUE)?
+            / PITCH_ALIGNMENT
+            * PITCH_ALIGNMENT;
+        let size = padded.checked_mul(height).ok_or(CUDA_ERROR_OUT_OF_MEMORY)?;
+        let ptr = initialized()?.allocate(size, MemoryKind::Device)?;
+        *pitch = padded;
+        write(dptr, ptr as CUdeviceptr)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemAllocManaged(
+    dptr: *mut CUdeviceptr,
+    bytesize: usize,
+    _flags: c_uint,
+) -> CUresult {
+    call("cuMemAllocManaged", || {
+        check(!dptr.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let ptr = initialized()?.allocate(bytesize, MemoryKind::Managed)?;
+        write(dptr, ptr as CUdeviceptr)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemFree_v2(dptr: CUdeviceptr) -> CUresult {
+    call("cuMemFree_v2", || {
+        initialized()?.free(dptr as usize, &[MemoryKind::Device, MemoryKind::Managed])
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemFreeAsync(dptr: CUdeviceptr, stream: CUstream) -> CUresult {
+    call("cuMemFreeAsync", || {
+        let mut driver = initialized()?;
+        driver.check_stream(stream)?;
+        driver.free(dptr as usize, &[MemoryKind::Device])
+    })
+}
+
+unsafe fn allocate_host(pp: *mut *mut c_void, bytesize: usize) -> MockResult {
+    check(!pp.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+    let ptr = initialized()?.allocate(bytesize, MemoryKind::Host)?;
+    write(pp, ptr as *mut c_void)
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemAllocHost_v2(pp: *mut *mut c_void, bytesize: usize) -> CUresult {
+    call("cuMemAllocHost_v2", || allocate_host(pp, bytesize))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemHostAlloc(
+    pp: *mut *mut c_void,
+    bytesize: usize,
+    _flags: c_uint,
+) -> CUresult {
+    call("cuMemHostAlloc", || allocate_host(pp, bytesize))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemFreeHost(p: *mut c_void) -> CUresult {
+    call("cuMemFreeHost", || {
+        initialized()?.free(p as usize, &[MemoryKind::Host])
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemHostRegister_v2(
+    p: *mut c_void,
+    bytesize: usize,
+    _flags: c_uint,
+) -> CUresult {
+    call("cuMemHostRegister_v2", || {
+        let mut driver = initialized()?;
+        let context = driver.current_context()?;
+        check(!p.is_null() && bytesize != 0, CUDA_ERROR_INVALID_VALUE)?;
+        let start = p as usize;
+        let end = start
+            .checked_add(bytesize)
+            .ok_or(CUDA_ERROR_INVALID_VALUE)?;
+        let overlaps = driver
+            .allocations
+            .range(..end)
+            .next_back()
+            .map_or(false, |(&ptr, alloc)| ptr + alloc.size > start);
+        check(!overlaps, CUDA_ERROR_HOST_MEMORY_ALREADY_REGISTERED)?;
+        driver.allocations.insert(
+            start,
+            Allocation {
+                size: bytesize,
+                kind: MemoryKind::Registered,
+                layout: None,
+                context,
+                owner: thread::current().id(),
+            },
+        );
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemHostUnregister(p: *mut c_void) -> CUresult {
+    call("cuMemHostUnregister", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        match driver.allocations.get(&(p as usize)) {
+            Some(alloc) if alloc.kind == MemoryKind::Registered => {
+                driver.release(p as usize);
+                Ok(())
+            }
+            _ => Err(CUDA_ERROR_HOST_MEMORY_NOT_REGISTERED),
+        }
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemHostGetDevicePointer_v2(
+    pdptr: *mut CUdeviceptr,
+    p: *mut c_void,
+    flags: c_uint,
+) -> CUresult {
+    call("cuMemHostGetDevicePointer_v2", || {
+        let driver = initialized()?;
+        driver.current_context()?;
+        check(flags == 0, CUDA_ERROR_INVALID_VALUE)?;
+        let (_, alloc) = driver.find(p as usize, 1).ok_or(CUDA_ERROR_INVALID_VALUE)?;
+        check(
+            matches!(alloc.kind, MemoryKind::Host | MemoryKind::Registered),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        // host and device addresses are the same thing here.
+        write(pdptr, p as usize as CUdeviceptr)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemGetInfo_v2(free: *mut usize, total: *mut usize) -> CUresult {
+    call("cuMemGetInfo_v2", || {
+        let driver = initialized()?;
+        driver.current_context()?;
+        write(free, TOTAL_MEMORY - driver.used_memory)?;
+        write(total, TOTAL_MEMORY)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemAdvise(
+    dptr: CUdeviceptr,
+    count: usize,
+    _advice: CUmem_advise,
+    device: CUdevice,
+) -> CUresult {
+    call("cuMemAdvise", || {
+        let driver = initialized()?;
+        // CU_DEVICE_CPU is -1.
+        check(
+            device == -1 || (0..DEVICE_COUNT).contains(&device),
+            CUDA_ERROR_INVALID_DEVICE,
+        )?;
+        driver.device_memory(dptr, count)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemPrefetchAsync(
+    dptr: CUdeviceptr,
+    count: usize,
+    device: CUdevice,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemPrefetchAsync", || {
+        let driver = initialized()?;
+        driver.check_stream(stream)?;
+        check(
+            device == -1 || (0..DEVICE_COUNT).contains(&device),
+            CUDA_ERROR_INVALID_DEVICE,
+        )?;
+        driver.device_memory(dptr, count)?;
+        Ok(())
+    })
+}
+
+// ---------------- Arrays ----------------
+
+#[no_mangle]
+unsafe extern "C" fn cuArray3DCreate_v2(
+    array: *mut CUarray,
+    descriptor: *const CUDA_ARRAY3D_DESCRIPTOR,
+) -> CUresult {
+    call("cuArray3DCreate_v2", || {
+        let mut driver = initialized()?;
+        let context = driver.current_context()?;
+        check(!descriptor.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let descriptor = *descriptor;
+        let element_size = array_element_size(descriptor.Format).ok_or(CUDA_ERROR_NOT_SUPPORTED)?;
+        check(
+            descriptor.Width != 0 && matches!(descriptor.NumChannels, 1 | 2 | 4),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        if descriptor.Flags & CUDA_ARRAY3D_CUBEMAP != 0 {
+            let faces = if descriptor.Flags & CUDA_ARRAY3D_LAYERED != 0 {
+                descriptor.Depth % 6 == 0
+            } else {
+                descriptor.Depth == 6
+            };
+            check(
+                descriptor.Width == descriptor.Height && descriptor.Depth != 0 && faces,
+                CUDA_ERROR_INVALID_VALUE,
+            )?;
+        }
+        let size = [
+            descriptor.Height.max(1),
+            descriptor.Depth.max(1),
+            descriptor.NumChannels as usize,
+            element_size,
+        ]
+        .iter()
+        .try_fold(descriptor.Width, |size, &dim| size.checked_mul(dim))
+        .ok_or(CUDA_ERROR_OUT_OF_MEMORY)?;
+        check(
+            size <= TOTAL_MEMORY - driver.used_memory,
+            CUDA_ERROR_OUT_OF_MEMORY,
+        )?;
+        let handle = driver.new_handle();
+        driver.used_memory += size;
+        driver.arrays.insert(
+            handle,
+            ArrayState {
+                descriptor,
+                data: vec![0; size],
+                context,
+            },
+        );
+        write(array, handle as CUarray)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuArray3DGetDescriptor_v2(
+    descriptor: *mut CUDA_ARRAY3D_DESCRIPTOR,
+    array: CUarray,
+) -> CUresult {
+    call("cuArray3DGetDescriptor_v2", || {
+        let driver = initialized()?;
+        write(descriptor, driver.array(array)?.descriptor)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuArrayDestroy(array: CUarray) -> CUresult {
+    call("cuArrayDestroy", || {
+        initialized()?
+            .destroy_array(array as usize)
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        Ok(())
+    })
+}
+
+// ---------------- Memcpy and memset ----------------
+
+// Copies between two host pointers, checking any device side was already done by the caller.
+unsafe fn copy_bytes(dst: *mut u8, src: *const u8, len: usize) -> MockResult {
+    if len != 0 {
+        check(!dst.is_null() && !src.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        ptr::copy(src, dst, len);
+    }
+    Ok(())
+}
+
+unsafe fn memcpy_htod(
+    dst: CUdeviceptr,
+    src: *const c_void,
+    len: usize,
+    stream: CUstream,
+) -> MockResult {
+    let driver = initialized()?;
+    driver.current_context()?;
+    driver.check_stream(stream)?;
+    copy_bytes(driver.device_memory(dst, len)?, src as *const u8, len)
+}
+
+unsafe fn memcpy_dtoh(
+    dst: *mut c_void,
+    src: CUdeviceptr,
+    len: usize,
+    stream: CUstream,
+) -> MockResult {
+    let driver = initialized()?;
+    driver.current_context()?;
+    driver.check_stream(stream)?;
+    copy_bytes(dst as *mut u8, driver.device_memory(src, len)?, len)
+}
+
+unsafe fn memcpy_dtod(
+    dst: CUdeviceptr,
+    src: CUdeviceptr,
+    len: usize,
+    stream: CUstream,
+) -> MockResult {
+    let driver = initialized()?;
+    driver.current_context()?;
+    driver.check_stream(stream)?;
+    copy_bytes(
+        driver.device_memory(dst, len)?,
+        driver.device_memory(src, len)?,
+        len,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyHtoD_v2(dst: CUdeviceptr, src: *const c_void, len: usize) -> CUresult {
+    call("cuMemcpyHtoD_v2", || {
+        memcpy_htod(dst, src, len, ptr::null_mut())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyHtoDAsync_v2(
+    dst: CUdeviceptr,
+    src: *const c_void,
+    len: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemcpyHtoDAsync_v2", || {
+        memcpy_htod(dst, src, len, stream)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyDtoH_v2(dst: *mut c_void, src: CUdeviceptr, len: usize) -> CUresult {
+    call("cuMemcpyDtoH_v2", || {
+        memcpy_dtoh(dst, src, len, ptr::null_mut())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyDtoHAsync_v2(
+    dst: *mut c_void,
+    src: CUdeviceptr,
+    len: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemcpyDtoHAsync_v2", || {
+        memcpy_dtoh(dst, src, len, stream)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyDtoD_v2(dst: CUdeviceptr, src: CUdeviceptr, len: usize) -> CUresult {
+    call("cuMemcpyDtoD_v2", || {
+        memcpy_dtod(dst, src, len, ptr::null_mut())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyDtoDAsync_v2(
+    dst: CUdeviceptr,
+    src: CUdeviceptr,
+    len: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemcpyDtoDAsync_v2", || {
+        memcpy_dtod(dst, src, len, stream)
+    })
+}
+
+unsafe fn memcpy_peer(
+    dst: CUdeviceptr,
+    dst_ctx: CUcontext,
+    src: CUdeviceptr,
+    src_ctx: CUcontext,
+    len: usize,
+    stream: CUstream,
+) -> MockResult {
+    let driver = initialized()?;
+    driver.check_context(dst_ctx)?;
+    driver.check_context(src_ctx)?;
+    driver.check_stream(stream)?;
+    copy_bytes(
+        driver.device_memory(dst, len)?,
+        driver.device_memory(src, len)?,
+        len,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyPeer(
+    dst: CUdeviceptr,
+    dst_ctx: CUcontext,
+    src: CUdeviceptr,
+    src_ctx: CUcontext,
+    len: usize,
+) -> CUresult {
+    call("cuMemcpyPeer", || {
+        memcpy_peer(dst, dst_ctx, src, src_ctx, len, ptr::null_mut())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyPeerAsync(
+    dst: CUdeviceptr,
+    dst_ctx: CUcontext,
+    src: CUdeviceptr,
+    src_ctx: CUcontext,
+    len: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemcpyPeerAsync", || {
+        memcpy_peer(dst, dst_ctx, src, src_ctx, len, stream)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyHtoA_v2(
+    dst: CUarray,
+    offset: usize,
+    src: *const c_void,
+    len: usize,
+) -> CUresult {
+    call("cuMemcpyHtoA_v2", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        copy_bytes(
+            driver.array_memory(dst, offset, len)?,
+            src as *const u8,
+            len,
+        )
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpyAtoH_v2(
+    dst: *mut c_void,
+    src: CUarray,
+    offset: usize,
+    len: usize,
+) -> CUresult {
+    call("cuMemcpyAtoH_v2", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        copy_bytes(dst as *mut u8, driver.array_memory(src, offset, len)?, len)
+    })
+}
+
+// One side of a 2D or 3D copy.
+struct Region {
+    memory_type: CUmemorytype,
+    host: *const c_void,
+    device: CUdeviceptr,
+    array: CUarray,
+    x: usize,
+    y: usize,
+    z: usize,
+    pitch: usize,
+    height: usize,
+}
+
+impl Region {
+    // Resolves the region to a host pointer to its first byte, checking that the whole
+    // `width` x `height` x `depth` extent lies in memory the device can access. Arrays have no
+    // pitch or height of their own in the copy, so those are filled in from the array.
+    unsafe fn resolve(
+        &mut self,
+        driver: &mut Driver,
+        width: usize,
+        height: usize,
+        depth: usize,
+    ) -> MockResult<*mut u8> {
+        use CUmemorytype::*;
+
+        if self.memory_type == CU_MEMORYTYPE_ARRAY {
+            let descriptor = driver.array(self.array)?.descriptor;
+            let element_size = array_element_size(descriptor.Format).unwrap();
+            self.pitch = descriptor.Width * descriptor.NumChannels as usize * element_size;
+            self.height = descriptor.Height.max(1);
+        }
+        let pitch = if self.pitch == 0 { width } else { self.pitch };
+        check(
+            pitch >= width && (depth <= 1 || self.height >= height),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        let offset = self.x + (self.y + self.z * self.height) * pitch;
+        let span = if width == 0 || height == 0 || depth == 0 {
+            0
+        } else {
+            (depth - 1) * self.height * pitch + (height - 1) * pitch + width
+        };
+        match self.memory_type {
+            CU_MEMORYTYPE_HOST => {
+                check(!self.host.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+                Ok((self.host as *mut u8).add(offset))
+            }
+            CU_MEMORYTYPE_DEVICE | CU_MEMORYTYPE_UNIFIED => {
+                driver.device_memory(self.device + offset as CUdeviceptr, span)
+            }
+            CU_MEMORYTYPE_ARRAY => driver.array_memory(self.array, offset, span),
+        }
+    }
+}
+
+unsafe fn memcpy_3d(
+    mut src: Region,
+    mut dst: Region,
+    width: usize,
+    height: usize,
+    depth: usize,
+    stream: CUstream,
+) -> MockResult {
+    let mut driver = initialized()?;
+    driver.current_context()?;
+    driver.check_stream(stream)?;
+    let src_ptr = src.resolve(&mut driver, width, height, depth)?;
+    let dst_ptr = dst.resolve(&mut driver, width, height, depth)?;
+    let src_pitch = if src.pitch == 0 { width } else { src.pitch };
+    let dst_pitch = if dst.pitch == 0 { width } else { dst.pitch };
+    for z in 0..depth {
+        for y in 0..height {
+            copy_bytes(
+                dst_ptr.add((z * dst.height + y) * dst_pitch),
+                src_ptr.add((z * src.height + y) * src_pitch),
+                width,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+unsafe fn memcpy_2d_params(copy: *const CUDA_MEMCPY2D, stream: CUstream) -> MockResult {
+    check(!copy.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+    let copy = &*copy;
+    let src = Region {
+        memory_type: copy.srcMemoryType,
+        host: copy.srcHost,
+        device: copy.srcDevice,
+        array: copy.srcArray,
+        x: copy.srcXInBytes,
+        y: copy.srcY,
+        z: 0,
+        pitch: copy.srcPitch,
+        height: copy.Height,
+    };
+    let dst = Region {
+        memory_type: copy.dstMemoryType,
+        host: copy.dstHost,
+        device: copy.dstDevice,
+        array: copy.dstArray,
+        x: copy.dstXInBytes,
+        y: copy.dstY,
+        z: 0,
+        pitch: copy.dstPitch,
+        height: copy.Height,
+    };
+    memcpy_3d(src, dst, copy.WidthInBytes, copy.Height, 1, stream)
+}
+
+unsafe fn memcpy_3d_params(copy: *const CUDA_MEMCPY3D, stream: CUstream) -> MockResult {
+    check(!copy.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+    let copy = &*copy;
+    check(
+        copy.srcLOD == 0 && copy.dstLOD == 0,
+        CUDA_ERROR_INVALID_VALUE,
+    )?;
+    let src = Region {
+        memory_type: copy.srcMemoryType,
+        host: copy.srcHost,
+        device: copy.srcDevice,
+        array: copy.srcArray,
+        x: copy.srcXInBytes,
+        y: copy.srcY,
+        z: copy.srcZ,
+        pitch: copy.srcPitch,
+        height: copy.srcHeight,
+    };
+    let dst = Region {
+        memory_type: copy.dstMemoryType,
+        host: copy.dstHost,
+        device: copy.dstDevice,
+        array: copy.dstArray,
+        x: copy.dstXInBytes,
+        y: copy.dstY,
+        z: copy.dstZ,
+        pitch: copy.dstPitch,
+        height: copy.dstHeight,
+    };
+    memcpy_3d(src, dst, copy.WidthInBytes, copy.Height, copy.Depth, stream)
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpy2D_v2(copy: *const CUDA_MEMCPY2D) -> CUresult {
+    call("cuMemcpy2D_v2", || memcpy_2d_params(copy, ptr::null_mut()))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpy2DAsync_v2(copy: *const CUDA_MEMCPY2D, stream: CUstream) -> CUresult {
+    call("cuMemcpy2DAsync_v2", || memcpy_2d_params(copy, stream))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpy3D_v2(copy: *const CUDA_MEMCPY3D) -> CUresult {
+    call("cuMemcpy3D_v2", || memcpy_3d_params(copy, ptr::null_mut()))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemcpy3DAsync_v2(copy: *const CUDA_MEMCPY3D, stream: CUstream) -> CUresult {
+    call("cuMemcpy3DAsync_v2", || memcpy_3d_params(copy, stream))
+}
+
+unsafe fn memset<T: Copy>(
+    dst: CUdeviceptr,
+    value: T,
+    count: usize,
+    stream: CUstream,
+) -> MockResult {
+    let driver = initialized()?;
+    driver.current_context()?;
+    driver.check_stream(stream)?;
+    let len = count
+        .checked_mul(std::mem::size_of::<T>())
+        .ok_or(CUDA_ERROR_INVALID_VALUE)?;
+    check(
+        dst as usize % std::mem::align_of::<T>() == 0,
+        CUDA_ERROR_INVALID_VALUE,
+    )?;
+    let ptr = driver.device_memory(dst, len)? as *mut T;
+    for i in 0..count {
+        ptr.add(i).write(value);
+    }
+    Ok(())
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemsetD8_v2(dst: CUdeviceptr, value: c_uchar, count: usize) -> CUresult {
+    call("cuMemsetD8_v2", || {
+        memset(dst, value, count, ptr::null_mut())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemsetD16_v2(dst: CUdeviceptr, value: c_ushort, count: usize) -> CUresult {
+    call("cuMemsetD16_v2", || {
+        memset(dst, value, count, ptr::null_mut())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemsetD32_v2(dst: CUdeviceptr, value: c_uint, count: usize) -> CUresult {
+    call("cuMemsetD32_v2", || {
+        memset(dst, value, count, ptr::null_mut())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemsetD8Async(
+    dst: CUdeviceptr,
+    value: c_uchar,
+    count: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemsetD8Async", || memset(dst, value, count, stream))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemsetD16Async(
+    dst: CUdeviceptr,
+    value: c_ushort,
+    count: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemsetD16Async", || memset(dst, value, count, stream))
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuMemsetD32Async(
+    dst: CUdeviceptr,
+    value: c_uint,
+    count: usize,
+    stream: CUstream,
+) -> CUresult {
+    call("cuMemsetD32Async", || memset(dst, value, count, stream))
+}
+
+// ---------------- Modules and launches ----------------
+
+fn load_module(module: *mut CUmodule) -> MockResult {
+    let mut driver = initialized()?;
+    let context = driver.current_context()?;
+    check(!module.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+    let handle = driver.new_handle();
+    driver.modules.insert(handle, context);
+    unsafe { write(module, handle as CUmodule) }
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuModuleLoad(module: *mut CUmodule, fname: *const c_char) -> CUresult {
+    call("cuModuleLoad", || {
+        check(!fname.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let path = CStr::from_ptr(fname)
+            .to_str()
+            .map_err(|_| CUDA_ERROR_INVALID_VALUE)?;
+        check(
+            std::path::Path::new(path).is_file(),
+            CUDA_ERROR_FILE_NOT_FOUND,
+        )?;
+        load_module(module)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuModuleLoadData(module: *mut CUmodule, image: *const c_void) -> CUresult {
+    call("cuModuleLoadData", || {
+        check(!image.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        load_module(module)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuModuleLoadDataEx(
+    module: *mut CUmodule,
+    image: *const c_void,
+    _num_options: c_uint,
+    _options: *mut CUjit_option,
+    _option_values: *mut *mut c_void,
+) -> CUresult {
+    call("cuModuleLoadDataEx", || {
+        check(!image.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        load_module(module)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuModuleUnload(module: CUmodule) -> CUresult {
+    call("cuModuleUnload", || {
+        let mut driver = initialized()?;
+        let module = module as usize;
+        driver
+            .modules
+            .remove(&module)
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        driver.functions.retain(|_, m| *m != module);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuModuleGetFunction(
+    func: *mut CUfunction,
+    module: CUmodule,
+    name: *const c_char,
+) -> CUresult {
+    call("cuModuleGetFunction", || {
+        let mut driver = initialized()?;
+        check(
+            driver.modules.contains_key(&(module as usize)),
+            CUDA_ERROR_INVALID_HANDLE,
+        )?;
+        check(!func.is_null() && !name.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let handle = driver.new_handle();
+        driver.functions.insert(handle, module as usize);
+        write(func, handle as CUfunction)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuModuleGetGlobal_v2(
+    _dptr: *mut CUdeviceptr,
+    _bytes: *mut usize,
+    module: CUmodule,
+    name: *const c_char,
+) -> CUresult {
+    call("cuModuleGetGlobal_v2", || {
+        let driver = initialized()?;
+        check(
+            driver.modules.contains_key(&(module as usize)),
+            CUDA_ERROR_INVALID_HANDLE,
+        )?;
+        check(!name.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        // modules are never actually loaded, so they have no globals.
+        Err(CUDA_ERROR_NOT_FOUND)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuFuncGetAttribute(
+    pi: *mut c_int,
+    attrib: CUfunction_attribute,
+    func: CUfunction,
+) -> CUresult {
+    call("cuFuncGetAttribute", || {
+        initialized()?.check_function(func)?;
+        let value = match attrib {
+            CUfunction_attribute::CU_FUNC_ATTRIBUTE_MAX_THREADS_PER_BLOCK => {
+                MAX_THREADS_PER_BLOCK as c_int
+            }
+            _ => 0,
+        };
+        write(pi, value)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuFuncSetCacheConfig(func: CUfunction, _config: CUfunc_cache) -> CUresult {
+    call("cuFuncSetCacheConfig", || {
+        initialized()?.check_function(func)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuFuncSetSharedMemConfig(
+    func: CUfunction,
+    _config: CUsharedconfig,
+) -> CUresult {
+    call("cuFuncSetSharedMemConfig", || {
+        initialized()?.check_function(func)
+    })
+}
+
+fn check_launch(
+    func: CUfunction,
+    grid: [c_uint; 3],
+    block: [c_uint; 3],
+    stream: CUstream,
+) -> MockResult {
+    let driver = initialized()?;
+    driver.current_context()?;
+    driver.check_function(func)?;
+    driver.check_stream(stream)?;
+    check(
+        !grid.contains(&0) && !block.contains(&0),
+        CUDA_ERROR_INVALID_VALUE,
+    )?;
+    let threads = block.iter().map(|&dim| dim as u64).product::<u64>();
+    check(
+        threads <= MAX_THREADS_PER_BLOCK as u64,
+        CUDA_ERROR_INVALID_VALUE,
+    )
+}
+
+// The number of blocks of `threads` threads using `shared_mem` bytes of dynamic shared memory
+// which fit on one multiprocessor at the same time.
+fn max_active_blocks(threads: u64, shared_mem: usize) -> MockResult<c_uint> {
+    check(
+        threads != 0
+            && threads <= MAX_THREADS_PER_BLOCK as u64
+            && shared_mem <= MAX_SHARED_MEMORY_PER_BLOCK,
+        CUDA_ERROR_INVALID_VALUE,
+    )?;
+    // threads are scheduled in whole warps.
+    let warps = (threads as c_uint + 31) / 32;
+    let mut blocks =
+        MAX_BLOCKS_PER_MULTIPROCESSOR.min(MAX_THREADS_PER_MULTIPROCESSOR / (warps * 32));
+    if shared_mem != 0 {
+        blocks = blocks.min((MAX_SHARED_MEMORY_PER_MULTIPROCESSOR / shared_mem) as c_uint);
+    }
+    Ok(blocks)
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuOccupancyMaxActiveBlocksPerMultiprocessor(
+    num_blocks: *mut c_int,
+    func: CUfunction,
+    block_size: c_int,
+    dynamic_smem_size: usize,
+) -> CUresult {
+    call("cuOccupancyMaxActiveBlocksPerMultiprocessor", || {
+        let driver = initialized()?;
+        driver.current_context()?;
+        driver.check_function(func)?;
+        check(block_size > 0, CUDA_ERROR_INVALID_VALUE)?;
+        let blocks = max_active_blocks(block_size as u64, dynamic_smem_size)?;
+        write(num_blocks, blocks as c_int)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuLaunchKernel(
+    func: CUfunction,
+    grid_x: c_uint,
+    grid_y: c_uint,
+    grid_z: c_uint,
+    block_x: c_uint,
+    block_y: c_uint,
+    block_z: c_uint,
+    _shared_mem_bytes: c_uint,
+    stream: CUstream,
+    _kernel_params: *mut *mut c_void,
+    _extra: *mut *mut c_void,
+) -> CUresult {
+    call("cuLaunchKernel", || {
+        check_launch(
+            func,
+            [grid_x, grid_y, grid_z],
+            [block_x, block_y, block_z],
+            stream,
+        )
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuLaunchCooperativeKernel(
+    func: CUfunction,
+    grid_x: c_uint,
+    grid_y: c_uint,
+    grid_z: c_uint,
+    block_x: c_uint,
+    block_y: c_uint,
+    block_z: c_uint,
+    shared_mem_bytes: c_uint,
+    stream: CUstream,
+    _kernel_params: *mut *mut c_void,
+) -> CUresult {
+    call("cuLaunchCooperativeKernel", || {
+        check_launch(
+            func,
+            [grid_x, grid_y, grid_z],
+            [block_x, block_y, block_z],
+            stream,
+        )?;
+        // every block has to be active at the same time.
+        let threads = block_x as u64 * block_y as u64 * block_z as u64;
+        let max_blocks = max_active_blocks(threads, shared_mem_bytes as usize)? as u64
+            * MULTIPROCESSOR_COUNT as u64;
+        check(
+            grid_x as u64 * grid_y as u64 * grid_z as u64 <= max_blocks,
+            CUDA_ERROR_COOPERATIVE_LAUNCH_TOO_LARGE,
+        )
+    })
+}
+
+// ---------------- Linking ----------------
+
+// The start of every image produced by the emulated linker.
+const LINKED_IMAGE_MAGIC: &[u8] = b"\x7fELF cust mock cubin\n";
+
+#[no_mangle]
+unsafe extern "C" fn cuLinkCreate_v2(
+    _num_options: c_uint,
+    _options: *mut CUjit_option,
+    _option_values: *mut *mut c_void,
+    state: *mut CUlinkState,
+) -> CUresult {
+    call("cuLinkCreate_v2", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        check(!state.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let handle = driver.new_handle();
+        driver.links.insert(
+            handle,
+            LinkState {
+                inputs: 0,
+                image: LINKED_IMAGE_MAGIC.to_vec(),
+            },
+        );
+        write(state, handle as CUlinkState)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuLinkAddData_v2(
+    state: CUlinkState,
+    _type: CUjitInputType,
+    data: *mut c_void,
+    size: usize,
+    _name: *const c_char,
+    _num_options: c_uint,
+    _options: *mut CUjit_option,
+    _option_values: *mut *mut c_void,
+) -> CUresult {
+    call("cuLinkAddData_v2", || {
+        let mut driver = initialized()?;
+        let link = driver
+            .links
+            .get_mut(&(state as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        check(!data.is_null() && size != 0, CUDA_ERROR_INVALID_VALUE)?;
+        link.inputs += 1;
+        link.image
+            .extend_from_slice(std::slice::from_raw_parts(data as *const u8, size));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuLinkComplete(
+    state: CUlinkState,
+    cubin: *mut *mut c_void,
+    size: *mut usize,
+) -> CUresult {
+    call("cuLinkComplete", || {
+        let mut driver = initialized()?;
+        let link = driver
+            .links
+            .get_mut(&(state as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        check(link.inputs != 0, CUDA_ERROR_INVALID_VALUE)?;
+        check(!size.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        write(cubin, link.image.as_mut_ptr() as *mut c_void)?;
+        write(size, link.image.len())
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuLinkDestroy(state: CUlinkState) -> CUresult {
+    call("cuLinkDestroy", || {
+        initialized()?
+            .links
+            .remove(&(state as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        Ok(())
+    })
+}
+
+// ---------------- External resources ----------------
+
+#[no_mangle]
+unsafe extern "C" fn cuImportExternalMemory(
+    ext_mem: *mut CUexternalMemory,
+    desc: *const CUDA_EXTERNAL_MEMORY_HANDLE_DESC,
+) -> CUresult {
+    call("cuImportExternalMemory", || {
+        let driver = initialized()?;
+        driver.current_context()?;
+        check(
+            !ext_mem.is_null() && !desc.is_null(),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        let desc = &*desc;
+        check(
+            desc.type_ == CUexternalMemoryHandleType::CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD,
+            CUDA_ERROR_NOT_SUPPORTED,
+        )?;
+        check(
+            desc.handle.fd >= 0 && desc.size != 0,
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        Err(CUDA_ERROR_NOT_SUPPORTED)
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuImportExternalSemaphore(
+    ext_sem: *mut CUexternalSemaphore,
+    desc: *const CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC,
+) -> CUresult {
+    call("cuImportExternalSemaphore", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        check(
+            !ext_sem.is_null() && !desc.is_null(),
+            CUDA_ERROR_INVALID_VALUE,
+        )?;
+        let desc = &*desc;
+        let timeline = match desc.type_ {
+            CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD => false,
+            CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_TIMELINE_SEMAPHORE_FD => {
+                true
+            }
+            _ => return Err(CUDA_ERROR_NOT_SUPPORTED),
+        };
+        check(desc.handle.fd >= 0, CUDA_ERROR_INVALID_VALUE)?;
+        let handle = driver.new_handle();
+        driver
+            .semaphores
+            .insert(handle, SemaphoreState { timeline, value: 0 });
+        write(ext_sem, handle as CUexternalSemaphore)
+    })
+}
+
+// Runs `f` on the state and fence value of each semaphore, after checking all of them.
+unsafe fn for_each_semaphore(
+    driver: &mut Driver,
+    semaphores: *const CUexternalSemaphore,
+    values: impl Fn(usize) -> u64,
+    count: c_uint,
+    stream: CUstream,
+    mut f: impl FnMut(&mut SemaphoreState, u64) -> MockResult,
+) -> MockResult {
+    driver.check_stream(stream)?;
+    check(
+        !semaphores.is_null() && count != 0,
+        CUDA_ERROR_INVALID_VALUE,
+    )?;
+    let semaphores = std::slice::from_raw_parts(semaphores, count as usize);
+    for semaphore in semaphores {
+        check(
+            driver.semaphores.contains_key(&(*semaphore as usize)),
+            CUDA_ERROR_INVALID_HANDLE,
+        )?;
+    }
+    for (i, semaphore) in semaphores.iter().enumerate() {
+        let state = driver.semaphores.get_mut(&(*semaphore as usize)).unwrap();
+        f(state, values(i))?;
+    }
+    Ok(())
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuSignalExternalSemaphoresAsync(
+    ext_sems: *const CUexternalSemaphore,
+    params: *const CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS,
+    count: c_uint,
+    stream: CUstream,
+) -> CUresult {
+    call("cuSignalExternalSemaphoresAsync", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        check(!params.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let value = |i| (*params.add(i)).params.fence.value;
+        for_each_semaphore(
+            &mut driver,
+            ext_sems,
+            value,
+            count,
+            stream,
+            |state, value| {
+                state.value = if state.timeline { value } else { 1 };
+                Ok(())
+            },
+        )
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuWaitExternalSemaphoresAsync(
+    ext_sems: *const CUexternalSemaphore,
+    params: *const CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS,
+    count: c_uint,
+    stream: CUstream,
+) -> CUresult {
+    call("cuWaitExternalSemaphoresAsync", || {
+        let mut driver = initialized()?;
+        driver.current_context()?;
+        check(!params.is_null(), CUDA_ERROR_INVALID_VALUE)?;
+        let value = |i| (*params.add(i)).params.fence.value;
+        // work runs synchronously, so a wait that is not satisfied yet would never finish.
+        for_each_semaphore(
+            &mut driver,
+            ext_sems,
+            value,
+            count,
+            stream,
+            |state, value| {
+                if state.timeline {
+                    check(state.value >= value, CUDA_ERROR_INVALID_VALUE)
+                } else {
+                    check(state.value == 1, CUDA_ERROR_INVALID_VALUE)?;
+                    state.value = 0;
+                    Ok(())
+                }
+            },
+        )
+    })
+}
+
+#[no_mangle]
+unsafe extern "C" fn cuDestroyExternalSemaphore(ext_sem: CUexternalSemaphore) -> CUresult {
+    call("cuDestroyExternalSemaphore", || {
+        initialized()?
+            .semaphores
+            .remove(&(ext_sem as usize))
+            .ok_or(CUDA_ERROR_INVALID_HANDLE)?;
+        Ok(())
+    })
+}
+
+// ---------------- Unsupported functions ----------------
+
+// The rest of the driver functions used by cust, which always fail with
+// `CUDA_ERROR_NOT_SUPPORTED`. They are declared with their real parameters in the bindings, but
+// in the C calling convention the caller cleans up the arguments, so defining them without any is
+// fine.
+macro_rules! unsupported {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[no_mangle]
+            unsafe extern "C" fn $name() -> CUresult {
+                call(stringify!($name), || Err(CUDA_ERROR_NOT_SUPPORTED))
+            }
+        )*
+    };
+}
+
+unsupported! {
+    cuCtxDisablePeerAccess,
+    cuCtxEnablePeerAccess,
+    cuDestroyExternalMemory,
+    cuDeviceGetDefaultMemPool,
+    cuExternalMemoryGetMappedBuffer,
+    cuExternalMemoryGetMappedMipmappedArray,
+    cuGraphAddChildGraphNode,
+    cuGraphAddEmptyNode,
+    cuGraphAddEventRecordNode,
+    cuGraphAddEventWaitNode,
+    cuGraphAddHostNode,
+    cuGraphAddKernelNode,
+    cuGraphAddMemcpyNode,
+    cuGraphAddMemsetNode,
+    cuGraphChildGraphNodeGetGraph,
+    cuGraphClone,
+    cuGraphCreate,
+    cuGraphDebugDotPrint,
+    cuGraphDestroy,
+    cuGraphEventRecordNodeGetEvent,
+    cuGraphEventWaitNodeGetEvent,
+    cuGraphExecDestroy,
+    cuGraphExecKernelNodeSetParams,
+    cuGraphExecUpdate,
+    cuGraphGetEdges,
+    cuGraphGetNodes,
+    cuGraphInstantiateWithFlags,
+    cuGraphKernelNodeGetParams,
+    cuGraphLaunch,
+    cuGraphMemcpyNodeGetParams,
+    cuGraphMemsetNodeGetParams,
+    cuGraphNodeGetType,
+    cuGraphRetainUserObject,
+    cuGraphUpload,
+    cuIpcCloseMemHandle,
+    cuIpcGetEventHandle,
+    cuIpcGetMemHandle,
+    cuIpcOpenEventHandle,
+    cuIpcOpenMemHandle_v2,
+    cuMemAddressFree,
+    cuMemAddressReserve,
+    cuMemAllocFromPoolAsync,
+    cuMemCreate,
+    cuMemGetAllocationGranularity,
+    cuMemMap,
+    cuMemPoolCreate,
+    cuMemPoolDestroy,
+    cuMemPoolGetAccess,
+    cuMemPoolGetAttribute,
+    cuMemPoolSetAccess,
+    cuMemPoolSetAttribute,
+    cuMemPoolTrimTo,
+    cuMemRelease,
+    cuMemSetAccess,
+    cuMemUnmap,
+    cuMipmappedArrayDestroy,
+    cuMipmappedArrayGetLevel,
+    cuOccupancyAvailableDynamicSMemPerBlock,
+    cuOccupancyMaxPotentialBlockSize,
+    cuStreamBeginCapture_v2,
+    cuStreamEndCapture,
+    cuStreamGetCaptureInfo,
+    cuSurfObjectCreate,
+    cuSurfObjectDestroy,
+    cuSurfObjectGetResourceDesc,
+    cuTexObjectCreate,
+    cuTexObjectDestroy,
+    cuTexObjectGetResourceDesc,
+    cuTexObjectGetResourceViewDesc,
+    cuThreadExchangeStreamCaptureMode,
+    cuUserObjectCreate,
+    cuUserObjectRelease,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    unsafe fn with_context<T>(f: impl FnOnce(CUcontext) -> T) -> T {
+        assert_eq!(cuInit(0), CUDA_SUCCESS);
+        let mut ctx = ptr::null_mut();
+        assert_eq!(cuCtxCreate_v2(&mut ctx, 0, 0), CUDA_SUCCESS);
+        let res = f(ctx);
+        assert_eq!(cuCtxDestroy_v2(ctx), CUDA_SUCCESS);
+        res
+    }
+
+    #[test]
+    fn test_memcpy_roundtrip() {
+        unsafe {
+            with_context(|_| {
+                let input = [1u32, 2, 3, 4];
+                let mut output = [0u32; 4];
+                let mut dptr = 0;
+                assert_eq!(cuMemAlloc_v2(&mut dptr, 16), CUDA_SUCCESS);
+                assert_eq!(
+                    cuMemcpyHtoD_v2(dptr, input.as_ptr().cast(), 16),
+                    CUDA_SUCCESS
+                );
+                assert_eq!(
+                    cuMemcpyDtoH_v2(output.as_mut_ptr().cast(), dptr, 16),
+                    CUDA_SUCCESS
+                );
+                assert_eq!(input, output);
+                // out of bounds of the allocation.
+                assert_eq!(
+                    cuMemcpyDtoH_v2(output.as_mut_ptr().cast(), dptr + 4, 16),
+                    CUDA_ERROR_INVALID_VALUE
+                );
+                assert_eq!(cuMemsetD32_v2(dptr, 7, 4), CUDA_SUCCESS);
+                cuMemcpyDtoH_v2(output.as_mut_ptr().cast(), dptr, 16);
+                assert_eq!(output, [7; 4]);
+                assert_eq!(cuMemFree_v2(dptr), CUDA_SUCCESS);
+                assert_eq!(cuMemFree_v2(dptr), CUDA_ERROR_INVALID_VALUE);
+            })
+        }
+    }
+
+    #[test]
+    fn test_fault_injection() {
+        unsafe {
+            with_context(|_| {
+                let mut dptr = 0;
+                fail_nth("cuMemAlloc_v2", 1, CUDA_ERROR_OUT_OF_MEMORY);
+                assert_eq!(cuMemAlloc_v2(&mut dptr, 4), CUDA_SUCCESS);
+                cuMemFree_v2(dptr);
+                assert_eq!(cuMemAlloc_v2(&mut dptr, 4), CUDA_ERROR_OUT_OF_MEMORY);
+                assert_eq!(cuMemAlloc_v2(&mut dptr, 4), CUDA_SUCCESS);
+                cuMemFree_v2(dptr);
+
+                fail_always("cuStreamSynchronize", CUDA_ERROR_LAUNCH_FAILED);
+                assert_eq!(
+                    cuStreamSynchronize(ptr::null_mut()),
+                    CUDA_ERROR_LAUNCH_FAILED
+                );
+                assert_eq!(
+                    cuStreamSynchronize(ptr::null_mut()),
+                    CUDA_ERROR_LAUNCH_FAILED
+                );
+                clear_faults();
+                assert_eq!(cuStreamSynchronize(ptr::null_mut()), CUDA_SUCCESS);
+            })
+        }
+    }
+
+    #[test]
+    fn test_destroying_context_frees_memory() {
+        unsafe {
+            let mut dptr = 0;
+            let before = live_allocations();
+            with_context(|_| {
+                assert_eq!(cuMemAlloc_v2(&mut dptr, 64), CUDA_SUCCESS);
+                assert_eq!(live_allocations(), before + 1);
+            });
+            assert_eq!(live_allocations(), before);
+            assert_eq!(cuMemFree_v2(dptr), CUDA_ERROR_INVALID_CONTEXT);
+        }
+    }
+
+    #[test]
+    fn test_link() {
+        unsafe {
+            with_context(|_| {
+                let mut state = ptr::null_mut();
+                assert_eq!(
+                    cuLinkCreate_v2(0, ptr::null_mut(), ptr::null_mut(), &mut state),
+                    CUDA_SUCCESS
+                );
+                let mut image = ptr::null_mut();
+                let mut size = 0;
+                // there is nothing to link yet.
+                assert_eq!(
+                    cuLinkComplete(state, &mut image, &mut size),
+                    CUDA_ERROR_INVALID_VALUE
+                );
+                let ptx = b"// ptx";
+                assert_eq!(
+                    cuLinkAddData_v2(
+                        state,
+                        CUjitInputType::CU_JIT_INPUT_PTX,
+                        ptx.as_ptr() as *mut c_void,
+                        ptx.len(),
+                        ptr::null(),
+                        0,
+                        ptr::null_mut(),
+                        ptr::null_mut(),
+                    ),
+                    CUDA_SUCCESS
+                );
+                assert_eq!(cuLinkComplete(state, &mut image, &mut size), CUDA_SUCCESS);
+                let image = std::slice::from_raw_parts(image as *const u8, size);
+                assert!(image.starts_with(LINKED_IMAGE_MAGIC));
+                assert!(image.ends_with(ptx));
+                let mut module = ptr::null_mut();
+                assert_eq!(
+                    cuModuleLoadData(&mut module, image.as_ptr().cast()),
+                    CUDA_SUCCESS
+                );
+                assert_eq!(cuLinkDestroy(state), CUDA_SUCCESS);
+                assert_eq!(cuLinkDestroy(state), CUDA_ERROR_INVALID_HANDLE);
+            })
+        }
+    }
+
+    #[test]
+    fn test_call_log() {
+        unsafe {
+            with_context(|_| {
+                clear_calls();
+                let mut stream = ptr::null_mut();
+                let mut event = ptr::null_mut();
+                cuStreamCreateWithPriority(&mut stream, 0, 0);
+                cuEventCreate(&mut event, 0);
+                cuEventRecord(event, stream);
+                cuEventDestroy_v2(event);
+                cuStreamDestroy_v2(stream);
+                assert_eq!(
+                    calls(),
+                    [
+                        "cuStreamCreateWithPriority",
+                        "cuEventCreate",
+                        "cuEventRecord",
+                        "cuEventDestroy_v2",
+                        "cuStreamDestroy_v2",
+                    ]
+                );
+            })
+        }
+    }
+
+    #[test]
+    fn test_external_semaphore() {
+        unsafe {
+            with_context(|_| {
+                let mut desc = CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC {
+                    type_: CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_TIMELINE_SEMAPHORE_FD,
+                    handle: CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC_st__bindgen_ty_1 { fd: -1 },
+                    flags: 0,
+                    reserved: [0; 16],
+                };
+                let mut sem = ptr::null_mut();
+                assert_eq!(
+                    cuImportExternalSemaphore(&mut sem, &desc),
+                    CUDA_ERROR_INVALID_VALUE
+                );
+                desc.handle.fd = 3;
+                assert_eq!(cuImportExternalSemaphore(&mut sem, &desc), CUDA_SUCCESS);
+
+                let mut signal: CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS = std::mem::zeroed();
+                let mut wait: CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS = std::mem::zeroed();
+                signal.params.fence.value = 2;
+                wait.params.fence.value = 3;
+                assert_eq!(
+                    cuSignalExternalSemaphoresAsync(&sem, &signal, 1, ptr::null_mut()),
+                    CUDA_SUCCESS
+                );
+                // nothing can signal 3 anymore.
+                assert_eq!(
+                    cuWaitExternalSemaphoresAsync(&sem, &wait, 1, ptr::null_mut()),
+                    CUDA_ERROR_INVALID_VALUE
+                );
+                wait.params.fence.value = 2;
+                assert_eq!(
+                    cuWaitExternalSemaphoresAsync(&sem, &wait, 1, ptr::null_mut()),
+                    CUDA_SUCCESS
+                );
+
+                assert_eq!(cuDestroyExternalSemaphore(sem), CUDA_SUCCESS);
+                assert_eq!(
+                    cuSignalExternalSemaphoresAsync(&sem, &signal, 1, ptr::null_mut()),
+                    CUDA_ERROR_INVALID_HANDLE
+                );
+                assert_eq!(cuDestroyExternalSemaphore(sem), CUDA_ERROR_INVALID_HANDLE);
+            })
+        }
+    }
+
+    #[test]
+    fn test_context_limits() {
+        unsafe {
+            with_context(|_| {
+                let mut value = 0;
+                assert_eq!(
+                    cuCtxGetLimit(&mut value, CUlimit::CU_LIMIT_STACK_SIZE),
+                    CUDA_SUCCESS
+                );
+                assert_eq!(value, 1024);
+                assert_eq!(
+                    cuCtxSetLimit(CUlimit::CU_LIMIT_STACK_SIZE, 4096),
+                    CUDA_SUCCESS
+                );
+                cuCtxGetLimit(&mut value, CUlimit::CU_LIMIT_STACK_SIZE);
+                assert_eq!(value, 4096);
+                assert_eq!(
+                    cuCtxSetLimit(CUlimit::CU_LIMIT_MAX, 1),
+                    CUDA_ERROR_INVALID_VALUE
+                );
+
+                let mut config = CUfunc_cache::CU_FUNC_CACHE_PREFER_NONE;
+                assert_eq!(
+                    cuCtxSetCacheConfig(CUfunc_cache::CU_FUNC_CACHE_PREFER_L1),
+                    CUDA_SUCCESS
+                );
+                cuCtxGetCacheConfig(&mut config);
+                assert_eq!(config, CUfunc_cache::CU_FUNC_CACHE_PREFER_L1);
+            });
+            // the settings belong to the context, a new one starts from the defaults.
+            with_context(|_| {
+                let mut value = 0;
+                cuCtxGetLimit(&mut value, CUlimit::CU_LIMIT_STACK_SIZE);
+                assert_eq!(value, 1024);
+            })
+        }
+    }
+
+    #[test]
+    fn test_array_copies() {
+        unsafe {
+            let mut array = ptr::null_mut();
+            with_context(|_| {
+                let descriptor = CUDA_ARRAY3D_DESCRIPTOR {
+                    Width: 3,
+                    Height: 2,
+                    Depth: 0,
+                    Format: CUarray_format::CU_AD_FORMAT_UNSIGNED_INT16,
+                    NumChannels: 2,
+                    Flags: 0,
+                };
+                assert_eq!(cuArray3DCreate_v2(&mut array, &descriptor), CUDA_SUCCESS);
+                let mut out = descriptor;
+                out.Width = 0;
+                assert_eq!(cuArray3DGetDescriptor_v2(&mut out, array), CUDA_SUCCESS);
+                assert_eq!(out, descriptor);
+
+                let input = (0..12u16).collect::<Vec<_>>();
+                let mut output = [0u16; 12];
+                assert_eq!(
+                    cuMemcpyHtoA_v2(array, 0, input.as_ptr().cast(), 24),
+                    CUDA_SUCCESS
+                );
+                // one row of the array, which is 12 bytes wide.
+                let copy = CUDA_MEMCPY2D {
+                    srcXInBytes: 0,
+                    srcY: 1,
+                    srcMemoryType: CUmemorytype::CU_MEMORYTYPE_ARRAY,
+                    srcHost: ptr::null(),
+                    srcDevice: 0,
+                    srcArray: array,
+                    srcPitch: 0,
+                    dstXInBytes: 0,
+                    dstY: 0,
+                    dstMemoryType: CUmemorytype::CU_MEMORYTYPE_HOST,
+                    dstHost: output.as_mut_ptr().cast(),
+                    dstDevice: 0,
+                    dstArray: ptr::null_mut(),
+                    dstPitch: 0,
+                    WidthInBytes: 12,
+                    Height: 1,
+                };
+                assert_eq!(cuMemcpy2D_v2(&copy), CUDA_SUCCESS);
+                assert_eq!(output[..6], input[6..]);
+                assert_eq!(
+                    cuMemcpyAtoH_v2(output.as_mut_ptr().cast(), array, 2, 24),
+                    CUDA_ERROR_INVALID_VALUE
+                );
+
+                let mut cubemap = descriptor;
+                cubemap.Flags = CUDA_ARRAY3D_CUBEMAP;
+                cubemap.Depth = 6;
+                assert_eq!(
+                    cuArray3DCreate_v2(&mut ptr::null_mut(), &cubemap),
+                    CUDA_ERROR_INVALID_VALUE
+                );
+            });
+            // destroying the context destroyed the array.
+            assert_eq!(cuArrayDestroy(array), CUDA_ERROR_INVALID_HANDLE);
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     fmt::{Debug, Display},
 };
 
-use cust::error::CudaError;
+use cust::error::{CudaError, DriverError};
 
 use crate::sys;
 
@@ -108,6 +108,12 @@ impl From<CudaError> for OptixError {
     }
 }
 
+impl From<DriverError> for OptixError {
+    fn from(_: DriverError) -> Self {
+        Self::CudaError
+    }
+}
+
 impl From<OptixError> for CudaError {
     fn from(_: OptixError) -> Self {
         CudaError::OptixError
@@ -212,6 +218,12 @@ impl From<CudaError> for Error {
     }
 }
 
+impl From<DriverError> for Error {
+    fn from(e: DriverError) -> Self {
+        Self::Cuda(e.error())
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {